@@ -20,6 +20,10 @@ pub fn token_derive(input: TokenStream) -> TokenStream {
         self.parser.clone()
       }
 
+      fn set_parser(&mut self, parser: &crate::parser::ParserRef) {
+        self.parser = parser.clone();
+      }
+
       fn get_captured_range(&self) -> &crate::source_range::SourceRange {
         &self.captured_range
       }
@@ -164,3 +168,269 @@ pub fn token_derive(input: TokenStream) -> TokenStream {
   // Return the generated impl
   TokenStream::from(expanded)
 }
+
+enum FromTokenSource {
+  Child(String),
+  Attribute(String),
+  Value,
+  Range,
+}
+
+struct FromTokenField {
+  source: FromTokenSource,
+  from_str: bool,
+}
+
+fn pascal_case(name: &str) -> String {
+  name
+    .split('_')
+    .filter(|part| !part.is_empty())
+    .map(|part| {
+      let mut chars = part.chars();
+      match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+      }
+    })
+    .collect()
+}
+
+// Returns the inner type if `ty` is `wrapper<T>` (i.e. `Option<T>`, or `Vec<T>`)
+fn wrapped_type<'a>(ty: &'a syn::Type, wrapper: &str) -> Option<&'a syn::Type> {
+  if let syn::Type::Path(type_path) = ty {
+    let segment = type_path.path.segments.last()?;
+    if segment.ident != wrapper {
+      return None;
+    }
+
+    if let syn::PathArguments::AngleBracketed(arguments) = &segment.arguments {
+      if let Some(syn::GenericArgument::Type(inner)) = arguments.args.first() {
+        return Some(inner);
+      }
+    }
+  }
+
+  None
+}
+
+fn parse_token_attributes(attributes: &[syn::Attribute]) -> syn::Result<Vec<syn::NestedMeta>> {
+  let mut items = Vec::new();
+
+  for attribute in attributes {
+    if !attribute.path.is_ident("token") {
+      continue;
+    }
+
+    match attribute.parse_meta()? {
+      syn::Meta::List(list) => items.extend(list.nested.into_iter()),
+      meta => return Err(syn::Error::new_spanned(meta, "expected `#[token(...)]`")),
+    }
+  }
+
+  Ok(items)
+}
+
+fn parse_from_token_field(field: &syn::Field) -> syn::Result<FromTokenField> {
+  let field_name = field.ident.as_ref().unwrap().to_string();
+  let mut source = None;
+  let mut from_str = false;
+
+  for item in parse_token_attributes(&field.attrs)? {
+    match &item {
+      syn::NestedMeta::Meta(syn::Meta::NameValue(name_value)) => {
+        let value = match &name_value.lit {
+          syn::Lit::Str(value) => value.value(),
+          _ => {
+            return Err(syn::Error::new_spanned(
+              &name_value.lit,
+              "expected a string",
+            ))
+          }
+        };
+
+        if name_value.path.is_ident("child") {
+          source = Some(FromTokenSource::Child(value));
+        } else if name_value.path.is_ident("attribute") {
+          source = Some(FromTokenSource::Attribute(value));
+        } else {
+          return Err(syn::Error::new_spanned(item, "unknown `token` option"));
+        }
+      }
+      syn::NestedMeta::Meta(syn::Meta::Path(path)) => {
+        if path.is_ident("value") {
+          source = Some(FromTokenSource::Value);
+        } else if path.is_ident("range") {
+          source = Some(FromTokenSource::Range);
+        } else if path.is_ident("from_str") {
+          from_str = true;
+        } else {
+          return Err(syn::Error::new_spanned(item, "unknown `token` option"));
+        }
+      }
+      _ => return Err(syn::Error::new_spanned(item, "unknown `token` option")),
+    }
+  }
+
+  Ok(FromTokenField {
+    source: source.unwrap_or_else(|| FromTokenSource::Child(pascal_case(&field_name))),
+    from_str,
+  })
+}
+
+fn from_token_conversion(
+  ty: &syn::Type,
+  token: proc_macro2::TokenStream,
+  field_name: &str,
+  from_str: bool,
+) -> proc_macro2::TokenStream {
+  if from_str {
+    quote! { ::adextopa_core::from_token::parse_value::<#ty>(&#token, #field_name)? }
+  } else {
+    quote! { <#ty as ::adextopa_core::from_token::FromToken>::from_token(&#token)? }
+  }
+}
+
+fn from_token_field_value(field: &syn::Field) -> syn::Result<proc_macro2::TokenStream> {
+  let options = parse_from_token_field(field)?;
+  let field_name = field.ident.as_ref().unwrap().to_string();
+  let ty = &field.ty;
+  let optional = wrapped_type(ty, "Option");
+  let repeated = wrapped_type(ty, "Vec");
+
+  let value = match &options.source {
+    FromTokenSource::Child(name) => {
+      if let Some(inner) = optional {
+        let conversion =
+          from_token_conversion(inner, quote! { child }, &field_name, options.from_str);
+        quote! {
+          match ::adextopa_core::from_token::find_child(token, #name) {
+            Some(child) => Some(#conversion),
+            None => None,
+          }
+        }
+      } else if let Some(inner) = repeated {
+        let conversion =
+          from_token_conversion(inner, quote! { child }, &field_name, options.from_str);
+        quote! {
+          {
+            let mut values = Vec::new();
+            for child in ::adextopa_core::from_token::children_named(token, #name) {
+              values.push(#conversion);
+            }
+
+            values
+          }
+        }
+      } else {
+        let conversion = from_token_conversion(ty, quote! { child }, &field_name, options.from_str);
+        quote! {
+          {
+            let child = ::adextopa_core::from_token::required_child(token, #name, #field_name)?;
+            #conversion
+          }
+        }
+      }
+    }
+    FromTokenSource::Attribute(name) => {
+      let (inner, is_optional) = match optional {
+        Some(inner) => (inner, true),
+        None => (ty, false),
+      };
+
+      let conversion = if options.from_str {
+        quote! { ::adextopa_core::from_token::parse_str::<#inner>(token, &value, #field_name)? }
+      } else {
+        quote! { <#inner as ::std::convert::From<String>>::from(value) }
+      };
+
+      if is_optional {
+        quote! {
+          match ::adextopa_core::from_token::find_attribute(token, #name) {
+            Some(value) => Some(#conversion),
+            None => None,
+          }
+        }
+      } else {
+        quote! {
+          {
+            let value = ::adextopa_core::from_token::required_attribute(token, #name, #field_name)?;
+            #conversion
+          }
+        }
+      }
+    }
+    FromTokenSource::Value => {
+      let conversion = from_token_conversion(ty, quote! { token }, &field_name, options.from_str);
+      quote! {
+        {
+          let token = token.clone();
+          #conversion
+        }
+      }
+    }
+    FromTokenSource::Range => quote! { *token.borrow().get_captured_range() },
+  };
+
+  let ident = &field.ident;
+  Ok(quote! { #ident: #value })
+}
+
+fn from_token_impl(input: &syn::DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+  let name = &input.ident;
+  let (impl_generics, type_generics, where_clause) = input.generics.split_for_impl();
+
+  let fields = match &input.data {
+    syn::Data::Struct(syn::DataStruct {
+      fields: syn::Fields::Named(fields),
+      ..
+    }) => &fields.named,
+    _ => {
+      return Err(syn::Error::new_spanned(
+        input,
+        "`FromToken` can only be derived for structs with named fields",
+      ))
+    }
+  };
+
+  let mut name_check = quote! {};
+  for item in parse_token_attributes(&input.attrs)? {
+    match &item {
+      syn::NestedMeta::Meta(syn::Meta::NameValue(name_value))
+        if name_value.path.is_ident("name") =>
+      {
+        let token_name = &name_value.lit;
+        name_check = quote! { ::adextopa_core::from_token::expect_name(token, #token_name)?; };
+      }
+      _ => return Err(syn::Error::new_spanned(item, "unknown `token` option")),
+    }
+  }
+
+  let mut values = Vec::new();
+  for field in fields {
+    values.push(from_token_field_value(field)?);
+  }
+
+  Ok(quote! {
+    impl #impl_generics ::adextopa_core::from_token::FromToken for #name #type_generics #where_clause {
+      fn from_token(
+        token: &::adextopa_core::token::TokenRef,
+      ) -> Result<Self, ::adextopa_core::parse_error::ParseError> {
+        #name_check
+
+        Ok(Self {
+          #(#values,)*
+        })
+      }
+    }
+  })
+}
+
+#[proc_macro_derive(FromToken, attributes(token))]
+pub fn from_token_derive(input: TokenStream) -> TokenStream {
+  let input = syn::parse_macro_input!(input as syn::DeriveInput);
+
+  match from_token_impl(&input) {
+    Ok(expanded) => TokenStream::from(expanded),
+    Err(error) => TokenStream::from(error.to_compile_error()),
+  }
+}
@@ -0,0 +1,477 @@
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+
+use regex::Regex;
+
+use crate::matcher::{MatcherKind, MatcherRef};
+use crate::parse_error::ParseError;
+use crate::parser::Parser;
+use crate::scope::VariableType;
+use crate::scope_context::ScopeContextRef;
+use crate::script::current::parser::compile_script;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum AnalysisWarningKind {
+  // A `Ref` to a name that is never defined
+  UndefinedReference(String),
+  // A scope assignment that is never referenced
+  UnusedAssignment(String),
+  // A `Switch` alternative (at `index`) that can never be reached,
+  // because an earlier alternative (at `shadowed_by`) always matches first
+  ShadowedAlternative { index: usize, shadowed_by: usize },
+  // A `Loop` whose body can succeed without consuming any input
+  EmptyLoopBody,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnalysisWarning {
+  pub kind: AnalysisWarningKind,
+  // Names of the matchers leading from the root to the offending matcher
+  pub path: Vec<String>,
+  pub message: String,
+}
+
+impl std::fmt::Display for AnalysisWarning {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "Warning: {}: {}", self.path.join(" > "), self.message)
+  }
+}
+
+fn matcher_key(matcher: &MatcherRef) -> usize {
+  Rc::as_ptr(matcher) as *const u8 as usize
+}
+
+fn scope_key(scope: &ScopeContextRef) -> usize {
+  Rc::as_ptr(scope) as usize
+}
+
+fn first_child(matcher: &MatcherRef) -> Option<MatcherRef> {
+  matcher
+    .borrow()
+    .get_children()
+    .and_then(|children| children.first().cloned())
+}
+
+// Does the regex `source` match all of `literal`, and only by looking at
+// `literal`? Assertions (i.e. `\b`) also depend on the text around a match,
+// so a regex that uses them can't be said to always match there
+fn regex_matches_whole(source: &str, literal: &str) -> bool {
+  if literal.is_empty() {
+    return false;
+  }
+
+  match regex_syntax::Parser::new().parse(source) {
+    Ok(hir) if hir.properties().look_set().is_empty() => {}
+    _ => return false,
+  }
+
+  match Regex::new(&format!(r"\A(?:{})\z", source)) {
+    Ok(regex) => regex.is_match(literal),
+    Err(_) => false,
+  }
+}
+
+struct Analyzer {
+  scopes: Vec<ScopeContextRef>,
+  visited: HashSet<(usize, usize)>,
+  used: HashSet<(usize, String)>,
+  custom_names: HashMap<String, MatcherRef>,
+  unresolved: Vec<(String, Vec<String>)>,
+  warnings: Vec<AnalysisWarning>,
+}
+
+impl Analyzer {
+  fn new(scope: ScopeContextRef) -> Self {
+    Self {
+      scopes: vec![scope],
+      visited: HashSet::new(),
+      used: HashSet::new(),
+      custom_names: HashMap::new(),
+      unresolved: Vec::new(),
+      warnings: Vec::new(),
+    }
+  }
+
+  fn warn(&mut self, kind: AnalysisWarningKind, path: &[String], message: String) {
+    self.warnings.push(AnalysisWarning {
+      kind,
+      path: path.to_vec(),
+      message,
+    });
+  }
+
+  fn resolve(&self, name: &str) -> Option<(usize, MatcherRef)> {
+    let scope = self.scopes.last().unwrap();
+
+    match scope.borrow().get(name) {
+      Some(VariableType::Matcher(matcher)) => Some((scope_key(scope), matcher)),
+      _ => self
+        .custom_names
+        .get(name)
+        .map(|matcher| (0, matcher.clone())),
+    }
+  }
+
+  fn walk(&mut self, matcher: &MatcherRef, path: &mut Vec<String>) {
+    let key = (matcher_key(matcher), scope_key(self.scopes.last().unwrap()));
+    if !self.visited.insert(key) {
+      return;
+    }
+
+    let _matcher = matcher.borrow();
+    let name = _matcher.get_name().to_string();
+    let kind = _matcher.get_kind();
+    let children = _matcher.get_children().unwrap_or_default();
+    let scope = _matcher.get_scope();
+
+    if _matcher.has_custom_name() {
+      self.custom_names.insert(name.clone(), matcher.clone());
+    }
+
+    drop(_matcher);
+
+    path.push(name.clone());
+
+    if let Some(scope) = &scope {
+      self.scopes.push(scope.clone());
+    }
+
+    match &kind {
+      MatcherKind::Ref(Some(target)) => match self.resolve(target) {
+        Some((scope_key, target_matcher)) => {
+          self.used.insert((scope_key, target.clone()));
+          self.walk(&target_matcher, path);
+        }
+        None => self.unresolved.push((target.clone(), path.clone())),
+      },
+      MatcherKind::Switch => self.check_shadowed_alternatives(&children, path),
+      MatcherKind::Loop(_) => {
+        let mut visiting = HashSet::new();
+        if children
+          .iter()
+          .all(|child| self.is_nullable(child, &mut visiting))
+        {
+          self.warn(
+            AnalysisWarningKind::EmptyLoopBody,
+            path,
+            format!(
+              "The body of loop `{}` can match without consuming any input",
+              name
+            ),
+          );
+        }
+      }
+      _ => {}
+    }
+
+    for child in &children {
+      self.walk(child, path);
+    }
+
+    if scope.is_some() {
+      self.scopes.pop();
+    }
+
+    path.pop();
+  }
+
+  // Can this matcher succeed without consuming any input?
+  fn is_nullable(&self, matcher: &MatcherRef, visiting: &mut HashSet<usize>) -> bool {
+    if !visiting.insert(matcher_key(matcher)) {
+      return false;
+    }
+
+    let _matcher = matcher.borrow();
+    let children = _matcher.get_children().unwrap_or_default();
+    let result = match _matcher.get_kind() {
+      MatcherKind::Equals(literal) => literal.is_some_and(|literal| literal.is_empty()),
+      MatcherKind::Matches(source) => match Regex::new(&source) {
+        Ok(regex) => regex.find("").is_some(),
+        Err(_) => false,
+      },
+      MatcherKind::Sequence(..) | MatcherKind::OneOf(_) => false,
+      MatcherKind::Program => children
+        .iter()
+        .all(|child| self.is_nullable(child, visiting)),
+      MatcherKind::Loop(range) => {
+        range.start == 0
+          || children
+            .iter()
+            .all(|child| self.is_nullable(child, visiting))
+      }
+      MatcherKind::SepBy(range) => {
+        range.start == 0
+          || children
+            .first()
+            .is_some_and(|item| self.is_nullable(item, visiting))
+      }
+      MatcherKind::Switch => children
+        .iter()
+        .any(|child| self.is_nullable(child, visiting)),
+      MatcherKind::Optional | MatcherKind::Not | MatcherKind::Peek => true,
+      MatcherKind::Discard | MatcherKind::ProxyChildren | MatcherKind::Wrapper => {
+        match children.first() {
+          Some(child) => self.is_nullable(child, visiting),
+          None => true,
+        }
+      }
+      MatcherKind::Ref(Some(target)) => match self.resolve(&target) {
+        Some((_, target)) => self.is_nullable(&target, visiting),
+        None => false,
+      },
+      MatcherKind::Ref(None) => false,
+      MatcherKind::Other => !_matcher.is_consuming(),
+    };
+
+    drop(_matcher);
+    visiting.remove(&matcher_key(matcher));
+
+    result
+  }
+
+  // Strip matchers that pass their child's result through
+  fn unwrap(&self, matcher: &MatcherRef, depth: usize) -> MatcherRef {
+    if depth > 32 {
+      return matcher.clone();
+    }
+
+    let kind = matcher.borrow().get_kind();
+    match kind {
+      MatcherKind::Discard | MatcherKind::ProxyChildren | MatcherKind::Wrapper => {
+        match first_child(matcher) {
+          Some(child) => self.unwrap(&child, depth + 1),
+          None => matcher.clone(),
+        }
+      }
+      MatcherKind::Ref(Some(target)) => match self.resolve(&target) {
+        Some((_, target)) => self.unwrap(&target, depth + 1),
+        None => matcher.clone(),
+      },
+      _ => matcher.clone(),
+    }
+  }
+
+  // The literal any input matched by this matcher must start with
+  fn leading_literal(&self, matcher: &MatcherRef, depth: usize) -> Option<String> {
+    if depth > 32 {
+      return None;
+    }
+
+    let matcher = self.unwrap(matcher, depth);
+    let kind = matcher.borrow().get_kind();
+
+    match kind {
+      MatcherKind::Equals(literal) => literal.filter(|literal| !literal.is_empty()),
+      MatcherKind::Sequence(start, ..) => start.filter(|start| !start.is_empty()),
+      MatcherKind::Program => self.leading_literal(&first_child(&matcher)?, depth + 1),
+      MatcherKind::Loop(range) if range.start > 0 => {
+        self.leading_literal(&first_child(&matcher)?, depth + 1)
+      }
+      _ => None,
+    }
+  }
+
+  // Does `earlier` always match (consuming input) wherever `later` could match?
+  fn shadows(&self, earlier: &MatcherRef, later: &MatcherRef) -> bool {
+    let earlier = self.unwrap(earlier, 0);
+    if Rc::ptr_eq(&earlier, &self.unwrap(later, 0)) {
+      return true;
+    }
+
+    let literal = match self.leading_literal(later, 0) {
+      Some(literal) => literal,
+      None => return false,
+    };
+
+    let kind = earlier.borrow().get_kind();
+    match kind {
+      MatcherKind::Equals(Some(prefix)) => !prefix.is_empty() && literal.starts_with(&prefix),
+      MatcherKind::Matches(source) => regex_matches_whole(&source, &literal),
+      _ => false,
+    }
+  }
+
+  fn check_shadowed_alternatives(&mut self, alternatives: &[MatcherRef], path: &[String]) {
+    for index in 1..alternatives.len() {
+      for shadowed_by in 0..index {
+        if self.shadows(&alternatives[shadowed_by], &alternatives[index]) {
+          let switch_name = path.last().cloned().unwrap_or_default();
+
+          self.warn(
+            AnalysisWarningKind::ShadowedAlternative { index, shadowed_by },
+            path,
+            format!(
+              "Alternative #{} of switch `{}` can never match, because alternative #{} always matches first",
+              index, switch_name, shadowed_by
+            ),
+          );
+
+          break;
+        }
+      }
+    }
+  }
+
+  fn resolve_deferred_references(&mut self) {
+    loop {
+      let unresolved = std::mem::take(&mut self.unresolved);
+      let mut progress = false;
+
+      for (name, mut path) in unresolved {
+        match self.custom_names.get(&name).cloned() {
+          Some(matcher) => {
+            progress = true;
+            self.walk(&matcher, &mut path);
+          }
+          None => self.unresolved.push((name, path)),
+        }
+      }
+
+      if !progress {
+        break;
+      }
+    }
+
+    for (name, path) in std::mem::take(&mut self.unresolved) {
+      self.warn(
+        AnalysisWarningKind::UndefinedReference(name.clone()),
+        &path,
+        format!("Reference to undefined matcher `{}`", name),
+      );
+    }
+  }
+}
+
+/// Statically analyze a compiled matcher graph, returning warnings for
+/// undefined references, unused assignments in `scope`, unreachable
+/// `Switch` alternatives, and loops that can match empty input.
+pub fn analyze(root_matcher: MatcherRef, scope: ScopeContextRef) -> Vec<AnalysisWarning> {
+  let mut analyzer = Analyzer::new(scope.clone());
+  let mut path = Vec::new();
+
+  analyzer.walk(&root_matcher, &mut path);
+  analyzer.resolve_deferred_references();
+
+  let root_scope_key = scope_key(&scope);
+  for name in scope.borrow().keys() {
+    if !matches!(scope.borrow().get(&name), Some(VariableType::Matcher(_))) {
+      continue;
+    }
+
+    if !analyzer.used.contains(&(root_scope_key, name.clone())) {
+      analyzer.warn(
+        AnalysisWarningKind::UnusedAssignment(name.clone()),
+        &[root_matcher.borrow().get_name().to_string()],
+        format!("`{}` is assigned, but never used", name),
+      );
+    }
+  }
+
+  analyzer.warnings
+}
+
+pub fn analyze_script(source: &str, name: &str) -> Result<Vec<AnalysisWarning>, Vec<ParseError>> {
+  let parser = Parser::new(source);
+  let (_, root_matcher, scope) = compile_script(parser, name.to_string(), None, 0)?;
+
+  Ok(analyze(root_matcher, scope))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{analyze, analyze_script, AnalysisWarningKind};
+  use crate::{scope_context::ScopeContext, Equals, Loop, Matches, Optional, Program, Ref, Switch};
+
+  #[test]
+  fn it_reports_undefined_references_and_unused_assignments() {
+    let warnings = analyze_script(
+      r"<!--[adextopa version='1' name='Test']
+        Word = </\w+/>
+        Unused = </\d+/>
+      -->
+
+      (<Word>)(<Wrod>)",
+      "Test",
+    )
+    .unwrap();
+
+    let kinds: Vec<AnalysisWarningKind> = warnings.iter().map(|w| w.kind.clone()).collect();
+    assert_eq!(
+      kinds,
+      vec![
+        AnalysisWarningKind::UndefinedReference("Wrod".to_string()),
+        AnalysisWarningKind::UnusedAssignment("Unused".to_string()),
+      ]
+    );
+
+    assert_eq!(
+      warnings[0].to_string(),
+      "Warning: Test > SetScope > Flatten > Program > Ref: Reference to undefined matcher `Wrod`"
+    );
+  }
+
+  #[test]
+  fn it_reports_shadowed_alternatives() {
+    let matcher = Switch!(Equals!("="), Equals!("=="), Matches!(r"\d+"), Equals!("42"));
+    let warnings = analyze(matcher, ScopeContext::new());
+
+    let kinds: Vec<AnalysisWarningKind> = warnings.iter().map(|w| w.kind.clone()).collect();
+    assert_eq!(
+      kinds,
+      vec![
+        AnalysisWarningKind::ShadowedAlternative {
+          index: 1,
+          shadowed_by: 0
+        },
+        AnalysisWarningKind::ShadowedAlternative {
+          index: 3,
+          shadowed_by: 2
+        },
+      ]
+    );
+  }
+
+  #[test]
+  fn it_only_reports_regexes_that_match_the_whole_literal() {
+    let matcher = Switch!(
+      Matches!(r"[a-z]"),
+      Matches!(r"if\b"),
+      Equals!("if"),
+      Matches!(r"[a-z]+"),
+      Equals!("else"),
+    );
+    let warnings = analyze(matcher, ScopeContext::new());
+
+    let kinds: Vec<AnalysisWarningKind> = warnings.iter().map(|w| w.kind.clone()).collect();
+    assert_eq!(
+      kinds,
+      vec![AnalysisWarningKind::ShadowedAlternative {
+        index: 4,
+        shadowed_by: 3
+      }]
+    );
+  }
+
+  #[test]
+  fn it_reports_loops_that_can_match_empty() {
+    let matcher = Program!(
+      Loop!(Optional!(Equals!("a"))),
+      Loop!(Matches!(r"\s*")),
+      Loop!(Equals!("b")),
+    );
+    let warnings = analyze(matcher, ScopeContext::new());
+
+    assert_eq!(warnings.len(), 2);
+    assert!(warnings
+      .iter()
+      .all(|w| w.kind == AnalysisWarningKind::EmptyLoopBody));
+  }
+
+  #[test]
+  fn it_resolves_references_to_custom_named_matchers() {
+    let matcher = Program!(Program!("Word"; Matches!(r"\w+")), Ref!("Word"));
+    let warnings = analyze(matcher, ScopeContext::new());
+
+    assert_eq!(warnings, vec![]);
+  }
+}
@@ -0,0 +1,1147 @@
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write;
+use std::path::Path;
+use std::rc::Rc;
+
+use crate::matcher::{MatcherKind, MatcherRef};
+use crate::parse_error::ParseError;
+use crate::parser::Parser;
+use crate::scope::VariableType;
+use crate::scope_context::{ScopeContext, ScopeContextRef};
+use crate::script::current::parser::compile_script;
+
+// Support code used by generated types. Generated files
+// reference this module, so it is part of the public API.
+pub mod runtime {
+  use crate::parse_error::ParseError;
+  use crate::source_range::SourceRange;
+  use crate::token::{TokenRef, IS_ERROR};
+
+  #[derive(Debug, Clone, Copy, PartialEq)]
+  pub enum Multiplicity {
+    One,
+    Optional,
+    Many,
+  }
+
+  // A captured token without a rule of its own
+  #[derive(Debug, Clone, PartialEq)]
+  pub struct Text {
+    pub range: SourceRange,
+    pub value: String,
+  }
+
+  impl TryFrom<TokenRef> for Text {
+    type Error = ParseError;
+
+    fn try_from(token: TokenRef) -> Result<Self, Self::Error> {
+      let token = token.borrow();
+
+      Ok(Self {
+        range: *token.get_captured_range(),
+        value: token.get_value().clone(),
+      })
+    }
+  }
+
+  pub fn unexpected_token(token: &TokenRef, expected: &[&str]) -> ParseError {
+    let token = token.borrow();
+
+    ParseError::new_with_range(
+      &format!(
+        "Expected a token named one of [{}], but found `{}`",
+        expected.join(", "),
+        token.get_name()
+      ),
+      *token.get_matched_range(),
+    )
+  }
+
+  pub fn expect_name(token: &TokenRef, expected: &[&str]) -> Result<(), ParseError> {
+    let _token = token.borrow();
+
+    if _token.flags_enabled(IS_ERROR) {
+      let message = match _token.get_attribute("__message") {
+        Some(message) => message.clone(),
+        None => "Error token encountered".to_string(),
+      };
+
+      return Err(ParseError::new_with_range(
+        &message,
+        *_token.get_matched_range(),
+      ));
+    }
+
+    if expected.contains(&_token.get_name().as_str()) {
+      Ok(())
+    } else {
+      drop(_token);
+      Err(unexpected_token(token, expected))
+    }
+  }
+
+  // Children of a token, distributed into the
+  // field slots of the generated type
+  pub struct Children {
+    range: SourceRange,
+    slots: Vec<Vec<TokenRef>>,
+  }
+
+  impl Children {
+    // Distribute children into slots, in order. When `repeats`
+    // is true (the token was produced by a loop), slots may be
+    // revisited once the end of the slot list has been reached.
+    pub fn collect(
+      token: &TokenRef,
+      expected: &[&str],
+      spec: &[(&[&str], Multiplicity)],
+      repeats: bool,
+    ) -> Result<Self, ParseError> {
+      expect_name(token, expected)?;
+
+      let _token = token.borrow();
+      let mut slots: Vec<Vec<TokenRef>> = spec.iter().map(|_| Vec::new()).collect();
+      let mut cursor = 0;
+
+      for child in _token.get_children() {
+        expect_name(child, &[child.borrow().get_name().as_str()])?;
+
+        let child_name = child.borrow().get_name().clone();
+        let accepts = |index: usize, slots: &Vec<Vec<TokenRef>>| {
+          let (names, multiplicity) = spec[index];
+          names.contains(&child_name.as_str())
+            && (multiplicity == Multiplicity::Many || slots[index].is_empty())
+        };
+
+        let mut found = (cursor..spec.len()).find(|index| accepts(*index, &slots));
+        if found.is_none() && repeats {
+          found = (0..cursor).find(|index| accepts(*index, &slots));
+        }
+
+        match found {
+          Some(index) => {
+            slots[index].push(child.clone());
+            cursor = index;
+          }
+          None => {
+            return Err(ParseError::new_with_range(
+              &format!(
+                "Unexpected `{}` child in `{}`",
+                child_name,
+                _token.get_name()
+              ),
+              *child.borrow().get_matched_range(),
+            ))
+          }
+        }
+      }
+
+      for (index, (names, multiplicity)) in spec.iter().enumerate() {
+        if *multiplicity == Multiplicity::One && slots[index].is_empty() {
+          return Err(ParseError::new_with_range(
+            &format!(
+              "`{}` is missing a required [{}] child",
+              _token.get_name(),
+              names.join(", ")
+            ),
+            *_token.get_matched_range(),
+          ));
+        }
+      }
+
+      Ok(Self {
+        range: *_token.get_captured_range(),
+        slots,
+      })
+    }
+
+    pub fn range(&self) -> SourceRange {
+      self.range
+    }
+
+    pub fn one(&mut self, index: usize) -> TokenRef {
+      self.slots[index].remove(0)
+    }
+
+    pub fn optional(&mut self, index: usize) -> Option<TokenRef> {
+      self.slots[index].pop()
+    }
+
+    pub fn many(&mut self, index: usize) -> Vec<TokenRef> {
+      std::mem::take(&mut self.slots[index])
+    }
+  }
+}
+
+use runtime::Multiplicity;
+
+const RESERVED_TYPE_NAMES: [&str; 6] = [
+  "Text",
+  "TokenRef",
+  "SourceRange",
+  "ParseError",
+  "Self",
+  "TryFrom",
+];
+
+const RUST_KEYWORDS: [&str; 38] = [
+  "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum", "extern",
+  "false", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub",
+  "ref", "return", "self", "static", "struct", "super", "trait", "true", "type", "unsafe", "use",
+  "where", "while", "yield",
+];
+
+#[derive(Debug, Clone, PartialEq)]
+enum FieldType {
+  Text,
+  Named(String),
+  Token,
+}
+
+#[derive(Debug, Clone)]
+struct Field {
+  label: String,
+  // Token names accepted by this field. Ignored for
+  // `Named` fields, which accept what their type accepts.
+  accepts: Vec<String>,
+  field_type: FieldType,
+  multiplicity: Multiplicity,
+}
+
+#[derive(Debug)]
+enum TypeShape {
+  Leaf,
+  Struct(Vec<Field>, bool),
+  Enum(Vec<Field>),
+  Alias(String),
+}
+
+#[derive(Debug)]
+struct TypeDefinition {
+  name: String,
+  token_names: Vec<String>,
+  shape: TypeShape,
+}
+
+enum RuleState {
+  InProgress(String),
+  Done(Vec<Field>),
+}
+
+fn pascal_case(name: &str) -> String {
+  let mut result = String::new();
+  let mut upper = true;
+
+  for c in name.chars() {
+    if c.is_ascii_alphanumeric() {
+      if upper {
+        result.push(c.to_ascii_uppercase());
+        upper = false;
+      } else {
+        result.push(c);
+      }
+    } else {
+      upper = true;
+    }
+  }
+
+  if result.is_empty() || result.starts_with(|c: char| c.is_ascii_digit()) {
+    result.insert(0, 'T');
+  }
+
+  result
+}
+
+fn snake_case(name: &str) -> String {
+  let mut result = String::new();
+  let mut previous_lower = false;
+
+  for c in name.chars() {
+    if c.is_ascii_alphanumeric() {
+      if c.is_ascii_uppercase() && previous_lower {
+        result.push('_');
+      }
+
+      previous_lower = c.is_ascii_lowercase() || c.is_ascii_digit();
+      result.push(c.to_ascii_lowercase());
+    } else {
+      if !result.is_empty() && !result.ends_with('_') {
+        result.push('_');
+      }
+
+      previous_lower = false;
+    }
+  }
+
+  let mut result = result.trim_matches('_').to_string();
+  if result.is_empty() || result.starts_with(|c: char| c.is_ascii_digit()) {
+    result.insert_str(0, "field_");
+  }
+
+  if RUST_KEYWORDS.contains(&result.as_str()) {
+    result.push('_');
+  }
+
+  result
+}
+
+fn with_multiplicity(fields: Vec<Field>, multiplicity: Multiplicity) -> Vec<Field> {
+  fields
+    .into_iter()
+    .map(|mut field| {
+      if field.multiplicity != Multiplicity::Many {
+        field.multiplicity = multiplicity;
+      }
+
+      field
+    })
+    .collect()
+}
+
+// Rules are keyed by the scope they were found in, and their name,
+// as imported scripts bring their own scope with them
+type RuleKey = (*const RefCell<ScopeContext>, String);
+
+struct Generator {
+  scopes: Vec<ScopeContextRef>,
+  types: Vec<TypeDefinition>,
+  type_names: HashSet<String>,
+  rule_type_names: HashMap<RuleKey, String>,
+  rules: HashMap<RuleKey, RuleState>,
+}
+
+impl Generator {
+  fn new(scope: ScopeContextRef) -> Self {
+    Self {
+      scopes: vec![scope],
+      types: Vec::new(),
+      type_names: RESERVED_TYPE_NAMES
+        .iter()
+        .map(|name| name.to_string())
+        .collect(),
+      rule_type_names: HashMap::new(),
+      rules: HashMap::new(),
+    }
+  }
+
+  fn reserve_type_name(&mut self, base: &str) -> String {
+    let mut name = base.to_string();
+    let mut index = 2;
+
+    while self.type_names.contains(&name) {
+      name = format!("{}{}", base, index);
+      index += 1;
+    }
+
+    self.type_names.insert(name.clone());
+
+    name
+  }
+
+  fn define(&mut self, name: &str, token_names: Vec<String>, shape: TypeShape) {
+    self.types.push(TypeDefinition {
+      name: name.to_string(),
+      token_names,
+      shape,
+    });
+  }
+
+  fn find_type(&self, name: &str) -> Option<&TypeDefinition> {
+    self.types.iter().find(|definition| definition.name == name)
+  }
+
+  fn type_name_for(&mut self, owner: &str, hint: Option<&str>, token_name: &str) -> String {
+    match hint {
+      Some(hint) => hint.to_string(),
+      None => self.reserve_type_name(&format!("{}{}", owner, pascal_case(token_name))),
+    }
+  }
+
+  fn children_fields(&mut self, children: Option<Vec<MatcherRef>>, owner: &str) -> Vec<Field> {
+    let mut fields = Vec::new();
+
+    for child in children.unwrap_or_default() {
+      fields.extend(self.fields_of(&child, owner, None));
+    }
+
+    fields
+  }
+
+  // The fields a matcher contributes to the token of its parent
+  fn fields_of(&mut self, matcher: &MatcherRef, owner: &str, hint: Option<&str>) -> Vec<Field> {
+    let _matcher = matcher.borrow();
+    let name = _matcher.get_name().to_string();
+    let children = _matcher.get_children();
+    let is_consuming = _matcher.is_consuming();
+    let kind = _matcher.get_kind();
+
+    drop(_matcher);
+
+    match kind {
+      MatcherKind::Wrapper => {
+        let child = match children.and_then(|children| children.first().cloned()) {
+          Some(child) => child,
+          None => return Vec::new(),
+        };
+
+        let scope = matcher.borrow().get_scope();
+        if let Some(scope) = &scope {
+          self.scopes.push(scope.clone());
+        }
+
+        let fields = self.fields_of(&child, owner, hint);
+
+        if scope.is_some() {
+          self.scopes.pop();
+        }
+
+        fields
+      }
+      MatcherKind::Equals(_)
+      | MatcherKind::OneOf(_)
+      | MatcherKind::Matches(_)
+      | MatcherKind::Sequence(..) => {
+        let field_type = match hint {
+          Some(hint) => {
+            self.define(hint, vec![name.clone()], TypeShape::Leaf);
+            FieldType::Named(hint.to_string())
+          }
+          None => FieldType::Text,
+        };
+
+        vec![Field {
+          label: name.clone(),
+          accepts: vec![name],
+          field_type,
+          multiplicity: Multiplicity::One,
+        }]
+      }
+      MatcherKind::Ref(Some(target)) => self.rule_fields(&target),
+      MatcherKind::Program | MatcherKind::Loop(_) | MatcherKind::SepBy(_) => {
+        let type_name = self.type_name_for(owner, hint, &name);
+        let (fields, repeats, multiplicity) = match kind {
+          MatcherKind::Loop(range) | MatcherKind::SepBy(range) => {
+            let fields = self.children_fields(children, &type_name);
+            let multiplicity = if range.start == 0 {
+              Multiplicity::Optional
+            } else {
+              Multiplicity::One
+            };
+
+            (
+              with_multiplicity(fields, Multiplicity::Many),
+              true,
+              multiplicity,
+            )
+          }
+          _ => (
+            self.children_fields(children, &type_name),
+            false,
+            Multiplicity::One,
+          ),
+        };
+
+        self.define(
+          &type_name,
+          vec![name.clone()],
+          TypeShape::Struct(fields, repeats),
+        );
+
+        vec![Field {
+          label: name.clone(),
+          accepts: vec![name],
+          field_type: FieldType::Named(type_name),
+          multiplicity,
+        }]
+      }
+      MatcherKind::Switch => {
+        let mut variants: Vec<Field> = Vec::new();
+        let mut multiplicity = Multiplicity::One;
+        let type_name = self.type_name_for(owner, hint, &name);
+
+        for child in children.unwrap_or_default() {
+          let fields = self.fields_of(&child, &type_name, None);
+
+          if fields.is_empty() {
+            multiplicity = Multiplicity::Optional;
+          }
+
+          for field in fields {
+            if field.multiplicity != Multiplicity::One {
+              multiplicity = Multiplicity::Optional;
+            }
+
+            let accepts = self.accepted_names(&field);
+            let known = variants
+              .iter()
+              .any(|variant| self.accepted_names(variant) == accepts);
+
+            if !known {
+              variants.push(field);
+            }
+          }
+        }
+
+        let token_names: Vec<String> = variants
+          .iter()
+          .flat_map(|variant| self.accepted_names(variant))
+          .collect();
+
+        self.define(&type_name, token_names.clone(), TypeShape::Enum(variants));
+
+        vec![Field {
+          label: name,
+          accepts: token_names,
+          field_type: FieldType::Named(type_name),
+          multiplicity,
+        }]
+      }
+      MatcherKind::Optional => match children.and_then(|children| children.first().cloned()) {
+        Some(child) => {
+          with_multiplicity(self.fields_of(&child, owner, hint), Multiplicity::Optional)
+        }
+        None => Vec::new(),
+      },
+      MatcherKind::ProxyChildren => {
+        let child = match children.and_then(|children| children.first().cloned()) {
+          Some(child) => child,
+          None => return Vec::new(),
+        };
+
+        let mut child = child;
+        while child.borrow().get_kind() == MatcherKind::Wrapper {
+          let next = child
+            .borrow()
+            .get_children()
+            .and_then(|children| children.first().cloned());
+
+          match next {
+            Some(next) => child = next,
+            None => return Vec::new(),
+          }
+        }
+
+        let kind = child.borrow().get_kind();
+        let grand_children = child.borrow().get_children();
+
+        match kind {
+          MatcherKind::Program => self.children_fields(grand_children, owner),
+          MatcherKind::Loop(_) => with_multiplicity(
+            self.children_fields(grand_children, owner),
+            Multiplicity::Many,
+          ),
+          _ => self.fields_of(&child, owner, hint),
+        }
+      }
+      MatcherKind::Not | MatcherKind::Peek | MatcherKind::Discard => Vec::new(),
+      MatcherKind::Ref(None) | MatcherKind::Other => {
+        if !is_consuming {
+          return Vec::new();
+        }
+
+        vec![Field {
+          label: name.clone(),
+          accepts: vec![name],
+          field_type: FieldType::Token,
+          multiplicity: Multiplicity::One,
+        }]
+      }
+    }
+  }
+
+  fn rule_key(&self, rule_name: &str) -> RuleKey {
+    (
+      Rc::as_ptr(self.scopes.last().unwrap()),
+      rule_name.to_string(),
+    )
+  }
+
+  fn rule_fields(&mut self, rule_name: &str) -> Vec<Field> {
+    let rule_key = self.rule_key(rule_name);
+
+    match self.rules.get(&rule_key) {
+      Some(RuleState::Done(fields)) => return fields.clone(),
+      Some(RuleState::InProgress(type_name)) => {
+        return vec![Field {
+          label: rule_name.to_string(),
+          accepts: vec![rule_name.to_string()],
+          field_type: FieldType::Named(type_name.clone()),
+          multiplicity: Multiplicity::One,
+        }]
+      }
+      None => {}
+    }
+
+    let scope = self.scopes.last().unwrap().clone();
+    let matcher = match scope.borrow().get(rule_name) {
+      Some(VariableType::Matcher(matcher)) => matcher,
+      _ => {
+        return vec![Field {
+          label: rule_name.to_string(),
+          accepts: vec![rule_name.to_string()],
+          field_type: FieldType::Token,
+          multiplicity: Multiplicity::One,
+        }]
+      }
+    };
+
+    let type_name = match self.rule_type_names.get(&rule_key) {
+      Some(type_name) => type_name.clone(),
+      None => self.reserve_type_name(&pascal_case(rule_name)),
+    };
+
+    self
+      .rules
+      .insert(rule_key.clone(), RuleState::InProgress(type_name.clone()));
+
+    let mut fields = self.fields_of(&matcher, &type_name, Some(&type_name));
+    let defined = self
+      .types
+      .iter()
+      .position(|definition| definition.name == type_name);
+
+    match defined {
+      Some(index) => {
+        // Imported rules are wrapped in a `SetScope`, and
+        // rename their tokens to the name they were imported as
+        let is_import = matcher.borrow().get_name() == "SetScope";
+
+        if is_import && !matches!(self.types[index].shape, TypeShape::Enum(_)) {
+          self.types[index].token_names = vec![rule_name.to_string()];
+
+          for field in &mut fields {
+            field.label = rule_name.to_string();
+            field.accepts = vec![rule_name.to_string()];
+          }
+        }
+      }
+      None => {
+        let target = match fields.as_slice() {
+          [field] => match &field.field_type {
+            FieldType::Named(name) => name.clone(),
+            FieldType::Text => "Text".to_string(),
+            FieldType::Token => "TokenRef".to_string(),
+          },
+          _ => "TokenRef".to_string(),
+        };
+
+        let token_names = match fields.as_slice() {
+          [field] => self.accepted_names(field),
+          _ => vec![rule_name.to_string()],
+        };
+
+        self.define(&type_name, token_names, TypeShape::Alias(target));
+      }
+    }
+
+    self.rules.insert(rule_key, RuleState::Done(fields.clone()));
+
+    fields
+  }
+
+  fn accepted_names(&self, field: &Field) -> Vec<String> {
+    match &field.field_type {
+      FieldType::Named(type_name) => match self.find_type(type_name) {
+        Some(definition) => match &definition.shape {
+          TypeShape::Alias(_) if definition.token_names.is_empty() => field.accepts.clone(),
+          _ => definition.token_names.clone(),
+        },
+        None => field.accepts.clone(),
+      },
+      _ => field.accepts.clone(),
+    }
+  }
+
+  fn conversion(field_type: &FieldType) -> String {
+    match field_type {
+      FieldType::Named(name) => format!("{}::try_from", name),
+      FieldType::Text => "Text::try_from".to_string(),
+      FieldType::Token => "Ok::<TokenRef, ParseError>".to_string(),
+    }
+  }
+
+  fn rust_type(field_type: &FieldType) -> String {
+    match field_type {
+      FieldType::Named(name) => name.clone(),
+      FieldType::Text => "Text".to_string(),
+      FieldType::Token => "TokenRef".to_string(),
+    }
+  }
+
+  fn quote_names(names: &[String]) -> String {
+    let names: Vec<String> = names.iter().map(|name| format!("{:?}", name)).collect();
+    format!("&[{}]", names.join(", "))
+  }
+
+  fn render(&self) -> String {
+    let mut output = String::new();
+
+    output.push_str(
+      "// This file is generated by `adextopa_core::codegen`. Do not edit it by hand.\n\n",
+    );
+    output.push_str("#[allow(unused_imports)]\nuse std::convert::TryFrom;\n\n");
+    output
+      .push_str("#[allow(unused_imports)]\nuse adextopa_core::codegen::runtime::{self, Text};\n");
+    output.push_str("#[allow(unused_imports)]\nuse adextopa_core::parse_error::ParseError;\n");
+    output.push_str("#[allow(unused_imports)]\nuse adextopa_core::source_range::SourceRange;\n");
+    output.push_str("#[allow(unused_imports)]\nuse adextopa_core::token::TokenRef;\n");
+
+    for definition in &self.types {
+      output.push('\n');
+
+      match &definition.shape {
+        TypeShape::Leaf => self.render_leaf(&mut output, definition),
+        TypeShape::Struct(fields, repeats) => {
+          self.render_struct(&mut output, definition, fields, *repeats)
+        }
+        TypeShape::Enum(variants) => self.render_enum(&mut output, definition, variants),
+        TypeShape::Alias(target) => {
+          writeln!(
+            output,
+            "#[allow(dead_code)]\npub type {} = {};",
+            definition.name, target
+          )
+          .unwrap();
+        }
+      }
+    }
+
+    output
+  }
+
+  fn render_leaf(&self, output: &mut String, definition: &TypeDefinition) {
+    let name = &definition.name;
+
+    writeln!(
+      output,
+      "#[allow(dead_code)]\n#[derive(Debug, Clone, PartialEq)]"
+    )
+    .unwrap();
+    writeln!(output, "pub struct {} {{", name).unwrap();
+    writeln!(output, "  pub range: SourceRange,").unwrap();
+    writeln!(output, "  pub value: String,").unwrap();
+    writeln!(output, "}}\n").unwrap();
+    writeln!(output, "impl TryFrom<TokenRef> for {} {{", name).unwrap();
+    writeln!(output, "  type Error = ParseError;\n").unwrap();
+    writeln!(
+      output,
+      "  fn try_from(token: TokenRef) -> Result<Self, Self::Error> {{"
+    )
+    .unwrap();
+    writeln!(
+      output,
+      "    runtime::expect_name(&token, {})?;",
+      Self::quote_names(&definition.token_names)
+    )
+    .unwrap();
+    writeln!(output, "    let text = Text::try_from(token)?;\n").unwrap();
+    writeln!(output, "    Ok(Self {{").unwrap();
+    writeln!(output, "      range: text.range,").unwrap();
+    writeln!(output, "      value: text.value,").unwrap();
+    writeln!(output, "    }})").unwrap();
+    writeln!(output, "  }}").unwrap();
+    writeln!(output, "}}").unwrap();
+  }
+
+  fn render_struct(
+    &self,
+    output: &mut String,
+    definition: &TypeDefinition,
+    fields: &[Field],
+    repeats: bool,
+  ) {
+    let name = &definition.name;
+    let mut field_names: Vec<String> = Vec::new();
+
+    for field in fields {
+      let base = snake_case(&field.label);
+      let mut field_name = base.clone();
+      let mut index = 2;
+
+      while field_name == "range" || field_names.contains(&field_name) {
+        field_name = format!("{}_{}", base, index);
+        index += 1;
+      }
+
+      field_names.push(field_name);
+    }
+
+    writeln!(output, "#[allow(dead_code)]\n#[derive(Debug, Clone)]").unwrap();
+    writeln!(output, "pub struct {} {{", name).unwrap();
+    writeln!(output, "  pub range: SourceRange,").unwrap();
+
+    for (field, field_name) in fields.iter().zip(&field_names) {
+      let rust_type = Self::rust_type(&field.field_type);
+      let rust_type = match field.multiplicity {
+        Multiplicity::One => rust_type,
+        Multiplicity::Optional => format!("Option<{}>", rust_type),
+        Multiplicity::Many => format!("Vec<{}>", rust_type),
+      };
+
+      writeln!(output, "  pub {}: {},", field_name, rust_type).unwrap();
+    }
+
+    writeln!(output, "}}\n").unwrap();
+    writeln!(output, "impl TryFrom<TokenRef> for {} {{", name).unwrap();
+    writeln!(output, "  type Error = ParseError;\n").unwrap();
+    writeln!(
+      output,
+      "  fn try_from(token: TokenRef) -> Result<Self, Self::Error> {{"
+    )
+    .unwrap();
+    writeln!(
+      output,
+      "    #[allow(unused_mut)]\n    let mut children = runtime::Children::collect(\n      &token,\n      {},\n      &[",
+      Self::quote_names(&definition.token_names)
+    )
+    .unwrap();
+
+    for field in fields {
+      writeln!(
+        output,
+        "        ({}, runtime::Multiplicity::{:?}),",
+        Self::quote_names(&self.accepted_names(field)),
+        field.multiplicity
+      )
+      .unwrap();
+    }
+
+    writeln!(output, "      ],\n      {},\n    )?;\n", repeats).unwrap();
+    writeln!(output, "    Ok(Self {{").unwrap();
+    writeln!(output, "      range: children.range(),").unwrap();
+
+    for (index, (field, field_name)) in fields.iter().zip(&field_names).enumerate() {
+      let conversion = Self::conversion(&field.field_type);
+      let value = match field.multiplicity {
+        Multiplicity::One => format!("{}(children.one({}))?", conversion, index),
+        Multiplicity::Optional => format!(
+          "children.optional({}).map({}).transpose()?",
+          index, conversion
+        ),
+        Multiplicity::Many => format!(
+          "children\n        .many({})\n        .into_iter()\n        .map({})\n        .collect::<Result<Vec<_>, _>>()?",
+          index, conversion
+        ),
+      };
+
+      writeln!(output, "      {}: {},", field_name, value).unwrap();
+    }
+
+    writeln!(output, "    }})").unwrap();
+    writeln!(output, "  }}").unwrap();
+    writeln!(output, "}}").unwrap();
+  }
+
+  fn render_enum(&self, output: &mut String, definition: &TypeDefinition, variants: &[Field]) {
+    let name = &definition.name;
+    let mut variant_names: Vec<String> = Vec::new();
+
+    for variant in variants {
+      let base = match &variant.field_type {
+        FieldType::Named(type_name) => type_name.clone(),
+        _ => pascal_case(&variant.label),
+      };
+
+      let mut variant_name = base.clone();
+      let mut index = 2;
+
+      while variant_names.contains(&variant_name) {
+        variant_name = format!("{}{}", base, index);
+        index += 1;
+      }
+
+      variant_names.push(variant_name);
+    }
+
+    writeln!(output, "#[allow(dead_code)]\n#[derive(Debug, Clone)]").unwrap();
+    writeln!(output, "pub enum {} {{", name).unwrap();
+
+    for (variant, variant_name) in variants.iter().zip(&variant_names) {
+      writeln!(
+        output,
+        "  {}({}),",
+        variant_name,
+        Self::rust_type(&variant.field_type)
+      )
+      .unwrap();
+    }
+
+    writeln!(output, "}}\n").unwrap();
+    writeln!(output, "impl TryFrom<TokenRef> for {} {{", name).unwrap();
+    writeln!(output, "  type Error = ParseError;\n").unwrap();
+    writeln!(
+      output,
+      "  fn try_from(token: TokenRef) -> Result<Self, Self::Error> {{"
+    )
+    .unwrap();
+    writeln!(
+      output,
+      "    runtime::expect_name(&token, {})?;\n",
+      Self::quote_names(&definition.token_names)
+    )
+    .unwrap();
+    writeln!(
+      output,
+      "    let name = token.borrow().get_name().clone();\n"
+    )
+    .unwrap();
+    writeln!(output, "    match name.as_str() {{").unwrap();
+
+    for (variant, variant_name) in variants.iter().zip(&variant_names) {
+      let names: Vec<String> = self
+        .accepted_names(variant)
+        .iter()
+        .map(|name| format!("{:?}", name))
+        .collect();
+
+      if names.is_empty() {
+        continue;
+      }
+
+      writeln!(
+        output,
+        "      {} => Ok(Self::{}({}(token)?)),",
+        names.join(" | "),
+        variant_name,
+        Self::conversion(&variant.field_type)
+      )
+      .unwrap();
+    }
+
+    writeln!(
+      output,
+      "      _ => Err(runtime::unexpected_token(&token, {})),",
+      Self::quote_names(&definition.token_names)
+    )
+    .unwrap();
+    writeln!(output, "    }}").unwrap();
+    writeln!(output, "  }}").unwrap();
+    writeln!(output, "}}").unwrap();
+  }
+}
+
+/// Generate Rust types, and `TryFrom<TokenRef>` conversions for them,
+/// from a compiled grammar. Every matcher stored in `scope` becomes a
+/// type named after its rule, and the root matcher becomes the type
+/// of the root token.
+pub fn generate(root_matcher: MatcherRef, scope: ScopeContextRef) -> String {
+  let mut generator = Generator::new(scope.clone());
+  let rule_names: Vec<String> = scope
+    .borrow()
+    .keys()
+    .into_iter()
+    .filter(|name| matches!(scope.borrow().get(name), Some(VariableType::Matcher(_))))
+    .collect();
+
+  for rule_name in &rule_names {
+    let type_name = generator.reserve_type_name(&pascal_case(rule_name));
+    let rule_key = generator.rule_key(rule_name);
+    generator.rule_type_names.insert(rule_key, type_name);
+  }
+
+  let root_name = root_matcher.borrow().get_name().to_string();
+  let mut root_type_name = pascal_case(&root_name);
+  if generator.type_names.contains(&root_type_name) {
+    root_type_name.push_str("Script");
+  }
+
+  let root_type_name = generator.reserve_type_name(&root_type_name);
+  generator.fields_of(&root_matcher, &root_type_name, Some(&root_type_name));
+
+  for rule_name in &rule_names {
+    generator.rule_fields(rule_name);
+  }
+
+  generator.render()
+}
+
+pub fn generate_from_str(source: &str, name: &str) -> Result<String, Vec<ParseError>> {
+  let parser = Parser::new(source);
+  let (_, root_matcher, scope) = compile_script(parser, name.to_string(), None, 0)?;
+
+  Ok(generate(root_matcher, scope))
+}
+
+pub fn generate_from_file(file_name: &str) -> Result<String, Vec<ParseError>> {
+  let full_path = match Path::new(file_name).canonicalize() {
+    Ok(path) => path,
+    Err(error) => {
+      return Err(vec![ParseError::new(&format!(
+        "Unable to open script '{}': {}",
+        file_name, error
+      ))])
+    }
+  };
+
+  let full_file_name = full_path.to_str().unwrap();
+  let parser = match Parser::new_from_file(full_file_name) {
+    Ok(parser) => parser,
+    Err(error) => {
+      return Err(vec![ParseError::new(&format!(
+        "Unable to read script '{}': {}",
+        file_name, error
+      ))])
+    }
+  };
+
+  let (_, root_matcher, scope) =
+    compile_script(parser, file_name.to_string(), Some(full_file_name), 0)?;
+
+  Ok(generate(root_matcher, scope))
+}
+
+/// For use from `build.rs`: generate types for the script at `file_name`,
+/// and write them to `out_file` (usually inside `OUT_DIR`). Include the
+/// result in a module of its own:
+///
+/// ```ignore
+/// mod ast {
+///   include!(concat!(env!("OUT_DIR"), "/ast.rs"));
+/// }
+/// ```
+pub fn generate_to_file(file_name: &str, out_file: &str) -> Result<(), Vec<ParseError>> {
+  let output = generate_from_file(file_name)?;
+
+  match std::fs::write(out_file, output) {
+    Ok(_) => Ok(()),
+    Err(error) => Err(vec![ParseError::new(&format!(
+      "Unable to write generated code to '{}': {}",
+      out_file, error
+    ))]),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{generate_from_file, generate_from_str, runtime::Text};
+  use crate::{
+    parser::Parser, parser_context::ParserContext,
+    script::current::parser::compile_script_from_file,
+  };
+
+  // Checked in, so that it is compiled along with the tests. If the
+  // generator or the grammar drift apart from it, either the build
+  // or `it_keeps_the_checked_in_types_up_to_date` fails
+  mod list_ast {
+    include!("script/v1/tests/script/test_list_generated.rs");
+  }
+
+  const LIST_SCRIPT: &str = "./src/script/v1/tests/script/test_list.axo";
+
+  #[test]
+  fn it_generates_types_for_a_script() {
+    let output = generate_from_file("./src/script/v1/tests/script/test_word.axo").unwrap();
+
+    assert!(
+      output.contains("pub struct Word {\n  pub range: SourceRange,\n  pub value: String,\n}")
+    );
+    assert!(
+      output.contains("pub struct WordScript {\n  pub range: SourceRange,\n  pub word: Word,\n}")
+    );
+    assert!(output.contains("(&[\"Word\"], runtime::Multiplicity::One),"));
+    assert!(output.contains("word: Word::try_from(children.one(0))?,"));
+  }
+
+  #[test]
+  fn it_generates_enums_for_switches_and_vectors_for_loops() {
+    let output = generate_from_str(
+      r"<!--[adextopa version='1' name='List']
+        Number = </\d+/>
+        Name = </[a-z]+/>
+        Value = <[ (<Number>) | (<Name>) ]>
+        Values = <{ (<Value>) </\s*/> }>
+      -->
+
+      (<Values>+)",
+      "List",
+    )
+    .unwrap();
+
+    assert!(output.contains("pub enum Value {\n  Number(Number),\n  Name(Name),\n}"));
+    assert!(output.contains("\"Number\" => Ok(Self::Number(Number::try_from(token)?)),"));
+    assert!(
+      output.contains("pub struct Values {\n  pub range: SourceRange,\n  pub value: Value,\n}")
+    );
+    assert!(output
+      .contains("pub struct ListLoop {\n  pub range: SourceRange,\n  pub values: Vec<Values>,\n}"));
+    assert!(output.contains("(&[\"Number\", \"Name\"], runtime::Multiplicity::One),"));
+  }
+
+  #[test]
+  fn it_converts_tokens_with_the_runtime() {
+    let parser = Parser::new("Testing 1234");
+    let parser_context = ParserContext::new(&parser, "Test");
+    let matcher = crate::Program!("Pair";
+      crate::Matches!("Key"; r"\w+"),
+      crate::Discard!(crate::Matches!(r"\s+")),
+      crate::Matches!("Value"; r"\d+"),
+    );
+
+    let token = ParserContext::tokenize(parser_context, matcher).unwrap();
+    let mut children = super::runtime::Children::collect(
+      &token,
+      &["Pair"],
+      &[
+        (&["Key"], super::runtime::Multiplicity::One),
+        (&["Value"], super::runtime::Multiplicity::Optional),
+      ],
+      false,
+    )
+    .unwrap();
+
+    let key = Text::try_from(children.one(0)).unwrap();
+    assert_eq!(key.value, "Testing");
+
+    let value = children
+      .optional(1)
+      .map(Text::try_from)
+      .transpose()
+      .unwrap();
+    assert_eq!(value.unwrap().value, "1234");
+
+    let result = super::runtime::Children::collect(
+      &token,
+      &["Pair"],
+      &[(&["Key"], super::runtime::Multiplicity::One)],
+      false,
+    );
+
+    assert_eq!(
+      result.err().unwrap().message,
+      "Unexpected `Value` child in `Pair`"
+    );
+  }
+
+  #[test]
+  fn it_keeps_the_checked_in_types_up_to_date() {
+    let output = generate_from_file(LIST_SCRIPT).unwrap();
+
+    assert_eq!(
+      output,
+      include_str!("script/v1/tests/script/test_list_generated.rs"),
+      "test_list_generated.rs is out of date, regenerate it from test_list.axo"
+    );
+  }
+
+  #[test]
+  fn it_parses_into_generated_types() {
+    let matcher = compile_script_from_file(LIST_SCRIPT, 0).unwrap();
+    let parser = Parser::new("abc 12 de");
+    let parser_context = ParserContext::new(&parser, "Test");
+    let token = ParserContext::tokenize(parser_context, matcher).unwrap();
+
+    let list = list_ast::List::try_from(token).unwrap();
+    let values: Vec<String> = list
+      .loop_
+      .values
+      .iter()
+      .map(|values| match &values.value {
+        list_ast::Value::Number(number) => format!("Number({})", number.value),
+        list_ast::Value::Name(name) => format!("Name({})", name.value),
+      })
+      .collect();
+
+    assert_eq!(values, ["Name(abc)", "Number(12)", "Name(de)"]);
+  }
+}
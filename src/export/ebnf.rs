@@ -0,0 +1,542 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::ops::Range;
+use std::rc::Rc;
+
+use crate::matcher::{MatcherKind, MatcherRef};
+use crate::matchers::program::loop_iteration_counts;
+use crate::scope::VariableType;
+use crate::scope_context::{ScopeContext, ScopeContextRef};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Notation {
+  // `Name <- a b / c`
+  Peg,
+  // W3C EBNF: `Name ::= a b | c`
+  Ebnf,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Expr {
+  Empty,
+  Literal(String),
+  Regex(String),
+  Sequence(Option<String>, Option<String>, Option<String>),
+  NonTerminal(String),
+  Seq(Vec<Expr>),
+  Choice(Vec<Expr>),
+  Repeat(Box<Expr>, Range<usize>),
+  Not(Box<Expr>),
+  Peek(Box<Expr>),
+  Annotated(String, Box<Expr>),
+  Builtin(String),
+}
+
+// Binding strength of each expression, used to decide where parentheses are needed
+const CHOICE: u8 = 0;
+const SEQUENCE: u8 = 1;
+const PREFIX: u8 = 2;
+const ATOM: u8 = 3;
+
+fn matcher_key(matcher: &MatcherRef) -> usize {
+  Rc::as_ptr(matcher) as *const u8 as usize
+}
+
+fn sequence(mut exprs: Vec<Expr>) -> Expr {
+  exprs.retain(|expr| *expr != Expr::Empty);
+
+  match exprs.len() {
+    0 => Expr::Empty,
+    1 => exprs.pop().unwrap(),
+    _ => Expr::Seq(exprs),
+  }
+}
+
+// The repeat range (in the loop sense) of the `(separator item)` tail
+// of a separated list whose item count is `range` (with an exclusive end)
+pub(crate) fn separated_tail_range(range: &Range<usize>) -> Range<usize> {
+  let start = range.start.saturating_sub(1);
+
+  match range.end {
+    usize::MAX => start..usize::MAX,
+    end => start..start + end.saturating_sub(2),
+  }
+}
+
+struct Printer {
+  notation: Notation,
+  names: HashMap<usize, String>,
+  used_names: HashSet<String>,
+  queue: VecDeque<(String, MatcherRef)>,
+  visiting: HashSet<usize>,
+  current: String,
+}
+
+impl Printer {
+  fn unique_name(&mut self, name: &str) -> String {
+    let mut unique = name.to_string();
+    let mut index = 1;
+
+    while self.used_names.contains(&unique) {
+      index += 1;
+      unique = format!("{}_{}", name, index);
+    }
+
+    self.used_names.insert(unique.clone());
+    unique
+  }
+
+  fn production_name(&mut self, matcher: &MatcherRef) -> String {
+    let key = matcher_key(matcher);
+    if let Some(name) = self.names.get(&key) {
+      return name.clone();
+    }
+
+    let name = self.unique_name(matcher.borrow().get_name());
+    self.names.insert(key, name.clone());
+    self.queue.push_back((name.clone(), matcher.clone()));
+
+    name
+  }
+
+  fn build_children(&mut self, matcher: &MatcherRef, top: bool) -> Expr {
+    let children = matcher.borrow().get_children().unwrap_or_default();
+
+    sequence(
+      children
+        .iter()
+        .map(|child| self.build(child, top))
+        .collect(),
+    )
+  }
+
+  // `top` is true while we are still at the root of the production
+  // being printed (wrappers don't change that), so the production's own
+  // named matcher is printed inline instead of as a reference to itself
+  fn build(&mut self, matcher: &MatcherRef, top: bool) -> Expr {
+    let key = matcher_key(matcher);
+
+    if !top {
+      if let Some(name) = self.names.get(&key) {
+        return Expr::NonTerminal(name.clone());
+      }
+
+      let (custom_name, name) = {
+        let _matcher = matcher.borrow();
+        (_matcher.has_custom_name(), _matcher.get_name().to_string())
+      };
+
+      if custom_name {
+        if name == self.current {
+          self.names.insert(key, name);
+        } else {
+          return Expr::NonTerminal(self.production_name(matcher));
+        }
+      }
+    }
+
+    if !self.visiting.insert(key) {
+      return Expr::NonTerminal(matcher.borrow().get_name().to_string());
+    }
+
+    let kind = matcher.borrow().get_kind();
+    let expr = match kind {
+      MatcherKind::Program => self.build_children(matcher, false),
+      MatcherKind::Switch => {
+        let children = matcher.borrow().get_children().unwrap_or_default();
+        let mut alternatives: Vec<Expr> = children
+          .iter()
+          .map(|child| self.build(child, false))
+          .collect();
+        alternatives.dedup();
+
+        match alternatives.len() {
+          0 => Expr::Empty,
+          1 => alternatives.pop().unwrap(),
+          _ => Expr::Choice(alternatives),
+        }
+      }
+      MatcherKind::Loop(range) => match self.build_children(matcher, false) {
+        Expr::Empty => Expr::Empty,
+        expr => Expr::Repeat(Box::new(expr), range),
+      },
+      MatcherKind::SepBy(range) => {
+        let children = matcher.borrow().get_children().unwrap_or_default();
+        let item = self.build(&children[0], false);
+        let separator = self.build(&children[1], false);
+        let tail_range = separated_tail_range(&range);
+        let tail = match tail_range.end - tail_range.start {
+          0 => Expr::Empty,
+          _ => Expr::Repeat(
+            Box::new(sequence(vec![separator, item.clone()])),
+            tail_range,
+          ),
+        };
+
+        match (range.start, sequence(vec![item, tail])) {
+          (_, Expr::Empty) => Expr::Empty,
+          (0, expr) => Expr::Repeat(Box::new(expr), 0..1),
+          (_, expr) => expr,
+        }
+      }
+      MatcherKind::Equals(Some(literal)) => Expr::Literal(literal),
+      MatcherKind::OneOf(Some(keywords)) => {
+        let mut alternatives: Vec<Expr> = keywords.into_iter().map(Expr::Literal).collect();
+        alternatives.dedup();
+
+        match alternatives.len() {
+          0 => Expr::Empty,
+          1 => alternatives.pop().unwrap(),
+          _ => Expr::Choice(alternatives),
+        }
+      }
+      MatcherKind::Matches(source) => Expr::Regex(source),
+      MatcherKind::Sequence(start, end, escape) => Expr::Sequence(start, end, escape),
+      MatcherKind::Ref(Some(target)) => Expr::NonTerminal(target),
+      MatcherKind::Equals(None) | MatcherKind::OneOf(None) | MatcherKind::Ref(None) => {
+        Expr::Builtin(matcher.borrow().get_name().to_string())
+      }
+      MatcherKind::Optional => match self.build_children(matcher, false) {
+        Expr::Empty => Expr::Empty,
+        expr => Expr::Repeat(Box::new(expr), 0..1),
+      },
+      MatcherKind::Not => Expr::Not(Box::new(self.build_children(matcher, false))),
+      MatcherKind::Peek => Expr::Peek(Box::new(self.build_children(matcher, false))),
+      MatcherKind::Discard => Expr::Annotated(
+        "discard".to_string(),
+        Box::new(self.build_children(matcher, false)),
+      ),
+      MatcherKind::ProxyChildren => Expr::Annotated(
+        "flatten".to_string(),
+        Box::new(self.build_children(matcher, false)),
+      ),
+      MatcherKind::Wrapper => self.build_children(matcher, top),
+      MatcherKind::Other => {
+        let name = matcher.borrow().get_name().to_string();
+
+        match self.build_children(matcher, false) {
+          Expr::Empty => Expr::Builtin(name),
+          expr => Expr::Annotated(name, Box::new(expr)),
+        }
+      }
+    };
+
+    self.visiting.remove(&key);
+
+    expr
+  }
+
+  fn quote(&self, literal: &str) -> String {
+    // W3C EBNF has no escapes, so prefer single quotes when that avoids one
+    if self.notation == Notation::Ebnf && literal.contains('"') && !literal.contains('\'') {
+      format!("'{}'", literal)
+    } else {
+      format!("{:?}", literal)
+    }
+  }
+
+  fn comment(&self, text: &str) -> String {
+    format!("/* {} */", text.replace("*/", "* /"))
+  }
+
+  fn render(&self, expr: &Expr, binding: u8) -> String {
+    let (output, strength) = match expr {
+      Expr::Empty => ("\"\"".to_string(), ATOM),
+      Expr::Literal(literal) => (self.quote(literal), ATOM),
+      Expr::Regex(source) => (format!("/{}/", source.replace('/', "\\/")), ATOM),
+      Expr::NonTerminal(name) => (name.clone(), ATOM),
+      Expr::Sequence(start, end, escape) => {
+        let quoted = |value: &Option<String>| match value {
+          Some(value) => self.quote(value),
+          None => "?".to_string(),
+        };
+
+        match self.notation {
+          Notation::Peg => {
+            let mut arguments = vec![quoted(start), quoted(end)];
+            if escape.is_some() {
+              arguments.push(quoted(escape));
+            }
+
+            (format!("@sequence({})", arguments.join(", ")), ATOM)
+          }
+          Notation::Ebnf => {
+            let body = match escape {
+              Some(_) => format!("any text, escaped by {}", quoted(escape)),
+              None => "any text".to_string(),
+            };
+
+            (
+              format!("{} {} {}", quoted(start), self.comment(&body), quoted(end)),
+              SEQUENCE,
+            )
+          }
+        }
+      }
+      Expr::Builtin(name) => match self.notation {
+        Notation::Peg => (format!("@{}", name), ATOM),
+        Notation::Ebnf => (self.comment(&format!("@{}", name)), ATOM),
+      },
+      Expr::Seq(exprs) => (
+        exprs
+          .iter()
+          .map(|expr| self.render(expr, PREFIX))
+          .collect::<Vec<String>>()
+          .join(" "),
+        SEQUENCE,
+      ),
+      Expr::Choice(exprs) => {
+        let separator = match self.notation {
+          Notation::Peg => " / ",
+          Notation::Ebnf => " | ",
+        };
+
+        (
+          exprs
+            .iter()
+            .map(|expr| self.render(expr, SEQUENCE))
+            .collect::<Vec<String>>()
+            .join(separator),
+          CHOICE,
+        )
+      }
+      Expr::Repeat(expr, range) => (self.render_repeat(expr, range), PREFIX),
+      Expr::Not(expr) => match self.notation {
+        Notation::Peg => (format!("!{}", self.render(expr, PREFIX)), PREFIX),
+        Notation::Ebnf => (
+          format!("{} {}", self.comment("@not"), self.render(expr, PREFIX)),
+          PREFIX,
+        ),
+      },
+      Expr::Peek(expr) => match self.notation {
+        Notation::Peg => (format!("&{}", self.render(expr, PREFIX)), PREFIX),
+        Notation::Ebnf => (
+          format!("{} {}", self.comment("@peek"), self.render(expr, PREFIX)),
+          PREFIX,
+        ),
+      },
+      Expr::Annotated(name, expr) => match self.notation {
+        Notation::Peg => (format!("@{}({})", name, self.render(expr, CHOICE)), ATOM),
+        Notation::Ebnf => (
+          format!(
+            "{} {}",
+            self.comment(&format!("@{}", name)),
+            self.render(expr, PREFIX)
+          ),
+          PREFIX,
+        ),
+      },
+    };
+
+    if strength < binding {
+      format!("({})", output)
+    } else {
+      output
+    }
+  }
+
+  fn render_repeat(&self, expr: &Expr, range: &Range<usize>) -> String {
+    let inner = self.render(expr, ATOM);
+    let counts = loop_iteration_counts(range);
+
+    // The counts have an exclusive end, where `usize::MAX` means "unbounded"
+    match (counts.start, counts.end) {
+      (0, 2) => format!("{}?", inner),
+      (0, usize::MAX) => format!("{}*", inner),
+      (1, usize::MAX) => format!("{}+", inner),
+      // The loop must iterate more times than it runs, so it never matches
+      (start, end) if end <= start => match self.notation {
+        Notation::Peg => "!\"\"".to_string(),
+        Notation::Ebnf => self.comment(&format!("never matches: {}", inner)),
+      },
+      (start, end) => {
+        let bounds = if end == usize::MAX {
+          format!("{{{},}}", start)
+        } else {
+          format!("{{{},{}}}", start, end - 1)
+        };
+
+        match self.notation {
+          Notation::Peg => format!("{}{}", inner, bounds),
+          Notation::Ebnf => {
+            let suffix = if start == 0 { "*" } else { "+" };
+            format!("{}{} {}", inner, suffix, self.comment(&bounds))
+          }
+        }
+      }
+    }
+  }
+
+  fn render_production(&self, name: &str, expr: &Expr) -> String {
+    let operator = match self.notation {
+      Notation::Peg => "<-",
+      Notation::Ebnf => "::=",
+    };
+
+    match expr {
+      // Top level alternatives go on their own lines, which keeps diffs readable
+      Expr::Choice(exprs) => {
+        let indent = " ".repeat(name.len() + 1);
+        let separator = match self.notation {
+          Notation::Peg => "/",
+          Notation::Ebnf => "|",
+        };
+        let alternatives: Vec<String> = exprs
+          .iter()
+          .map(|expr| self.render(expr, SEQUENCE))
+          .collect();
+
+        format!(
+          "{} {} {}",
+          name,
+          operator,
+          alternatives.join(&format!("\n{}{} ", indent, separator))
+        )
+      }
+      expr => format!("{} {} {}", name, operator, self.render(expr, CHOICE)),
+    }
+  }
+}
+
+/// Render a grammar as text. The root matcher becomes the first
+/// production, followed by every matcher stored in `scope` (in name
+/// order), followed by any other named matchers found along the way.
+pub fn render(root_matcher: MatcherRef, scope: ScopeContextRef, notation: Notation) -> String {
+  let mut printer = Printer {
+    notation,
+    names: HashMap::new(),
+    used_names: HashSet::new(),
+    queue: VecDeque::new(),
+    visiting: HashSet::new(),
+    current: String::new(),
+  };
+
+  let mut rules = Vec::new();
+  for name in scope.borrow().keys() {
+    if let Some(VariableType::Matcher(matcher)) = scope.borrow().get(&name) {
+      printer.used_names.insert(name.clone());
+      printer.names.insert(matcher_key(&matcher), name.clone());
+      rules.push((name, matcher));
+    }
+  }
+
+  let root_name = printer.unique_name(root_matcher.borrow().get_name());
+  printer
+    .names
+    .insert(matcher_key(&root_matcher), root_name.clone());
+  printer.queue.push_back((root_name, root_matcher));
+  printer.queue.extend(rules);
+
+  let mut productions = Vec::new();
+  while let Some((name, matcher)) = printer.queue.pop_front() {
+    printer.current = name.clone();
+    let expr = printer.build(&matcher, true);
+    productions.push(printer.render_production(&name, &expr));
+  }
+
+  let mut output = productions.join("\n");
+  output.push('\n');
+  output
+}
+
+/// Render any matcher graph, without a scope to pull rules from
+pub fn render_matcher(matcher: MatcherRef, notation: Notation) -> String {
+  render(matcher, ScopeContext::new(), notation)
+}
+
+pub fn to_peg(root_matcher: MatcherRef, scope: ScopeContextRef) -> String {
+  render(root_matcher, scope, Notation::Peg)
+}
+
+pub fn to_ebnf(root_matcher: MatcherRef, scope: ScopeContextRef) -> String {
+  render(root_matcher, scope, Notation::Ebnf)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{render_matcher, to_ebnf, to_peg, Notation};
+  use crate::{
+    parser::Parser, script::current::parser::compile_script, Discard, Equals, Loop, Matches, Not,
+    Optional, Program, Ref, Sequence, Switch,
+  };
+
+  #[test]
+  fn it_renders_a_matcher_graph() {
+    let matcher = Program!("Call";
+      Matches!("Identifier"; r"[a-z]+"),
+      Discard!(Equals!("(")),
+      Optional!(Switch!(Ref!("Number"), Sequence!("\"", "\"", "\\"))),
+      Loop!(0..3; Program!(Equals!(","), Not!(Equals!(")")))),
+      Equals!(")"),
+    );
+
+    assert_eq!(
+      render_matcher(matcher.clone(), Notation::Peg),
+      concat!(
+        "Call <- Identifier @discard(\"(\") (Number / @sequence(\"\\\"\", \"\\\"\", \"\\\\\"))? (\",\" !\")\"){0,3} \")\"\n",
+        "Identifier <- /[a-z]+/\n",
+      )
+    );
+
+    assert_eq!(
+      render_matcher(matcher, Notation::Ebnf),
+      concat!(
+        "Call ::= Identifier /* @discard */ \"(\" (Number | '\"' /* any text, escaped by \"\\\\\" */ '\"')? (\",\" /* @not */ \")\")* /* {0,3} */ \")\"\n",
+        "Identifier ::= /[a-z]+/\n",
+      )
+    );
+  }
+
+  #[test]
+  fn it_renders_the_iteration_counts_of_a_loop() {
+    let matcher = Program!("Counts";
+      Loop!(2..5; Equals!("a")),
+      Loop!(3..5; Equals!("b")),
+    );
+
+    assert_eq!(
+      render_matcher(matcher.clone(), Notation::Peg),
+      "Counts <- \"a\"{2,3} !\"\"\n"
+    );
+
+    assert_eq!(
+      render_matcher(matcher, Notation::Ebnf),
+      "Counts ::= \"a\"+ /* {2,3} */ /* never matches: \"b\" */\n"
+    );
+  }
+
+  #[test]
+  fn it_renders_a_compiled_script() {
+    let parser = Parser::new(
+      r#"<!--[adextopa version='1' name='Words']
+        Word = <[ </\w+/> | <='x'> | <Words> ]>
+        Words = <{ (<Word>) <!='y'> ?</\s+/> }>
+      -->
+
+      (<Words>+)"#,
+    );
+
+    let (_, root_matcher, scope) = compile_script(parser, "Words".to_string(), None, 0).unwrap();
+
+    assert_eq!(
+      to_peg(root_matcher.clone(), scope.clone()),
+      concat!(
+        "Words_2 <- @flatten(Words+)\n",
+        "Word <- @discard(/\\w+/)\n",
+        "     / @discard(\"x\")\n",
+        "     / @discard(Words)\n",
+        "Words <- Word @discard(!\"y\") @discard(/\\s+/?)\n",
+      )
+    );
+
+    assert_eq!(
+      to_ebnf(root_matcher, scope),
+      concat!(
+        "Words_2 ::= /* @flatten */ Words+\n",
+        "Word ::= /* @discard */ /\\w+/\n",
+        "     | /* @discard */ \"x\"\n",
+        "     | /* @discard */ Words\n",
+        "Words ::= Word /* @discard */ /* @not */ \"y\" /* @discard */ /\\s+/?\n",
+      )
+    );
+  }
+}
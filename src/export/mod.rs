@@ -0,0 +1,2 @@
+pub mod ebnf;
+pub mod railroad;
@@ -0,0 +1,803 @@
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::fmt::Write;
+use std::path::Path;
+use std::rc::Rc;
+
+use super::ebnf::separated_tail_range;
+use crate::matcher::{MatcherKind, MatcherRef};
+use crate::scope::VariableType;
+use crate::scope_context::ScopeContextRef;
+
+// Radius of the arcs connecting branches, and the vertical
+// spacing between stacked branches
+const ARC: f64 = 10.0;
+// Horizontal space between items in a sequence
+const GAP: f64 = 10.0;
+const BOX_HEIGHT: f64 = 22.0;
+const CHAR_WIDTH: f64 = 8.0;
+const PADDING: f64 = 10.0;
+const LABEL_HEIGHT: f64 = 14.0;
+const MARGIN: f64 = 20.0;
+
+const STYLE: &str = "path { fill: none; stroke: #222; stroke-width: 1.5; } \
+rect { fill: #fffbe6; stroke: #222; stroke-width: 1.5; } \
+rect.terminal { fill: #e6f4ff; } \
+rect.group { fill: none; stroke: #888; stroke-dasharray: 4 3; } \
+text { font-family: monospace; font-size: 13px; fill: #222; } \
+text.label { font-size: 11px; fill: #666; } \
+a text { fill: #0645ad; text-decoration: underline; }";
+
+#[derive(Debug, Clone, PartialEq)]
+enum Node {
+  Skip,
+  Terminal(String, &'static str),
+  NonTerminal(String),
+  Sequence(Vec<Node>),
+  Choice(Vec<Node>),
+  // Body, and a label describing how often it repeats
+  Loop(Box<Node>, Option<String>),
+  Group(Box<Node>, String),
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Layout {
+  width: f64,
+  up: f64,
+  down: f64,
+}
+
+fn escape_xml(text: &str) -> String {
+  text
+    .replace('&', "&amp;")
+    .replace('<', "&lt;")
+    .replace('>', "&gt;")
+    .replace('"', "&quot;")
+}
+
+fn text_width(text: &str) -> f64 {
+  text.chars().count() as f64 * CHAR_WIDTH
+}
+
+fn sequence(mut nodes: Vec<Node>) -> Node {
+  nodes.retain(|node| *node != Node::Skip);
+
+  match nodes.len() {
+    0 => Node::Skip,
+    1 => nodes.pop().unwrap(),
+    _ => Node::Sequence(nodes),
+  }
+}
+
+fn optional(node: Node) -> Node {
+  match node {
+    Node::Skip => Node::Skip,
+    node => Node::Choice(vec![Node::Skip, node]),
+  }
+}
+
+fn repeat_label(start: usize, end: usize) -> Option<String> {
+  // `usize::MAX` means "unbounded", and a loop over
+  // `start..end` runs at most `end - start` iterations
+  match (start, end) {
+    (0, usize::MAX) | (1, usize::MAX) => None,
+    (start, usize::MAX) => Some(format!("{}+ times", start)),
+    (start, end) => Some(format!("{}..{} times", start, end - start)),
+  }
+}
+
+fn build_node(matcher: &MatcherRef, visiting: &mut HashSet<usize>) -> Node {
+  let key = Rc::as_ptr(matcher) as *const u8 as usize;
+  if !visiting.insert(key) {
+    return Node::NonTerminal(matcher.borrow().get_name().to_string());
+  }
+
+  let _matcher = matcher.borrow();
+  let children = _matcher.get_children().unwrap_or_default();
+  let child_nodes = |visiting: &mut HashSet<usize>| -> Vec<Node> {
+    children
+      .iter()
+      .map(|child| build_node(child, visiting))
+      .collect()
+  };
+
+  let node = match _matcher.get_kind() {
+    MatcherKind::Program => sequence(child_nodes(visiting)),
+    MatcherKind::Switch => {
+      let mut alternatives = child_nodes(visiting);
+      alternatives.dedup();
+
+      match alternatives.len() {
+        0 => Node::Skip,
+        1 => alternatives.pop().unwrap(),
+        _ => Node::Choice(alternatives),
+      }
+    }
+    MatcherKind::Loop(range) => {
+      let body = sequence(child_nodes(visiting));
+      let label = repeat_label(range.start, range.end);
+      let node = Node::Loop(Box::new(body), label);
+
+      if range.start == 0 {
+        optional(node)
+      } else {
+        node
+      }
+    }
+    MatcherKind::SepBy(range) => {
+      let mut nodes = child_nodes(visiting);
+      let separator = nodes.pop().unwrap();
+      let item = nodes.pop().unwrap();
+      let tail_range = separated_tail_range(&range);
+      let tail = match tail_range.end - tail_range.start {
+        0 => Node::Skip,
+        _ => {
+          let label = repeat_label(tail_range.start, tail_range.end);
+          let node = Node::Loop(Box::new(sequence(vec![separator, item.clone()])), label);
+
+          if tail_range.start == 0 {
+            optional(node)
+          } else {
+            node
+          }
+        }
+      };
+      let node = sequence(vec![item, tail]);
+
+      if range.start == 0 {
+        optional(node)
+      } else {
+        node
+      }
+    }
+    MatcherKind::Equals(Some(literal)) => Node::Terminal(format!("{:?}", literal), "terminal"),
+    MatcherKind::Equals(None) => Node::Terminal("<dynamic>".to_string(), "terminal"),
+    MatcherKind::OneOf(Some(keywords)) => {
+      let mut alternatives: Vec<Node> = keywords
+        .iter()
+        .map(|keyword| Node::Terminal(format!("{:?}", keyword), "terminal"))
+        .collect();
+      alternatives.dedup();
+
+      match alternatives.len() {
+        0 => Node::Skip,
+        1 => alternatives.pop().unwrap(),
+        _ => Node::Choice(alternatives),
+      }
+    }
+    MatcherKind::OneOf(None) => Node::Terminal("<dynamic>".to_string(), "terminal"),
+    MatcherKind::Matches(source) => Node::Terminal(format!("/{}/", source), "terminal"),
+    MatcherKind::Sequence(start, end, _) => Node::Terminal(
+      format!(
+        "{} … {}",
+        start.map_or("?".to_string(), |start| format!("{:?}", start)),
+        end.map_or("?".to_string(), |end| format!("{:?}", end))
+      ),
+      "terminal",
+    ),
+    MatcherKind::Ref(Some(target)) => Node::NonTerminal(target),
+    MatcherKind::Ref(None) => Node::NonTerminal("<dynamic>".to_string()),
+    MatcherKind::Optional => optional(sequence(child_nodes(visiting))),
+    MatcherKind::Not => match sequence(child_nodes(visiting)) {
+      Node::Skip => Node::Skip,
+      node => Node::Group(Box::new(node), "not".to_string()),
+    },
+    MatcherKind::Peek => match sequence(child_nodes(visiting)) {
+      Node::Skip => Node::Skip,
+      node => Node::Group(Box::new(node), "peek".to_string()),
+    },
+    MatcherKind::Discard | MatcherKind::ProxyChildren | MatcherKind::Wrapper => {
+      sequence(child_nodes(visiting))
+    }
+    MatcherKind::Other => {
+      if !_matcher.is_consuming() {
+        Node::Skip
+      } else if children.is_empty() {
+        Node::Terminal(_matcher.get_name().to_string(), "other")
+      } else {
+        sequence(child_nodes(visiting))
+      }
+    }
+  };
+
+  drop(_matcher);
+  visiting.remove(&key);
+
+  node
+}
+
+fn collect_references(node: &Node, references: &mut BTreeSet<String>) {
+  match node {
+    Node::NonTerminal(name) => {
+      references.insert(name.clone());
+    }
+    Node::Sequence(nodes) | Node::Choice(nodes) => {
+      for node in nodes {
+        collect_references(node, references);
+      }
+    }
+    Node::Loop(node, _) | Node::Group(node, _) => collect_references(node, references),
+    Node::Skip | Node::Terminal(..) => {}
+  }
+}
+
+fn layout(node: &Node) -> Layout {
+  match node {
+    Node::Skip => Layout {
+      width: 0.0,
+      up: 0.0,
+      down: 0.0,
+    },
+    Node::Terminal(text, _) | Node::NonTerminal(text) => Layout {
+      width: text_width(text) + PADDING * 2.0,
+      up: BOX_HEIGHT / 2.0,
+      down: BOX_HEIGHT / 2.0,
+    },
+    Node::Sequence(nodes) => {
+      let layouts: Vec<Layout> = nodes.iter().map(layout).collect();
+
+      Layout {
+        width: layouts.iter().map(|l| l.width).sum::<f64>()
+          + GAP * (layouts.len().saturating_sub(1)) as f64,
+        up: layouts.iter().map(|l| l.up).fold(0.0, f64::max),
+        down: layouts.iter().map(|l| l.down).fold(0.0, f64::max),
+      }
+    }
+    Node::Choice(nodes) => {
+      let layouts: Vec<Layout> = nodes.iter().map(layout).collect();
+      let offsets = choice_offsets(&layouts);
+      let last = layouts.len() - 1;
+
+      Layout {
+        width: layouts.iter().map(|l| l.width).fold(0.0, f64::max) + ARC * 4.0,
+        up: layouts[0].up,
+        down: offsets[last] + layouts[last].down,
+      }
+    }
+    Node::Loop(node, label) => {
+      let inner = layout(node);
+      let label_height = if label.is_some() { LABEL_HEIGHT } else { 0.0 };
+
+      Layout {
+        width: inner.width + ARC * 4.0,
+        up: inner.up,
+        down: loop_offset(&inner) + label_height,
+      }
+    }
+    Node::Group(node, _) => {
+      let inner = layout(node);
+
+      Layout {
+        width: inner.width + PADDING * 2.0,
+        up: inner.up + PADDING + LABEL_HEIGHT,
+        down: inner.down + PADDING,
+      }
+    }
+  }
+}
+
+// Vertical offset of each branch of a choice, relative to the main line
+fn choice_offsets(layouts: &[Layout]) -> Vec<f64> {
+  let mut offsets = vec![0.0];
+
+  for index in 1..layouts.len() {
+    let previous = offsets[index - 1] + layouts[index - 1].down;
+    offsets.push(previous + ARC * 2.0 + layouts[index].up);
+  }
+
+  offsets
+}
+
+// Vertical offset of the return path of a loop
+fn loop_offset(inner: &Layout) -> f64 {
+  f64::max(inner.down + ARC, ARC * 2.0)
+}
+
+struct Renderer<'a> {
+  output: String,
+  link: &'a dyn Fn(&str) -> Option<String>,
+}
+
+impl<'a> Renderer<'a> {
+  fn line(&mut self, x1: f64, x2: f64, y: f64) {
+    if x2 > x1 {
+      writeln!(self.output, "<path d=\"M{} {} H{}\"/>", x1, y, x2).unwrap();
+    }
+  }
+
+  fn render(&mut self, node: &Node, x: f64, y: f64) {
+    let node_layout = layout(node);
+
+    match node {
+      Node::Skip => {}
+      Node::Terminal(text, class) => {
+        writeln!(
+          self.output,
+          "<rect class=\"{}\" x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" rx=\"10\"/>",
+          class,
+          x,
+          y - BOX_HEIGHT / 2.0,
+          node_layout.width,
+          BOX_HEIGHT
+        )
+        .unwrap();
+        self.text(text, x + node_layout.width / 2.0, y + 4.0, "");
+      }
+      Node::NonTerminal(name) => {
+        let link = (self.link)(name);
+
+        if let Some(link) = &link {
+          writeln!(self.output, "<a href=\"{}\">", escape_xml(link)).unwrap();
+        }
+
+        writeln!(
+          self.output,
+          "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\"/>",
+          x,
+          y - BOX_HEIGHT / 2.0,
+          node_layout.width,
+          BOX_HEIGHT
+        )
+        .unwrap();
+        self.text(name, x + node_layout.width / 2.0, y + 4.0, "");
+
+        if link.is_some() {
+          writeln!(self.output, "</a>").unwrap();
+        }
+      }
+      Node::Sequence(nodes) => {
+        let mut cursor = x;
+
+        for (index, node) in nodes.iter().enumerate() {
+          if index > 0 {
+            self.line(cursor, cursor + GAP, y);
+            cursor += GAP;
+          }
+
+          self.render(node, cursor, y);
+          cursor += layout(node).width;
+        }
+      }
+      Node::Choice(nodes) => {
+        let layouts: Vec<Layout> = nodes.iter().map(layout).collect();
+        let offsets = choice_offsets(&layouts);
+        let inner_start = x + ARC * 2.0;
+        let inner_end = x + node_layout.width - ARC * 2.0;
+
+        for (index, node) in nodes.iter().enumerate() {
+          let branch_y = y + offsets[index];
+          let branch_end = inner_start + layouts[index].width;
+
+          if index == 0 {
+            self.line(x, inner_start, y);
+          } else {
+            let drop = offsets[index] - ARC * 2.0;
+            writeln!(
+              self.output,
+              "<path d=\"M{} {} a{arc} {arc} 0 0 1 {arc} {arc} v{} a{arc} {arc} 0 0 0 {arc} {arc}\"/>",
+              x,
+              y,
+              drop,
+              arc = ARC
+            )
+            .unwrap();
+          }
+
+          self.render(node, inner_start, branch_y);
+          self.line(branch_end, inner_end, branch_y);
+
+          if index == 0 {
+            self.line(inner_end, x + node_layout.width, y);
+          } else {
+            let rise = offsets[index] - ARC * 2.0;
+            writeln!(
+              self.output,
+              "<path d=\"M{} {} a{arc} {arc} 0 0 0 {arc} -{arc} v-{} a{arc} {arc} 0 0 1 {arc} -{arc}\"/>",
+              inner_end,
+              branch_y,
+              rise,
+              arc = ARC
+            )
+            .unwrap();
+          }
+        }
+      }
+      Node::Loop(node, label) => {
+        let inner = layout(node);
+        let inner_start = x + ARC * 2.0;
+        let inner_end = inner_start + inner.width;
+        let bottom = y + loop_offset(&inner);
+
+        self.line(x, inner_start, y);
+        self.render(node, inner_start, y);
+        self.line(inner_end, x + node_layout.width, y);
+
+        writeln!(
+          self.output,
+          "<path d=\"M{} {} a{arc} {arc} 0 0 1 {arc} {arc} V{} a{arc} {arc} 0 0 1 -{arc} {arc} H{} a{arc} {arc} 0 0 1 -{arc} -{arc} V{} a{arc} {arc} 0 0 1 {arc} -{arc}\"/>",
+          inner_end,
+          y,
+          bottom - ARC,
+          inner_start,
+          y + ARC,
+          arc = ARC
+        )
+        .unwrap();
+
+        if let Some(label) = label {
+          self.text(
+            label,
+            inner_start + inner.width / 2.0,
+            bottom + LABEL_HEIGHT - 2.0,
+            "label",
+          );
+        }
+      }
+      Node::Group(node, label) => {
+        let inner = layout(node);
+
+        writeln!(
+          self.output,
+          "<rect class=\"group\" x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\"/>",
+          x,
+          y - inner.up - PADDING,
+          node_layout.width,
+          inner.up + inner.down + PADDING * 2.0
+        )
+        .unwrap();
+
+        self.text(
+          label,
+          x + text_width(label) / 2.0,
+          y - inner.up - PADDING - 3.0,
+          "label",
+        );
+
+        self.line(x, x + PADDING, y);
+        self.render(node, x + PADDING, y);
+        self.line(x + PADDING + inner.width, x + node_layout.width, y);
+      }
+    }
+  }
+
+  fn text(&mut self, text: &str, x: f64, y: f64, class: &str) {
+    let class = if class.is_empty() {
+      String::new()
+    } else {
+      format!(" class=\"{}\"", class)
+    };
+
+    writeln!(
+      self.output,
+      "<text{} x=\"{}\" y=\"{}\" text-anchor=\"middle\">{}</text>",
+      class,
+      x,
+      y,
+      escape_xml(text)
+    )
+    .unwrap();
+  }
+}
+
+fn render_svg(name: &str, node: &Node, link: &dyn Fn(&str) -> Option<String>) -> String {
+  let node_layout = layout(node);
+  let width = node_layout.width + MARGIN * 2.0 + ARC * 2.0;
+  let height = node_layout.up + node_layout.down + MARGIN * 2.0;
+  let x = MARGIN + ARC;
+  let y = MARGIN + node_layout.up;
+  let mut renderer = Renderer {
+    output: String::new(),
+    link,
+  };
+
+  writeln!(
+    renderer.output,
+    "<svg xmlns=\"http://www.w3.org/2000/svg\" class=\"railroad-diagram\" width=\"{}\" height=\"{}\" viewBox=\"0 0 {} {}\">",
+    width, height, width, height
+  )
+  .unwrap();
+  writeln!(renderer.output, "<title>{}</title>", escape_xml(name)).unwrap();
+  writeln!(renderer.output, "<style>{}</style>", STYLE).unwrap();
+
+  // Start and end markers
+  writeln!(
+    renderer.output,
+    "<path d=\"M{} {} v{} M{} {} H{}\"/>",
+    MARGIN,
+    y - ARC,
+    ARC * 2.0,
+    MARGIN,
+    y,
+    x
+  )
+  .unwrap();
+
+  renderer.render(node, x, y);
+
+  let end = x + node_layout.width;
+  writeln!(
+    renderer.output,
+    "<path d=\"M{} {} H{} m0 -{} v{}\"/>",
+    end,
+    y,
+    end + ARC,
+    ARC,
+    ARC * 2.0
+  )
+  .unwrap();
+  writeln!(renderer.output, "</svg>").unwrap();
+
+  renderer.output
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RailroadDiagram {
+  pub name: String,
+  // Unique, file name safe identifier of this diagram
+  pub id: String,
+  pub svg: String,
+  // Names of the rules referenced from this diagram
+  pub references: Vec<String>,
+}
+
+fn diagram_id(name: &str) -> String {
+  name
+    .chars()
+    .map(|c| {
+      if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+        c
+      } else {
+        '_'
+      }
+    })
+    .collect()
+}
+
+fn rules(root_matcher: &MatcherRef, scope: &ScopeContextRef) -> Vec<(String, String, Node)> {
+  let mut rules = Vec::new();
+  let mut ids = HashSet::new();
+
+  for name in scope.borrow().keys() {
+    if let Some(VariableType::Matcher(matcher)) = scope.borrow().get(&name) {
+      let id = diagram_id(&name);
+      ids.insert(id.clone());
+      rules.push((name, id, build_node(&matcher, &mut HashSet::new())));
+    }
+  }
+
+  let root_name = root_matcher.borrow().get_name().to_string();
+  let mut root_id = diagram_id(&root_name);
+  while ids.contains(&root_id) {
+    root_id.push_str("-root");
+  }
+
+  rules.insert(
+    0,
+    (
+      root_name,
+      root_id,
+      build_node(root_matcher, &mut HashSet::new()),
+    ),
+  );
+
+  rules
+}
+
+fn build_diagrams(
+  root_matcher: &MatcherRef,
+  scope: &ScopeContextRef,
+  link_format: &dyn Fn(&str) -> String,
+) -> Vec<RailroadDiagram> {
+  let rules = rules(root_matcher, scope);
+  let rule_ids: HashMap<String, String> = rules
+    .iter()
+    .skip(1)
+    .map(|(name, id, _)| (name.clone(), id.clone()))
+    .collect();
+  let link = |name: &str| rule_ids.get(name).map(|id| link_format(id));
+
+  rules
+    .iter()
+    .map(|(name, id, node)| {
+      let mut references = BTreeSet::new();
+      collect_references(node, &mut references);
+
+      RailroadDiagram {
+        name: name.clone(),
+        id: id.clone(),
+        svg: render_svg(name, node, &link),
+        references: references.into_iter().collect(),
+      }
+    })
+    .collect()
+}
+
+/// One standalone SVG railroad diagram per named rule (the root
+/// matcher first). References to other rules link to `<id>.svg`.
+pub fn diagrams(root_matcher: MatcherRef, scope: ScopeContextRef) -> Vec<RailroadDiagram> {
+  build_diagrams(&root_matcher, &scope, &|id| format!("{}.svg", id))
+}
+
+/// A single HTML page with every rule's diagram inlined, and
+/// links between rules (both in the diagrams, and as lists).
+pub fn html_index(root_matcher: MatcherRef, scope: ScopeContextRef) -> String {
+  let diagrams = build_diagrams(&root_matcher, &scope, &|id| format!("#rule-{}", id));
+  let ids: HashMap<&str, &str> = diagrams
+    .iter()
+    .skip(1)
+    .map(|diagram| (diagram.name.as_str(), diagram.id.as_str()))
+    .collect();
+  let link_list = |names: &[&str]| -> String {
+    names
+      .iter()
+      .map(|name| match ids.get(name) {
+        Some(id) => format!("<a href=\"#rule-{}\">{}</a>", id, escape_xml(name)),
+        None => escape_xml(name),
+      })
+      .collect::<Vec<String>>()
+      .join(", ")
+  };
+
+  let mut output = String::new();
+  let title = escape_xml(&diagrams[0].name);
+
+  writeln!(
+    output,
+    "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">"
+  )
+  .unwrap();
+  writeln!(output, "<title>{}</title>", title).unwrap();
+  writeln!(
+    output,
+    "<style>body {{ font-family: sans-serif; }} section {{ margin-bottom: 2em; }}</style>"
+  )
+  .unwrap();
+  writeln!(output, "</head>\n<body>\n<h1>{}</h1>\n<ul>", title).unwrap();
+
+  for diagram in &diagrams {
+    writeln!(
+      output,
+      "<li><a href=\"#rule-{}\">{}</a></li>",
+      diagram.id,
+      escape_xml(&diagram.name)
+    )
+    .unwrap();
+  }
+
+  writeln!(output, "</ul>").unwrap();
+
+  for diagram in &diagrams {
+    writeln!(
+      output,
+      "<section id=\"rule-{}\">\n<h2>{}</h2>\n{}",
+      diagram.id,
+      escape_xml(&diagram.name),
+      diagram.svg
+    )
+    .unwrap();
+
+    let uses: Vec<&str> = diagram
+      .references
+      .iter()
+      .map(|name| name.as_str())
+      .collect();
+    if !uses.is_empty() {
+      writeln!(output, "<p>Uses: {}</p>", link_list(&uses)).unwrap();
+    }
+
+    let used_by: Vec<&str> = diagrams
+      .iter()
+      .filter(|other| other.references.contains(&diagram.name))
+      .map(|other| other.name.as_str())
+      .collect();
+    if !used_by.is_empty() {
+      writeln!(output, "<p>Used by: {}</p>", link_list(&used_by)).unwrap();
+    }
+
+    writeln!(output, "</section>").unwrap();
+  }
+
+  writeln!(output, "</body>\n</html>").unwrap();
+
+  output
+}
+
+/// Write `<id>.svg` for every rule, and an `index.html`, into `directory`
+pub fn export_to_directory(
+  root_matcher: MatcherRef,
+  scope: ScopeContextRef,
+  directory: &Path,
+) -> std::io::Result<()> {
+  std::fs::create_dir_all(directory)?;
+
+  for diagram in diagrams(root_matcher.clone(), scope.clone()) {
+    std::fs::write(directory.join(format!("{}.svg", diagram.id)), diagram.svg)?;
+  }
+
+  std::fs::write(
+    directory.join("index.html"),
+    html_index(root_matcher, scope),
+  )
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{build_node, diagrams, html_index, Node};
+  use crate::{
+    parser::Parser, script::current::parser::compile_script, Equals, Loop, Matches, Not, Optional,
+    Program, Ref, Switch,
+  };
+  use std::collections::HashSet;
+
+  #[test]
+  fn it_builds_diagram_nodes_from_matchers() {
+    let matcher = Program!(
+      Equals!("("),
+      Switch!(Ref!("Number"), Matches!(r"[a-z]+")),
+      Optional!(Not!(Equals!(")"))),
+      Loop!(1..4; Equals!(",")),
+    );
+
+    assert_eq!(
+      build_node(&matcher, &mut HashSet::new()),
+      Node::Sequence(vec![
+        Node::Terminal("\"(\"".to_string(), "terminal"),
+        Node::Choice(vec![
+          Node::NonTerminal("Number".to_string()),
+          Node::Terminal("/[a-z]+/".to_string(), "terminal"),
+        ]),
+        Node::Choice(vec![
+          Node::Skip,
+          Node::Group(
+            Box::new(Node::Terminal("\")\"".to_string(), "terminal")),
+            "not".to_string()
+          ),
+        ]),
+        Node::Loop(
+          Box::new(Node::Terminal("\",\"".to_string(), "terminal")),
+          Some("1..3 times".to_string())
+        ),
+      ])
+    );
+  }
+
+  #[test]
+  fn it_renders_linked_diagrams_for_each_rule() {
+    let parser = Parser::new(
+      r"<!--[adextopa version='1' name='Words']
+        Word = </\w+/>
+        Words = <{ (<Word>) </\s*/> }>
+      -->
+
+      (<Words>+)",
+    );
+
+    let (_, root_matcher, scope) = compile_script(parser, "Words".to_string(), None, 0).unwrap();
+    let diagrams = diagrams(root_matcher.clone(), scope.clone());
+    let names: Vec<(&str, &str)> = diagrams
+      .iter()
+      .map(|diagram| (diagram.name.as_str(), diagram.id.as_str()))
+      .collect();
+
+    assert_eq!(
+      names,
+      vec![
+        ("Words", "Words-root"),
+        ("Word", "Word"),
+        ("Words", "Words")
+      ]
+    );
+
+    assert!(diagrams[0]
+      .svg
+      .starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\""));
+    assert!(diagrams[0].svg.contains("<a href=\"Words.svg\">"));
+    assert!(diagrams[2].svg.contains("<a href=\"Word.svg\">"));
+    assert!(diagrams[1].svg.contains("/\\w+/</text>"));
+    assert_eq!(diagrams[2].references, vec!["Word".to_string()]);
+
+    let html = html_index(root_matcher, scope);
+    assert!(html.contains("<section id=\"rule-Word\">"));
+    assert!(html.contains("<a href=\"#rule-Word\">"));
+    assert!(html.contains("<p>Used by: <a href=\"#rule-Words\">Words</a></p>"));
+  }
+}
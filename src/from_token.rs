@@ -0,0 +1,248 @@
+use std::fmt::Display;
+use std::str::FromStr;
+
+use crate::parse_error::ParseError;
+use crate::source_range::SourceRange;
+use crate::token::TokenRef;
+
+// Convert a token (sub)tree into a user type. Usually
+// implemented with `#[derive(adextopa_macros::FromToken)]`.
+pub trait FromToken: Sized {
+  fn from_token(token: &TokenRef) -> Result<Self, ParseError>;
+}
+
+impl FromToken for String {
+  fn from_token(token: &TokenRef) -> Result<Self, ParseError> {
+    Ok(token.borrow().get_value().clone())
+  }
+}
+
+impl FromToken for TokenRef {
+  fn from_token(token: &TokenRef) -> Result<Self, ParseError> {
+    Ok(token.clone())
+  }
+}
+
+impl FromToken for SourceRange {
+  fn from_token(token: &TokenRef) -> Result<Self, ParseError> {
+    Ok(*token.borrow().get_captured_range())
+  }
+}
+
+impl<T> FromToken for Box<T>
+where
+  T: FromToken,
+{
+  fn from_token(token: &TokenRef) -> Result<Self, ParseError> {
+    Ok(Box::new(T::from_token(token)?))
+  }
+}
+
+// The functions below are used by code generated by `#[derive(FromToken)]`
+
+pub fn expect_name(token: &TokenRef, name: &str) -> Result<(), ParseError> {
+  let _token = token.borrow();
+
+  if _token.get_name() == name {
+    Ok(())
+  } else {
+    Err(ParseError::new_with_range(
+      &format!(
+        "Expected a `{}` token, but received a `{}` token instead",
+        name,
+        _token.get_name()
+      ),
+      *_token.get_matched_range(),
+    ))
+  }
+}
+
+pub fn find_child(token: &TokenRef, name: &str) -> Option<TokenRef> {
+  token.borrow().find_child(name)
+}
+
+pub fn required_child(token: &TokenRef, name: &str, field: &str) -> Result<TokenRef, ParseError> {
+  match find_child(token, name) {
+    Some(child) => Ok(child),
+    None => {
+      let _token = token.borrow();
+
+      Err(ParseError::new_with_range(
+        &format!(
+          "Expected `{}` token to have a `{}` child (for field `{}`)",
+          _token.get_name(),
+          name,
+          field
+        ),
+        *_token.get_matched_range(),
+      ))
+    }
+  }
+}
+
+pub fn children_named(token: &TokenRef, name: &str) -> Vec<TokenRef> {
+  token
+    .borrow()
+    .get_children()
+    .iter()
+    .filter(|child| child.borrow().get_name() == name)
+    .cloned()
+    .collect()
+}
+
+pub fn find_attribute(token: &TokenRef, name: &str) -> Option<String> {
+  token.borrow().get_attribute(name).cloned()
+}
+
+pub fn required_attribute(token: &TokenRef, name: &str, field: &str) -> Result<String, ParseError> {
+  match find_attribute(token, name) {
+    Some(value) => Ok(value),
+    None => {
+      let _token = token.borrow();
+
+      Err(ParseError::new_with_range(
+        &format!(
+          "Expected `{}` token to have a `{}` attribute (for field `{}`)",
+          _token.get_name(),
+          name,
+          field
+        ),
+        *_token.get_matched_range(),
+      ))
+    }
+  }
+}
+
+pub fn parse_str<T>(token: &TokenRef, value: &str, field: &str) -> Result<T, ParseError>
+where
+  T: FromStr,
+  T::Err: Display,
+{
+  match value.parse::<T>() {
+    Ok(result) => Ok(result),
+    Err(error) => Err(ParseError::new_with_range(
+      &format!(
+        "Unable to parse `{}` for field `{}`: {}",
+        value, field, error
+      ),
+      *token.borrow().get_matched_range(),
+    )),
+  }
+}
+
+pub fn parse_value<T>(token: &TokenRef, field: &str) -> Result<T, ParseError>
+where
+  T: FromStr,
+  T::Err: Display,
+{
+  let value = token.borrow().get_value().clone();
+  parse_str(token, &value, field)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::FromToken;
+  use crate::{
+    parse_error::ParseError, parser::Parser, parser_context::ParserContext,
+    source_range::SourceRange, Discard, Equals, Loop, Map, Matches, Optional, Program, TokenResult,
+  };
+
+  #[derive(adextopa_macros::FromToken, Debug, PartialEq)]
+  #[token(name = "Attribute")]
+  struct Attribute {
+    #[token(child = "Name")]
+    name: String,
+    #[token(child = "Value", from_str)]
+    value: u32,
+    #[token(child = "Unit")]
+    unit: Option<String>,
+    #[token(attribute = "kind")]
+    kind: String,
+    #[token(attribute = "missing")]
+    missing: Option<String>,
+    #[token(range)]
+    range: SourceRange,
+  }
+
+  #[derive(adextopa_macros::FromToken, Debug, PartialEq)]
+  struct Attributes {
+    #[token(child = "Attribute")]
+    attributes: Vec<Attribute>,
+  }
+
+  fn attribute_matcher() -> crate::matcher::MatcherRef {
+    Map!(
+      Program!("Attribute";
+        Matches!("Name"; r"\w+"),
+        Discard!(Equals!("=")),
+        Matches!("Value"; r"\d+|[a-z]+"),
+        Optional!(Matches!("Unit"; r"px|em")),
+        Discard!(Matches!(r"\s*")),
+      ),
+      |token, _, __| {
+        token.borrow_mut().set_attribute("kind", "size");
+        TokenResult!(token.clone())
+      }
+    )
+  }
+
+  #[test]
+  fn it_converts_a_token_into_a_struct() {
+    let parser = Parser::new("width=10px");
+    let parser_context = ParserContext::new(&parser, "Test");
+    let token = ParserContext::tokenize(parser_context, attribute_matcher()).unwrap();
+
+    assert_eq!(
+      Attribute::from_token(&token),
+      Ok(Attribute {
+        name: "width".to_string(),
+        value: 10,
+        unit: Some("px".to_string()),
+        kind: "size".to_string(),
+        missing: None,
+        range: SourceRange::new(0, 10),
+      })
+    );
+  }
+
+  #[test]
+  fn it_converts_repeated_children_into_a_vec() {
+    let parser = Parser::new("width=10px height=20");
+    let parser_context = ParserContext::new(&parser, "Test");
+    let matcher = Loop!("Attributes"; attribute_matcher());
+    let token = ParserContext::tokenize(parser_context, matcher).unwrap();
+    let attributes = Attributes::from_token(&token).unwrap();
+
+    assert_eq!(attributes.attributes.len(), 2);
+    assert_eq!(attributes.attributes[1].name, "height");
+    assert_eq!(attributes.attributes[1].value, 20);
+    assert_eq!(attributes.attributes[1].unit, None);
+  }
+
+  #[test]
+  fn it_reports_errors_with_a_range() {
+    let parser = Parser::new("width=ten");
+    let parser_context = ParserContext::new(&parser, "Test");
+    let token = ParserContext::tokenize(parser_context, attribute_matcher()).unwrap();
+
+    assert_eq!(
+      Attribute::from_token(&token),
+      Err(ParseError::new_with_range(
+        "Unable to parse `ten` for field `value`: invalid digit found in string",
+        SourceRange::new(6, 9)
+      ))
+    );
+
+    let parser = Parser::new("width");
+    let parser_context = ParserContext::new(&parser, "Test");
+    let token = ParserContext::tokenize(parser_context, Matches!("Attribute"; r"\w+")).unwrap();
+
+    assert_eq!(
+      Attribute::from_token(&token),
+      Err(ParseError::new_with_range(
+        "Expected `Attribute` token to have a `Name` child (for field `name`)",
+        SourceRange::new(0, 5)
+      ))
+    );
+  }
+}
@@ -0,0 +1,400 @@
+use std::collections::HashMap;
+
+use super::{
+  first_child, parse_source, token_error, Expr, ImportWarning, ImportedGrammar, Rule, RuleKind,
+};
+use crate::matcher::MatcherRef;
+use crate::parse_error::ParseError;
+use crate::token::TokenRef;
+use crate::{Discard, Equals, Loop, Matches, Optional, Program, ProxyChildren, Ref, Switch};
+
+// Whitespace inside of a rule: a line break only continues
+// the rule if the next line starts with whitespace
+const C_WSP: &str = r"(?:[ \t]|(?:;[^\r\n]*)?\r?\n[ \t])*";
+// Blank lines and comments between rules
+const C_NL: &str = r"(?:[ \t]*(?:;[^\r\n]*)?(?:\r?\n|$))*";
+const RULE_NAME: &str = r"[A-Za-z][A-Za-z0-9-]*";
+const NUM_VAL: &str = r"%[bdxBDX][0-9A-Fa-f]+(?:(?:\.[0-9A-Fa-f]+)+|-[0-9A-Fa-f]+)?";
+
+// RFC 5234 Appendix B.1, as regular expressions
+const CORE_RULES: &[(&str, &str)] = &[
+  ("ALPHA", "[A-Za-z]"),
+  ("BIT", "[01]"),
+  ("CHAR", r"[\x01-\x7F]"),
+  ("CR", r"\r"),
+  ("CRLF", r"\r\n"),
+  ("CTL", r"[\x00-\x1F\x7F]"),
+  ("DIGIT", "[0-9]"),
+  ("DQUOTE", "\""),
+  ("HEXDIG", "[0-9A-Fa-f]"),
+  ("HTAB", r"\t"),
+  ("LF", r"\n"),
+  ("LWSP", r"(?:[ \t]|\r\n[ \t])*"),
+  ("OCTET", r"[\x00-\xFF]"),
+  ("SP", " "),
+  ("VCHAR", r"[\x21-\x7E]"),
+  ("WSP", r"[ \t]"),
+];
+
+fn c_wsp() -> MatcherRef {
+  Discard!(Matches!(C_WSP))
+}
+
+fn grammar() -> (MatcherRef, Vec<(&'static str, MatcherRef)>) {
+  let alternation = Program!("Alternation";
+    Ref!("Concatenation"),
+    Optional!(ProxyChildren!(Loop!(c_wsp(), Discard!(Equals!("/")), c_wsp(), Ref!("Concatenation")))),
+  );
+
+  let concatenation = Program!("Concatenation";
+    Ref!("Repetition"),
+    Optional!(ProxyChildren!(Loop!(c_wsp(), Ref!("Repetition")))),
+  );
+
+  let repetition = Program!("Repetition";
+    Optional!(Matches!("Repeat"; r"\d*\*\d*|\d+")),
+    Switch!(
+      Program!("Group";
+        Discard!(Equals!("(")),
+        c_wsp(),
+        Ref!("Alternation"),
+        c_wsp(),
+        Discard!(Equals!(")")),
+      ),
+      Program!("Option";
+        Discard!(Equals!("[")),
+        c_wsp(),
+        Ref!("Alternation"),
+        c_wsp(),
+        Discard!(Equals!("]")),
+      ),
+      Matches!("CharVal"; r#"(?:%[siSI])?"[^"]*""#),
+      Matches!("NumVal"; NUM_VAL),
+      Matches!("ProseVal"; r"<[^>]*>"),
+      Matches!("RuleName"; RULE_NAME),
+    ),
+  );
+
+  let rule = Program!("Rule";
+    Discard!(Matches!(C_NL)),
+    Matches!("Name"; RULE_NAME),
+    c_wsp(),
+    Matches!("Operator"; r"=/|="),
+    c_wsp(),
+    Ref!("Alternation"),
+    c_wsp(),
+  );
+
+  let root = Program!("Grammar"; ProxyChildren!(Loop!(rule)), Discard!(Matches!(C_NL)));
+
+  (
+    root,
+    vec![
+      ("Alternation", alternation),
+      ("Concatenation", concatenation),
+      ("Repetition", repetition),
+    ],
+  )
+}
+
+// ABNF rule names are case insensitive, and may contain `-`
+fn rule_key(name: &str) -> String {
+  name.to_ascii_lowercase()
+}
+
+fn rule_name(name: &str) -> String {
+  name.replace('-', "_")
+}
+
+struct Translator {
+  rule: String,
+  // Lower case rule name -> rule name as first defined
+  names: HashMap<String, String>,
+  warnings: Vec<ImportWarning>,
+}
+
+impl Translator {
+  fn warn(&mut self, token: &TokenRef, message: String) {
+    self.warnings.push(ImportWarning {
+      rule: Some(self.rule.clone()),
+      message,
+      range: Some(*token.borrow().get_matched_range()),
+    });
+  }
+
+  fn num_val(&mut self, token: &TokenRef) -> Result<Expr, ParseError> {
+    let value = token.borrow().get_value().clone();
+    let radix = match &value[1..2] {
+      "b" | "B" => 2,
+      "d" | "D" => 10,
+      _ => 16,
+    };
+
+    let to_char = |digits: &str| -> Result<char, ParseError> {
+      u32::from_str_radix(digits, radix)
+        .ok()
+        .and_then(char::from_u32)
+        .ok_or_else(|| token_error(token, &format!("Invalid character value `{}`", digits)))
+    };
+
+    let digits = &value[2..];
+    if let Some((start, end)) = digits.split_once('-') {
+      Ok(Expr::Regex(format!(
+        "[{}-{}]",
+        regex::escape(&to_char(start)?.to_string()),
+        regex::escape(&to_char(end)?.to_string())
+      )))
+    } else {
+      let literal = digits
+        .split('.')
+        .map(to_char)
+        .collect::<Result<String, ParseError>>()?;
+
+      Ok(Expr::Literal(literal))
+    }
+  }
+
+  fn translate(&mut self, token: &TokenRef) -> Result<Expr, ParseError> {
+    let name = token.borrow().get_name().to_string();
+    let children = token.borrow().get_children().clone();
+
+    match name.as_str() {
+      "Alternation" => Ok(Expr::choice(
+        children
+          .iter()
+          .map(|child| self.translate(child))
+          .collect::<Result<_, _>>()?,
+      )),
+      "Concatenation" => Ok(Expr::sequence(
+        children
+          .iter()
+          .map(|child| self.translate(child))
+          .collect::<Result<_, _>>()?,
+      )),
+      "Repetition" => {
+        let element = self.translate(children.last().unwrap())?;
+
+        if children.len() == 1 {
+          return Ok(element);
+        }
+
+        let repeat = children[0].borrow().get_value().clone();
+        let number = |digits: &str| -> Result<Option<usize>, ParseError> {
+          if digits.is_empty() {
+            Ok(None)
+          } else {
+            digits
+              .parse::<usize>()
+              .map(Some)
+              .map_err(|error| token_error(&children[0], &error.to_string()))
+          }
+        };
+
+        let (min, max) = match repeat.split_once('*') {
+          Some((min, max)) => (number(min)?.unwrap_or(0), number(max)?),
+          None => {
+            let count = number(&repeat)?;
+            (count.unwrap_or(0), count)
+          }
+        };
+
+        Ok(Expr::Repeat(Box::new(element), min, max))
+      }
+      "Group" => self.translate(&first_child(token).unwrap()),
+      "Option" => Ok(Expr::optional(
+        self.translate(&first_child(token).unwrap())?,
+      )),
+      "CharVal" => {
+        let value = token.borrow().get_value().clone();
+        let (case_sensitive, quoted) = match value.strip_prefix('%') {
+          Some(rest) => (rest.starts_with(['s', 'S']), &rest[1..]),
+          None => (false, value.as_str()),
+        };
+        let literal = &quoted[1..quoted.len() - 1];
+
+        if case_sensitive {
+          Ok(Expr::Literal(literal.to_string()))
+        } else {
+          Ok(Expr::case_insensitive(literal))
+        }
+      }
+      "NumVal" => self.num_val(token),
+      "ProseVal" => {
+        let prose = token.borrow().get_value().clone();
+        self.warn(
+          token,
+          format!("Prose value `{}` can not be translated", prose),
+        );
+        Ok(Expr::Empty)
+      }
+      "RuleName" => {
+        let name = token.borrow().get_value().clone();
+
+        match self.names.get(&rule_key(&name)) {
+          Some(defined) => Ok(Expr::Ref(rule_name(defined))),
+          None => Ok(Expr::Ref(rule_name(&name))),
+        }
+      }
+      name => Err(token_error(
+        token,
+        &format!("Unexpected `{}` token in ABNF grammar", name),
+      )),
+    }
+  }
+}
+
+/// Import an RFC 5234 (and RFC 7405 `%s`/`%i`) ABNF grammar. Rule names
+/// have `-` replaced with `_`, and core rules are added when referenced.
+pub fn import(source: &str, name: &str) -> Result<ImportedGrammar, Vec<ParseError>> {
+  let (matcher, rules) = grammar();
+  let token = parse_source(source, name, matcher, rules)?;
+  let rule_tokens = token.borrow().get_children().clone();
+
+  let mut translator = Translator {
+    rule: String::new(),
+    names: HashMap::new(),
+    warnings: Vec::new(),
+  };
+
+  for rule_token in &rule_tokens {
+    let name = rule_token.borrow().get_children()[0]
+      .borrow()
+      .get_value()
+      .clone();
+
+    translator.names.entry(rule_key(&name)).or_insert(name);
+  }
+
+  let mut rules: Vec<Rule> = Vec::new();
+  for rule_token in &rule_tokens {
+    let _rule_token = rule_token.borrow();
+    let children = _rule_token.get_children();
+    let defined_name = translator.names[&rule_key(children[0].borrow().get_value())].clone();
+    let name = rule_name(&defined_name);
+    let incremental = children[1].borrow().get_value() == "=/";
+
+    translator.rule = name.clone();
+    let expr = translator
+      .translate(&children[2])
+      .map_err(|error| vec![error])?;
+
+    match rules.iter_mut().find(|rule| rule.name == name) {
+      Some(rule) => {
+        if !incremental {
+          translator.warn(
+            rule_token,
+            "Rule is defined more than once; later alternatives were appended".to_string(),
+          );
+        }
+
+        let mut alternatives = match std::mem::replace(&mut rule.expr, Expr::Empty) {
+          Expr::Choice(alternatives) => alternatives,
+          expr => vec![expr],
+        };
+
+        match expr {
+          Expr::Choice(exprs) => alternatives.extend(exprs),
+          expr => alternatives.push(expr),
+        }
+
+        rule.expr = Expr::choice(alternatives);
+      }
+      None => rules.push(Rule {
+        name,
+        kind: RuleKind::Normal,
+        expr,
+      }),
+    }
+  }
+
+  // Add any core rules that are used, but not defined
+  let mut index = 0;
+  while index < rules.len() {
+    for name in rules[index].expr.references() {
+      if rules.iter().any(|rule| rule.name == name) {
+        continue;
+      }
+
+      if let Some((_, source)) = CORE_RULES
+        .iter()
+        .find(|(core_name, _)| core_name.eq_ignore_ascii_case(&name))
+      {
+        let expr = if source.len() == 1 {
+          Expr::Literal(source.to_string())
+        } else {
+          Expr::Regex(source.to_string())
+        };
+
+        rules.push(Rule {
+          name,
+          kind: RuleKind::Normal,
+          expr,
+        });
+      }
+    }
+
+    index += 1;
+  }
+
+  Ok(ImportedGrammar::new(name, rules, translator.warnings))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::import;
+  use crate::import::Expr;
+  use crate::{parser::Parser, parser_context::ParserContext};
+
+  #[test]
+  fn it_imports_an_abnf_grammar() {
+    let grammar = import(
+      concat!(
+        "; A date, like 2022-01-31\r\n",
+        "date = year \"-\" month \"-\" day ; comment\r\n",
+        "year = 4DIGIT\r\n",
+        "month = 2DIGIT\r\n",
+        "day = 2DIGIT\r\n",
+        "       [ %s\"Z\" / %x2B.2B ]\r\n",
+        "day =/ <anything goes>\r\n",
+      ),
+      "Date",
+    )
+    .unwrap();
+
+    let names: Vec<&str> = grammar
+      .rules
+      .iter()
+      .map(|rule| rule.name.as_str())
+      .collect();
+
+    assert_eq!(names, vec!["date", "year", "month", "day", "DIGIT"]);
+    assert_eq!(
+      grammar.get_rule("year").unwrap().expr,
+      Expr::Repeat(Box::new(Expr::Ref("DIGIT".to_string())), 4, Some(4))
+    );
+    assert_eq!(
+      grammar.get_rule("day").unwrap().expr,
+      Expr::Choice(vec![
+        Expr::Sequence(vec![
+          Expr::Repeat(Box::new(Expr::Ref("DIGIT".to_string())), 2, Some(2)),
+          Expr::optional(Expr::Choice(vec![
+            Expr::Literal("Z".to_string()),
+            Expr::Literal("++".to_string()),
+          ])),
+        ]),
+        Expr::Empty,
+      ])
+    );
+    assert_eq!(
+      grammar.warnings[0].to_string(),
+      "Warning: day: Prose value `<anything goes>` can not be translated"
+    );
+
+    let (root_matcher, _) = grammar.to_matcher();
+    let parser = Parser::new("2022-01-31Z");
+    let parser_context = ParserContext::new(&parser, "Test");
+    let token = ParserContext::tokenize(parser_context, root_matcher).unwrap();
+
+    assert_eq!(token.borrow().get_value(), "2022-01-31Z");
+  }
+}
@@ -0,0 +1,291 @@
+use super::{
+  first_child, parse_source, token_error, Expr, ImportWarning, ImportedGrammar, Rule, RuleKind,
+};
+use crate::matcher::MatcherRef;
+use crate::parse_error::ParseError;
+use crate::token::TokenRef;
+use crate::{Discard, Equals, Loop, Matches, Not, Optional, Program, ProxyChildren, Ref, Switch};
+
+const WS: &str = r"(?:\s|/\*(?s:.*?)\*/)*";
+const SYMBOL: &str = r"[A-Za-z_][A-Za-z0-9_.-]*";
+// Rule numbers, as used in the XML specification (`[1] document ::= ...`)
+const LABEL: &str = r"\[[0-9a-zA-Z]+\]";
+
+fn ws() -> MatcherRef {
+  Discard!(Matches!(WS))
+}
+
+fn grammar() -> (MatcherRef, Vec<(&'static str, MatcherRef)>) {
+  let choice = Program!("Choice";
+    Ref!("Sequence"),
+    Optional!(ProxyChildren!(Loop!(ws(), Discard!(Equals!("|")), ws(), Ref!("Sequence")))),
+  );
+
+  // Productions have no terminator, so a sequence
+  // ends where the next `symbol ::=` starts
+  let sequence = Program!("Sequence";
+    Ref!("Difference"),
+    Optional!(ProxyChildren!(Loop!(
+      ws(),
+      Not!(Program!(
+        Optional!(Matches!(LABEL)),
+        ws(),
+        Matches!(SYMBOL),
+        ws(),
+        Equals!("::="),
+      )),
+      Ref!("Difference"),
+    ))),
+  );
+
+  let difference = Program!("Difference";
+    Ref!("Item"),
+    Optional!(Program!("Except";
+      ws(),
+      Discard!(Equals!("-")),
+      ws(),
+      Ref!("Item"),
+    )),
+  );
+
+  let item = Program!("Item";
+    Switch!(
+      Program!("Group";
+        Discard!(Equals!("(")),
+        ws(),
+        Ref!("Choice"),
+        ws(),
+        Discard!(Equals!(")")),
+      ),
+      Matches!("String"; r#""[^"]*"|'[^']*'"#),
+      Matches!("Constraint"; r"\[\s*(?i:wfc|vc)\s*:[^\]]*\]"),
+      Matches!("CharClass"; r"\[\^?(?:[^\]\\]|\\.)+\]"),
+      Matches!("Char"; r"#x[0-9A-Fa-f]+"),
+      Matches!("Symbol"; SYMBOL),
+    ),
+    Optional!(Matches!("Postfix"; r"[?*+]")),
+  );
+
+  let rule = Program!("Rule";
+    ws(),
+    Optional!(Discard!(Matches!(LABEL))),
+    ws(),
+    Matches!("Name"; SYMBOL),
+    ws(),
+    Discard!(Equals!("::=")),
+    ws(),
+    Ref!("Choice"),
+  );
+
+  let root = Program!("Grammar"; ProxyChildren!(Loop!(rule)), ws());
+
+  (
+    root,
+    vec![
+      ("Choice", choice),
+      ("Sequence", sequence),
+      ("Difference", difference),
+      ("Item", item),
+    ],
+  )
+}
+
+fn hex_char(token: &TokenRef, hex: &str) -> Result<char, ParseError> {
+  u32::from_str_radix(hex, 16)
+    .ok()
+    .and_then(char::from_u32)
+    .ok_or_else(|| token_error(token, &format!("Invalid character `#x{}`", hex)))
+}
+
+// Translate the body of a W3C character class (`[a-z#x20]`) into a regex class
+fn char_class(token: &TokenRef, class: &str) -> Result<String, ParseError> {
+  let (negated, body) = match class.strip_prefix('^') {
+    Some(body) => (true, body),
+    None => (false, class),
+  };
+
+  let mut result = String::from(if negated { "[^" } else { "[" });
+  let mut chars = body.chars().peekable();
+
+  while let Some(c) = chars.next() {
+    let c = if c == '#' && chars.peek() == Some(&'x') {
+      chars.next();
+
+      let mut hex = String::new();
+      while let Some(digit) = chars.peek().filter(|c| c.is_ascii_hexdigit()) {
+        hex.push(*digit);
+        chars.next();
+      }
+
+      hex_char(token, &hex)?
+    } else if c == '-' && !result.ends_with('[') && chars.peek().is_some() {
+      result.push('-');
+      continue;
+    } else {
+      c
+    };
+
+    result.push_str(&regex::escape(&c.to_string()));
+  }
+
+  result.push(']');
+  Ok(result)
+}
+
+struct Translator {
+  rule: String,
+  warnings: Vec<ImportWarning>,
+}
+
+impl Translator {
+  fn translate(&mut self, token: &TokenRef) -> Result<Expr, ParseError> {
+    let name = token.borrow().get_name().to_string();
+    let children = token.borrow().get_children().clone();
+    let value = token.borrow().get_value().clone();
+
+    match name.as_str() {
+      "Choice" => Ok(Expr::choice(
+        children
+          .iter()
+          .map(|child| self.translate(child))
+          .collect::<Result<_, _>>()?,
+      )),
+      "Sequence" => Ok(Expr::sequence(
+        children
+          .iter()
+          .map(|child| self.translate(child))
+          .collect::<Result<_, _>>()?,
+      )),
+      "Difference" => {
+        let expr = self.translate(&children[0])?;
+
+        match children.get(1) {
+          // `A - B` matches `A`, as long as it doesn't match `B`
+          Some(except) => Ok(Expr::Sequence(vec![
+            Expr::Not(Box::new(self.translate(&first_child(except).unwrap())?)),
+            expr,
+          ])),
+          None => Ok(expr),
+        }
+      }
+      "Item" => {
+        let expr = self.translate(&children[0])?;
+
+        match children
+          .get(1)
+          .map(|postfix| postfix.borrow().get_value().clone())
+        {
+          Some(postfix) if postfix == "?" => Ok(Expr::optional(expr)),
+          Some(postfix) if postfix == "*" => Ok(Expr::Repeat(Box::new(expr), 0, None)),
+          Some(_) => Ok(Expr::Repeat(Box::new(expr), 1, None)),
+          None => Ok(expr),
+        }
+      }
+      "Group" => self.translate(&first_child(token).unwrap()),
+      "String" => Ok(Expr::Literal(value[1..value.len() - 1].to_string())),
+      "Constraint" => {
+        self.warnings.push(ImportWarning {
+          rule: Some(self.rule.clone()),
+          message: format!("Constraint `{}` can not be translated", value),
+          range: Some(*token.borrow().get_matched_range()),
+        });
+
+        Ok(Expr::Empty)
+      }
+      "CharClass" => Ok(Expr::Regex(char_class(token, &value[1..value.len() - 1])?)),
+      "Char" => Ok(Expr::Literal(hex_char(token, &value[2..])?.to_string())),
+      "Symbol" => Ok(Expr::Ref(value.replace(['.', '-'], "_"))),
+      name => Err(token_error(
+        token,
+        &format!("Unexpected `{}` token in EBNF grammar", name),
+      )),
+    }
+  }
+}
+
+/// Import a W3C EBNF grammar (the notation used by the XML specification).
+/// `.` and `-` in symbol names are replaced with `_`.
+pub fn import(source: &str, name: &str) -> Result<ImportedGrammar, Vec<ParseError>> {
+  let (matcher, rules) = grammar();
+  let token = parse_source(source, name, matcher, rules)?;
+  let rule_tokens = token.borrow().get_children().clone();
+
+  let mut translator = Translator {
+    rule: String::new(),
+    warnings: Vec::new(),
+  };
+
+  let mut rules = Vec::new();
+  for rule_token in &rule_tokens {
+    let _rule_token = rule_token.borrow();
+    let children = _rule_token.get_children();
+    let rule_name = children[0].borrow().get_value().replace(['.', '-'], "_");
+
+    translator.rule = rule_name.clone();
+    let expr = translator
+      .translate(&children[1])
+      .map_err(|error| vec![error])?;
+
+    rules.push(Rule {
+      name: rule_name,
+      kind: RuleKind::Normal,
+      expr,
+    });
+  }
+
+  Ok(ImportedGrammar::new(name, rules, translator.warnings))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::import;
+  use crate::import::Expr;
+  use crate::{parser::Parser, parser_context::ParserContext};
+
+  #[test]
+  fn it_imports_a_w3c_ebnf_grammar() {
+    let grammar = import(
+      r#"
+        /* Adapted from the XML specification */
+        [1] Names ::= Name (#x20 Name)*
+        [2] Name ::= NameStartChar (NameChar)* [ wfc: No Digits ]
+        [3] NameStartChar ::= [a-zA-Z_:] | [#xC0-#xD6]
+        [4] NameChar ::= (NameStartChar | [0-9.-]) - 'x'
+      "#,
+      "Names",
+    )
+    .unwrap();
+
+    assert_eq!(
+      grammar.get_rule("NameStartChar").unwrap().expr,
+      Expr::Choice(vec![
+        Expr::Regex(r"[a-zA-Z_:]".to_string()),
+        Expr::Regex("[À-Ö]".to_string()),
+      ])
+    );
+    assert_eq!(
+      grammar.get_rule("NameChar").unwrap().expr,
+      Expr::Sequence(vec![
+        Expr::Not(Box::new(Expr::Literal("x".to_string()))),
+        Expr::Choice(vec![
+          Expr::Ref("NameStartChar".to_string()),
+          Expr::Regex(r"[0-9\.\-]".to_string()),
+        ]),
+      ])
+    );
+    assert_eq!(
+      grammar.warnings[0].to_string(),
+      "Warning: Name: Constraint `[ wfc: No Digits ]` can not be translated"
+    );
+
+    let (root_matcher, _) = grammar.to_matcher();
+    let parser = Parser::new("ab c-d.e");
+    let parser_context = ParserContext::new(&parser, "Test");
+    let token = ParserContext::tokenize(parser_context, root_matcher).unwrap();
+    assert_eq!(token.borrow().get_value(), "ab c-d.e");
+
+    let (script, _) = grammar.to_script();
+    assert!(script
+      .contains("  NameChar = <{ (<!='x'>) (<[ (<NameStartChar>) | (</[0-9\\.\\-]/>) ]>) }>\n"));
+  }
+}
@@ -0,0 +1,613 @@
+pub mod abnf;
+pub mod ebnf;
+pub mod pest;
+
+use crate::matcher::MatcherRef;
+use crate::matchers::program::{MatchAction, ProgramPattern};
+use crate::parse_error::ParseError;
+use crate::parser::Parser;
+use crate::parser_context::ParserContext;
+use crate::scope::VariableType;
+use crate::scope_context::{ScopeContext, ScopeContextRef};
+use crate::source_range::SourceRange;
+use crate::token::TokenRef;
+use crate::{
+  Equals, Loop, Map, Matches, Not, Null, Optional, Peek, ProxyChildren, Ref, SetScope, TokenResult,
+};
+
+// Marks the tokens of silent rules, until they are removed from the final token tree
+const SILENT_ATTRIBUTE: &str = "__silent";
+
+// Notation independent grammar expression, that every importer translates into
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+  Empty,
+  Literal(String),
+  Regex(String),
+  Ref(String),
+  Sequence(Vec<Expr>),
+  Choice(Vec<Expr>),
+  // Minimum, and (inclusive) maximum number of repetitions
+  Repeat(Box<Expr>, usize, Option<usize>),
+  Not(Box<Expr>),
+  // Positive lookahead
+  And(Box<Expr>),
+}
+
+impl Expr {
+  pub fn optional(expr: Expr) -> Expr {
+    Expr::Repeat(Box::new(expr), 0, Some(1))
+  }
+
+  pub fn case_insensitive(literal: &str) -> Expr {
+    if literal
+      .chars()
+      .any(|c| c.to_lowercase().ne(c.to_uppercase()))
+    {
+      Expr::Regex(format!("(?i:{})", regex::escape(literal)))
+    } else {
+      Expr::Literal(literal.to_string())
+    }
+  }
+
+  pub fn sequence(mut exprs: Vec<Expr>) -> Expr {
+    exprs.retain(|expr| *expr != Expr::Empty);
+
+    match exprs.len() {
+      0 => Expr::Empty,
+      1 => exprs.pop().unwrap(),
+      _ => Expr::Sequence(exprs),
+    }
+  }
+
+  // Names of all rules referenced from this expression
+  pub fn references(&self) -> Vec<String> {
+    match self {
+      Expr::Ref(name) => vec![name.clone()],
+      Expr::Sequence(exprs) | Expr::Choice(exprs) => {
+        exprs.iter().flat_map(|expr| expr.references()).collect()
+      }
+      Expr::Repeat(expr, _, _) | Expr::Not(expr) | Expr::And(expr) => expr.references(),
+      _ => Vec::new(),
+    }
+  }
+
+  // Does this expression use a positive lookahead anywhere?
+  fn has_lookahead(&self) -> bool {
+    match self {
+      Expr::And(_) => true,
+      Expr::Sequence(exprs) | Expr::Choice(exprs) => exprs.iter().any(Expr::has_lookahead),
+      Expr::Repeat(expr, _, _) | Expr::Not(expr) => expr.has_lookahead(),
+      _ => false,
+    }
+  }
+
+  pub fn choice(mut exprs: Vec<Expr>) -> Expr {
+    match exprs.len() {
+      0 => Expr::Empty,
+      1 => exprs.pop().unwrap(),
+      _ => Expr::Choice(exprs),
+    }
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RuleKind {
+  Normal,
+  // Produces no token of its own, only its children
+  Silent,
+  // Produces a token, but none of its children
+  Atomic,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rule {
+  pub name: String,
+  pub kind: RuleKind,
+  pub expr: Expr,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImportWarning {
+  pub rule: Option<String>,
+  pub message: String,
+  pub range: Option<SourceRange>,
+}
+
+impl std::fmt::Display for ImportWarning {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match &self.rule {
+      Some(rule) => write!(f, "Warning: {}: {}", rule, self.message),
+      None => write!(f, "Warning: {}", self.message),
+    }
+  }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImportedGrammar {
+  pub name: String,
+  // Rule the grammar starts matching from (the first rule, unless changed)
+  pub root: String,
+  pub rules: Vec<Rule>,
+  // Constructs that could not be translated (faithfully) into matchers
+  pub warnings: Vec<ImportWarning>,
+}
+
+impl ImportedGrammar {
+  pub fn new(name: &str, rules: Vec<Rule>, warnings: Vec<ImportWarning>) -> Self {
+    Self {
+      name: name.to_string(),
+      root: rules
+        .first()
+        .map(|rule| rule.name.clone())
+        .unwrap_or_default(),
+      rules,
+      warnings,
+    }
+  }
+
+  pub fn get_rule(&self, name: &str) -> Option<&Rule> {
+    self.rules.iter().find(|rule| rule.name == name)
+  }
+
+  /// Build a matcher graph for this grammar. Every rule is stored in
+  /// the returned scope, and the root matcher calls into the `root` rule.
+  pub fn to_matcher(&self) -> (MatcherRef, ScopeContextRef) {
+    let scope = ScopeContext::new();
+
+    for rule in &self.rules {
+      scope
+        .borrow_mut()
+        .set(&rule.name, VariableType::Matcher(rule_to_matcher(rule)));
+    }
+
+    let root_matcher = ProgramPattern::new_program_with_name(
+      vec![SetScope!(scope.clone(), Ref!(self.root.clone()))],
+      self.name.clone(),
+      MatchAction::Continue,
+    );
+
+    let root_matcher = Map!(root_matcher, |token, _, __| {
+      remove_silent_tokens(&token);
+      TokenResult!(token)
+    });
+
+    (root_matcher, scope)
+  }
+
+  /// Render this grammar as a v1 `.axo` script. Returns the script, and
+  /// every warning for this grammar, including any constructs that
+  /// the script format itself can not express.
+  pub fn to_script(&self) -> (String, Vec<ImportWarning>) {
+    let mut warnings = self.warnings.clone();
+    let mut output = format!(
+      "<!--[adextopa version='1' name='{}']\n",
+      escape_string(&self.name)
+    );
+
+    for rule in &self.rules {
+      match rule.kind {
+        RuleKind::Normal => {}
+        RuleKind::Silent => warnings.push(ImportWarning {
+          rule: Some(rule.name.clone()),
+          message: "Silent rules can not be expressed in a script; emitted as a normal rule"
+            .to_string(),
+          range: None,
+        }),
+        RuleKind::Atomic => warnings.push(ImportWarning {
+          rule: Some(rule.name.clone()),
+          message: "Atomic rules can not be expressed in a script; emitted as a normal rule"
+            .to_string(),
+          range: None,
+        }),
+      }
+
+      if rule.expr.has_lookahead() {
+        warnings.push(ImportWarning {
+          rule: Some(rule.name.clone()),
+          message: "Positive lookahead can not be expressed in a script; emitted as a pattern that always matches"
+            .to_string(),
+          range: None,
+        });
+      }
+
+      let pattern = rule_pattern(&rule.expr);
+      output.push_str(&format!(
+        "  {} = {}\n",
+        rule.name,
+        render_pattern(&pattern, false)
+      ));
+    }
+
+    output.push_str(&format!("-->\n\n(<{}>)\n", self.root));
+
+    (output, warnings)
+  }
+}
+
+fn sequence_to_matcher(exprs: &[Expr]) -> MatcherRef {
+  ProgramPattern::new_program(
+    exprs.iter().map(expr_to_matcher).collect(),
+    MatchAction::Continue,
+  )
+}
+
+// Only rules (and terminals) produce tokens, so
+// grouping constructs are flattened into their parent
+fn expr_to_matcher(expr: &Expr) -> MatcherRef {
+  match expr {
+    Expr::Empty => Null!(),
+    Expr::Literal(literal) if literal.is_empty() => Null!(),
+    Expr::Literal(literal) => Equals!(literal.clone()),
+    Expr::Regex(source) => Matches!(source),
+    Expr::Ref(name) => Ref!(name.clone()),
+    Expr::Sequence(exprs) => ProxyChildren!(sequence_to_matcher(exprs)),
+    Expr::Choice(exprs) => ProgramPattern::new_program(
+      exprs.iter().map(expr_to_matcher).collect(),
+      MatchAction::Stop,
+    ),
+    Expr::Repeat(expr, 0, Some(1)) => Optional!(expr_to_matcher(expr)),
+    Expr::Repeat(expr, min, max) => {
+      // A loop over `start..end` runs at most `end - start` iterations
+      let end = max.map_or(usize::MAX, |max| min + max);
+      let matcher = ProxyChildren!(Loop!(*min..end; expr_to_matcher(expr)));
+
+      // A loop fails if it doesn't match at all, even when its range starts at zero
+      if *min == 0 {
+        Optional!(matcher)
+      } else {
+        matcher
+      }
+    }
+    Expr::Not(expr) => Not!(expr_to_matcher(expr)),
+    Expr::And(expr) => Peek!(expr_to_matcher(expr)),
+  }
+}
+
+fn rule_to_matcher(rule: &Rule) -> MatcherRef {
+  let matcher = match &rule.expr {
+    // These already produce a token of their own, that can carry the rule name
+    Expr::Sequence(exprs) => sequence_to_matcher(exprs),
+    Expr::Literal(_) | Expr::Regex(_) => expr_to_matcher(&rule.expr),
+    expr => ProgramPattern::new_program(vec![expr_to_matcher(expr)], MatchAction::Continue),
+  };
+
+  let name = rule.name.clone();
+
+  // The wrapped rules name their token instead of their matcher, as a matcher with
+  // a custom name registers itself under that name when it runs, and so would
+  // replace the wrapper for every later `Ref` to the rule
+  match rule.kind {
+    RuleKind::Normal => {
+      matcher.borrow_mut().set_name(&name);
+      matcher
+    }
+    // Silent rules only contribute the tokens of their children. Their own
+    // token is kept until the whole input is parsed (see `remove_silent_tokens`),
+    // as a loop over a silent rule fails if it collected no tokens at all
+    RuleKind::Silent => Map!(matcher, move |token, _, __| {
+      token.borrow_mut().set_name(&name);
+      token.borrow_mut().set_attribute(SILENT_ATTRIBUTE, "true");
+      TokenResult!(token)
+    }),
+    RuleKind::Atomic => Map!(matcher, move |token, _, __| {
+      token.borrow_mut().set_name(&name);
+      token.borrow_mut().set_children(Vec::new());
+      TokenResult!(token.clone())
+    }),
+  }
+}
+
+// Replace the tokens of silent rules with their children, everywhere below `token`
+fn remove_silent_tokens(token: &TokenRef) {
+  let children = token.borrow().get_children().clone();
+  let mut kept = Vec::new();
+
+  for child in children {
+    remove_silent_tokens(&child);
+
+    if child.borrow().has_attribute(SILENT_ATTRIBUTE) {
+      for grandchild in child.borrow().get_children() {
+        grandchild.borrow_mut().set_parent(Some(token.clone()));
+        kept.push(grandchild.clone());
+      }
+    } else {
+      kept.push(child);
+    }
+  }
+
+  token.borrow_mut().set_children(kept);
+}
+
+fn escape_string(value: &str) -> String {
+  value.replace('\\', "\\\\").replace('\'', "\\'")
+}
+
+// The pieces of a v1 pattern: `outer<inner matcher>repeat`
+#[derive(Debug, Default)]
+struct Pattern {
+  outer: Option<char>,
+  inner: Option<char>,
+  matcher: String,
+  repeat: Option<String>,
+}
+
+impl Pattern {
+  fn new(matcher: String) -> Self {
+    Self {
+      matcher,
+      ..Self::default()
+    }
+  }
+
+  // Nest this pattern inside a program, so more modifiers can be applied
+  fn wrap(self) -> Self {
+    Self::new(format!("{{ {} }}", render_pattern(&self, true)))
+  }
+}
+
+fn render_pattern(pattern: &Pattern, captured: bool) -> String {
+  let rendered = format!(
+    "{}<{}{}>{}",
+    pattern.outer.map(String::from).unwrap_or_default(),
+    pattern.inner.map(String::from).unwrap_or_default(),
+    pattern.matcher,
+    pattern.repeat.as_deref().unwrap_or("")
+  );
+
+  if captured {
+    format!("({})", rendered)
+  } else {
+    rendered
+  }
+}
+
+fn repeat_specifier(min: usize, max: Option<usize>) -> String {
+  // `{min,end}` compiles to a `Loop` over `min..end`, which runs at most `end - min` iterations
+  match (min, max) {
+    (0, None) => "*".to_string(),
+    (1, None) => "+".to_string(),
+    (min, None) => format!("{{{},}}", min),
+    (min, Some(max)) => format!("{{{},{}}}", min, min + max),
+  }
+}
+
+fn pattern(expr: &Expr) -> Pattern {
+  let join = |exprs: &Vec<Expr>, separator: &str| -> String {
+    exprs
+      .iter()
+      .map(|expr| render_pattern(&pattern(expr), true))
+      .collect::<Vec<String>>()
+      .join(separator)
+  };
+
+  match expr {
+    Expr::Empty => Pattern::new("[ ]".to_string()),
+    Expr::Literal(literal) if literal.is_empty() => Pattern::new("[ ]".to_string()),
+    Expr::Literal(literal) => Pattern::new(format!("='{}'", escape_string(literal))),
+    Expr::Regex(source) => Pattern::new(format!("/{}/", source.replace('/', "\\/"))),
+    Expr::Ref(name) => Pattern::new(name.clone()),
+    Expr::Sequence(exprs) => Pattern::new(format!("{{ {} }}", join(exprs, " "))),
+    Expr::Choice(exprs) => Pattern::new(format!("[ {} ]", join(exprs, " | "))),
+    Expr::Repeat(expr, 0, Some(1)) => {
+      let mut inner = pattern(expr);
+
+      if inner.inner.is_none() && inner.repeat.is_none() && inner.outer.is_none() {
+        inner.inner = Some('?');
+      } else if inner.outer.is_none() {
+        inner.outer = Some('?');
+      } else {
+        inner = inner.wrap();
+        inner.inner = Some('?');
+      }
+
+      inner
+    }
+    Expr::Repeat(expr, min, max) => {
+      let mut inner = pattern(expr);
+
+      if inner.repeat.is_some() || inner.outer.is_some() {
+        inner = inner.wrap();
+      }
+
+      inner.repeat = Some(repeat_specifier(*min, *max));
+
+      // Same as for matchers: a loop that matches nothing fails, unless optional
+      if *min == 0 {
+        inner.outer = Some('?');
+      }
+
+      inner
+    }
+    Expr::Not(expr) => {
+      let mut inner = pattern(expr);
+
+      if inner.inner.is_none() && inner.repeat.is_none() && inner.outer.is_none() {
+        inner.inner = Some('!');
+      } else if inner.outer.is_none() {
+        inner.outer = Some('!');
+      } else {
+        inner = inner.wrap();
+        inner.inner = Some('!');
+      }
+
+      inner
+    }
+    Expr::And(expr) => pattern(&Expr::Not(Box::new(Expr::Not(expr.clone())))),
+  }
+}
+
+// Same wrapping as `rule_to_matcher`, so the script and matcher graph produce the same tokens
+fn rule_pattern(expr: &Expr) -> Pattern {
+  match expr {
+    Expr::Sequence(_) | Expr::Literal(_) | Expr::Regex(_) => pattern(expr),
+    expr => Pattern::new(format!("{{ {} }}", render_pattern(&pattern(expr), true))),
+  }
+}
+
+// Run an importer's own grammar (built from adextopa matchers) over `source`,
+// and make sure all of the input was consumed
+fn parse_source(
+  source: &str,
+  name: &str,
+  matcher: MatcherRef,
+  rules: Vec<(&str, MatcherRef)>,
+) -> Result<TokenRef, Vec<ParseError>> {
+  let parser = Parser::new(source);
+  let parser_context = ParserContext::new(&parser, name);
+
+  for (rule_name, rule) in rules {
+    parser_context
+      .borrow()
+      .register_matcher_with_name(rule_name, rule);
+  }
+
+  let unexpected_input = |offset: usize| {
+    let range = SourceRange::new(offset, source.len());
+    let message = parser_context
+      .borrow()
+      .get_error_as_string("Unable to parse grammar from here", &range);
+
+    vec![ParseError::new_with_range(&message, range)]
+  };
+
+  match ParserContext::tokenize(parser_context.clone(), matcher) {
+    Ok(token) => {
+      let end = token.borrow().get_matched_range().end;
+
+      if source[end..].trim().is_empty() {
+        Ok(token)
+      } else {
+        Err(unexpected_input(end))
+      }
+    }
+    Err(crate::matcher::MatcherFailure::Fail) => Err(unexpected_input(0)),
+    Err(crate::matcher::MatcherFailure::Error(error)) => Err(vec![error]),
+  }
+}
+
+// Shared by the importers: the (single) child of a token
+fn first_child(token: &TokenRef) -> Option<TokenRef> {
+  token.borrow().get_children().first().cloned()
+}
+
+fn token_error(token: &TokenRef, message: &str) -> ParseError {
+  ParseError::new_with_range(message, *token.borrow().get_matched_range())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{Expr, ImportedGrammar, Rule, RuleKind};
+  use crate::{parser::Parser, parser_context::ParserContext};
+
+  fn grammar() -> ImportedGrammar {
+    ImportedGrammar::new(
+      "List",
+      vec![
+        Rule {
+          name: "list".to_string(),
+          kind: RuleKind::Normal,
+          expr: Expr::Sequence(vec![
+            Expr::Ref("item".to_string()),
+            Expr::Repeat(
+              Box::new(Expr::Sequence(vec![
+                Expr::Literal(",".to_string()),
+                Expr::Ref("item".to_string()),
+              ])),
+              0,
+              None,
+            ),
+          ]),
+        },
+        Rule {
+          name: "item".to_string(),
+          kind: RuleKind::Normal,
+          expr: Expr::Choice(vec![
+            Expr::Regex("[0-9]+".to_string()),
+            Expr::Literal("x".to_string()),
+          ]),
+        },
+      ],
+      Vec::new(),
+    )
+  }
+
+  #[test]
+  fn it_builds_a_matcher_graph() {
+    let (root_matcher, _) = grammar().to_matcher();
+    let parser = Parser::new("1,x,23");
+    let parser_context = ParserContext::new(&parser, "Test");
+    let token = ParserContext::tokenize(parser_context, root_matcher).unwrap();
+    let token = token.borrow();
+
+    assert_eq!(token.get_name(), "List");
+    assert_eq!(token.get_value(), "1,x,23");
+
+    let list = token.get_children()[0].borrow();
+    assert_eq!(list.get_name(), "list");
+    assert_eq!(list.get_children()[0].borrow().get_name(), "item");
+  }
+
+  #[test]
+  fn it_renders_a_script() {
+    let (script, warnings) = grammar().to_script();
+
+    assert_eq!(
+      script,
+      concat!(
+        "<!--[adextopa version='1' name='List']\n",
+        "  list = <{ (<item>) (?<{ (<=','>) (<item>) }>*) }>\n",
+        "  item = <{ (<[ (</[0-9]+/>) | (<='x'>) ]>) }>\n",
+        "-->\n",
+        "\n",
+        "(<list>)\n",
+      )
+    );
+    assert!(warnings.is_empty());
+
+    let (_, root_matcher, _) = crate::script::current::parser::compile_script(
+      Parser::new(&script),
+      "List".to_string(),
+      None,
+      0,
+    )
+    .unwrap();
+
+    let parser = Parser::new("1,x,23");
+    let parser_context = ParserContext::new(&parser, "Test");
+    let token = ParserContext::tokenize(parser_context, root_matcher).unwrap();
+    assert_eq!(token.borrow().get_value(), "1,x,23");
+  }
+
+  #[test]
+  fn it_builds_positive_lookaheads() {
+    let grammar = ImportedGrammar::new(
+      "Word",
+      vec![Rule {
+        name: "word".to_string(),
+        kind: RuleKind::Normal,
+        expr: Expr::Sequence(vec![
+          Expr::And(Box::new(Expr::Literal("a".to_string()))),
+          Expr::Regex("[a-z]+".to_string()),
+        ]),
+      }],
+      Vec::new(),
+    );
+
+    let (root_matcher, _) = grammar.to_matcher();
+    let parser = Parser::new("abc");
+    let parser_context = ParserContext::new(&parser, "Test");
+    let token = ParserContext::tokenize(parser_context, root_matcher.clone()).unwrap();
+    assert_eq!(token.borrow().get_value(), "abc");
+
+    let parser = Parser::new("bc");
+    let parser_context = ParserContext::new(&parser, "Test");
+    assert!(ParserContext::tokenize(parser_context, root_matcher).is_err());
+
+    let (_, warnings) = grammar.to_script();
+    assert_eq!(
+      warnings[0].to_string(),
+      "Warning: word: Positive lookahead can not be expressed in a script; emitted as a pattern that always matches"
+    );
+  }
+}
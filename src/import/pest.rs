@@ -0,0 +1,603 @@
+use std::collections::HashSet;
+
+use super::{
+  first_child, parse_source, token_error, Expr, ImportWarning, ImportedGrammar, Rule, RuleKind,
+};
+use crate::matcher::MatcherRef;
+use crate::parse_error::ParseError;
+use crate::token::TokenRef;
+use crate::{Discard, Equals, Loop, Matches, Optional, Program, ProxyChildren, Ref, Switch};
+
+// Whitespace, and all three comment styles (`//`, `///`, `//!`, `/* */`)
+const WS: &str = r"(?:\s|//[^\n]*|/\*(?s:.*?)\*/)*";
+const IDENTIFIER: &str = r"[A-Za-z_][A-Za-z0-9_]*";
+const STRING: &str = r#""(?:[^"\\]|\\.)*""#;
+const CHAR: &str = r"'(?:[^'\\]|\\.|\\u\{[0-9A-Fa-f]+\})'";
+
+// Stack operations, that have no matcher equivalent
+const STACK_OPERATIONS: &[&str] = &[
+  "PUSH",
+  "PUSH_LITERAL",
+  "POP",
+  "POP_ALL",
+  "PEEK",
+  "PEEK_ALL",
+  "DROP",
+];
+
+fn ws() -> MatcherRef {
+  Discard!(Matches!(WS))
+}
+
+fn grammar() -> (MatcherRef, Vec<(&'static str, MatcherRef)>) {
+  let choice = Program!("Choice";
+    ws(),
+    Optional!(Discard!(Equals!("|"))),
+    ws(),
+    Ref!("Sequence"),
+    Optional!(ProxyChildren!(Loop!(ws(), Discard!(Equals!("|")), ws(), Ref!("Sequence")))),
+  );
+
+  let sequence = Program!("Sequence";
+    Ref!("Prefixed"),
+    Optional!(ProxyChildren!(Loop!(ws(), Discard!(Equals!("~")), ws(), Ref!("Prefixed")))),
+  );
+
+  let prefixed = Program!("Prefixed";
+    Optional!(ProxyChildren!(Loop!(Matches!("Prefix"; r"[&!]"), ws()))),
+    Ref!("Postfixed"),
+  );
+
+  let postfixed = Program!("Postfixed";
+    Ref!("Primary"),
+    Optional!(ProxyChildren!(Loop!(
+      ws(),
+      Switch!(
+        Matches!("Postfix"; r"[*+?]"),
+        Program!("Bounds";
+          Discard!(Equals!("{")),
+          ws(),
+          Optional!(Matches!("Minimum"; r"\d+")),
+          ws(),
+          Optional!(Equals!("Separator"; ",")),
+          ws(),
+          Optional!(Matches!("Maximum"; r"\d+")),
+          ws(),
+          Discard!(Equals!("}")),
+        ),
+      ),
+    ))),
+  );
+
+  let primary = Switch!(
+    Program!("Group";
+      Discard!(Equals!("(")),
+      Ref!("Choice"),
+      ws(),
+      Discard!(Equals!(")")),
+    ),
+    Program!("Range";
+      Matches!("Char"; CHAR),
+      ws(),
+      Discard!(Equals!("..")),
+      ws(),
+      Matches!("Char"; CHAR),
+    ),
+    Program!("Insensitive"; Discard!(Equals!("^")), Matches!("String"; STRING)),
+    Matches!("String"; STRING),
+    Matches!("Char"; CHAR),
+    Program!("Tag";
+      Discard!(Equals!("#")),
+      Matches!("Name"; IDENTIFIER),
+      ws(),
+      Discard!(Equals!("=")),
+      ws(),
+      Ref!("Prefixed"),
+    ),
+    Program!("Call";
+      Matches!("Name"; IDENTIFIER),
+      ws(),
+      Switch!(
+        ProxyChildren!(Program!(
+          Discard!(Equals!("(")),
+          Ref!("Choice"),
+          ws(),
+          Discard!(Equals!(")")),
+        )),
+        Matches!("Slice"; r"\[[^\]]*\]"),
+      ),
+    ),
+    Matches!("Identifier"; IDENTIFIER),
+  );
+
+  let rule = Program!("Rule";
+    ws(),
+    Matches!("Name"; IDENTIFIER),
+    ws(),
+    Discard!(Equals!("=")),
+    ws(),
+    Optional!(Matches!("Modifier"; r"[_@$!]")),
+    ws(),
+    Discard!(Equals!("{")),
+    Ref!("Choice"),
+    ws(),
+    Discard!(Equals!("}")),
+  );
+
+  let root = Program!("Grammar"; ProxyChildren!(Loop!(rule)), ws());
+
+  (
+    root,
+    vec![
+      ("Choice", choice),
+      ("Sequence", sequence),
+      ("Prefixed", prefixed),
+      ("Postfixed", postfixed),
+      ("Primary", primary),
+    ],
+  )
+}
+
+fn unescape(token: &TokenRef, quoted: &str) -> Result<String, ParseError> {
+  let inner = &quoted[1..quoted.len() - 1];
+  let mut result = String::new();
+  let mut chars = inner.chars();
+
+  while let Some(c) = chars.next() {
+    if c != '\\' {
+      result.push(c);
+      continue;
+    }
+
+    match chars.next() {
+      Some('n') => result.push('\n'),
+      Some('r') => result.push('\r'),
+      Some('t') => result.push('\t'),
+      Some('0') => result.push('\0'),
+      Some('x') => {
+        let hex: String = chars.by_ref().take(2).collect();
+        result.push(parse_code_point(token, &hex)?);
+      }
+      Some('u') => {
+        let hex: String = chars
+          .by_ref()
+          .skip_while(|c| *c == '{')
+          .take_while(|c| *c != '}')
+          .collect();
+        result.push(parse_code_point(token, &hex)?);
+      }
+      Some(c) => result.push(c),
+      None => {}
+    }
+  }
+
+  Ok(result)
+}
+
+fn parse_code_point(token: &TokenRef, hex: &str) -> Result<char, ParseError> {
+  u32::from_str_radix(hex, 16)
+    .ok()
+    .and_then(char::from_u32)
+    .ok_or_else(|| token_error(token, &format!("Invalid character escape `{}`", hex)))
+}
+
+fn builtin(name: &str) -> Option<Expr> {
+  let class = |class: &str| Some(Expr::Regex(class.to_string()));
+
+  match name {
+    "ANY" => class("(?s:.)"),
+    "EOI" => Some(Expr::Not(Box::new(Expr::Regex("(?s:.)".to_string())))),
+    "NEWLINE" => class(r"\r\n|\n|\r"),
+    "ASCII_DIGIT" => class("[0-9]"),
+    "ASCII_NONZERO_DIGIT" => class("[1-9]"),
+    "ASCII_BIN_DIGIT" => class("[01]"),
+    "ASCII_OCT_DIGIT" => class("[0-7]"),
+    "ASCII_HEX_DIGIT" => class("[0-9a-fA-F]"),
+    "ASCII_ALPHA_LOWER" => class("[a-z]"),
+    "ASCII_ALPHA_UPPER" => class("[A-Z]"),
+    "ASCII_ALPHA" => class("[a-zA-Z]"),
+    "ASCII_ALPHANUMERIC" => class("[a-zA-Z0-9]"),
+    "ASCII" => class(r"[\x00-\x7F]"),
+    "LETTER" => class(r"\p{L}"),
+    "LOWERCASE_LETTER" => class(r"\p{Ll}"),
+    "UPPERCASE_LETTER" => class(r"\p{Lu}"),
+    "MARK" => class(r"\p{M}"),
+    "NUMBER" => class(r"\p{N}"),
+    "DECIMAL_NUMBER" => class(r"\p{Nd}"),
+    "PUNCTUATION" => class(r"\p{P}"),
+    "SYMBOL" => class(r"\p{S}"),
+    "SEPARATOR" => class(r"\p{Z}"),
+    "SPACE_SEPARATOR" => class(r"\p{Zs}"),
+    "WHITE_SPACE" => class(r"\p{White_Space}"),
+    "ALPHABETIC" => class(r"\p{Alphabetic}"),
+    "XID_START" => class(r"\p{XID_Start}"),
+    "XID_CONTINUE" => class(r"\p{XID_Continue}"),
+    _ => None,
+  }
+}
+
+struct Translator {
+  rule: String,
+  rule_names: HashSet<String>,
+  warnings: Vec<ImportWarning>,
+}
+
+impl Translator {
+  fn warn(&mut self, token: &TokenRef, message: String) {
+    self.warnings.push(ImportWarning {
+      rule: Some(self.rule.clone()),
+      message,
+      range: Some(*token.borrow().get_matched_range()),
+    });
+  }
+
+  fn translate(&mut self, token: &TokenRef) -> Result<Expr, ParseError> {
+    let name = token.borrow().get_name().to_string();
+    let children = token.borrow().get_children().clone();
+
+    match name.as_str() {
+      "Choice" => Ok(Expr::choice(
+        children
+          .iter()
+          .map(|child| self.translate(child))
+          .collect::<Result<_, _>>()?,
+      )),
+      "Sequence" => Ok(Expr::sequence(
+        children
+          .iter()
+          .map(|child| self.translate(child))
+          .collect::<Result<_, _>>()?,
+      )),
+      "Prefixed" => {
+        let (operand, prefixes) = children.split_last().unwrap();
+        let mut expr = self.translate(operand)?;
+
+        for prefix in prefixes.iter().rev() {
+          expr = match prefix.borrow().get_value().as_str() {
+            "!" => Expr::Not(Box::new(expr)),
+            _ => Expr::And(Box::new(expr)),
+          };
+        }
+
+        Ok(expr)
+      }
+      "Postfixed" => {
+        let (operand, postfixes) = children.split_first().unwrap();
+        let mut expr = self.translate(operand)?;
+
+        for postfix in postfixes {
+          let _postfix = postfix.borrow();
+
+          let (min, max) = if _postfix.get_name() == "Bounds" {
+            let number = |name: &str| -> Result<Option<usize>, ParseError> {
+              match _postfix.find_child(name) {
+                Some(child) => child
+                  .borrow()
+                  .get_value()
+                  .parse::<usize>()
+                  .map(Some)
+                  .map_err(|error| token_error(postfix, &error.to_string())),
+                None => Ok(None),
+              }
+            };
+
+            let minimum = number("Minimum")?;
+            let maximum = number("Maximum")?;
+
+            if _postfix.has_child("Separator") {
+              (minimum.unwrap_or(0), maximum)
+            } else {
+              (minimum.unwrap_or(0), minimum)
+            }
+          } else {
+            match _postfix.get_value().as_str() {
+              "*" => (0, None),
+              "+" => (1, None),
+              _ => (0, Some(1)),
+            }
+          };
+
+          expr = Expr::Repeat(Box::new(expr), min, max);
+        }
+
+        Ok(expr)
+      }
+      "Group" => self.translate(&first_child(token).unwrap()),
+      "String" => Ok(Expr::Literal(unescape(token, token.borrow().get_value())?)),
+      "Insensitive" => {
+        let string = first_child(token).unwrap();
+        let value = unescape(&string, string.borrow().get_value())?;
+        Ok(Expr::case_insensitive(&value))
+      }
+      "Char" => Ok(Expr::Literal(unescape(token, token.borrow().get_value())?)),
+      "Range" => {
+        let mut bounds = Vec::new();
+        for child in &children {
+          bounds.push(regex::escape(&unescape(child, child.borrow().get_value())?));
+        }
+
+        Ok(Expr::Regex(format!("[{}-{}]", bounds[0], bounds[1])))
+      }
+      "Tag" => {
+        let tag = children[0].borrow().get_value().clone();
+        self.warn(token, format!("Node tag `#{}` was dropped", tag));
+        self.translate(&children[1])
+      }
+      "Call" => {
+        let function = children[0].borrow().get_value().clone();
+
+        if function == "PUSH" && children[1].borrow().get_name() == "Choice" {
+          self.warn(
+            token,
+            "`PUSH` matches its argument, but the stack is not supported".to_string(),
+          );
+          self.translate(&children[1])
+        } else {
+          self.warn(
+            token,
+            format!("Stack operation `{}` is not supported", function),
+          );
+          Ok(Expr::Empty)
+        }
+      }
+      "Identifier" => {
+        let identifier = token.borrow().get_value().clone();
+
+        if self.rule_names.contains(&identifier) {
+          return Ok(Expr::Ref(identifier));
+        }
+
+        if let Some(expr) = builtin(&identifier) {
+          return Ok(expr);
+        }
+
+        if identifier == "SOI" {
+          self.warn(token, "`SOI` is not supported".to_string());
+          Ok(Expr::Empty)
+        } else if STACK_OPERATIONS.contains(&identifier.as_str()) {
+          self.warn(
+            token,
+            format!("Stack operation `{}` is not supported", identifier),
+          );
+          Ok(Expr::Empty)
+        } else {
+          Ok(Expr::Ref(identifier))
+        }
+      }
+      name => Err(token_error(
+        token,
+        &format!("Unexpected `{}` token in pest grammar", name),
+      )),
+    }
+  }
+}
+
+// Insert implicit `WHITESPACE`/`COMMENT` skipping, the way pest does for non-atomic rules
+fn insert_skip(expr: Expr, skip: &Expr) -> Expr {
+  match expr {
+    Expr::Sequence(exprs) => {
+      let mut result = Vec::new();
+
+      for (index, expr) in exprs.into_iter().enumerate() {
+        if index > 0 {
+          result.push(skip.clone());
+        }
+
+        result.push(insert_skip(expr, skip));
+      }
+
+      Expr::Sequence(result)
+    }
+    Expr::Choice(exprs) => Expr::Choice(
+      exprs
+        .into_iter()
+        .map(|expr| insert_skip(expr, skip))
+        .collect(),
+    ),
+    Expr::Repeat(expr, 0, Some(1)) => Expr::optional(insert_skip(*expr, skip)),
+    Expr::Repeat(expr, min, max) => {
+      let expr = insert_skip(*expr, skip);
+
+      if max == Some(0) {
+        return Expr::Empty;
+      }
+
+      // `e{min,max}` becomes `e ~ (skip ~ e){min - 1,max - 1}`
+      let rest = Expr::Repeat(
+        Box::new(Expr::Sequence(vec![skip.clone(), expr.clone()])),
+        min.saturating_sub(1),
+        max.map(|max| max - 1),
+      );
+      let repeated = Expr::Sequence(vec![expr, rest]);
+
+      if min == 0 {
+        Expr::optional(repeated)
+      } else {
+        repeated
+      }
+    }
+    Expr::Not(expr) => Expr::Not(Box::new(insert_skip(*expr, skip))),
+    Expr::And(expr) => Expr::And(Box::new(insert_skip(*expr, skip))),
+    expr => expr,
+  }
+}
+
+/// Import a pest grammar (the contents of a `.pest` file). Rules
+/// keep their names; `_` rules become silent, and `@` rules atomic.
+pub fn import(source: &str, name: &str) -> Result<ImportedGrammar, Vec<ParseError>> {
+  let (matcher, rules) = grammar();
+  let token = parse_source(source, name, matcher, rules)?;
+  let rule_tokens = token.borrow().get_children().clone();
+
+  let rule_names: HashSet<String> = rule_tokens
+    .iter()
+    .map(|rule| rule.borrow().get_children()[0].borrow().get_value().clone())
+    .collect();
+
+  let mut translator = Translator {
+    rule: String::new(),
+    rule_names,
+    warnings: Vec::new(),
+  };
+
+  // Rule name, modifier, expression
+  let mut parsed = Vec::new();
+  for rule_token in &rule_tokens {
+    let _rule_token = rule_token.borrow();
+    let rule_name = _rule_token.get_children()[0].borrow().get_value().clone();
+    let modifier = _rule_token
+      .find_child("Modifier")
+      .map(|modifier| modifier.borrow().get_value().clone())
+      .unwrap_or_default();
+    let body = _rule_token.find_child("Choice").unwrap();
+
+    translator.rule = rule_name.clone();
+    let expr = translator.translate(&body).map_err(|error| vec![error])?;
+    parsed.push((rule_name, modifier, expr));
+  }
+
+  let mut skip_rules = Vec::new();
+  for implicit in ["WHITESPACE", "COMMENT"] {
+    if translator.rule_names.contains(implicit) {
+      skip_rules.push(Expr::Ref(implicit.to_string()));
+    }
+  }
+
+  let skip = if skip_rules.is_empty() {
+    None
+  } else {
+    Some(Expr::Repeat(Box::new(Expr::choice(skip_rules)), 0, None))
+  };
+
+  let atomic_rules: Vec<&(String, String, Expr)> = parsed
+    .iter()
+    .filter(|(_, modifier, _)| modifier == "@" || modifier == "$")
+    .collect();
+
+  let mut rules = Vec::new();
+  for (rule_name, modifier, expr) in parsed.iter() {
+    let is_atomic = modifier == "@" || modifier == "$";
+    let is_implicit = rule_name == "WHITESPACE" || rule_name == "COMMENT";
+    let mut expr = expr.clone();
+
+    if let Some(skip) = &skip {
+      if !is_atomic && !is_implicit {
+        expr = insert_skip(expr, skip);
+
+        // pest would run this rule atomically when called from an atomic rule
+        for (atomic_name, _, atomic_expr) in &atomic_rules {
+          if modifier != "!" && atomic_expr.references().contains(rule_name) {
+            translator.warnings.push(ImportWarning {
+              rule: Some(rule_name.clone()),
+              message: format!(
+                "Called from atomic rule `{}`, but implicit whitespace is still skipped inside of it",
+                atomic_name
+              ),
+              range: None,
+            });
+          }
+        }
+      }
+    }
+
+    let kind = match modifier.as_str() {
+      "_" => RuleKind::Silent,
+      "@" => RuleKind::Atomic,
+      _ => RuleKind::Normal,
+    };
+
+    rules.push(Rule {
+      name: rule_name.clone(),
+      kind,
+      expr,
+    });
+  }
+
+  Ok(ImportedGrammar::new(name, rules, translator.warnings))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::import;
+  use crate::import::{Expr, RuleKind};
+  use crate::{parser::Parser, parser_context::ParserContext};
+
+  #[test]
+  fn it_imports_a_pest_grammar() {
+    let grammar = import(
+      r#"
+        // A list of numbers
+        list = { SOI ~ number ~ ("," ~ number)* ~ EOI }
+        number = @{ "-"? ~ ASCII_DIGIT{1,3} }
+        keyword = _{ ^"let" | 'a'..'z' }
+        WHITESPACE = _{ " " }
+        stack = { PUSH("x") ~ POP }
+      "#,
+      "Numbers",
+    )
+    .unwrap();
+
+    assert_eq!(grammar.root, "list");
+    assert_eq!(grammar.rules.len(), 5);
+    assert_eq!(grammar.get_rule("number").unwrap().kind, RuleKind::Atomic);
+    assert_eq!(grammar.get_rule("keyword").unwrap().kind, RuleKind::Silent);
+    assert_eq!(
+      grammar.get_rule("number").unwrap().expr,
+      Expr::Sequence(vec![
+        Expr::optional(Expr::Literal("-".to_string())),
+        Expr::Repeat(Box::new(Expr::Regex("[0-9]".to_string())), 1, Some(3)),
+      ])
+    );
+    assert_eq!(
+      grammar.get_rule("keyword").unwrap().expr,
+      Expr::Choice(vec![
+        Expr::Regex("(?i:let)".to_string()),
+        Expr::Regex("[a-z]".to_string()),
+      ])
+    );
+
+    let messages: Vec<String> = grammar
+      .warnings
+      .iter()
+      .map(|warning| warning.to_string())
+      .collect();
+
+    assert_eq!(
+      messages,
+      vec![
+        "Warning: list: `SOI` is not supported",
+        "Warning: stack: `PUSH` matches its argument, but the stack is not supported",
+        "Warning: stack: Stack operation `POP` is not supported",
+      ]
+    );
+
+    let (root_matcher, _) = grammar.to_matcher();
+    let parser = Parser::new("12 , -3,4");
+    let parser_context = ParserContext::new(&parser, "Test");
+    let token = ParserContext::tokenize(parser_context, root_matcher).unwrap();
+    let token = token.borrow();
+    let list = token.get_children()[0].borrow();
+    let numbers: Vec<String> = list
+      .get_children()
+      .iter()
+      .filter(|child| child.borrow().get_name() == "number")
+      .map(|child| child.borrow().get_value().clone())
+      .collect();
+
+    assert_eq!(list.get_name(), "list");
+    assert_eq!(numbers, vec!["12", "-3", "4"]);
+    // Whitespace is silent, and numbers are atomic
+    assert!(list.get_children().iter().all(|child| {
+      let child = child.borrow();
+      child.get_name() != "WHITESPACE" && child.get_children().is_empty()
+    }));
+  }
+
+  #[test]
+  fn it_reports_where_parsing_failed() {
+    let errors = import("a = { \"x\" }\nb = { ~ }", "Broken").unwrap_err();
+
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].range.unwrap().start, 12);
+  }
+}
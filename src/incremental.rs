@@ -0,0 +1,508 @@
+use std::{collections::HashMap, rc::Rc};
+
+use crate::{
+  matcher::{MatcherFailure, MatcherRef, MatcherSuccess},
+  parse_error::ParseError,
+  parser::{Parser, ParserRef},
+  parser_context::ParserContext,
+  source_range::SourceRange,
+  token::{StandardToken, TokenRef, TokenRefInner, IS_ERROR},
+};
+
+/// Replaces `range` (byte offsets into the old source) with `replacement`
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextEdit {
+  pub range: SourceRange,
+  pub replacement: String,
+}
+
+impl TextEdit {
+  pub fn new(range: SourceRange, replacement: &str) -> Self {
+    Self {
+      range,
+      replacement: replacement.to_string(),
+    }
+  }
+
+  /// A new parser for the edited source, with the same file name
+  pub fn apply(&self, parser: &ParserRef) -> Result<ParserRef, ParseError> {
+    let parser = parser.borrow();
+    let source = parser.get_source();
+
+    if self.range.start > self.range.end
+      || self.range.end > source.len()
+      || !source.is_char_boundary(self.range.start)
+      || !source.is_char_boundary(self.range.end)
+    {
+      return Err(ParseError::new_with_range(
+        &format!(
+          "Edit range {} is not valid for a source of {} bytes",
+          self.range,
+          source.len()
+        ),
+        self.range,
+      ));
+    }
+
+    let mut edited = String::with_capacity(source.len() + self.replacement.len());
+    edited.push_str(&source[..self.range.start]);
+    edited.push_str(&self.replacement);
+    edited.push_str(&source[self.range.end..]);
+
+    Ok(Parser::new_with_file_name(&edited, &parser.filename))
+  }
+
+  fn delta(&self) -> isize {
+    self.replacement.len() as isize - (self.range.end - self.range.start) as isize
+  }
+
+  // Where `offset` in the old source ends up after the edit. Only
+  // valid for offsets outside of the replaced range
+  fn shift(&self, offset: usize) -> usize {
+    if offset >= self.range.end {
+      (offset as isize + self.delta()) as usize
+    } else {
+      offset
+    }
+  }
+
+  fn shift_range(&self, range: &SourceRange) -> SourceRange {
+    SourceRange::new(self.shift(range.start), self.shift(range.end))
+  }
+}
+
+/// Re-parses a source after a `TextEdit`, reusing the unaffected parts
+/// of the previous token tree.
+///
+/// Only the nearest token that strictly contains the edit, and has a
+/// reparse point registered for its name, is matched again. The tokens
+/// enclosing it are rebuilt, and everything else is taken over from the
+/// previous tree: tokens before the edit as they are, and tokens after it
+/// with their ranges moved.
+/// If the new token doesn't end exactly where the old one did (after
+/// the edit), the next reparse point up is tried, and finally the whole
+/// source is parsed again.
+///
+/// This gives the same tree as a full parse as long as each reparse point
+/// is self contained: it must match the same way no matter what comes
+/// before it (i.e. it doesn't read scope variables set outside of it), and
+/// nothing outside of it may look at its contents (i.e. a block between
+/// delimiters).
+pub struct IncrementalParser {
+  name: String,
+  matcher: MatcherRef,
+  reparse_points: HashMap<String, MatcherRef>,
+}
+
+impl IncrementalParser {
+  pub fn new(name: &str, matcher: MatcherRef) -> Self {
+    Self {
+      name: name.to_string(),
+      matcher,
+      reparse_points: HashMap::new(),
+    }
+  }
+
+  /// Tokens named `token_name` can be re-parsed on their own with `matcher`
+  pub fn add_reparse_point(&mut self, token_name: &str, matcher: MatcherRef) {
+    self.reparse_points.insert(token_name.to_string(), matcher);
+  }
+
+  pub fn parse(&self, parser: &ParserRef) -> Result<TokenRef, MatcherFailure> {
+    ParserContext::tokenize(ParserContext::new(parser, &self.name), self.matcher.clone())
+  }
+
+  /// Apply `edit` to the source of `parser`, which `previous` was parsed
+  /// from, and parse it again. Returns the parser for the new source,
+  /// along with the new token tree.
+  ///
+  /// The new tree shares its unaffected tokens with `previous`, which
+  /// should no longer be used afterwards
+  pub fn reparse(
+    &self,
+    previous: &TokenRef,
+    parser: &ParserRef,
+    edit: &TextEdit,
+  ) -> Result<(ParserRef, TokenRef), MatcherFailure> {
+    let new_parser = edit.apply(parser).map_err(MatcherFailure::Error)?;
+    let path = find_enclosing_path(previous, &edit.range);
+
+    // Values rewritten by a matcher (i.e. a `Map`) can't be trusted
+    // once the source under them changes, so reuse stops there
+    let mut reusable = path.len();
+    for (index, token) in path.iter().enumerate() {
+      let token = token.borrow();
+      if token.has_attribute("__value") || token.get_value() != token.get_captured_value() {
+        reusable = index + 1;
+        break;
+      }
+    }
+
+    for index in (1..reusable).rev() {
+      let old_token = &path[index];
+      let matcher = match self.reparse_points.get(old_token.borrow().get_name()) {
+        Some(matcher) => matcher.clone(),
+        None => continue,
+      };
+
+      if has_error_within(previous, old_token.borrow().get_matched_range()) {
+        continue;
+      }
+
+      if let Some(new_token) = self.reparse_token(&new_parser, old_token, &matcher, edit) {
+        let splice = Splice::new(edit, old_token, &new_token);
+        let root = splice.rebase_token(previous, &new_parser, None);
+        return Ok((new_parser, root));
+      }
+    }
+
+    let root = self.parse(&new_parser)?;
+    Ok((new_parser, root))
+  }
+
+  fn reparse_token(
+    &self,
+    new_parser: &ParserRef,
+    old_token: &TokenRef,
+    matcher: &MatcherRef,
+    edit: &TextEdit,
+  ) -> Option<TokenRef> {
+    let old_token = old_token.borrow();
+    let old_range = old_token.get_matched_range();
+    let expected_range = edit.shift_range(old_range);
+    let context = ParserContext::new_with_offset(
+      new_parser,
+      SourceRange::new(old_range.start, new_parser.borrow().get_end()),
+      &self.name,
+    );
+    let scope = context.borrow().scope.clone();
+
+    match matcher.borrow().exec(matcher.clone(), context, scope) {
+      Ok(MatcherSuccess::Token(token)) => {
+        let is_same = {
+          let new_token = token.borrow();
+          new_token.get_name() == old_token.get_name()
+            && *new_token.get_matched_range() == expected_range
+        };
+
+        // Errors would have been collected up to the root by a full parse
+        if is_same && !contains_error(&token) {
+          Some(token)
+        } else {
+          None
+        }
+      }
+      _ => None,
+    }
+  }
+}
+
+// The root, followed by each descendant that strictly contains `range`,
+// so that the text just around the edit is matched again too
+fn find_enclosing_path(root: &TokenRef, range: &SourceRange) -> Vec<TokenRef> {
+  let mut path = vec![root.clone()];
+
+  loop {
+    let next = path.last().and_then(|token| {
+      token
+        .borrow()
+        .get_children()
+        .iter()
+        .find(|child| {
+          let child_range = *child.borrow().get_matched_range();
+          child_range.start < range.start && range.end < child_range.end
+        })
+        .cloned()
+    });
+
+    match next {
+      Some(token) => path.push(token),
+      None => return path,
+    }
+  }
+}
+
+fn contains_error(token: &TokenRef) -> bool {
+  let token = token.borrow();
+
+  token.flags_enabled(IS_ERROR) || token.get_children().iter().any(contains_error)
+}
+
+// Errors are collected at the root, in the order they were found, so
+// re-parsing anything that had errors would need them put back in order
+fn has_error_within(root: &TokenRef, range: &SourceRange) -> bool {
+  root.borrow().get_children().iter().any(|child| {
+    let child = child.borrow();
+    let child_range = child.get_matched_range();
+
+    child.flags_enabled(IS_ERROR)
+      && child_range.end >= range.start
+      && child_range.start <= range.end
+  })
+}
+
+// A re-parsed token, and the edit that made it necessary
+struct Splice<'a> {
+  edit: &'a TextEdit,
+  replaced: TokenRef,
+  replacement: TokenRef,
+}
+
+impl<'a> Splice<'a> {
+  fn new(edit: &'a TextEdit, replaced: &TokenRef, replacement: &TokenRef) -> Self {
+    Self {
+      edit,
+      replaced: replaced.clone(),
+      replacement: replacement.clone(),
+    }
+  }
+
+  // The captured range of a token enclosing the replaced one may start or
+  // end with what it captured, which doesn't simply move with the edit
+  fn shift_captured_range(&self, range: &SourceRange) -> SourceRange {
+    let old_range = *self.replaced.borrow().get_captured_range();
+    let new_range = *self.replacement.borrow().get_captured_range();
+    let shift = |offset: usize| {
+      if offset == old_range.start {
+        new_range.start
+      } else if offset == old_range.end {
+        new_range.end
+      } else {
+        self.edit.shift(offset)
+      }
+    };
+
+    SourceRange::new(shift(range.start), shift(range.end))
+  }
+
+  fn encloses_replaced(&self, token: &TokenRefInner) -> bool {
+    let range = token.get_matched_range();
+    let replaced_range = *self.replaced.borrow().get_matched_range();
+
+    range.start <= replaced_range.start && replaced_range.end <= range.end
+  }
+
+  fn ends_before_edit(&self, token: &TokenRefInner) -> bool {
+    token.get_captured_range().end <= self.edit.range.start
+      && token.get_matched_range().end <= self.edit.range.start
+  }
+
+  // Only the tokens enclosing the replaced one are rebuilt on `new_parser`.
+  // Everything else is taken over from the previous tree: tokens before the
+  // edit as they are (the source before it didn't change), and tokens after
+  // it moved along with the text
+  fn rebase_token(
+    &self,
+    token: &TokenRef,
+    new_parser: &ParserRef,
+    parent: Option<TokenRef>,
+  ) -> TokenRef {
+    if Rc::ptr_eq(token, &self.replaced) {
+      self.replacement.borrow_mut().set_parent(parent);
+      return self.replacement.clone();
+    }
+
+    let mut old_token = token.borrow_mut();
+    if !self.encloses_replaced(&**old_token) {
+      if !self.ends_before_edit(&**old_token) {
+        self.move_token(&mut **old_token, new_parser);
+      }
+
+      old_token.set_parent(parent);
+      return token.clone();
+    }
+
+    let new_token = StandardToken::new_with_matched_range(
+      new_parser,
+      old_token.get_name().clone(),
+      self.shift_captured_range(old_token.get_captured_range()),
+      self.edit.shift_range(old_token.get_matched_range()),
+    );
+
+    {
+      let mut new_token = new_token.borrow_mut();
+
+      if old_token.get_value() != old_token.get_captured_value()
+        && !old_token.has_attribute("__value")
+      {
+        new_token.set_value(old_token.get_value());
+      }
+
+      for (name, value) in old_token.get_attributes() {
+        new_token.set_attribute(name, value);
+      }
+
+      new_token.set_flags(old_token.get_flags());
+      new_token.set_parent(parent);
+    }
+
+    let children: Vec<TokenRef> = old_token
+      .get_children()
+      .iter()
+      .map(|child| self.rebase_token(child, new_parser, Some(new_token.clone())))
+      .collect();
+
+    new_token.borrow_mut().set_children(children);
+
+    new_token
+  }
+
+  // The text under a token after the edit is the same, just somewhere
+  // else, so only its ranges (and those of its children) need moving
+  fn move_token(&self, token: &mut TokenRefInner, new_parser: &ParserRef) {
+    *token.get_captured_range_mut() = self.edit.shift_range(token.get_captured_range());
+    *token.get_matched_range_mut() = self.edit.shift_range(token.get_matched_range());
+    token.set_parser(new_parser);
+
+    for child in token.get_children() {
+      self.move_token(&mut **child.borrow_mut(), new_parser);
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use proptest::prelude::*;
+
+  use super::{IncrementalParser, TextEdit};
+  use crate::{
+    matcher::MatcherRef, parser::Parser, source_range::SourceRange, token::TokenRef, Discard,
+    Equals, Loop, Matches, Program, Ref, Switch,
+  };
+
+  fn block() -> MatcherRef {
+    Program!("Block";
+      Discard!(Equals!("{")),
+      Loop!(0..; "Items"; Switch!(Ref!("Block"), Matches!("Word"; r"[a-z]+"), Matches!("Space"; r" +"))),
+      Discard!(Equals!("}")),
+    )
+  }
+
+  fn incremental_parser() -> IncrementalParser {
+    let block = block();
+    let mut incremental_parser = IncrementalParser::new(
+      "Test",
+      Loop!(1..; "Document"; Switch!(block.clone(), Matches!("Word"; r"[a-z]+"), Matches!("Space"; r" +"))),
+    );
+
+    incremental_parser.add_reparse_point("Block", block);
+    incremental_parser
+  }
+
+  fn assert_same_tree(left: &TokenRef, right: &TokenRef) {
+    let left = left.borrow();
+    let right = right.borrow();
+
+    assert_eq!(left.get_name(), right.get_name());
+    assert_eq!(left.get_captured_range(), right.get_captured_range());
+    assert_eq!(left.get_matched_range(), right.get_matched_range());
+    assert_eq!(left.get_value(), right.get_value());
+    assert_eq!(left.get_matched_value(), right.get_matched_value());
+    assert_eq!(left.get_attributes(), right.get_attributes());
+    assert_eq!(left.get_children().len(), right.get_children().len());
+
+    for (left_child, right_child) in left.get_children().iter().zip(right.get_children()) {
+      assert!(std::ptr::eq(
+        &*left_child.borrow().get_parent().unwrap().borrow(),
+        &*left,
+      ));
+      assert_same_tree(left_child, right_child);
+    }
+  }
+
+  #[test]
+  fn it_reuses_the_enclosing_block() {
+    let incremental_parser = incremental_parser();
+    let parser = Parser::new("one {two {three} four} five");
+    let previous = incremental_parser.parse(&parser).unwrap();
+    let one = previous.borrow().get_children()[0].clone();
+    let five = previous.borrow().get_children()[4].clone();
+
+    let edit = TextEdit::new(SourceRange::new(10, 15), "tres tres");
+    let (new_parser, token) = incremental_parser
+      .reparse(&previous, &parser, &edit)
+      .unwrap();
+
+    assert_eq!(
+      new_parser.borrow().get_source(),
+      "one {two {tres tres} four} five"
+    );
+    assert_same_tree(&token, &incremental_parser.parse(&new_parser).unwrap());
+
+    // Tokens outside of the re-parsed block are reused, and
+    // the ones after the edit are moved along with the text
+    let new_one = token.borrow().get_children()[0].clone();
+    let new_five = token.borrow().get_children()[4].clone();
+    assert!(std::rc::Rc::ptr_eq(&one, &new_one));
+    assert!(std::rc::Rc::ptr_eq(&five, &new_five));
+    assert_eq!(
+      *new_five.borrow().get_matched_range(),
+      SourceRange::new(27, 31)
+    );
+    assert_eq!(new_five.borrow().get_value(), "five");
+    assert!(std::rc::Rc::ptr_eq(
+      &new_five.borrow().get_parser(),
+      &new_parser
+    ));
+  }
+
+  #[test]
+  fn it_falls_back_to_a_full_parse() {
+    let incremental_parser = incremental_parser();
+    let parser = Parser::new("one {two} three");
+    let previous = incremental_parser.parse(&parser).unwrap();
+
+    // Closes the block early, so it no longer ends where it used to
+    let edit = TextEdit::new(SourceRange::new(7, 7), "} {");
+    let (new_parser, token) = incremental_parser
+      .reparse(&previous, &parser, &edit)
+      .unwrap();
+
+    assert_eq!(new_parser.borrow().get_source(), "one {tw} {o} three");
+    assert_same_tree(&token, &incremental_parser.parse(&new_parser).unwrap());
+
+    let edit = TextEdit::new(SourceRange::new(7, 20), "");
+    assert!(incremental_parser
+      .reparse(&previous, &parser, &edit)
+      .is_err());
+  }
+
+  // Words and spaces, with nested blocks so that most edits land in one
+  fn document() -> impl Strategy<Value = String> {
+    let item = "[a-c]{1,3}".prop_recursive(3, 24, 4, |inner| {
+      prop::collection::vec(inner, 0..4).prop_map(|items| format!("{{{}}}", items.join(" ")))
+    });
+
+    prop::collection::vec(item, 1..5).prop_map(|items| items.join(" "))
+  }
+
+  proptest! {
+    #[test]
+    fn it_matches_a_full_parse(
+      source in document(),
+      start in any::<prop::sample::Index>(),
+      len in 0usize..4,
+      // Mostly edits that keep the blocks balanced
+      replacement in prop_oneof![3 => "[a-c ]{0,4}", 1 => "[a-c {}]{0,4}"],
+    ) {
+      let incremental_parser = incremental_parser();
+      let parser = Parser::new(&source);
+
+      if let Ok(previous) = incremental_parser.parse(&parser) {
+        let start = start.index(source.len() + 1);
+        let edit = TextEdit::new(
+          SourceRange::new(start, (start + len).min(source.len())),
+          &replacement,
+        );
+        let result = incremental_parser.reparse(&previous, &parser, &edit);
+        let full_parser = edit.apply(&parser).unwrap();
+
+        match (result, incremental_parser.parse(&full_parser)) {
+          (Ok((_, token)), Ok(expected)) => assert_same_tree(&token, &expected),
+          (Err(error), Err(expected)) => prop_assert_eq!(error, expected),
+          (result, expected) => prop_assert!(false, "{:?} != {:?}", result.map(|(_, token)| token), expected),
+        }
+      }
+    }
+  }
+}
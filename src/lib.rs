@@ -1,3 +1,12 @@
+// Lets `adextopa_macros` derives refer to this crate as `::adextopa_core` internally too
+extern crate self as adextopa_core;
+
+pub mod analysis;
+pub mod codegen;
+pub mod export;
+pub mod from_token;
+pub mod import;
+pub mod incremental;
 pub mod matcher;
 pub mod matchers;
 pub mod parse_error;
@@ -6,9 +15,12 @@ pub mod parser_context;
 pub mod scope;
 pub mod scope_context;
 pub mod script;
+pub mod source;
 pub mod source_range;
 pub mod token;
+pub mod token_iterator;
 pub mod token_visitor;
+pub mod transforms;
 
 #[cfg(test)]
 mod tests {
@@ -1,4 +1,5 @@
 use std::cell::RefCell;
+use std::ops::Range;
 use std::rc::Rc;
 
 use super::token::TokenRef;
@@ -26,6 +27,37 @@ pub enum MatcherFailure {
 
 pub type MatcherRef = Rc<RefCell<Box<dyn Matcher>>>;
 
+/// Describes what a matcher is, so tooling (code generation,
+/// exporters, analysis) can walk a compiled matcher graph
+/// without executing it.
+#[derive(Debug, PartialEq, Clone)]
+pub enum MatcherKind {
+  Program,
+  Switch,
+  Loop(Range<usize>),
+  // Item count (with an exclusive end), children are the item, then the separator
+  SepBy(Range<usize>),
+  // Literal pattern, if it is known before execution
+  Equals(Option<String>),
+  // Keyword alternatives, if they are known before execution
+  OneOf(Option<Vec<String>>),
+  // Regular expression source
+  Matches(String),
+  // Start, end, and escape patterns, if known before execution
+  Sequence(Option<String>, Option<String>, Option<String>),
+  // Target name, if known before execution
+  Ref(Option<String>),
+  Optional,
+  Not,
+  // Positive lookahead
+  Peek,
+  Discard,
+  ProxyChildren,
+  // Passes the result of its single child through (i.e. `Map`, `SetScope`)
+  Wrapper,
+  Other,
+}
+
 impl std::fmt::Debug for dyn Matcher {
   fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
     f.write_str(&self.to_string())
@@ -69,6 +101,15 @@ pub trait Matcher {
   fn is_consuming(&self) -> bool {
     true
   }
+
+  fn get_kind(&self) -> MatcherKind {
+    MatcherKind::Other
+  }
+
+  // The scope this matcher executes its children in, if it provides one
+  fn get_scope(&self) -> Option<ScopeContextRef> {
+    None
+  }
 }
 
 #[macro_export]
@@ -0,0 +1,250 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::matcher::{Matcher, MatcherFailure, MatcherRef, MatcherSuccess};
+use crate::parser_context::ParserContextRef;
+use crate::scope_context::ScopeContextRef;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Anchor {
+  /// Start of input
+  Bof,
+  /// End of input
+  Eof,
+  /// Start of a line
+  Bol,
+  /// End of a line
+  Eol,
+  /// Between a word character and a non-word character (either way around)
+  WordBoundary,
+}
+
+impl Anchor {
+  fn name(&self) -> &'static str {
+    match self {
+      Anchor::Bof => "Bof",
+      Anchor::Eof => "Eof",
+      Anchor::Bol => "Bol",
+      Anchor::Eol => "Eol",
+      Anchor::WordBoundary => "WordBoundary",
+    }
+  }
+}
+
+fn is_word_char(c: Option<char>) -> bool {
+  c.is_some_and(|c| c.is_alphanumeric() || c == '_')
+}
+
+fn is_newline(c: Option<char>) -> bool {
+  matches!(c, Some('\n') | Some('\r'))
+}
+
+// Zero-width assertions about the current position. These are
+// always evaluated against the full source, not the context range
+#[derive(Debug)]
+pub struct AnchorPattern {
+  anchor: Anchor,
+}
+
+impl AnchorPattern {
+  pub fn new(anchor: Anchor) -> MatcherRef {
+    Rc::new(RefCell::new(Box::new(AnchorPattern { anchor })))
+  }
+
+  fn _exec(
+    &self,
+    context: ParserContextRef,
+    _: ScopeContextRef,
+  ) -> Result<MatcherSuccess, MatcherFailure> {
+    let context = context.borrow();
+    let offset = context.offset.start;
+    let end = context.parser.borrow_mut().load_to(offset + 4);
+    let parser = context.parser.borrow();
+    let previous = parser
+      .slice(parser.source.start(), offset)
+      .chars()
+      .next_back();
+    let next = parser.slice(offset, end).chars().next();
+
+    let matched = match self.anchor {
+      Anchor::Bof => offset == 0,
+      Anchor::Eof => offset >= end,
+      Anchor::Bol => previous.is_none() || is_newline(previous),
+      Anchor::Eol => next.is_none() || is_newline(next),
+      Anchor::WordBoundary => is_word_char(previous) != is_word_char(next),
+    };
+
+    if matched {
+      Ok(MatcherSuccess::Skip(0))
+    } else {
+      Err(MatcherFailure::Fail)
+    }
+  }
+}
+
+impl Matcher for AnchorPattern {
+  fn exec(
+    &self,
+    this_matcher: MatcherRef,
+    context: ParserContextRef,
+    scope: ScopeContextRef,
+  ) -> Result<MatcherSuccess, MatcherFailure> {
+    self.before_exec(this_matcher.clone(), context.clone(), scope.clone());
+    let result = self._exec(context.clone(), scope.clone());
+    self.after_exec(this_matcher.clone(), context.clone(), scope.clone());
+
+    result
+  }
+
+  fn is_consuming(&self) -> bool {
+    false
+  }
+
+  fn get_name(&self) -> &str {
+    self.anchor.name()
+  }
+
+  fn set_name(&mut self, _: &str) {}
+
+  fn get_children(&self) -> Option<Vec<MatcherRef>> {
+    None
+  }
+
+  fn add_pattern(&mut self, _: MatcherRef) {
+    panic!(
+      "Can not add a pattern to a `{}` matcher",
+      self.anchor.name()
+    );
+  }
+
+  fn to_string(&self) -> String {
+    format!("{:?}", self)
+  }
+}
+
+#[macro_export]
+macro_rules! Bof {
+  () => {
+    $crate::matchers::anchor::AnchorPattern::new($crate::matchers::anchor::Anchor::Bof)
+  };
+}
+
+#[macro_export]
+macro_rules! Eof {
+  () => {
+    $crate::matchers::anchor::AnchorPattern::new($crate::matchers::anchor::Anchor::Eof)
+  };
+}
+
+#[macro_export]
+macro_rules! Bol {
+  () => {
+    $crate::matchers::anchor::AnchorPattern::new($crate::matchers::anchor::Anchor::Bol)
+  };
+}
+
+#[macro_export]
+macro_rules! Eol {
+  () => {
+    $crate::matchers::anchor::AnchorPattern::new($crate::matchers::anchor::Anchor::Eol)
+  };
+}
+
+#[macro_export]
+macro_rules! WordBoundary {
+  () => {
+    $crate::matchers::anchor::AnchorPattern::new($crate::matchers::anchor::Anchor::WordBoundary)
+  };
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::{
+    matcher::MatcherFailure, parser::Parser, parser_context::ParserContext,
+    source_range::SourceRange, Equals, Matches, Program,
+  };
+
+  #[test]
+  fn it_matches_the_start_and_end_of_input() {
+    let parser = Parser::new("test");
+    let parser_context = ParserContext::new(&parser, "Test");
+    let matcher = Program!(Bof!(), Equals!("test"), Eof!());
+
+    if let Ok(token) = ParserContext::tokenize(parser_context, matcher) {
+      assert_eq!(*token.borrow().get_matched_range(), SourceRange::new(0, 4));
+    } else {
+      unreachable!("Test failed!");
+    };
+
+    let parser = Parser::new("tester");
+    let parser_context = ParserContext::new(&parser, "Test");
+    let matcher = Program!(Equals!("test"), Eof!());
+
+    assert_eq!(
+      ParserContext::tokenize(parser_context, matcher),
+      Err(MatcherFailure::Fail)
+    );
+  }
+
+  #[test]
+  fn it_matches_line_boundaries() {
+    let parser = Parser::new("one\ntwo");
+    let parser_context = ParserContext::new(&parser, "Test");
+    let matcher = Program!(
+      Matches!(r"\w+"),
+      Eol!(),
+      Matches!(r"\s+"),
+      Bol!(),
+      Matches!(r"\w+"),
+      Eol!(),
+    );
+
+    if let Ok(token) = ParserContext::tokenize(parser_context, matcher) {
+      assert_eq!(*token.borrow().get_matched_range(), SourceRange::new(0, 7));
+    } else {
+      unreachable!("Test failed!");
+    };
+
+    let parser = Parser::new("one two");
+    let parser_context = ParserContext::new(&parser, "Test");
+    let matcher = Program!(Matches!(r"\w+"), Matches!(r"\s+"), Bol!());
+
+    assert_eq!(
+      ParserContext::tokenize(parser_context, matcher),
+      Err(MatcherFailure::Fail)
+    );
+  }
+
+  #[test]
+  fn it_matches_word_boundaries_against_the_full_source() {
+    let parser = Parser::new("iffy if");
+    let parser_context = ParserContext::new(&parser, "Test");
+    let matcher = Program!(Equals!("if"), WordBoundary!());
+
+    assert_eq!(
+      ParserContext::tokenize(parser_context, matcher),
+      Err(MatcherFailure::Fail)
+    );
+
+    // `fy` at offset 2 is preceded by `if`, which the
+    // matcher can only see by looking behind the offset
+    let parser_context = ParserContext::new(&parser, "Test");
+    parser_context.borrow_mut().set_start(2);
+    let matcher = Program!(WordBoundary!(), Equals!("fy"));
+
+    assert_eq!(
+      ParserContext::tokenize(parser_context, matcher),
+      Err(MatcherFailure::Fail)
+    );
+
+    let parser_context = ParserContext::new(&parser, "Test");
+    parser_context.borrow_mut().set_start(5);
+    let matcher = Program!(WordBoundary!(), Equals!("if"), WordBoundary!());
+
+    if let Ok(token) = ParserContext::tokenize(parser_context, matcher) {
+      assert_eq!(*token.borrow().get_matched_range(), SourceRange::new(5, 7));
+    } else {
+      unreachable!("Test failed!");
+    };
+  }
+}
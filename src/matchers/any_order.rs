@@ -0,0 +1,425 @@
+use crate::matcher::{Matcher, MatcherFailure, MatcherRef, MatcherSuccess};
+use crate::parse_error::ParseError;
+use crate::parser_context::ParserContextRef;
+use crate::scope_context::ScopeContextRef;
+use crate::source_range::SourceRange;
+use crate::token::{StandardToken, TokenRef};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// Attribute set on each child token, holding the
+/// index of the element that produced it
+pub const ELEMENT_ATTRIBUTE: &str = "__element";
+
+#[derive(Debug)]
+pub struct AnyOrderElement {
+  pub matcher: MatcherRef,
+  pub required: bool,
+}
+
+impl AnyOrderElement {
+  pub fn required(matcher: MatcherRef) -> Self {
+    Self {
+      matcher,
+      required: true,
+    }
+  }
+
+  pub fn optional(matcher: MatcherRef) -> Self {
+    Self {
+      matcher,
+      required: false,
+    }
+  }
+}
+
+// The result of running a single element
+struct Step {
+  children: Vec<TokenRef>,
+  end: usize,
+}
+
+// Matches each of its elements at most once, in whatever order they
+// appear in the source. Running into an element that has already
+// matched, or stopping before every required element has matched,
+// is a parse error (unless nothing matched at all, which just fails)
+#[derive(Debug)]
+pub struct AnyOrderPattern {
+  elements: Vec<AnyOrderElement>,
+  name: String,
+  custom_name: bool,
+}
+
+impl AnyOrderPattern {
+  pub fn new(elements: Vec<AnyOrderElement>) -> MatcherRef {
+    Rc::new(RefCell::new(Box::new(Self {
+      elements,
+      name: "AnyOrder".to_string(),
+      custom_name: false,
+    })))
+  }
+
+  pub fn new_with_name(name: &str, elements: Vec<AnyOrderElement>) -> MatcherRef {
+    Rc::new(RefCell::new(Box::new(Self {
+      elements,
+      name: name.to_string(),
+      custom_name: true,
+    })))
+  }
+
+  fn step(
+    &self,
+    index: usize,
+    context: &ParserContextRef,
+    scope: &ScopeContextRef,
+    offset: usize,
+  ) -> Result<Option<Step>, MatcherFailure> {
+    let matcher = &self.elements[index].matcher;
+    let sub_context = context.borrow().clone_with_name(self.get_name());
+    sub_context.borrow_mut().set_start(offset);
+
+    let savepoint = scope.borrow().savepoint();
+    let result = matcher
+      .borrow()
+      .exec(matcher.clone(), sub_context, scope.clone());
+
+    // An element that didn't match here leaves nothing behind
+    if result.is_err() {
+      scope.borrow_mut().rollback_to_savepoint(savepoint);
+    }
+
+    let (children, end) = match result {
+      Ok(MatcherSuccess::Token(token)) => {
+        let end = token.borrow().get_matched_range().end;
+        let children = if token.borrow().should_discard() {
+          Vec::new()
+        } else {
+          vec![token]
+        };
+
+        (children, end)
+      }
+      Ok(MatcherSuccess::ProxyChildren(token)) => {
+        let _token = token.borrow();
+        let children = _token
+          .get_children()
+          .iter()
+          .filter(|child| !child.borrow().should_discard())
+          .cloned()
+          .collect();
+
+        (children, _token.get_matched_range().end)
+      }
+      Ok(MatcherSuccess::Skip(amount)) => (Vec::new(), (offset as isize + amount) as usize),
+      Ok(_) => (Vec::new(), offset),
+      Err(MatcherFailure::Fail) => return Ok(None),
+      Err(error) => return Err(error),
+    };
+
+    let tag = index.to_string();
+    for child in &children {
+      child.borrow_mut().set_attribute(ELEMENT_ATTRIBUTE, &tag);
+    }
+
+    Ok(Some(Step { children, end }))
+  }
+
+  fn error(&self, context: &ParserContextRef, message: &str, range: SourceRange) -> MatcherFailure {
+    MatcherFailure::Error(ParseError::new_with_range(
+      &context.borrow().get_error_as_string(message, &range),
+      range,
+    ))
+  }
+
+  fn _exec(
+    &self,
+    context: ParserContextRef,
+    scope: ScopeContextRef,
+  ) -> Result<MatcherSuccess, MatcherFailure> {
+    let start_offset = context.borrow().offset.start;
+    let mut offset = start_offset;
+    let mut matched = vec![false; self.elements.len()];
+    let mut children = Vec::<TokenRef>::new();
+
+    loop {
+      let mut progressed = false;
+
+      for (index, done) in matched.iter_mut().enumerate() {
+        if *done {
+          continue;
+        }
+
+        if let Some(step) = self.step(index, &context, &scope, offset)? {
+          *done = true;
+          children.extend(step.children);
+          offset = step.end;
+          progressed = true;
+          break;
+        }
+      }
+
+      if !progressed {
+        break;
+      }
+    }
+
+    // Nothing else matched here, but if an element that already
+    // matched does, then it was given more than once
+    for (index, _) in matched.iter().enumerate().filter(|(_, done)| **done) {
+      // This is only a check, so nothing it stores is kept
+      let savepoint = scope.borrow().savepoint();
+      let step = self.step(index, &context, &scope, offset);
+      scope.borrow_mut().rollback_to_savepoint(savepoint);
+
+      if let Some(step) = step? {
+        if step.end > offset {
+          let name = self.elements[index].matcher.borrow().get_name().to_string();
+
+          return Err(self.error(
+            &context,
+            &format!("Duplicate `{}` found in `{}`", name, self.name),
+            SourceRange::new(offset, step.end),
+          ));
+        }
+      }
+    }
+
+    let missing: Vec<String> = self
+      .elements
+      .iter()
+      .zip(&matched)
+      .filter(|(element, matched)| element.required && !**matched)
+      .map(|(element, _)| format!("`{}`", element.matcher.borrow().get_name()))
+      .collect();
+
+    if !missing.is_empty() {
+      if !matched.contains(&true) {
+        return Err(MatcherFailure::Fail);
+      }
+
+      return Err(self.error(
+        &context,
+        &format!("Missing required {} in `{}`", missing.join(", "), self.name),
+        SourceRange::new(start_offset, offset),
+      ));
+    }
+
+    if children.is_empty() {
+      return Ok(MatcherSuccess::Skip((offset - start_offset) as isize));
+    }
+
+    let mut captured_range = SourceRange::new(usize::MAX, 0);
+    for child in &children {
+      let child_range = *child.borrow().get_captured_range();
+      captured_range.start = captured_range.start.min(child_range.start);
+      captured_range.end = captured_range.end.max(child_range.end);
+    }
+
+    let token = StandardToken::new_with_matched_range(
+      &context.borrow().parser,
+      self.name.to_string(),
+      captured_range,
+      SourceRange::new(start_offset, offset),
+    );
+
+    for child in &children {
+      child.borrow_mut().set_parent(Some(token.clone()));
+    }
+
+    token.borrow_mut().set_children(children);
+
+    Ok(MatcherSuccess::Token(token))
+  }
+}
+
+impl Matcher for AnyOrderPattern {
+  fn exec(
+    &self,
+    this_matcher: MatcherRef,
+    context: ParserContextRef,
+    scope: ScopeContextRef,
+  ) -> Result<MatcherSuccess, MatcherFailure> {
+    self.before_exec(this_matcher.clone(), context.clone(), scope.clone());
+    let checkpoint = scope.borrow_mut().begin();
+    let result = self._exec(context.clone(), scope.clone());
+
+    // Writes are only journaled inside a transaction, which
+    // is what lets rejected elements (see `step`) be undone
+    match result {
+      Ok(_) => scope.borrow_mut().commit(),
+      Err(_) => scope.borrow_mut().rollback(checkpoint),
+    }
+
+    self.after_exec(this_matcher.clone(), context.clone(), scope.clone());
+
+    result
+  }
+
+  fn has_custom_name(&self) -> bool {
+    self.custom_name
+  }
+
+  fn get_name(&self) -> &str {
+    self.name.as_str()
+  }
+
+  fn set_name(&mut self, name: &str) {
+    self.name = name.to_string();
+    self.custom_name = name != "AnyOrder";
+  }
+
+  fn set_child(&mut self, index: usize, matcher: MatcherRef) {
+    match self.elements.get_mut(index) {
+      Some(element) => element.matcher = matcher,
+      None => panic!("Attempt to set child at an index that is out of bounds"),
+    }
+  }
+
+  fn get_children(&self) -> Option<Vec<MatcherRef>> {
+    Some(
+      self
+        .elements
+        .iter()
+        .map(|element| element.matcher.clone())
+        .collect(),
+    )
+  }
+
+  fn add_pattern(&mut self, pattern: MatcherRef) {
+    self.elements.push(AnyOrderElement::required(pattern));
+  }
+
+  fn to_string(&self) -> String {
+    format!("{:?}", self)
+  }
+}
+
+// Elements are required unless prefixed with `?`, i.e. `AnyOrder!(a, ?b)`
+#[macro_export]
+macro_rules! AnyOrder {
+  (@elements [$($elements:expr),*]) => {
+    vec![$($elements),*]
+  };
+
+  (@elements [$($elements:expr),*] ? $matcher:expr $(, $($rest:tt)*)?) => {
+    $crate::AnyOrder!(@elements [
+      $($elements,)*
+      $crate::matchers::any_order::AnyOrderElement::optional($matcher)
+    ] $($($rest)*)?)
+  };
+
+  (@elements [$($elements:expr),*] $matcher:expr $(, $($rest:tt)*)?) => {
+    $crate::AnyOrder!(@elements [
+      $($elements,)*
+      $crate::matchers::any_order::AnyOrderElement::required($matcher)
+    ] $($($rest)*)?)
+  };
+
+  ($name:literal; $($rest:tt)*) => {
+    $crate::matchers::any_order::AnyOrderPattern::new_with_name(
+      $name,
+      $crate::AnyOrder!(@elements [] $($rest)*),
+    )
+  };
+
+  ($($rest:tt)*) => {
+    $crate::matchers::any_order::AnyOrderPattern::new($crate::AnyOrder!(@elements [] $($rest)*))
+  };
+}
+
+#[cfg(test)]
+mod tests {
+  use super::ELEMENT_ATTRIBUTE;
+  use crate::{
+    matcher::MatcherFailure, parser::Parser, parser_context::ParserContext,
+    source_range::SourceRange, Discard, Equals, Matches, Program,
+  };
+
+  fn tags(token: &crate::token::TokenRef) -> Vec<(String, String)> {
+    token
+      .borrow()
+      .get_children()
+      .iter()
+      .map(|child| {
+        let child = child.borrow();
+        (
+          child.get_value().clone(),
+          child.get_attribute(ELEMENT_ATTRIBUTE).unwrap().clone(),
+        )
+      })
+      .collect()
+  }
+
+  #[test]
+  fn it_matches_elements_in_any_order() {
+    let parser = Parser::new("static pub const;");
+    let parser_context = ParserContext::new(&parser, "Test");
+    let matcher = AnyOrder!("Modifiers";
+      Program!(Equals!("pub"), Discard!(Matches!(r"\s*"))),
+      ?Program!(Equals!("static"), Discard!(Matches!(r"\s*"))),
+      ?Program!(Equals!("const"), Discard!(Matches!(r"\s*"))),
+    );
+
+    if let Ok(token) = ParserContext::tokenize(parser_context, matcher) {
+      assert_eq!(token.borrow().get_name(), "Modifiers");
+      assert_eq!(*token.borrow().get_matched_range(), SourceRange::new(0, 16));
+      assert_eq!(
+        tags(&token),
+        vec![
+          ("static".to_string(), "1".to_string()),
+          ("pub".to_string(), "0".to_string()),
+          ("const".to_string(), "2".to_string()),
+        ]
+      );
+    } else {
+      unreachable!("Test failed!");
+    };
+  }
+
+  #[test]
+  fn it_can_skip_optional_elements() {
+    let parser = Parser::new("b");
+    let parser_context = ParserContext::new(&parser, "Test");
+    let matcher = AnyOrder!(?Equals!("a"), Equals!("b"));
+
+    if let Ok(token) = ParserContext::tokenize(parser_context, matcher) {
+      assert_eq!(tags(&token), vec![("b".to_string(), "1".to_string())]);
+    } else {
+      unreachable!("Test failed!");
+    };
+
+    let parser = Parser::new("c");
+    let parser_context = ParserContext::new(&parser, "Test");
+    let matcher = AnyOrder!(?Equals!("a"), Equals!("b"));
+
+    assert_eq!(
+      ParserContext::tokenize(parser_context, matcher),
+      Err(MatcherFailure::Fail)
+    );
+  }
+
+  #[test]
+  fn it_reports_duplicate_and_missing_elements() {
+    let parser = Parser::new("aba");
+    let parser_context = ParserContext::new(&parser, "Test");
+    let matcher = AnyOrder!(Equals!("A"; "a"), ?Equals!("B"; "b"), Equals!("C"; "c"));
+
+    if let Err(MatcherFailure::Error(error)) = ParserContext::tokenize(parser_context, matcher) {
+      assert_eq!(error.range, Some(SourceRange::new(2, 3)));
+      assert!(error.message.contains("Duplicate `A`"));
+    } else {
+      unreachable!("Test failed!");
+    };
+
+    let parser = Parser::new("ba");
+    let parser_context = ParserContext::new(&parser, "Test");
+    let matcher = AnyOrder!(Equals!("A"; "a"), ?Equals!("B"; "b"), Equals!("C"; "c"));
+
+    if let Err(MatcherFailure::Error(error)) = ParserContext::tokenize(parser_context, matcher) {
+      assert_eq!(error.range, Some(SourceRange::new(0, 2)));
+      assert!(error.message.contains("Missing required `C`"));
+    } else {
+      unreachable!("Test failed!");
+    };
+  }
+}
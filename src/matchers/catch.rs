@@ -1,4 +1,4 @@
-use crate::matcher::{Matcher, MatcherFailure, MatcherRef, MatcherSuccess};
+use crate::matcher::{Matcher, MatcherFailure, MatcherKind, MatcherRef, MatcherSuccess};
 use crate::parser_context::ParserContextRef;
 use crate::scope_context::ScopeContextRef;
 use std::cell::RefCell;
@@ -100,6 +100,10 @@ where
   fn to_string(&self) -> String {
     format!("{:?}", self)
   }
+
+  fn get_kind(&self) -> MatcherKind {
+    MatcherKind::Wrapper
+  }
 }
 
 #[macro_export]
@@ -174,4 +178,29 @@ mod tests {
       unreachable!("Test failed!");
     };
   }
+
+  #[test]
+  fn it_can_reach_user_data_from_the_context() {
+    let parser = Parser::new("Testing 1234");
+    let parser_context = ParserContext::new(&parser, "Test");
+    parser_context
+      .borrow_mut()
+      .set_user_data("expected a greeting".to_string());
+
+    let matcher = Catch!(Equals!("Hello"), |context, _| {
+      let message = context
+        .borrow()
+        .with_user_data(|message: &mut String| message.clone())
+        .map_err(MatcherFailure::Error)?
+        .unwrap_or_default();
+
+      Err(MatcherFailure::Error(ParseError::new(&message)))
+    });
+
+    if let Err(MatcherFailure::Error(failure)) = ParserContext::tokenize(parser_context, matcher) {
+      assert_eq!(failure.message, "expected a greeting");
+    } else {
+      unreachable!("Test failed!");
+    };
+  }
 }
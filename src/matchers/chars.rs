@@ -0,0 +1,500 @@
+use std::cell::RefCell;
+use std::ops::{Range, RangeBounds, RangeInclusive};
+use std::rc::Rc;
+
+use regex_syntax::hir::{Class, ClassUnicode, ClassUnicodeRange, Hir, HirKind};
+
+use super::program::get_range;
+use crate::matcher::{Matcher, MatcherFailure, MatcherKind, MatcherRef, MatcherSuccess};
+use crate::parser_context::ParserContextRef;
+use crate::scope_context::ScopeContextRef;
+use crate::source_range::SourceRange;
+use crate::token::StandardToken;
+
+// How much input is looked at, at a time
+const CHUNK_SIZE: usize = 4096;
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum CharSpec {
+  Char(char),
+  Range(char, char),
+  /// A Unicode general category, i.e. `L`, `Lu` or `Letter`
+  Category(String),
+  /// A Perl class, one of `d`, `w` or `s`
+  Perl(char),
+}
+
+impl From<char> for CharSpec {
+  fn from(c: char) -> Self {
+    CharSpec::Char(c)
+  }
+}
+
+impl From<RangeInclusive<char>> for CharSpec {
+  fn from(range: RangeInclusive<char>) -> Self {
+    CharSpec::Range(*range.start(), *range.end())
+  }
+}
+
+fn parse_class(source: &str) -> Result<ClassUnicode, String> {
+  let hir = regex_syntax::Parser::new()
+    .parse(source)
+    .map_err(|error| error.to_string())?;
+
+  match hir.kind() {
+    HirKind::Class(Class::Unicode(class)) => Ok(class.clone()),
+    _ => Err(format!("`{}` is not a character class", source)),
+  }
+}
+
+/// A set of characters, stored as sorted, non-overlapping ranges
+#[derive(Debug, PartialEq, Clone)]
+pub struct CharSet {
+  ranges: Vec<(char, char)>,
+  // Membership of each ASCII character, so the common case is a single bit test
+  ascii: u128,
+}
+
+impl CharSet {
+  pub fn new<I, S>(specs: I) -> Result<Self, String>
+  where
+    I: IntoIterator<Item = S>,
+    S: Into<CharSpec>,
+  {
+    let mut class = ClassUnicode::empty();
+
+    for spec in specs {
+      let spec_class = match spec.into() {
+        CharSpec::Char(c) => ClassUnicode::new([ClassUnicodeRange::new(c, c)]),
+        CharSpec::Range(start, end) => ClassUnicode::new([ClassUnicodeRange::new(start, end)]),
+        CharSpec::Category(name) => parse_class(&format!(r"\p{{{}}}", name))?,
+        CharSpec::Perl(name @ ('d' | 'w' | 's')) => parse_class(&format!(r"\{}", name))?,
+        CharSpec::Perl(name) => return Err(format!("Unknown Perl class `\\{}`", name)),
+      };
+
+      class.union(&spec_class);
+    }
+
+    Ok(Self::from_class(&class))
+  }
+
+  fn from_class(class: &ClassUnicode) -> Self {
+    let ranges: Vec<(char, char)> = class
+      .ranges()
+      .iter()
+      .map(|range| (range.start(), range.end()))
+      .collect();
+
+    let mut ascii = 0u128;
+    for (start, end) in &ranges {
+      for c in (*start as u32)..=(*end as u32).min(0x7f) {
+        ascii |= 1 << c;
+      }
+    }
+
+    Self { ranges, ascii }
+  }
+
+  pub fn negated(&self) -> Self {
+    let mut class = ClassUnicode::new(
+      self
+        .ranges
+        .iter()
+        .map(|(start, end)| ClassUnicodeRange::new(*start, *end)),
+    );
+    class.negate();
+
+    Self::from_class(&class)
+  }
+
+  pub fn contains(&self, c: char) -> bool {
+    if c.is_ascii() {
+      return self.ascii & (1 << c as u32) != 0;
+    }
+
+    self
+      .ranges
+      .binary_search_by(|(start, end)| {
+        if *end < c {
+          std::cmp::Ordering::Less
+        } else if *start > c {
+          std::cmp::Ordering::Greater
+        } else {
+          std::cmp::Ordering::Equal
+        }
+      })
+      .is_ok()
+  }
+
+  // The equivalent regular expression class, for tooling
+  pub fn to_regex(&self) -> String {
+    let mut result = String::from("[");
+
+    for (start, end) in &self.ranges {
+      result.push_str(&regex::escape(&start.to_string()));
+
+      if start != end {
+        result.push('-');
+        result.push_str(&regex::escape(&end.to_string()));
+      }
+    }
+
+    result.push(']');
+    result
+  }
+}
+
+// A greedy repetition of a single class (i.e. `[a-z_]+`) is
+// the only kind of regular expression that can be lowered
+fn lower_hir(hir: &Hir) -> Option<(CharSet, Range<usize>)> {
+  match hir.kind() {
+    HirKind::Class(Class::Unicode(class)) => Some((CharSet::from_class(class), 1..2)),
+    HirKind::Repetition(repetition) if repetition.greedy => match repetition.sub.kind() {
+      HirKind::Class(Class::Unicode(class)) => {
+        let end = match repetition.max {
+          Some(max) => max as usize + 1,
+          None => usize::MAX,
+        };
+
+        Some((CharSet::from_class(class), repetition.min as usize..end))
+      }
+      _ => None,
+    },
+    _ => None,
+  }
+}
+
+#[derive(Debug)]
+pub struct CharsPattern {
+  set: CharSet,
+  range: Range<usize>,
+  // The regular expression this matcher was lowered from
+  source: Option<String>,
+  name: String,
+  custom_name: bool,
+}
+
+impl CharsPattern {
+  pub fn new<T>(range: T, set: CharSet) -> MatcherRef
+  where
+    T: RangeBounds<usize>,
+  {
+    Rc::new(RefCell::new(Box::new(Self {
+      set,
+      range: get_range(range),
+      source: None,
+      name: "Chars".to_string(),
+      custom_name: false,
+    })))
+  }
+
+  pub fn new_with_name<T>(name: &str, range: T, set: CharSet) -> MatcherRef
+  where
+    T: RangeBounds<usize>,
+  {
+    Rc::new(RefCell::new(Box::new(Self {
+      set,
+      range: get_range(range),
+      source: None,
+      name: name.to_string(),
+      custom_name: true,
+    })))
+  }
+
+  /// Build a matcher equivalent to `Matches!(source)`, if the regular
+  /// expression is simple enough. It keeps the name of a `Matches` matcher
+  pub fn new_from_regex(source: &str) -> Option<MatcherRef> {
+    let hir = regex_syntax::Parser::new().parse(source).ok()?;
+    let (set, range) = lower_hir(&hir)?;
+
+    Some(Rc::new(RefCell::new(Box::new(Self {
+      set,
+      range,
+      source: Some(source.to_string()),
+      name: "Matches".to_string(),
+      custom_name: false,
+    }))))
+  }
+
+  fn _exec(
+    &self,
+    context: ParserContextRef,
+    _: ScopeContextRef,
+  ) -> Result<MatcherSuccess, MatcherFailure> {
+    let sub_context = context.borrow().clone_with_name(self.get_name());
+    let _sc = sub_context.borrow();
+    let start = _sc.offset.start;
+    let max = self.range.end.saturating_sub(1);
+    let mut offset = start;
+    let mut count = 0;
+
+    // A chunk of input at a time, so that no more of it
+    // is loaded than is needed to find where the run ends
+    'chunks: while count < max {
+      let input = _sc.get_input(offset, offset.saturating_add(CHUNK_SIZE));
+      let bytes = input.as_bytes();
+      let mut index = 0;
+
+      if bytes.is_empty() {
+        break;
+      }
+
+      while count < max && index < bytes.len() {
+        let byte = bytes[index];
+
+        if byte < 0x80 {
+          if self.set.ascii & (1 << byte) == 0 {
+            offset += index;
+            break 'chunks;
+          }
+
+          index += 1;
+        } else {
+          let c = input[index..].chars().next().unwrap();
+          if !self.set.contains(c) {
+            offset += index;
+            break 'chunks;
+          }
+
+          index += c.len_utf8();
+        }
+
+        count += 1;
+      }
+
+      offset += index;
+    }
+
+    if count < self.range.start {
+      return Err(MatcherFailure::Fail);
+    }
+
+    // Like `Matches`, a zero width match is skipped
+    if offset == start {
+      return Ok(MatcherSuccess::Skip(0));
+    }
+
+    Ok(MatcherSuccess::Token(StandardToken::new(
+      &_sc.parser,
+      self.name.to_string(),
+      SourceRange::new(start, offset),
+    )))
+  }
+}
+
+impl Matcher for CharsPattern {
+  fn exec(
+    &self,
+    this_matcher: MatcherRef,
+    context: ParserContextRef,
+    scope: ScopeContextRef,
+  ) -> Result<MatcherSuccess, MatcherFailure> {
+    self.before_exec(this_matcher.clone(), context.clone(), scope.clone());
+    let result = self._exec(context.clone(), scope.clone());
+    self.after_exec(this_matcher.clone(), context.clone(), scope.clone());
+
+    result
+  }
+
+  fn has_custom_name(&self) -> bool {
+    self.custom_name
+  }
+
+  fn get_name(&self) -> &str {
+    self.name.as_str()
+  }
+
+  fn set_name(&mut self, name: &str) {
+    self.name = name.to_string();
+    self.custom_name = name != "Chars" && name != "Matches";
+  }
+
+  fn get_children(&self) -> Option<Vec<MatcherRef>> {
+    None
+  }
+
+  fn add_pattern(&mut self, _: MatcherRef) {
+    panic!("Can not add a pattern to a `Chars` matcher");
+  }
+
+  fn to_string(&self) -> String {
+    format!("{:?}", self)
+  }
+
+  fn get_kind(&self) -> MatcherKind {
+    if let Some(source) = &self.source {
+      return MatcherKind::Matches(source.clone());
+    }
+
+    let quantifier = match (self.range.start, self.range.end) {
+      (1, 2) => String::new(),
+      (0, usize::MAX) => "*".to_string(),
+      (1, usize::MAX) => "+".to_string(),
+      (start, usize::MAX) => format!("{{{},}}", start),
+      (start, end) => format!("{{{},{}}}", start, end - 1),
+    };
+
+    MatcherKind::Matches(format!("{}{}", self.set.to_regex(), quantifier))
+  }
+}
+
+#[macro_export]
+macro_rules! Chars {
+  ($name:literal; $range:expr; [$($spec:expr),* $(,)?]) => {
+    $crate::matchers::chars::CharsPattern::new_with_name(
+      $name,
+      $range,
+      $crate::matchers::chars::CharSet::new([
+        $($crate::matchers::chars::CharSpec::from($spec)),*
+      ])
+      .unwrap(),
+    )
+  };
+
+  ($name:literal; [$($spec:expr),* $(,)?]) => {
+    $crate::Chars!($name; 1..; [$($spec),*])
+  };
+
+  ($range:expr; [$($spec:expr),* $(,)?]) => {
+    $crate::matchers::chars::CharsPattern::new(
+      $range,
+      $crate::matchers::chars::CharSet::new([
+        $($crate::matchers::chars::CharSpec::from($spec)),*
+      ])
+      .unwrap(),
+    )
+  };
+
+  ([$($spec:expr),* $(,)?]) => {
+    $crate::Chars!(1..; [$($spec),*])
+  };
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{CharSet, CharSpec, CharsPattern};
+  use crate::{
+    matcher::{MatcherFailure, MatcherKind, MatcherSuccess},
+    parser::Parser,
+    parser_context::ParserContext,
+    source_range::SourceRange,
+  };
+
+  #[test]
+  fn it_matches_a_run_of_characters() {
+    let parser = Parser::new("some_identifier1 = 2");
+    let parser_context = ParserContext::new(&parser, "Test");
+    let matcher = Chars!("Identifier"; ['a'..='z', 'A'..='Z', '_']);
+
+    if let Ok(token) = ParserContext::tokenize(parser_context, matcher) {
+      let token = token.borrow();
+      assert_eq!(token.get_name(), "Identifier");
+      assert_eq!(*token.get_captured_range(), SourceRange::new(0, 15));
+      assert_eq!(token.get_value(), "some_identifier");
+    } else {
+      unreachable!("Test failed!");
+    };
+  }
+
+  #[test]
+  fn it_matches_unicode_categories() {
+    let parser = Parser::new("Grüße42");
+    let parser_context = ParserContext::new(&parser, "Test");
+    let matcher = Chars!([CharSpec::Category("L".to_string())]);
+
+    if let Ok(token) = ParserContext::tokenize(parser_context, matcher) {
+      assert_eq!(*token.borrow().get_captured_range(), SourceRange::new(0, 7));
+    } else {
+      unreachable!("Test failed!");
+    };
+
+    let parser_context = ParserContext::new(&parser, "Test");
+    let matcher = Chars!([CharSpec::Perl('d')]);
+
+    assert_eq!(
+      ParserContext::tokenize(parser_context, matcher),
+      Err(MatcherFailure::Fail)
+    );
+
+    assert!(CharSet::new([CharSpec::Category("Nope".to_string())]).is_err());
+  }
+
+  #[test]
+  fn it_respects_the_count() {
+    let parser = Parser::new("123456");
+    let parser_context = ParserContext::new(&parser, "Test");
+    let matcher = Chars!(2..=4; ['0'..='9']);
+
+    if let Ok(token) = ParserContext::tokenize(parser_context, matcher) {
+      assert_eq!(token.borrow().get_value(), "1234");
+    } else {
+      unreachable!("Test failed!");
+    };
+
+    let parser = Parser::new("1a");
+    let parser_context = ParserContext::new(&parser, "Test");
+    let matcher = Chars!(2..=4; ['0'..='9']);
+
+    assert_eq!(
+      ParserContext::tokenize(parser_context, matcher),
+      Err(MatcherFailure::Fail)
+    );
+
+    let parser = Parser::new("abc");
+    let parser_context = ParserContext::new(&parser, "Test");
+    let matcher = Chars!(0..; ['0'..='9']);
+
+    assert_eq!(
+      Ok(MatcherSuccess::Skip(0)),
+      matcher.borrow().exec(
+        matcher.clone(),
+        parser_context.clone(),
+        parser_context.borrow().scope.clone(),
+      )
+    );
+  }
+
+  #[test]
+  fn it_can_be_negated() {
+    let set = CharSet::new(['"', '\\']).unwrap().negated();
+
+    let parser = Parser::new("some \"quoted\" text");
+    let parser_context = ParserContext::new(&parser, "Test");
+    let matcher = CharsPattern::new(1.., set);
+
+    if let Ok(token) = ParserContext::tokenize(parser_context, matcher) {
+      assert_eq!(token.borrow().get_value(), "some ");
+    } else {
+      unreachable!("Test failed!");
+    };
+  }
+
+  #[test]
+  fn it_lowers_simple_regular_expressions() {
+    let matcher = CharsPattern::new_from_regex(r"(?i)[a-z_]+").unwrap();
+    assert_eq!(matcher.borrow().get_name(), "Matches");
+    assert_eq!(
+      matcher.borrow().get_kind(),
+      MatcherKind::Matches(r"(?i)[a-z_]+".to_string())
+    );
+
+    let parser = Parser::new("Snake_Case1");
+    let parser_context = ParserContext::new(&parser, "Test");
+    if let Ok(token) = ParserContext::tokenize(parser_context, matcher) {
+      assert_eq!(token.borrow().get_value(), "Snake_Case");
+    } else {
+      unreachable!("Test failed!");
+    };
+
+    let matcher = Chars!(2..=3; ['a'..='z', '-']);
+    assert_eq!(
+      matcher.borrow().get_kind(),
+      MatcherKind::Matches(r"[\-a-z]{2,3}".to_string())
+    );
+
+    assert!(CharsPattern::new_from_regex(r"\d{2,3}").is_some());
+    assert!(CharsPattern::new_from_regex(r"[a-z]+?").is_none());
+    assert!(CharsPattern::new_from_regex(r"[a-z]+[0-9]").is_none());
+    assert!(CharsPattern::new_from_regex(r"test").is_none());
+  }
+}
@@ -1,4 +1,4 @@
-use crate::matcher::{Matcher, MatcherFailure, MatcherRef, MatcherSuccess};
+use crate::matcher::{Matcher, MatcherFailure, MatcherKind, MatcherRef, MatcherSuccess};
 use crate::parser_context::ParserContextRef;
 use crate::scope_context::ScopeContextRef;
 use std::cell::RefCell;
@@ -135,6 +135,10 @@ impl Matcher for DebugPattern {
   fn to_string(&self) -> String {
     format!("{:?}", self)
   }
+
+  fn get_kind(&self) -> MatcherKind {
+    MatcherKind::Wrapper
+  }
 }
 
 #[macro_export]
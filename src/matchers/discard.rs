@@ -1,7 +1,7 @@
 use std::cell::RefCell;
 use std::rc::Rc;
 
-use crate::matcher::{Matcher, MatcherFailure, MatcherRef, MatcherSuccess};
+use crate::matcher::{Matcher, MatcherFailure, MatcherKind, MatcherRef, MatcherSuccess};
 use crate::parser_context::ParserContextRef;
 use crate::scope_context::ScopeContextRef;
 use crate::source_range::SourceRange;
@@ -194,6 +194,10 @@ impl Matcher for DiscardPattern {
   fn to_string(&self) -> String {
     format!("{:?}", self)
   }
+
+  fn get_kind(&self) -> MatcherKind {
+    MatcherKind::Discard
+  }
 }
 
 #[macro_export]
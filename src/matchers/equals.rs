@@ -2,13 +2,131 @@ extern crate adextopa_macros;
 use std::cell::RefCell;
 use std::rc::Rc;
 
-use crate::matcher::{Matcher, MatcherFailure, MatcherRef, MatcherSuccess};
+use caseless::default_case_fold_str;
+use unicode_normalization::char::{canonical_combining_class, compose};
+use unicode_normalization::UnicodeNormalization;
+
+use crate::matcher::{Matcher, MatcherFailure, MatcherKind, MatcherRef, MatcherSuccess};
 use crate::parse_error::ParseError;
-use crate::parser_context::ParserContextRef;
+use crate::parser_context::{ParserContext, ParserContextRef};
 use crate::scope_context::ScopeContextRef;
+use crate::source_range::SourceRange;
 use crate::token::StandardToken;
 
-use super::fetch::{Fetchable, FetchableType};
+use super::fetch::Fetchable;
+
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub enum CaseFolding {
+  #[default]
+  None,
+  /// Only `A-Z` and `a-z` are considered equal
+  Ascii,
+  /// Characters are compared by their full Unicode case folding (i.e. `ß` equals `SS`)
+  Unicode,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub enum Normalization {
+  #[default]
+  None,
+  Nfc,
+  Nfkc,
+}
+
+/// How the pattern of an `Equals` matcher is compared against the input
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub struct EqualsOptions {
+  pub case_folding: CaseFolding,
+  pub normalization: Normalization,
+}
+
+impl EqualsOptions {
+  pub fn is_exact(&self) -> bool {
+    *self == Self::default()
+  }
+}
+
+fn fold(value: &str, case_folding: CaseFolding) -> String {
+  match case_folding {
+    CaseFolding::None => value.to_string(),
+    CaseFolding::Ascii => value.to_ascii_lowercase(),
+    CaseFolding::Unicode => default_case_fold_str(value),
+  }
+}
+
+fn transform(value: &str, options: &EqualsOptions) -> String {
+  let normalized = match options.normalization {
+    Normalization::None => value.to_string(),
+    Normalization::Nfc => value.nfc().collect(),
+    Normalization::Nfkc => value.nfkc().collect(),
+  };
+
+  fold(&normalized, options.case_folding)
+}
+
+// Where normalization can't combine the text before `offset` with the
+// text after it: before each starter that doesn't compose with the
+// starter of the piece so far (i.e. Hangul jamo compose into syllables)
+fn piece_ends(input: &str, normalization: Normalization) -> impl Iterator<Item = usize> + '_ {
+  let mut starter: Option<char> = None;
+
+  input
+    .char_indices()
+    .filter_map(move |(offset, c)| {
+      let previous = starter;
+
+      if normalization == Normalization::None {
+        starter = Some(c);
+        return previous.map(|_| offset);
+      }
+
+      match previous.and_then(|previous| compose(previous, c)) {
+        Some(composed) => {
+          starter = Some(composed);
+          None
+        }
+        None if canonical_combining_class(c) != 0 => None,
+        None => {
+          starter = Some(c);
+          previous.map(|_| offset)
+        }
+      }
+    })
+    .chain(std::iter::once(input.len()))
+}
+
+// Length (in bytes) of the input that `pattern` matches, once both are
+// normalized and case folded. The input is transformed a piece at a time
+// (see `piece_ends`), in a single pass, so the end of each piece maps the
+// transformed input back onto the source
+fn match_transformed(input: &str, pattern: &str, options: &EqualsOptions) -> Option<usize> {
+  let target = transform(pattern, options);
+  let mut transformed = String::with_capacity(target.len());
+  let mut piece_start = 0;
+
+  for piece_end in piece_ends(input, options.normalization) {
+    if piece_end == piece_start {
+      break;
+    }
+
+    transformed.push_str(&transform(&input[piece_start..piece_end], options));
+    piece_start = piece_end;
+
+    if transformed.len() >= target.len() {
+      return if transformed == target {
+        Some(piece_end)
+      } else {
+        None
+      };
+    }
+
+    if !target.starts_with(&transformed) {
+      return None;
+    }
+  }
+
+  None
+}
 
 pub struct EqualsPattern<T>
 where
@@ -16,6 +134,7 @@ where
   T: std::fmt::Debug,
 {
   pattern: T,
+  options: EqualsOptions,
   name: String,
   custom_name: bool,
 }
@@ -26,8 +145,14 @@ where
   T: std::fmt::Debug,
 {
   fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-    f.debug_struct("EqualsPattern")
-      .field("pattern", &self.pattern)
+    let mut debug = f.debug_struct("EqualsPattern");
+    debug.field("pattern", &self.pattern);
+
+    if !self.options.is_exact() {
+      debug.field("options", &self.options);
+    }
+
+    debug
       .field("name", &self.name)
       .field("custom_name", &self.custom_name)
       .finish()
@@ -41,40 +166,82 @@ where
   T: std::fmt::Debug,
 {
   pub fn new(pattern: T) -> MatcherRef {
+    Self::new_with_options(pattern, EqualsOptions::default())
+  }
+
+  pub fn new_with_name(name: &str, pattern: T) -> MatcherRef {
+    Self::new_with_name_and_options(name, pattern, EqualsOptions::default())
+  }
+
+  pub fn new_with_options(pattern: T, options: EqualsOptions) -> MatcherRef {
     Rc::new(RefCell::new(Box::new(Self {
       pattern,
+      options,
       name: "Equals".to_string(),
       custom_name: false,
     })))
   }
 
-  pub fn new_with_name(name: &str, pattern: T) -> MatcherRef {
+  pub fn new_with_name_and_options(name: &str, pattern: T, options: EqualsOptions) -> MatcherRef {
     Rc::new(RefCell::new(Box::new(Self {
       pattern,
+      options,
       name: name.to_string(),
       custom_name: true,
     })))
   }
 
+  fn match_with_options(&self, context: &ParserContext, pattern: &str) -> Option<SourceRange> {
+    if self.options.is_exact() {
+      return context.matches_str(pattern);
+    }
+
+    if pattern.is_empty() {
+      return None;
+    }
+
+    // Each character of the pattern comes from at most one character of
+    // input, with room for combining marks after the last one
+    let start = context.offset.start;
+    let input = &*context.get_input(start, start + (pattern.len() + 8) * 4);
+
+    let len = if self.options.normalization == Normalization::None
+      && self.options.case_folding == CaseFolding::Ascii
+    {
+      match input.get(..pattern.len()) {
+        Some(prefix) if prefix.eq_ignore_ascii_case(pattern) => Some(pattern.len()),
+        _ => None,
+      }
+    } else {
+      match_transformed(input, pattern, &self.options)
+    };
+
+    len.map(|len| SourceRange::new(start, start + len))
+  }
+
   fn _exec(
     &self,
     context: ParserContextRef,
     scope: ScopeContextRef,
   ) -> Result<MatcherSuccess, MatcherFailure> {
     let sub_context = context.borrow().clone_with_name(self.get_name());
-    let pattern_value = self.pattern.fetch_value(sub_context.clone(), scope.clone());
+    let pattern_value = self
+      .pattern
+      .fetch_value(sub_context.clone(), scope.clone())?;
+    let pattern_type = pattern_value.type_name();
     let debug_mode = sub_context.borrow().debug_mode_level();
 
-    match pattern_value {
-      FetchableType::String(pattern_value) => {
+    match pattern_value.into_string() {
+      Some(pattern_value) => {
         if debug_mode > 1 {
           print!("{{Equals}} ");
         }
 
         let _sc = sub_context.borrow();
-        if let Some(range) = _sc.matches_str(pattern_value.as_str()) {
+        if let Some(range) = self.match_with_options(&_sc, pattern_value.as_str()) {
           if debug_mode > 0 {
-            println!("`{}` Succeeded matching against `{}` -->|{}|--> @[{}-{}]",
+            println!(
+              "`{}` Succeeded matching against `{}` -->|{}|--> @[{}-{}]",
               self.get_name(),
               pattern_value,
               _sc
@@ -113,11 +280,11 @@ where
           Err(MatcherFailure::Fail)
         }
       }
-      FetchableType::Matcher(_) => Err(MatcherFailure::Error(
-        ParseError::new(
-        &format!("`Equals` ({}) matcher received another matcher as a pattern... this makes no sense... aborting...", self.get_name())
-        )
-      )),
+      None => Err(MatcherFailure::Error(ParseError::new(&format!(
+        "`Equals` ({}) matcher received a {} as a pattern... this makes no sense... aborting...",
+        self.get_name(),
+        pattern_type
+      )))),
     }
   }
 }
@@ -165,14 +332,35 @@ where
   fn to_string(&self) -> String {
     format!("{:?}", self)
   }
+
+  fn get_kind(&self) -> MatcherKind {
+    // Tooling treats a literal as an exact match
+    if self.options.is_exact() {
+      MatcherKind::Equals(self.pattern.as_literal())
+    } else {
+      MatcherKind::Equals(None)
+    }
+  }
 }
 
 #[macro_export]
 macro_rules! Equals {
+  ($name:expr; $arg:expr, $options:expr) => {
+    $crate::matchers::equals::EqualsPattern::new_with_name_and_options(
+      $name,
+      $arg.to_string(),
+      $options,
+    )
+  };
+
   ($name:expr; $arg:expr) => {
     $crate::matchers::equals::EqualsPattern::new_with_name($name, $arg.to_string())
   };
 
+  ($arg:expr, $options:expr) => {
+    $crate::matchers::equals::EqualsPattern::new_with_options($arg, $options)
+  };
+
   ($arg:expr) => {
     $crate::matchers::equals::EqualsPattern::new($arg)
   };
@@ -180,6 +368,7 @@ macro_rules! Equals {
 
 #[cfg(test)]
 mod tests {
+  use super::{CaseFolding, EqualsOptions, Normalization};
   use crate::{
     matcher::MatcherFailure, parser::Parser, parser_context::ParserContext,
     source_range::SourceRange,
@@ -212,4 +401,144 @@ mod tests {
       Err(MatcherFailure::Fail)
     );
   }
+
+  #[test]
+  fn it_can_ignore_case() {
+    let parser = Parser::new("école primaire");
+    let parser_context = ParserContext::new(&parser, "Test");
+    let matcher = Equals!(
+      "ÉCOLE",
+      EqualsOptions {
+        case_folding: CaseFolding::Ascii,
+        ..EqualsOptions::default()
+      }
+    );
+
+    assert_eq!(
+      ParserContext::tokenize(parser_context, matcher),
+      Err(MatcherFailure::Fail)
+    );
+
+    let parser_context = ParserContext::new(&parser, "Test");
+    let matcher = Equals!(
+      "ÉCOLE",
+      EqualsOptions {
+        case_folding: CaseFolding::Unicode,
+        ..EqualsOptions::default()
+      }
+    );
+
+    if let Ok(token) = ParserContext::tokenize(parser_context, matcher) {
+      let token = token.borrow();
+      assert_eq!(*token.get_captured_range(), SourceRange::new(0, 6));
+    } else {
+      unreachable!("Test failed!");
+    };
+
+    let parser = Parser::new("Select *");
+    let parser_context = ParserContext::new(&parser, "Test");
+    let matcher = Equals!(
+      "Keyword"; "SELECT",
+      EqualsOptions {
+        case_folding: CaseFolding::Ascii,
+        ..EqualsOptions::default()
+      }
+    );
+
+    if let Ok(token) = ParserContext::tokenize(parser_context, matcher) {
+      let token = token.borrow();
+      assert_eq!(token.get_name(), "Keyword");
+      assert_eq!(token.get_value(), "Select");
+    } else {
+      unreachable!("Test failed!");
+    };
+  }
+
+  #[test]
+  fn it_folds_case_fully() {
+    let options = EqualsOptions {
+      case_folding: CaseFolding::Unicode,
+      ..EqualsOptions::default()
+    };
+
+    let parser = Parser::new("STRASSE 1");
+    let parser_context = ParserContext::new(&parser, "Test");
+
+    if let Ok(token) = ParserContext::tokenize(parser_context, Equals!("straße", options)) {
+      assert_eq!(*token.borrow().get_captured_range(), SourceRange::new(0, 7));
+    } else {
+      unreachable!("Test failed!");
+    };
+
+    // A single `ß` in the input can't be matched halfway
+    let parser = Parser::new("ßa");
+    let parser_context = ParserContext::new(&parser, "Test");
+
+    assert_eq!(
+      ParserContext::tokenize(parser_context, Equals!("s", options)),
+      Err(MatcherFailure::Fail)
+    );
+  }
+
+  #[test]
+  fn it_can_match_normalized_input() {
+    let options = EqualsOptions {
+      normalization: Normalization::Nfc,
+      ..EqualsOptions::default()
+    };
+
+    // `e` followed by a combining acute accent
+    let parser = Parser::new("cafe\u{301} au lait");
+    let parser_context = ParserContext::new(&parser, "Test");
+
+    if let Ok(token) = ParserContext::tokenize(parser_context, Equals!("café", options)) {
+      assert_eq!(*token.borrow().get_captured_range(), SourceRange::new(0, 6));
+    } else {
+      unreachable!("Test failed!");
+    };
+
+    // The accent belongs to the last character of the input,
+    // so `cafe` must not match only part of it
+    let parser_context = ParserContext::new(&parser, "Test");
+
+    assert_eq!(
+      ParserContext::tokenize(parser_context, Equals!("cafe", options)),
+      Err(MatcherFailure::Fail)
+    );
+
+    // Hangul syllables compose from jamo, which are not combining marks
+    let parser = Parser::new("\u{1100}\u{1161}\u{11A8}!");
+    let parser_context = ParserContext::new(&parser, "Test");
+
+    if let Ok(token) = ParserContext::tokenize(parser_context, Equals!("\u{AC01}", options)) {
+      assert_eq!(*token.borrow().get_captured_range(), SourceRange::new(0, 9));
+    } else {
+      unreachable!("Test failed!");
+    };
+  }
+
+  #[test]
+  fn it_can_match_compatibility_normalized_input() {
+    let options = EqualsOptions {
+      case_folding: CaseFolding::Unicode,
+      normalization: Normalization::Nfkc,
+    };
+
+    // A `ﬁ` ligature, and a fullwidth `Ｘ`
+    let parser = Parser::new("\u{FB01}\u{FF38} = 1");
+    let parser_context = ParserContext::new(&parser, "Test");
+
+    if let Ok(token) = ParserContext::tokenize(parser_context, Equals!("FIX", options)) {
+      assert_eq!(*token.borrow().get_captured_range(), SourceRange::new(0, 6));
+    } else {
+      unreachable!("Test failed!");
+    };
+
+    let parser_context = ParserContext::new(&parser, "Test");
+
+    assert_eq!(
+      ParserContext::tokenize(parser_context, Equals!("f", options)),
+      Err(MatcherFailure::Fail)
+    );
+  }
 }
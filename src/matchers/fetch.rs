@@ -1,103 +1,276 @@
+use std::collections::HashMap;
+
 use crate::matcher::{Matcher, MatcherFailure, MatcherRef, MatcherSuccess};
+use crate::parse_error::ParseError;
 use crate::parser_context::ParserContextRef;
 use crate::scope::VariableType;
 use crate::scope_context::ScopeContextRef;
+use crate::source_range::SourceRange;
 
 pub enum FetchableType {
   String(String),
   Matcher(MatcherRef),
+  Int(i64),
+  Bool(bool),
+  List(Vec<VariableType>),
+  Map(HashMap<String, VariableType>),
+}
+
+impl FetchableType {
+  pub fn type_name(&self) -> &'static str {
+    match self {
+      FetchableType::String(_) => "String",
+      FetchableType::Matcher(_) => "Matcher",
+      FetchableType::Int(_) => "Int",
+      FetchableType::Bool(_) => "Bool",
+      FetchableType::List(_) => "List",
+      FetchableType::Map(_) => "Map",
+    }
+  }
+
+  /// Scalars as text, for matchers that match against the source
+  pub fn into_string(self) -> Option<String> {
+    match self {
+      FetchableType::String(value) => Some(value),
+      FetchableType::Int(value) => Some(value.to_string()),
+      FetchableType::Bool(value) => Some(value.to_string()),
+      _ => None,
+    }
+  }
+}
+
+impl From<FetchableType> for VariableType {
+  fn from(value: FetchableType) -> Self {
+    match value {
+      FetchableType::String(value) => VariableType::String(value),
+      FetchableType::Matcher(matcher) => VariableType::Matcher(matcher),
+      FetchableType::Int(value) => VariableType::Int(value),
+      FetchableType::Bool(value) => VariableType::Bool(value),
+      FetchableType::List(items) => VariableType::List(items),
+      FetchableType::Map(entries) => VariableType::Map(entries),
+    }
+  }
 }
 
 pub trait Fetchable {
-  fn fetch_value(&self, context: ParserContextRef, scope: ScopeContextRef) -> FetchableType;
+  fn fetch_value(
+    &self,
+    context: ParserContextRef,
+    scope: ScopeContextRef,
+  ) -> Result<FetchableType, MatcherFailure>;
+
+  // The value of this fetchable, if it is known without a context
+  fn as_literal(&self) -> Option<String> {
+    None
+  }
 }
 
-impl Fetchable for FetchPattern {
-  fn fetch_value(&self, _: ParserContextRef, scope: ScopeContextRef) -> FetchableType {
-    let name = self.get_name();
-    let name_path: Vec<&str> = name.split(".").collect();
-
-    match scope.borrow().get(name_path[0]) {
-      Some(VariableType::Token(ref token)) => {
-        if name_path.len() != 2 {
-          panic!(
-            "Invalid variable reference `{}`: Don't know how to fetch this value on a Token reference",
-            name
-          );
-        }
+// A parse error at the current offset
+fn fetch_error(context: &ParserContextRef, message: &str) -> MatcherFailure {
+  let context = context.borrow();
+  let range = SourceRange::new(context.offset.start, context.offset.start);
 
-        let token = token.borrow();
-        let sub_name = name_path[1];
-
-        let value = if sub_name == "captured_value" {
-          token.get_value().clone()
-        } else if sub_name == "matched_value" {
-          token.get_matched_value().clone()
-        } else if sub_name == "start" {
-          format!("{}", token.get_matched_range().start)
-        } else if sub_name == "end" {
-          format!("{}", token.get_matched_range().end)
-        } else if sub_name == "captured_start" {
-          format!("{}", token.get_captured_range().start)
-        } else if sub_name == "captured_end" {
-          format!("{}", token.get_captured_range().end)
-        } else if sub_name == "range" {
+  MatcherFailure::Error(ParseError::new_with_range(
+    &context.get_error_as_string(message, &range),
+    range,
+  ))
+}
+
+// Fetch a count (i.e. a length prefix) for the `matcher_name` matcher,
+// reporting anything that isn't an unsigned integer as a parse error
+pub fn fetch_count<T>(
+  fetchable: &T,
+  matcher_name: &str,
+  context: ParserContextRef,
+  scope: ScopeContextRef,
+) -> Result<usize, MatcherFailure>
+where
+  T: Fetchable,
+{
+  let value = match fetchable.fetch_value(context.clone(), scope)? {
+    FetchableType::Int(value) if value >= 0 => return Ok(value as usize),
+    value => match value.into_string() {
+      Some(value) => value,
+      None => {
+        return Err(fetch_error(
+          &context,
+          &format!(
+            "`{}` matcher received a non-scalar value as a count, expected an integer",
+            matcher_name
+          ),
+        ))
+      }
+    },
+  };
+
+  value.trim().parse::<usize>().map_err(|error| {
+    fetch_error(
+      &context,
+      &format!(
+        "`{}` matcher could not use `{}` as a count: {}",
+        matcher_name, value, error
+      ),
+    )
+  })
+}
+
+// One step along a fetch path, i.e. `value` in `Name.value`
+fn get_field(value: &VariableType, field: &str) -> Option<VariableType> {
+  match value {
+    VariableType::Token(token) => {
+      let token = token.borrow();
+
+      let value = match field {
+        "value" | "captured_value" => VariableType::String(token.get_value().clone()),
+        "matched_value" => VariableType::String(token.get_matched_value().clone()),
+        "name" => VariableType::String(token.get_name().clone()),
+        "start" => VariableType::Int(token.get_matched_range().start as i64),
+        "end" => VariableType::Int(token.get_matched_range().end as i64),
+        "captured_start" => VariableType::Int(token.get_captured_range().start as i64),
+        "captured_end" => VariableType::Int(token.get_captured_range().end as i64),
+        "range" => {
           let range = token.get_matched_range();
-          format!("{}..{}", range.start, range.end)
-        } else if sub_name == "captured_range" {
+          VariableType::String(format!("{}..{}", range.start, range.end))
+        }
+        "captured_range" => {
           let range = token.get_captured_range();
-          format!("{}..{}", range.start, range.end)
-        } else {
-          panic!(
-            "Invalid variable reference `{}`: Don't know how to fetch this value on a Token reference",
-            name
-          );
-        };
-
-        FetchableType::String(value)
-      }
-      Some(VariableType::String(ref value)) => {
-        if name_path.len() > 1 {
-          panic!(
-            "Invalid variable reference `{}`: Don't know how to fetch this value on a String reference",
-            name
-          );
+          VariableType::String(format!("{}..{}", range.start, range.end))
         }
+        "children" => VariableType::List(
+          token
+            .get_children()
+            .iter()
+            .map(|child| VariableType::Token(child.clone()))
+            .collect(),
+        ),
+        "attributes" => VariableType::Map(
+          token
+            .get_attributes()
+            .iter()
+            .map(|(name, value)| (name.clone(), VariableType::String(value.clone())))
+            .collect(),
+        ),
+        _ => return None,
+      };
+
+      Some(value)
+    }
+    VariableType::List(items) => match field {
+      "len" => Some(VariableType::Int(items.len() as i64)),
+      "last" => items.last().cloned(),
+      _ => items.get(field.parse::<usize>().ok()?).cloned(),
+    },
+    VariableType::Map(entries) => entries.get(field).cloned(),
+    _ => None,
+  }
+}
 
-        FetchableType::String(value.clone())
-      }
-      Some(VariableType::Matcher(matcher)) => FetchableType::Matcher(matcher.clone()),
-      None => {
-        panic!("Invalid variable reference `{}`: Not found", name);
-      }
+impl Fetchable for FetchPattern {
+  fn fetch_value(
+    &self,
+    context: ParserContextRef,
+    scope: ScopeContextRef,
+  ) -> Result<FetchableType, MatcherFailure> {
+    let name = self.get_name();
+    let value = self
+      .lookup(&scope)
+      .map_err(|error| self.lookup_failure(&context, error))?;
+
+    match value {
+      VariableType::Token(_) => Err(fetch_error(
+        &context,
+        &format!(
+          "Invalid variable reference `{}`: Can not fetch a Token, fetch one of its fields instead (i.e. `{}.value`)",
+          name, name
+        ),
+      )),
+      VariableType::String(value) => Ok(FetchableType::String(value)),
+      VariableType::Matcher(matcher) => Ok(FetchableType::Matcher(matcher)),
+      VariableType::Int(value) => Ok(FetchableType::Int(value)),
+      VariableType::Bool(value) => Ok(FetchableType::Bool(value)),
+      VariableType::List(items) => Ok(FetchableType::List(items)),
+      VariableType::Map(entries) => Ok(FetchableType::Map(entries)),
     }
   }
 }
 
 impl Fetchable for &str {
-  fn fetch_value(&self, _: ParserContextRef, _: ScopeContextRef) -> FetchableType {
-    FetchableType::String(self.to_string())
+  fn fetch_value(
+    &self,
+    _: ParserContextRef,
+    _: ScopeContextRef,
+  ) -> Result<FetchableType, MatcherFailure> {
+    Ok(FetchableType::String(self.to_string()))
+  }
+
+  fn as_literal(&self) -> Option<String> {
+    Some(self.to_string())
   }
 }
 
 impl Fetchable for String {
-  fn fetch_value(&self, _: ParserContextRef, _: ScopeContextRef) -> FetchableType {
-    FetchableType::String(self.clone())
+  fn fetch_value(
+    &self,
+    _: ParserContextRef,
+    _: ScopeContextRef,
+  ) -> Result<FetchableType, MatcherFailure> {
+    Ok(FetchableType::String(self.clone()))
+  }
+
+  fn as_literal(&self) -> Option<String> {
+    Some(self.clone())
   }
 }
 
 impl Fetchable for &String {
-  fn fetch_value(&self, _: ParserContextRef, _: ScopeContextRef) -> FetchableType {
-    FetchableType::String((*self).clone())
+  fn fetch_value(
+    &self,
+    _: ParserContextRef,
+    _: ScopeContextRef,
+  ) -> Result<FetchableType, MatcherFailure> {
+    Ok(FetchableType::String((*self).clone()))
+  }
+
+  fn as_literal(&self) -> Option<String> {
+    Some((*self).clone())
+  }
+}
+
+impl Fetchable for i64 {
+  fn fetch_value(
+    &self,
+    _: ParserContextRef,
+    _: ScopeContextRef,
+  ) -> Result<FetchableType, MatcherFailure> {
+    Ok(FetchableType::Int(*self))
+  }
+
+  fn as_literal(&self) -> Option<String> {
+    Some(self.to_string())
   }
 }
 
 impl Fetchable for MatcherRef {
-  fn fetch_value(&self, _: ParserContextRef, _: ScopeContextRef) -> FetchableType {
-    FetchableType::Matcher(self.clone())
+  fn fetch_value(
+    &self,
+    _: ParserContextRef,
+    _: ScopeContextRef,
+  ) -> Result<FetchableType, MatcherFailure> {
+    Ok(FetchableType::Matcher(self.clone()))
   }
 }
 
+/// Why a fetch path doesn't lead to a value
+#[derive(Debug, PartialEq)]
+pub enum LookupError {
+  /// There is no variable with the name the path starts with
+  Missing,
+  /// A list has no such item, or a map no such key
+  NoEntry(&'static str, String),
+  /// A value of this type has no such field
+  NoField(&'static str, String),
+}
+
 #[derive(Debug)]
 pub struct FetchPattern {
   name: String,
@@ -110,6 +283,45 @@ impl FetchPattern {
     }
   }
 
+  /// The variable the path names, or the field of one it leads to.
+  /// Unlike `fetch_value`, this can return a token
+  pub fn lookup(&self, scope: &ScopeContextRef) -> Result<VariableType, LookupError> {
+    let mut path = self.name.split('.');
+    let root = path.next().unwrap_or_default();
+    let mut value = scope.borrow().get(root).ok_or(LookupError::Missing)?;
+
+    for field in path {
+      value = match get_field(&value, field) {
+        Some(value) => value,
+        None => {
+          let type_name = value.type_name();
+
+          return Err(match value {
+            VariableType::List(_) | VariableType::Map(_) => {
+              LookupError::NoEntry(type_name, field.to_string())
+            }
+            _ => LookupError::NoField(type_name, field.to_string()),
+          });
+        }
+      };
+    }
+
+    Ok(value)
+  }
+
+  /// The parse error for a path that `lookup` couldn't follow
+  pub fn lookup_failure(&self, context: &ParserContextRef, error: LookupError) -> MatcherFailure {
+    let message = match error {
+      LookupError::Missing => format!("Invalid variable reference `{}`: Not found", self.name),
+      LookupError::NoEntry(type_name, field) | LookupError::NoField(type_name, field) => format!(
+        "Invalid variable reference `{}`: {} has no field `{}`",
+        self.name, type_name, field
+      ),
+    };
+
+    fetch_error(context, &message)
+  }
+
   fn _exec(
     &self,
     _: ParserContextRef,
@@ -171,9 +383,12 @@ macro_rules! Fetch {
 
 #[cfg(test)]
 mod tests {
+  use std::collections::HashMap;
+
+  use super::FetchPattern;
   use crate::{
-    parser::Parser, parser_context::ParserContext, source_range::SourceRange, Discard, Equals,
-    Matches, Program, Store, Switch,
+    matcher::MatcherFailure, parser::Parser, parser_context::ParserContext, scope::VariableType,
+    source_range::SourceRange, Discard, Equals, Matches, Program, Store, Switch,
   };
 
   #[test]
@@ -229,4 +444,75 @@ mod tests {
       unreachable!("Test failed!");
     };
   }
+
+  #[test]
+  fn it_fetches_typed_values_along_a_path() {
+    let parser = Parser::new("key=value true 2");
+    let parser_context = ParserContext::new(&parser, "Test");
+
+    {
+      let mut _pc = parser_context.borrow_mut();
+      _pc.set_scope_variable("Flag", VariableType::Bool(true));
+      _pc.set_scope_variable("Count", VariableType::Int(2));
+      _pc.set_scope_variable(
+        "Config",
+        VariableType::Map(HashMap::from([(
+          "keys".to_string(),
+          VariableType::List(vec![VariableType::String("key".to_string())]),
+        )])),
+      );
+    }
+
+    let matcher = Program!(
+      Store!("Pair"; Program!(Matches!(r"\w+"), Discard!(Equals!("=")), Matches!(r"\w+"))),
+      Discard!(Equals!(" ")),
+      Equals!(Fetch!("Flag")),
+      Discard!(Equals!(" ")),
+      Equals!(Fetch!("Pair.children.len")),
+    );
+
+    if let Ok(token) = ParserContext::tokenize(parser_context.clone(), matcher) {
+      let token = token.borrow();
+      assert_eq!(token.get_children()[1].borrow().get_value(), "true");
+      assert_eq!(token.get_children()[2].borrow().get_value(), "2");
+    } else {
+      unreachable!("Test failed!");
+    };
+
+    let parser_context = parser_context.borrow().clone_with_name("Test");
+    let matcher = Program!(
+      Equals!(Fetch!("Config.keys.0")),
+      Discard!(Equals!("=")),
+      Equals!(Fetch!("Pair.children.1.value")),
+    );
+
+    if let Ok(token) = ParserContext::tokenize(parser_context, matcher) {
+      assert_eq!(token.borrow().get_value(), "key=value");
+    } else {
+      unreachable!("Test failed!");
+    };
+  }
+
+  #[test]
+  fn it_reports_bad_references_as_errors() {
+    let parser = Parser::new("Testing");
+    let parser_context = ParserContext::new(&parser, "Test");
+    parser_context
+      .borrow_mut()
+      .set_scope_variable("Count", VariableType::Int(2));
+
+    for (name, message) in [
+      ("Missing", "Invalid variable reference `Missing`: Not found"),
+      ("Count.value", "Int has no field `value`"),
+    ] {
+      let matcher = Equals!(FetchPattern::new(name));
+      let context = parser_context.borrow().clone_with_name("Test");
+
+      if let Err(MatcherFailure::Error(error)) = ParserContext::tokenize(context, matcher) {
+        assert!(error.message.contains(message), "{}", error.message);
+      } else {
+        unreachable!("Test failed!");
+      };
+    }
+  }
 }
@@ -0,0 +1,403 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use super::fetch::{FetchPattern, Fetchable, FetchableType, LookupError};
+use crate::matcher::{Matcher, MatcherFailure, MatcherKind, MatcherRef, MatcherSuccess};
+use crate::parse_error::ParseError;
+use crate::parser_context::ParserContextRef;
+use crate::scope_context::ScopeContextRef;
+
+/// A value compared by a `Condition`
+pub trait Operand: Fetchable + std::fmt::Debug {}
+
+impl<T> Operand for T where T: Fetchable + std::fmt::Debug {}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Comparison {
+  /// Equal, compared as text (so `Int(2)` equals `"2"`)
+  Eq,
+  Ne,
+  /// The rest are numeric comparisons
+  Lt,
+  Le,
+  Gt,
+  Ge,
+}
+
+impl Comparison {
+  pub fn from_operator(operator: &str) -> Option<Self> {
+    match operator {
+      "==" => Some(Comparison::Eq),
+      "!=" => Some(Comparison::Ne),
+      "<" => Some(Comparison::Lt),
+      "<=" => Some(Comparison::Le),
+      ">" => Some(Comparison::Gt),
+      ">=" => Some(Comparison::Ge),
+      _ => None,
+    }
+  }
+}
+
+/// A predicate over the scope, checked before an `If` picks a branch
+#[derive(Debug)]
+pub enum Condition {
+  /// The variable is set (to anything, tokens included), and the
+  /// path leads somewhere. A path into a list or map is false if the
+  /// item isn't there, but an unknown field on any other value is an error
+  Exists(FetchPattern),
+  Compare(Box<dyn Operand>, Comparison, Box<dyn Operand>),
+  Not(Box<Condition>),
+  All(Vec<Condition>),
+  Any(Vec<Condition>),
+}
+
+impl Condition {
+  pub fn exists(name: &str) -> Self {
+    Condition::Exists(FetchPattern::new(name))
+  }
+
+  pub fn compare<L, R>(left: L, comparison: Comparison, right: R) -> Self
+  where
+    L: Operand + 'static,
+    R: Operand + 'static,
+  {
+    Condition::Compare(Box::new(left), comparison, Box::new(right))
+  }
+
+  pub fn equals<L, R>(left: L, right: R) -> Self
+  where
+    L: Operand + 'static,
+    R: Operand + 'static,
+  {
+    Self::compare(left, Comparison::Eq, right)
+  }
+
+  pub fn evaluate(
+    &self,
+    context: ParserContextRef,
+    scope: ScopeContextRef,
+  ) -> Result<bool, MatcherFailure> {
+    match self {
+      Condition::Exists(fetch) => match fetch.lookup(&scope) {
+        Ok(_) => Ok(true),
+        Err(LookupError::Missing) | Err(LookupError::NoEntry(..)) => Ok(false),
+        Err(error) => Err(fetch.lookup_failure(&context, error)),
+      },
+      Condition::Compare(left, comparison, right) => {
+        let left = left.fetch_value(context.clone(), scope.clone())?;
+        let right = right.fetch_value(context, scope)?;
+
+        compare(left, *comparison, right)
+      }
+      Condition::Not(condition) => Ok(!condition.evaluate(context, scope)?),
+      Condition::All(conditions) => {
+        for condition in conditions {
+          if !condition.evaluate(context.clone(), scope.clone())? {
+            return Ok(false);
+          }
+        }
+
+        Ok(true)
+      }
+      Condition::Any(conditions) => {
+        for condition in conditions {
+          if condition.evaluate(context.clone(), scope.clone())? {
+            return Ok(true);
+          }
+        }
+
+        Ok(false)
+      }
+    }
+  }
+}
+
+impl std::ops::Not for Condition {
+  type Output = Condition;
+
+  fn not(self) -> Self::Output {
+    Condition::Not(Box::new(self))
+  }
+}
+
+fn as_text(value: FetchableType, comparison: Comparison) -> Result<String, MatcherFailure> {
+  let type_name = value.type_name();
+
+  value.into_string().ok_or_else(|| {
+    MatcherFailure::Error(ParseError::new(&format!(
+      "`If` condition can not compare a {} ({:?})",
+      type_name, comparison
+    )))
+  })
+}
+
+fn as_number(value: FetchableType, comparison: Comparison) -> Result<i64, MatcherFailure> {
+  if let FetchableType::Int(value) = value {
+    return Ok(value);
+  }
+
+  let value = as_text(value, comparison)?;
+  value.trim().parse::<i64>().map_err(|_| {
+    MatcherFailure::Error(ParseError::new(&format!(
+      "`If` condition expected a number, but found `{}` ({:?})",
+      value, comparison
+    )))
+  })
+}
+
+fn compare(
+  left: FetchableType,
+  comparison: Comparison,
+  right: FetchableType,
+) -> Result<bool, MatcherFailure> {
+  match comparison {
+    Comparison::Eq => Ok(as_text(left, comparison)? == as_text(right, comparison)?),
+    Comparison::Ne => Ok(as_text(left, comparison)? != as_text(right, comparison)?),
+    _ => {
+      let left = as_number(left, comparison)?;
+      let right = as_number(right, comparison)?;
+
+      Ok(match comparison {
+        Comparison::Lt => left < right,
+        Comparison::Le => left <= right,
+        Comparison::Gt => left > right,
+        _ => left >= right,
+      })
+    }
+  }
+}
+
+// Runs one matcher or the other, depending on the state of
+// the scope. Without an `else` matcher, a false condition fails
+#[derive(Debug)]
+pub struct IfPattern {
+  condition: Condition,
+  then_matcher: MatcherRef,
+  else_matcher: Option<MatcherRef>,
+}
+
+impl IfPattern {
+  pub fn new(
+    condition: Condition,
+    then_matcher: MatcherRef,
+    else_matcher: Option<MatcherRef>,
+  ) -> MatcherRef {
+    Rc::new(RefCell::new(Box::new(Self {
+      condition,
+      then_matcher,
+      else_matcher,
+    })))
+  }
+
+  fn _exec(
+    &self,
+    context: ParserContextRef,
+    scope: ScopeContextRef,
+  ) -> Result<MatcherSuccess, MatcherFailure> {
+    let sub_context = context.borrow().clone_with_name(self.get_name());
+    let matcher = if self
+      .condition
+      .evaluate(sub_context.clone(), scope.clone())?
+    {
+      &self.then_matcher
+    } else {
+      match &self.else_matcher {
+        Some(matcher) => matcher,
+        None => return Err(MatcherFailure::Fail),
+      }
+    };
+
+    matcher
+      .borrow()
+      .exec(matcher.clone(), sub_context, scope.clone())
+  }
+}
+
+impl Matcher for IfPattern {
+  fn exec(
+    &self,
+    this_matcher: MatcherRef,
+    context: ParserContextRef,
+    scope: ScopeContextRef,
+  ) -> Result<MatcherSuccess, MatcherFailure> {
+    self.before_exec(this_matcher.clone(), context.clone(), scope.clone());
+    let result = self._exec(context.clone(), scope.clone());
+    self.after_exec(this_matcher.clone(), context.clone(), scope.clone());
+
+    result
+  }
+
+  fn get_name(&self) -> &str {
+    "If"
+  }
+
+  fn set_name(&mut self, name: &str) {
+    self.then_matcher.borrow_mut().set_name(name);
+
+    if let Some(matcher) = &self.else_matcher {
+      matcher.borrow_mut().set_name(name);
+    }
+  }
+
+  fn set_child(&mut self, index: usize, matcher: MatcherRef) {
+    match (index, &mut self.else_matcher) {
+      (0, _) => self.then_matcher = matcher,
+      (1, Some(else_matcher)) => *else_matcher = matcher,
+      _ => panic!("Attempt to set child at an index that is out of bounds"),
+    }
+  }
+
+  fn get_children(&self) -> Option<Vec<MatcherRef>> {
+    let mut children = vec![self.then_matcher.clone()];
+    children.extend(self.else_matcher.clone());
+
+    Some(children)
+  }
+
+  fn add_pattern(&mut self, _: MatcherRef) {
+    panic!("Can not add a pattern to an `If` matcher");
+  }
+
+  fn to_string(&self) -> String {
+    format!("{:?}", self)
+  }
+
+  // Whichever branch runs, it is one or the other
+  fn get_kind(&self) -> MatcherKind {
+    MatcherKind::Switch
+  }
+}
+
+#[macro_export]
+macro_rules! If {
+  ($condition:expr, $then:expr, $else:expr $(,)?) => {
+    $crate::matchers::r#if::IfPattern::new($condition, $then, Some($else))
+  };
+
+  ($condition:expr, $then:expr $(,)?) => {
+    $crate::matchers::r#if::IfPattern::new($condition, $then, None)
+  };
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{Comparison, Condition};
+  use crate::{
+    matcher::MatcherFailure, parser::Parser, parser_context::ParserContext, scope::VariableType,
+    Equals, Fetch, Matches, Program, Store,
+  };
+
+  #[test]
+  fn it_picks_a_branch_from_the_scope() {
+    let parser = Parser::new("await");
+    let parser_context = ParserContext::new(&parser, "Test");
+    let matcher = If!(Condition::exists("Async"), Equals!("await"));
+
+    assert_eq!(
+      ParserContext::tokenize(parser_context.clone(), matcher),
+      Err(MatcherFailure::Fail)
+    );
+
+    parser_context
+      .borrow_mut()
+      .set_scope_variable("Async", VariableType::Bool(true));
+
+    let matcher = If!(
+      Condition::exists("Async"),
+      Equals!("Await"; "await"),
+      Matches!("Identifier"; r"\w+"),
+    );
+
+    if let Ok(token) = ParserContext::tokenize(parser_context, matcher) {
+      assert_eq!(token.borrow().get_name(), "Await");
+    } else {
+      unreachable!("Test failed!");
+    };
+  }
+
+  #[test]
+  fn it_checks_for_a_stored_token() {
+    let parser = Parser::new("async fn");
+    let parser_context = ParserContext::new(&parser, "Test");
+    let matcher = Program!(
+      Store!("Async"; Matches!(r"async\s+")),
+      If!(
+        Condition::exists("Async"),
+        Equals!("AsyncFn"; "fn"),
+        Equals!("Fn"; "fn")
+      ),
+    );
+
+    if let Ok(token) = ParserContext::tokenize(parser_context.clone(), matcher) {
+      assert_eq!(
+        token.borrow().get_children()[1].borrow().get_name(),
+        "AsyncFn"
+      );
+    } else {
+      unreachable!("Test failed!");
+    };
+
+    let scope = parser_context.borrow().scope.clone();
+    for (name, expected) in [
+      ("Async.value", Ok(true)),
+      ("Async.children.0", Ok(false)),
+      ("Await", Ok(false)),
+    ] {
+      assert_eq!(
+        Condition::exists(name).evaluate(parser_context.clone(), scope.clone()),
+        expected
+      );
+    }
+
+    // A field tokens don't have is a mistake, not a missing value
+    match Condition::exists("Async.valeu").evaluate(parser_context.clone(), scope) {
+      Err(MatcherFailure::Error(error)) => {
+        assert!(error.message.contains("Token has no field `valeu`"))
+      }
+      result => unreachable!("{:?}", result),
+    }
+  }
+
+  #[test]
+  fn it_compares_values() {
+    let parser = Parser::new("new");
+    let parser_context = ParserContext::new(&parser, "Test");
+    parser_context
+      .borrow_mut()
+      .set_scope_variable("Version", VariableType::String("2".to_string()));
+
+    for (condition, expected) in [
+      (Condition::equals(Fetch!("Version"), "2"), "Then"),
+      (
+        Condition::compare(Fetch!("Version"), Comparison::Ge, "10"),
+        "Else",
+      ),
+      (
+        !Condition::compare(Fetch!("Version"), Comparison::Lt, "1"),
+        "Then",
+      ),
+    ] {
+      let context = parser_context.borrow().clone_with_name("Test");
+      let matcher = If!(condition, Equals!("Then"; "new"), Equals!("Else"; "new"));
+
+      if let Ok(token) = ParserContext::tokenize(context, matcher) {
+        assert_eq!(token.borrow().get_name(), expected);
+      } else {
+        unreachable!("Test failed!");
+      };
+    }
+
+    let matcher = If!(
+      Condition::compare(Fetch!("Version"), Comparison::Gt, "latest"),
+      Equals!("new"),
+    );
+
+    if let Err(MatcherFailure::Error(error)) = ParserContext::tokenize(parser_context, matcher) {
+      assert!(error
+        .message
+        .contains("expected a number, but found `latest`"));
+    } else {
+      unreachable!("Test failed!");
+    };
+  }
+}
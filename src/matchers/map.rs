@@ -1,4 +1,4 @@
-use crate::matcher::{Matcher, MatcherFailure, MatcherRef, MatcherSuccess};
+use crate::matcher::{Matcher, MatcherFailure, MatcherKind, MatcherRef, MatcherSuccess};
 use crate::parser_context::ParserContextRef;
 use crate::scope_context::ScopeContextRef;
 use crate::token::TokenRef;
@@ -143,6 +143,10 @@ where
   fn to_string(&self) -> String {
     format!("{:?}", self)
   }
+
+  fn get_kind(&self) -> MatcherKind {
+    MatcherKind::Wrapper
+  }
 }
 
 #[macro_export]
@@ -160,7 +164,7 @@ macro_rules! Map {
 mod tests {
   use crate::{
     matcher::MatcherFailure, parser::Parser, parser_context::ParserContext,
-    source_range::SourceRange, Equals, ErrorTokenResult, TokenResult,
+    source_range::SourceRange, Discard, Equals, ErrorTokenResult, Loop, Matches, TokenResult,
   };
 
   #[test]
@@ -234,4 +238,78 @@ mod tests {
       ParserContext::tokenize(parser_context, matcher)
     );
   }
+
+  #[test]
+  fn it_can_reach_user_data_from_the_context() {
+    // One grammar, reused with a different symbol table for each parse
+    let matcher = Loop!(
+      Map!(Matches!(r"\w+"), |token, context, __| {
+        let name = token.borrow().get_value().clone();
+        context
+          .borrow()
+          .with_user_data(|symbols: &mut Vec<String>| symbols.push(name))
+          .map_err(MatcherFailure::Error)?;
+
+        TokenResult!(token.clone())
+      }),
+      Discard!(Matches!(r"\s*")),
+    );
+
+    for (source, expected) in [("a b", vec!["a", "b"]), ("c", vec!["c"])] {
+      let parser = Parser::new(source);
+      let parser_context = ParserContext::new(&parser, "Test");
+      parser_context
+        .borrow_mut()
+        .set_user_data(Vec::<String>::new());
+
+      assert!(ParserContext::tokenize(parser_context.clone(), matcher.clone()).is_ok());
+      assert_eq!(
+        parser_context
+          .borrow()
+          .with_user_data(|symbols: &mut Vec<String>| symbols.clone()),
+        Ok(Some(expected.iter().map(|name| name.to_string()).collect()))
+      );
+    }
+
+    // Asking for the wrong type doesn't find anything
+    let parser = Parser::new("a");
+    let parser_context = ParserContext::new(&parser, "Test");
+    parser_context.borrow_mut().set_user_data(1_usize);
+
+    assert_eq!(
+      parser_context
+        .borrow()
+        .with_user_data(|_: &mut Vec<String>| ()),
+      Ok(None)
+    );
+  }
+
+  #[test]
+  fn it_reports_nested_use_of_user_data() {
+    let parser = Parser::new("a");
+    let parser_context = ParserContext::new(&parser, "Test");
+    parser_context
+      .borrow_mut()
+      .set_user_data(Vec::<String>::new());
+
+    let matcher = Map!(Matches!(r"\w"), |token, context, __| {
+      let context = context.borrow();
+
+      context
+        .with_user_data(|_: &mut Vec<String>| context.with_user_data(|_: &mut Vec<String>| ()))
+        .map_err(MatcherFailure::Error)?
+        .transpose()
+        .map_err(MatcherFailure::Error)?;
+
+      TokenResult!(token.clone())
+    });
+
+    if let Err(MatcherFailure::Error(error)) = ParserContext::tokenize(parser_context, matcher) {
+      assert!(error
+        .message
+        .contains("User data is already in use, `with_user_data` can not be nested"));
+    } else {
+      unreachable!("Test failed!");
+    };
+  }
 }
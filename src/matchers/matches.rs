@@ -2,7 +2,7 @@ extern crate adextopa_macros;
 use std::cell::RefCell;
 use std::rc::Rc;
 
-use crate::matcher::{Matcher, MatcherFailure, MatcherRef, MatcherSuccess};
+use crate::matcher::{Matcher, MatcherFailure, MatcherKind, MatcherRef, MatcherSuccess};
 use crate::parser_context::ParserContextRef;
 use crate::scope_context::ScopeContextRef;
 use crate::token::StandardToken;
@@ -142,6 +142,10 @@ impl Matcher for MatchesPattern {
   fn to_string(&self) -> String {
     format!("{:?}", self)
   }
+
+  fn get_kind(&self) -> MatcherKind {
+    MatcherKind::Matches(self.regex.as_str().to_string())
+  }
 }
 
 #[macro_export]
@@ -213,4 +217,30 @@ mod tests {
       Err(MatcherFailure::Fail)
     );
   }
+
+  #[test]
+  fn it_sees_the_source_before_the_offset() {
+    let parser = Parser::new("Testing 1234");
+
+    // `\b` and `^` are relative to the full source, not the offset
+    for (pattern, offset, expected) in [
+      (r"\bing", 4, false),
+      (r"\b\d+", 8, true),
+      (r"^\d+", 8, false),
+      (r"(?m)^\w+", 0, true),
+    ] {
+      let parser_context = ParserContext::new(&parser, "Test");
+      parser_context.borrow_mut().offset.start = offset;
+
+      assert_eq!(
+        parser_context
+          .borrow()
+          .matches_regexp(&regex::Regex::new(pattern).unwrap())
+          .is_some(),
+        expected,
+        "{}",
+        pattern
+      );
+    }
+  }
 }
@@ -1,7 +1,10 @@
+pub mod anchor;
+pub mod any_order;
 pub mod assert_if;
 pub mod assert_if_not;
 pub mod r#break;
 pub mod catch;
+pub mod chars;
 pub mod debug;
 pub mod discard;
 pub mod equals;
@@ -10,17 +13,24 @@ pub mod expand_range;
 pub mod fatal_if;
 pub mod fatal_if_not;
 pub mod fetch;
+pub mod r#if;
 pub mod map;
 pub mod matches;
+pub mod mutate;
 pub mod not;
 pub mod null;
+pub mod one_of;
 pub mod optional;
 pub mod panic;
+pub mod peek;
 pub mod pin;
 pub mod program;
 pub mod proxy_children;
 pub mod r#ref;
 pub mod register;
+pub mod sep_by;
 pub mod sequence;
 pub mod set_scope;
 pub mod store;
+pub mod take;
+pub mod until;
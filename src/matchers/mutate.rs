@@ -0,0 +1,375 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use super::r#if::Operand;
+use crate::matcher::{Matcher, MatcherFailure, MatcherRef, MatcherSuccess};
+use crate::parse_error::ParseError;
+use crate::parser_context::ParserContextRef;
+use crate::scope::VariableType;
+use crate::scope_context::ScopeContextRef;
+use crate::source_range::SourceRange;
+
+#[derive(Debug)]
+pub enum Mutation {
+  Set(Box<dyn Operand>),
+  /// Add to an Int (decrement with a negative amount). A
+  /// missing variable counts from zero
+  Increment(i64),
+  /// Append to the end of a List, creating it if it is missing
+  Push(Box<dyn Operand>),
+  /// Remove the last item of a List, failing if there is none.
+  /// Use `Fetch!("Name.last")` to look at it first
+  Pop,
+}
+
+impl Mutation {
+  fn name(&self) -> &'static str {
+    match self {
+      Mutation::Set(_) => "Set",
+      Mutation::Increment(amount) if *amount < 0 => "Decr",
+      Mutation::Increment(_) => "Incr",
+      Mutation::Push(_) => "Push",
+      Mutation::Pop => "Pop",
+    }
+  }
+}
+
+// Updates a variable in the scope, without consuming any
+// input. Writes go to the frame that owns the variable (the
+// innermost one if it is missing), and like `Store` they are
+// rolled back when an enclosing program fails
+#[derive(Debug)]
+pub struct MutatePattern {
+  variable: String,
+  mutation: Mutation,
+}
+
+impl MutatePattern {
+  pub fn new(variable: &str, mutation: Mutation) -> MatcherRef {
+    if variable.contains('.') {
+      panic!(
+        "`{}`: Variable names can not contain `.` characters",
+        mutation.name()
+      );
+    }
+
+    Rc::new(RefCell::new(Box::new(Self {
+      variable: variable.to_string(),
+      mutation,
+    })))
+  }
+
+  fn error(&self, context: &ParserContextRef, message: &str) -> MatcherFailure {
+    let context = context.borrow();
+    let range = SourceRange::new(context.offset.start, context.offset.start);
+
+    MatcherFailure::Error(ParseError::new_with_range(
+      &context.get_error_as_string(message, &range),
+      range,
+    ))
+  }
+
+  fn _exec(
+    &self,
+    context: ParserContextRef,
+    scope: ScopeContextRef,
+  ) -> Result<MatcherSuccess, MatcherFailure> {
+    let current = scope.borrow().get(&self.variable);
+    let value = match &self.mutation {
+      Mutation::Set(value) => value.fetch_value(context.clone(), scope.clone())?.into(),
+      Mutation::Increment(amount) => {
+        let current = match current {
+          None => 0,
+          Some(VariableType::Int(value)) => value,
+          Some(VariableType::String(ref value)) if value.trim().parse::<i64>().is_ok() => {
+            value.trim().parse::<i64>().unwrap_or_default()
+          }
+          Some(value) => {
+            return Err(self.error(
+              &context,
+              &format!(
+                "`{}` matcher expected `{}` to be an Int, but it is a {}",
+                self.mutation.name(),
+                self.variable,
+                value.type_name()
+              ),
+            ))
+          }
+        };
+
+        match current.checked_add(*amount) {
+          Some(value) => VariableType::Int(value),
+          None => {
+            return Err(self.error(
+              &context,
+              &format!(
+                "`{}` matcher overflowed `{}` ({} + {})",
+                self.mutation.name(),
+                self.variable,
+                current,
+                amount
+              ),
+            ))
+          }
+        }
+      }
+      Mutation::Push(value) => {
+        let value = value.fetch_value(context.clone(), scope.clone())?.into();
+
+        match current {
+          None => VariableType::List(vec![value]),
+          Some(VariableType::List(mut items)) => {
+            items.push(value);
+            VariableType::List(items)
+          }
+          Some(value) => {
+            return Err(self.error(
+              &context,
+              &format!(
+                "`Push` matcher expected `{}` to be a List, but it is a {}",
+                self.variable,
+                value.type_name()
+              ),
+            ))
+          }
+        }
+      }
+      Mutation::Pop => match current {
+        Some(VariableType::List(mut items)) => {
+          if items.pop().is_none() {
+            return Err(MatcherFailure::Fail);
+          }
+
+          VariableType::List(items)
+        }
+        None => return Err(MatcherFailure::Fail),
+        Some(value) => {
+          return Err(self.error(
+            &context,
+            &format!(
+              "`Pop` matcher expected `{}` to be a List, but it is a {}",
+              self.variable,
+              value.type_name()
+            ),
+          ))
+        }
+      },
+    };
+
+    scope.borrow_mut().update(&self.variable, value);
+
+    Ok(MatcherSuccess::Skip(0))
+  }
+}
+
+impl Matcher for MutatePattern {
+  fn exec(
+    &self,
+    this_matcher: MatcherRef,
+    context: ParserContextRef,
+    scope: ScopeContextRef,
+  ) -> Result<MatcherSuccess, MatcherFailure> {
+    self.before_exec(this_matcher.clone(), context.clone(), scope.clone());
+    let result = self._exec(context.clone(), scope.clone());
+    self.after_exec(this_matcher.clone(), context.clone(), scope.clone());
+
+    result
+  }
+
+  fn is_consuming(&self) -> bool {
+    false
+  }
+
+  fn get_name(&self) -> &str {
+    self.mutation.name()
+  }
+
+  fn set_name(&mut self, _: &str) {}
+
+  fn get_children(&self) -> Option<Vec<MatcherRef>> {
+    None
+  }
+
+  fn add_pattern(&mut self, _: MatcherRef) {
+    panic!(
+      "Can not add a pattern to a `{}` matcher",
+      self.mutation.name()
+    );
+  }
+
+  fn to_string(&self) -> String {
+    format!("{:?}", self)
+  }
+}
+
+#[macro_export]
+macro_rules! Set {
+  ($name:expr; $value:expr) => {
+    $crate::matchers::mutate::MutatePattern::new(
+      $name,
+      $crate::matchers::mutate::Mutation::Set(Box::new($value)),
+    )
+  };
+}
+
+#[macro_export]
+macro_rules! Incr {
+  ($name:expr; $amount:expr) => {
+    $crate::matchers::mutate::MutatePattern::new(
+      $name,
+      $crate::matchers::mutate::Mutation::Increment($amount),
+    )
+  };
+
+  ($name:expr) => {
+    $crate::Incr!($name; 1)
+  };
+}
+
+#[macro_export]
+macro_rules! Decr {
+  ($name:expr; $amount:expr) => {
+    $crate::Incr!($name; -($amount))
+  };
+
+  ($name:expr) => {
+    $crate::Incr!($name; -1)
+  };
+}
+
+#[macro_export]
+macro_rules! Push {
+  ($name:expr; $value:expr) => {
+    $crate::matchers::mutate::MutatePattern::new(
+      $name,
+      $crate::matchers::mutate::Mutation::Push(Box::new($value)),
+    )
+  };
+}
+
+#[macro_export]
+macro_rules! Pop {
+  ($name:expr) => {
+    $crate::matchers::mutate::MutatePattern::new($name, $crate::matchers::mutate::Mutation::Pop)
+  };
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::{
+    matcher::MatcherFailure, parser::Parser, parser_context::ParserContext, scope::VariableType,
+    Discard, Equals, Fetch, Matches, Program, SetScope, Store, Switch,
+  };
+
+  #[test]
+  fn it_counts_and_rolls_back_with_failed_alternatives() {
+    let parser = Parser::new("y");
+    let parser_context = ParserContext::new(&parser, "Test");
+    let matcher = Program!(
+      Set!("Depth"; 10),
+      Decr!("Depth"; 9),
+      Switch!(Program!(Incr!("Depth"), Equals!("x")), Equals!("y")),
+    );
+
+    assert!(ParserContext::tokenize(parser_context.clone(), matcher).is_ok());
+    assert!(matches!(
+      parser_context.borrow().get_scope_variable("Depth"),
+      Some(VariableType::Int(1))
+    ));
+  }
+
+  #[test]
+  fn it_pushes_and_pops_a_stack() {
+    let parser = Parser::new("ab|ba");
+    let parser_context = ParserContext::new(&parser, "Test");
+    let matcher = Program!(
+      Store!("Char"; Matches!(r"\w")),
+      Push!("Stack"; Fetch!("Char.value")),
+      Store!("Char"; Matches!(r"\w")),
+      Push!("Stack"; Fetch!("Char.value")),
+      Discard!(Equals!("|")),
+      Equals!(Fetch!("Stack.last")),
+      Pop!("Stack"),
+      Equals!(Fetch!("Stack.last")),
+      Pop!("Stack"),
+    );
+
+    if let Ok(token) = ParserContext::tokenize(parser_context.clone(), matcher) {
+      assert_eq!(token.borrow().get_value(), "ab|ba");
+    } else {
+      unreachable!("Test failed!");
+    };
+
+    assert!(matches!(
+      parser_context.borrow().get_scope_variable("Stack"),
+      Some(VariableType::List(items)) if items.is_empty()
+    ));
+
+    let parser = Parser::new("a");
+    let parser_context = ParserContext::new(&parser, "Test");
+
+    assert_eq!(
+      ParserContext::tokenize(parser_context, Program!(Pop!("Stack"), Equals!("a"))),
+      Err(MatcherFailure::Fail)
+    );
+  }
+
+  #[test]
+  fn it_reports_the_wrong_type() {
+    let parser = Parser::new("a");
+    let parser_context = ParserContext::new(&parser, "Test");
+    let matcher = Program!(Set!("Depth"; "deep"), Incr!("Depth"), Equals!("a"));
+
+    if let Err(MatcherFailure::Error(error)) = ParserContext::tokenize(parser_context, matcher) {
+      assert!(error
+        .message
+        .contains("`Incr` matcher expected `Depth` to be an Int, but it is a String"));
+    } else {
+      unreachable!("Test failed!");
+    };
+  }
+
+  #[test]
+  fn it_updates_a_variable_owned_by_an_outer_scope() {
+    let parser = Parser::new("ab");
+    let parser_context = ParserContext::new(&parser, "Test");
+    let matcher = Program!(
+      Set!("Count"; 0),
+      Push!("Seen"; "start"),
+      SetScope!(Program!(
+        Store!("Char"; Matches!(r"\w")),
+        Incr!("Count"),
+        Push!("Seen"; Fetch!("Char.value")),
+      )),
+      SetScope!(Program!(
+        Store!("Char"; Matches!(r"\w")),
+        Incr!("Count"),
+        Push!("Seen"; Fetch!("Char.value")),
+      )),
+    );
+
+    assert!(ParserContext::tokenize(parser_context.clone(), matcher).is_ok());
+    assert!(matches!(
+      parser_context.borrow().get_scope_variable("Count"),
+      Some(VariableType::Int(2))
+    ));
+    assert!(matches!(
+      parser_context.borrow().get_scope_variable("Seen"),
+      Some(VariableType::List(items)) if items.len() == 3
+    ));
+    assert!(parser_context.borrow().get_scope_variable("Char").is_none());
+  }
+
+  #[test]
+  fn it_reports_an_overflow() {
+    let parser = Parser::new("a");
+    let parser_context = ParserContext::new(&parser, "Test");
+    let matcher = Program!(Set!("Depth"; i64::MAX), Incr!("Depth"), Equals!("a"));
+
+    if let Err(MatcherFailure::Error(error)) = ParserContext::tokenize(parser_context, matcher) {
+      assert!(error.message.contains("`Incr` matcher overflowed `Depth`"));
+    } else {
+      unreachable!("Test failed!");
+    };
+  }
+}
@@ -0,0 +1,484 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use crate::matcher::{Matcher, MatcherFailure, MatcherKind, MatcherRef, MatcherSuccess};
+use crate::parse_error::ParseError;
+use crate::parser_context::ParserContextRef;
+use crate::scope::VariableType;
+use crate::scope_context::ScopeContextRef;
+use crate::source_range::SourceRange;
+use crate::token::StandardToken;
+
+use super::fetch::{FetchPattern, Fetchable, FetchableType};
+
+/// Whether a keyword must end on a word boundary
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Boundary {
+  /// Any match is accepted (i.e. for operators)
+  None,
+  /// A keyword ending in a word character can not be directly
+  /// followed by another word character, so `if` won't match `iffy`
+  Word,
+}
+
+#[derive(Debug, Default)]
+struct TrieNode {
+  children: HashMap<u8, usize>,
+  // Index of the keyword that ends at this node
+  keyword: Option<usize>,
+}
+
+/// A set of keywords, stored as a byte-wise trie
+#[derive(Debug)]
+pub struct KeywordTrie {
+  nodes: Vec<TrieNode>,
+  keywords: Vec<String>,
+  longest: usize,
+}
+
+impl KeywordTrie {
+  pub fn new<I, S>(keywords: I) -> Self
+  where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+  {
+    let mut trie = Self {
+      nodes: vec![TrieNode::default()],
+      keywords: Vec::new(),
+      longest: 0,
+    };
+
+    for keyword in keywords {
+      trie.insert(keyword.as_ref());
+    }
+
+    trie
+  }
+
+  pub fn insert(&mut self, keyword: &str) {
+    // An empty keyword never matches, but still takes up its index
+    if keyword.is_empty() {
+      self.keywords.push(String::new());
+      return;
+    }
+
+    let mut node = 0;
+    for byte in keyword.bytes() {
+      node = match self.nodes[node].children.get(&byte) {
+        Some(next) => *next,
+        None => {
+          self.nodes.push(TrieNode::default());
+          let next = self.nodes.len() - 1;
+          self.nodes[node].children.insert(byte, next);
+          next
+        }
+      };
+    }
+
+    // The first occurrence of a duplicate keyword wins
+    if self.nodes[node].keyword.is_none() {
+      self.nodes[node].keyword = Some(self.keywords.len());
+    }
+
+    self.keywords.push(keyword.to_string());
+    self.longest = self.longest.max(keyword.len());
+  }
+
+  pub fn keywords(&self) -> &Vec<String> {
+    &self.keywords
+  }
+
+  /// Length of the longest keyword, in bytes
+  pub fn longest(&self) -> usize {
+    self.longest
+  }
+
+  /// The index of the longest keyword `source` starts with
+  /// (that satisfies `boundary`), and its length in bytes
+  pub fn longest_match(&self, source: &str, boundary: Boundary) -> Option<(usize, usize)> {
+    let bytes = source.as_bytes();
+    let mut node = 0;
+    let mut result = None;
+
+    for (offset, byte) in bytes.iter().enumerate() {
+      node = match self.nodes[node].children.get(byte) {
+        Some(next) => *next,
+        None => break,
+      };
+
+      if let Some(index) = self.nodes[node].keyword {
+        let len = offset + 1;
+
+        if boundary == Boundary::None || !splits_word(bytes, len) {
+          result = Some((index, len));
+        }
+      }
+    }
+
+    result
+  }
+}
+
+fn is_word_byte(byte: u8) -> bool {
+  byte.is_ascii_alphanumeric() || byte == b'_' || byte >= 0x80
+}
+
+// Would ending a match at `len` split a word in two?
+fn splits_word(bytes: &[u8], len: usize) -> bool {
+  match bytes.get(len) {
+    Some(next) => is_word_byte(bytes[len - 1]) && is_word_byte(*next),
+    None => false,
+  }
+}
+
+#[derive(Debug)]
+enum Keywords {
+  Static(KeywordTrie),
+  // The trie of the last keyword list fetched, which
+  // is rebuilt only when the fetched value changes
+  Fetched(
+    FetchPattern,
+    RefCell<Option<(Vec<String>, Rc<KeywordTrie>)>>,
+  ),
+}
+
+#[derive(Debug)]
+pub struct OneOfPattern {
+  keywords: Keywords,
+  boundary: Boundary,
+  name: String,
+  custom_name: bool,
+}
+
+impl OneOfPattern {
+  pub fn new<I, S>(keywords: I, boundary: Boundary) -> MatcherRef
+  where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+  {
+    Self::new_with_name("OneOf", keywords, boundary)
+  }
+
+  pub fn new_with_name<I, S>(name: &str, keywords: I, boundary: Boundary) -> MatcherRef
+  where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+  {
+    Rc::new(RefCell::new(Box::new(Self {
+      keywords: Keywords::Static(KeywordTrie::new(keywords)),
+      boundary,
+      name: name.to_string(),
+      custom_name: name != "OneOf",
+    })))
+  }
+
+  /// Load the keywords (separated by whitespace) from a scope variable when executed
+  pub fn new_fetched(fetch: FetchPattern, boundary: Boundary) -> MatcherRef {
+    Self::new_fetched_with_name("OneOf", fetch, boundary)
+  }
+
+  pub fn new_fetched_with_name(name: &str, fetch: FetchPattern, boundary: Boundary) -> MatcherRef {
+    Rc::new(RefCell::new(Box::new(Self {
+      keywords: Keywords::Fetched(fetch, RefCell::new(None)),
+      boundary,
+      name: name.to_string(),
+      custom_name: name != "OneOf",
+    })))
+  }
+
+  fn fetch_trie(
+    &self,
+    fetch: &FetchPattern,
+    cache: &RefCell<Option<(Vec<String>, Rc<KeywordTrie>)>>,
+    context: ParserContextRef,
+    scope: ScopeContextRef,
+  ) -> Result<Rc<KeywordTrie>, MatcherFailure> {
+    // Either a list of keywords, or a whitespace separated string of them
+    let keywords = match fetch.fetch_value(context, scope)? {
+      FetchableType::List(items) => {
+        let mut keywords = Vec::with_capacity(items.len());
+
+        for item in items {
+          match item {
+            VariableType::String(keyword) => keywords.push(keyword),
+            item => {
+              return Err(MatcherFailure::Error(ParseError::new(&format!(
+                "`OneOf` ({}) matcher expected a keyword list in `{}`, but found a {} in it",
+                self.get_name(),
+                fetch.get_name(),
+                item.type_name()
+              ))))
+            }
+          }
+        }
+
+        keywords
+      }
+      FetchableType::String(list) => list.split_whitespace().map(String::from).collect(),
+      value => {
+        return Err(MatcherFailure::Error(ParseError::new(&format!(
+          "`OneOf` ({}) matcher expected a keyword list in `{}`, but found a {}",
+          self.get_name(),
+          fetch.get_name(),
+          value.type_name()
+        ))))
+      }
+    };
+
+    let mut cache = cache.borrow_mut();
+    match &*cache {
+      Some((cached_keywords, trie)) if *cached_keywords == keywords => Ok(trie.clone()),
+      _ => {
+        let trie = Rc::new(KeywordTrie::new(&keywords));
+        *cache = Some((keywords, trie.clone()));
+        Ok(trie)
+      }
+    }
+  }
+
+  fn _exec(
+    &self,
+    context: ParserContextRef,
+    scope: ScopeContextRef,
+  ) -> Result<MatcherSuccess, MatcherFailure> {
+    let sub_context = context.borrow().clone_with_name(self.get_name());
+    let fetched;
+    let trie = match &self.keywords {
+      Keywords::Static(trie) => trie,
+      Keywords::Fetched(fetch, cache) => {
+        fetched = self.fetch_trie(fetch, cache, sub_context.clone(), scope)?;
+        fetched.as_ref()
+      }
+    };
+
+    let _sc = sub_context.borrow();
+    let start = _sc.offset.start;
+    // Up to the longest keyword, and the character after it
+    let found = trie.longest_match(
+      &_sc.get_input(start, start + trie.longest() + 4),
+      self.boundary,
+    );
+
+    match found {
+      Some((index, len)) => {
+        let token = StandardToken::new(
+          &_sc.parser,
+          self.name.to_string(),
+          SourceRange::new(start, start + len),
+        );
+
+        // Record which alternative matched
+        let mut _token = token.borrow_mut();
+        _token.set_attribute("keyword", &trie.keywords()[index]);
+        _token.set_attribute("index", &index.to_string());
+        drop(_token);
+
+        Ok(MatcherSuccess::Token(token))
+      }
+      None => Err(MatcherFailure::Fail),
+    }
+  }
+}
+
+impl Matcher for OneOfPattern {
+  fn exec(
+    &self,
+    this_matcher: MatcherRef,
+    context: ParserContextRef,
+    scope: ScopeContextRef,
+  ) -> Result<MatcherSuccess, MatcherFailure> {
+    self.before_exec(this_matcher.clone(), context.clone(), scope.clone());
+    let result = self._exec(context.clone(), scope.clone());
+    self.after_exec(this_matcher.clone(), context.clone(), scope.clone());
+
+    result
+  }
+
+  fn has_custom_name(&self) -> bool {
+    self.custom_name
+  }
+
+  fn get_name(&self) -> &str {
+    self.name.as_str()
+  }
+
+  fn set_name(&mut self, name: &str) {
+    self.name = name.to_string();
+    self.custom_name = name != "OneOf";
+  }
+
+  fn get_children(&self) -> Option<Vec<MatcherRef>> {
+    None
+  }
+
+  fn add_pattern(&mut self, _: MatcherRef) {
+    panic!("Can not add a pattern to a `OneOf` matcher");
+  }
+
+  fn to_string(&self) -> String {
+    format!("{:?}", self)
+  }
+
+  fn get_kind(&self) -> MatcherKind {
+    match &self.keywords {
+      Keywords::Static(trie) => MatcherKind::OneOf(Some(
+        trie
+          .keywords()
+          .iter()
+          .filter(|keyword| !keyword.is_empty())
+          .cloned()
+          .collect(),
+      )),
+      Keywords::Fetched(..) => MatcherKind::OneOf(None),
+    }
+  }
+}
+
+#[macro_export]
+macro_rules! OneOf {
+  (@boundary) => {
+    $crate::matchers::one_of::Boundary::None
+  };
+
+  (@boundary $boundary:expr) => {
+    $boundary
+  };
+
+  ($name:literal; [$($keyword:expr),* $(,)?] $(, $boundary:expr)?) => {
+    $crate::matchers::one_of::OneOfPattern::new_with_name(
+      $name,
+      [$($keyword),*],
+      $crate::OneOf!(@boundary $($boundary)?),
+    )
+  };
+
+  ($name:literal; $fetch:expr $(, $boundary:expr)?) => {
+    $crate::matchers::one_of::OneOfPattern::new_fetched_with_name(
+      $name,
+      $fetch,
+      $crate::OneOf!(@boundary $($boundary)?),
+    )
+  };
+
+  ([$($keyword:expr),* $(,)?] $(, $boundary:expr)?) => {
+    $crate::matchers::one_of::OneOfPattern::new(
+      [$($keyword),*],
+      $crate::OneOf!(@boundary $($boundary)?),
+    )
+  };
+
+  ($fetch:expr $(, $boundary:expr)?) => {
+    $crate::matchers::one_of::OneOfPattern::new_fetched(
+      $fetch,
+      $crate::OneOf!(@boundary $($boundary)?),
+    )
+  };
+}
+
+#[cfg(test)]
+mod tests {
+  use super::Boundary;
+  use crate::{
+    matcher::MatcherFailure, parser::Parser, parser_context::ParserContext, scope::VariableType,
+    source_range::SourceRange, Fetch, Program, Store,
+  };
+
+  #[test]
+  fn it_matches_the_longest_keyword() {
+    let parser = Parser::new("elif x");
+    let parser_context = ParserContext::new(&parser, "Test");
+    let matcher = OneOf!("Keyword"; ["if", "el", "else", "elif"]);
+
+    if let Ok(token) = ParserContext::tokenize(parser_context, matcher) {
+      let token = token.borrow();
+      assert_eq!(token.get_name(), "Keyword");
+      assert_eq!(*token.get_captured_range(), SourceRange::new(0, 4));
+      assert_eq!(token.get_value(), "elif");
+      assert_eq!(token.get_attribute("keyword").unwrap(), "elif");
+      assert_eq!(token.get_attribute("index").unwrap(), "3");
+    } else {
+      unreachable!("Test failed!");
+    };
+  }
+
+  #[test]
+  fn it_can_check_for_a_word_boundary() {
+    let parser = Parser::new("iffy");
+    let parser_context = ParserContext::new(&parser, "Test");
+    let matcher = OneOf!(["if", "else"], Boundary::Word);
+
+    assert_eq!(
+      ParserContext::tokenize(parser_context, matcher),
+      Err(MatcherFailure::Fail)
+    );
+
+    // Falls back to a shorter keyword that ends on a boundary,
+    // and operators never need one
+    let parser = Parser::new("in_ +=1");
+    let parser_context = ParserContext::new(&parser, "Test");
+    let matcher = OneOf!(["in", "in_", "in_x"], Boundary::Word);
+
+    if let Ok(token) = ParserContext::tokenize(parser_context, matcher) {
+      assert_eq!(token.borrow().get_value(), "in_");
+    } else {
+      unreachable!("Test failed!");
+    };
+
+    let parser = Parser::new("+=1");
+    let parser_context = ParserContext::new(&parser, "Test");
+    let matcher = OneOf!(["+", "+="], Boundary::Word);
+
+    if let Ok(token) = ParserContext::tokenize(parser_context, matcher) {
+      assert_eq!(token.borrow().get_value(), "+=");
+    } else {
+      unreachable!("Test failed!");
+    };
+  }
+
+  #[test]
+  fn it_can_load_keywords_from_the_scope() {
+    let parser = Parser::new("while true");
+    let parser_context = ParserContext::new(&parser, "Test");
+    let matcher = Program!(
+      Store!("keywords"; "if else\nwhile"),
+      OneOf!("Keyword"; Fetch!("keywords"), Boundary::Word),
+    );
+
+    if let Ok(token) = ParserContext::tokenize(parser_context, matcher) {
+      let token = token.borrow();
+      let keyword = token.get_children()[0].borrow();
+      assert_eq!(keyword.get_name(), "Keyword");
+      assert_eq!(keyword.get_value(), "while");
+      assert_eq!(keyword.get_attribute("index").unwrap(), "2");
+    } else {
+      unreachable!("Test failed!");
+    };
+  }
+
+  #[test]
+  fn it_keeps_the_keywords_of_a_fetched_list_whole() {
+    let parser = Parser::new("else if x");
+    let parser_context = ParserContext::new(&parser, "Test");
+    let keywords = ["if", "", "else if", "else"]
+      .iter()
+      .map(|keyword| VariableType::String(keyword.to_string()))
+      .collect();
+
+    parser_context
+      .borrow()
+      .scope
+      .borrow_mut()
+      .set("keywords", VariableType::List(keywords));
+
+    let matcher = OneOf!("Keyword"; Fetch!("keywords"), Boundary::Word);
+
+    if let Ok(token) = ParserContext::tokenize(parser_context, matcher) {
+      let token = token.borrow();
+      assert_eq!(token.get_value(), "else if");
+      assert_eq!(token.get_attribute("index").unwrap(), "2");
+    } else {
+      unreachable!("Test failed!");
+    };
+  }
+}
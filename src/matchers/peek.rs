@@ -0,0 +1,150 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::matcher::{Matcher, MatcherFailure, MatcherKind, MatcherRef, MatcherSuccess};
+use crate::parser_context::ParserContextRef;
+use crate::scope_context::ScopeContextRef;
+
+// Positive lookahead: succeeds without consuming
+// any input, but only if its matcher would match
+#[derive(Debug)]
+pub struct PeekPattern {
+  matcher: MatcherRef,
+}
+
+impl PeekPattern {
+  pub fn new(matcher: MatcherRef) -> MatcherRef {
+    Rc::new(RefCell::new(Box::new(Self { matcher })))
+  }
+
+  fn _exec(
+    &self,
+    context: ParserContextRef,
+    scope: ScopeContextRef,
+  ) -> Result<MatcherSuccess, MatcherFailure> {
+    match self.matcher.borrow().exec(
+      self.matcher.clone(),
+      context.borrow().clone_with_name(self.get_name()),
+      scope.clone(),
+    ) {
+      Ok(success) => match success {
+        // Succeed on success, but don't consume anything
+        MatcherSuccess::Token(_) | MatcherSuccess::ProxyChildren(_) | MatcherSuccess::Skip(_) => {
+          Ok(MatcherSuccess::Skip(0))
+        }
+        // For other success types (Stop, Break, Continue, None) succeed
+        _ => Ok(success),
+      },
+      Err(failure) => Err(failure),
+    }
+  }
+}
+
+impl Matcher for PeekPattern {
+  fn exec(
+    &self,
+    this_matcher: MatcherRef,
+    context: ParserContextRef,
+    scope: ScopeContextRef,
+  ) -> Result<MatcherSuccess, MatcherFailure> {
+    self.before_exec(this_matcher.clone(), context.clone(), scope.clone());
+    let result = self._exec(context.clone(), scope.clone());
+    self.after_exec(this_matcher.clone(), context.clone(), scope.clone());
+
+    result
+  }
+
+  fn get_name(&self) -> &str {
+    "Peek"
+  }
+
+  fn set_name(&mut self, name: &str) {
+    self.matcher.borrow_mut().set_name(name);
+  }
+
+  fn set_child(&mut self, index: usize, matcher: MatcherRef) {
+    if index > 0 {
+      panic!("Attempt to set child at an index that is out of bounds");
+    }
+
+    self.matcher = matcher;
+  }
+
+  fn get_children(&self) -> Option<Vec<MatcherRef>> {
+    Some(vec![self.matcher.clone()])
+  }
+
+  fn add_pattern(&mut self, _: MatcherRef) {
+    panic!("Can not add a pattern to a Peek pattern");
+  }
+
+  fn to_string(&self) -> String {
+    format!("{:?}", self)
+  }
+
+  fn get_kind(&self) -> MatcherKind {
+    MatcherKind::Peek
+  }
+}
+
+#[macro_export]
+macro_rules! Peek {
+  ($arg:expr) => {
+    $crate::matchers::peek::PeekPattern::new($arg)
+  };
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::{
+    matcher::{MatcherFailure, MatcherSuccess},
+    parser::Parser,
+    parser_context::ParserContext,
+    source_range::SourceRange,
+    Equals, Matches, Program,
+  };
+
+  #[test]
+  fn it_matches_against_a_string() {
+    let parser = Parser::new("Testing 1234");
+    let parser_context = ParserContext::new(&parser, "Test");
+    let matcher = Peek!(Equals!("Testing"));
+
+    assert_eq!(
+      Ok(MatcherSuccess::Skip(0)),
+      matcher.borrow().exec(
+        matcher.clone(),
+        parser_context.clone(),
+        parser_context.borrow().scope.clone(),
+      )
+    );
+  }
+
+  #[test]
+  fn it_fails_to_match_against_a_string() {
+    let parser = Parser::new("Testing 1234");
+    let parser_context = ParserContext::new(&parser, "Test");
+    let matcher = Peek!(Equals!("testing"));
+
+    assert_eq!(
+      Err(MatcherFailure::Fail),
+      ParserContext::tokenize(parser_context, matcher)
+    );
+  }
+
+  #[test]
+  fn it_does_not_consume_input() {
+    let parser = Parser::new("Testing 1234");
+    let parser_context = ParserContext::new(&parser, "Test");
+    let matcher = Program!(Peek!(Matches!(r"\w+")), Matches!(r"\w+"));
+
+    if let Ok(token) = ParserContext::tokenize(parser_context, matcher) {
+      let token = token.borrow();
+      assert_eq!(token.get_children().len(), 1);
+      assert_eq!(*token.get_captured_range(), SourceRange::new(0, 7));
+      assert_eq!(token.get_value(), "Testing");
+    } else {
+      unreachable!("Test failed!");
+    };
+  }
+}
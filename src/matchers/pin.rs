@@ -2,7 +2,7 @@ extern crate adextopa_macros;
 use std::cell::RefCell;
 use std::rc::Rc;
 
-use super::fetch::{Fetchable, FetchableType};
+use super::fetch::Fetchable;
 use crate::matcher::{Matcher, MatcherFailure, MatcherRef, MatcherSuccess};
 use crate::parse_error::ParseError;
 use crate::parser::ParserRef;
@@ -122,6 +122,10 @@ impl Token for PinToken {
     self.parser.clone()
   }
 
+  fn set_parser(&mut self, parser: &crate::parser::ParserRef) {
+    self.parser = parser.clone();
+  }
+
   fn get_captured_range(&self) -> &crate::source_range::SourceRange {
     &self.captured_range
   }
@@ -322,12 +326,14 @@ where
     scope: ScopeContextRef,
   ) -> Result<MatcherSuccess, MatcherFailure> {
     let sub_context = context.borrow().clone_with_name(self.get_name());
-    let offset_value_fetchable = self.offset.fetch_value(sub_context.clone(), scope.clone());
-    let offset_value = match offset_value_fetchable {
-      FetchableType::String(value) => value,
-      FetchableType::Matcher(_) => return Err(MatcherFailure::Error(ParseError::new(
-        "`Pin` matcher received another matcher as an offset... this makes no sense... aborting...",
-      ))),
+    let offset_value_fetchable = self.offset.fetch_value(sub_context.clone(), scope.clone())?;
+    let offset_type = offset_value_fetchable.type_name();
+    let offset_value = match offset_value_fetchable.into_string() {
+      Some(value) => value,
+      None => return Err(MatcherFailure::Error(ParseError::new(&format!(
+        "`Pin` matcher received a {} as an offset... this makes no sense... aborting...",
+        offset_type
+      )))),
     };
 
     let offset_value_parts: Vec<&str> = offset_value.split("..").collect();
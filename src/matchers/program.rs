@@ -1,6 +1,7 @@
 extern crate adextopa_macros;
 
-use crate::matcher::{Matcher, MatcherFailure, MatcherRef, MatcherSuccess};
+use super::fetch::{fetch_count, FetchPattern};
+use crate::matcher::{Matcher, MatcherFailure, MatcherKind, MatcherRef, MatcherSuccess};
 use crate::parser_context::ParserContextRef;
 use crate::scope_context::ScopeContextRef;
 use crate::source_range::SourceRange;
@@ -9,21 +10,73 @@ use std::cell::RefCell;
 use std::ops::{Bound, Range, RangeBounds};
 use std::rc::Rc;
 
-fn get_range<T>(r: T) -> Range<usize>
+// Normalize to a range with an exclusive end, where `usize::MAX` means "unbounded"
+pub(crate) fn get_range<T>(r: T) -> Range<usize>
 where
   T: RangeBounds<usize>,
 {
-  let mut range = 0..usize::MAX;
+  let start = match r.start_bound() {
+    Bound::Included(start) => *start,
+    Bound::Excluded(start) => *start + 1,
+    Bound::Unbounded => 0,
+  };
+
+  let end = match r.end_bound() {
+    Bound::Included(end) => end.saturating_add(1),
+    Bound::Excluded(end) => *end,
+    Bound::Unbounded => usize::MAX,
+  };
+
+  start..end
+}
 
-  if let Bound::Included(start) = r.start_bound() {
-    range.start = *start;
+/// The iteration counts a `Loop` over `range` accepts, with an exclusive
+/// end. It must iterate at least `range.start` times, and (as it iterates
+/// over `range` itself) at most `range.end - range.start` times
+pub fn loop_iteration_counts(range: &Range<usize>) -> Range<usize> {
+  if range.end == usize::MAX {
+    return range.start..usize::MAX;
   }
 
-  if let Bound::Excluded(end) = r.end_bound() {
-    range.end = *end;
+  range.start..(range.end.saturating_sub(range.start) + 1)
+}
+
+/// How many times a `Loop` iterates
+pub enum LoopRange {
+  Static(Range<usize>),
+  /// Exactly this many times, fetched when the loop executes (i.e. a length prefix)
+  Fetched(FetchPattern),
+}
+
+pub trait IntoLoopRange {
+  fn into_loop_range(self) -> LoopRange;
+}
+
+impl<T> IntoLoopRange for T
+where
+  T: RangeBounds<usize>,
+{
+  fn into_loop_range(self) -> LoopRange {
+    LoopRange::Static(get_range(self))
   }
+}
 
-  range
+impl IntoLoopRange for FetchPattern {
+  fn into_loop_range(self) -> LoopRange {
+    LoopRange::Fetched(self)
+  }
+}
+
+// Split into the static range (a fetched count can
+// be anything), and the count to fetch, if any
+fn split_loop_range<T>(r: T) -> (Range<usize>, Option<FetchPattern>)
+where
+  T: IntoLoopRange,
+{
+  match r.into_loop_range() {
+    LoopRange::Static(range) => (range, None),
+    LoopRange::Fetched(count) => (0..usize::MAX, Some(count)),
+  }
 }
 
 #[derive(Debug)]
@@ -36,6 +89,7 @@ pub struct ProgramPattern {
   patterns: Vec<MatcherRef>,
   name: String,
   pub(self) iterate_range: Option<Range<usize>>,
+  pub(self) iterate_count: Option<FetchPattern>,
   pub(self) on_first_match: MatchAction,
   custom_name: bool,
 }
@@ -52,10 +106,17 @@ impl std::fmt::Debug for ProgramPattern {
       name = "ProgramPattern";
     }
 
-    f.debug_struct(name)
+    let mut debug = f.debug_struct(name);
+    debug
       .field("patterns", &self.patterns)
       .field("name", &self.name)
-      .field("iterate_range", &self.iterate_range)
+      .field("iterate_range", &self.iterate_range);
+
+    if let Some(count) = &self.iterate_count {
+      debug.field("iterate_count", count);
+    }
+
+    debug
       .field("on_first_match", &self.on_first_match)
       .field("custom_name", &self.custom_name)
       .finish()
@@ -72,6 +133,7 @@ impl ProgramPattern {
     Rc::new(RefCell::new(Box::new(Self {
       patterns: Vec::new(),
       iterate_range: None,
+      iterate_count: None,
       name: name.to_string(),
       on_first_match,
       custom_name: false,
@@ -80,12 +142,15 @@ impl ProgramPattern {
 
   pub fn new_blank_loop<T>(r: T) -> MatcherRef
   where
-    T: RangeBounds<usize>,
+    T: IntoLoopRange,
   {
+    let (range, count) = split_loop_range(r);
+
     Rc::new(RefCell::new(Box::new(Self {
       patterns: Vec::new(),
       name: "Loop".to_string(),
-      iterate_range: Some(get_range(r)),
+      iterate_range: Some(range),
+      iterate_count: count,
       on_first_match: MatchAction::Continue,
       custom_name: false,
     })))
@@ -100,6 +165,7 @@ impl ProgramPattern {
     Rc::new(RefCell::new(Box::new(Self {
       patterns,
       iterate_range: None,
+      iterate_count: None,
       name: name.to_string(),
       on_first_match,
       custom_name: false,
@@ -108,12 +174,15 @@ impl ProgramPattern {
 
   pub fn new_loop<T>(patterns: Vec<MatcherRef>, r: T) -> MatcherRef
   where
-    T: RangeBounds<usize>,
+    T: IntoLoopRange,
   {
+    let (range, count) = split_loop_range(r);
+
     Rc::new(RefCell::new(Box::new(Self {
       patterns,
       name: "Loop".to_string(),
-      iterate_range: Some(get_range(r)),
+      iterate_range: Some(range),
+      iterate_count: count,
       on_first_match: MatchAction::Continue,
       custom_name: false,
     })))
@@ -128,6 +197,7 @@ impl ProgramPattern {
       patterns,
       name,
       iterate_range: None,
+      iterate_count: None,
       on_first_match,
       custom_name: true,
     })))
@@ -139,12 +209,15 @@ impl ProgramPattern {
     r: T,
   ) -> MatcherRef
   where
-    T: RangeBounds<usize>,
+    T: IntoLoopRange,
   {
+    let (range, count) = split_loop_range(r);
+
     Rc::new(RefCell::new(Box::new(Self {
       patterns,
       name,
-      iterate_range: Some(get_range(r)),
+      iterate_range: Some(range),
+      iterate_count: count,
       on_first_match: MatchAction::Continue,
       custom_name: true,
     })))
@@ -170,21 +243,33 @@ impl ProgramPattern {
       Some(_) => true,
       None => false,
     };
-    let iterate_range = match &self.iterate_range {
-      Some(range) => range.clone(),
-      None => (0..1),
+    // A fetched count has to be matched exactly
+    let loop_range = match &self.iterate_count {
+      Some(count) => {
+        let count = fetch_count(count, self.get_name(), sub_context.clone(), scope.clone())?;
+        Some(count..count + 1)
+      }
+      None => self.iterate_range.clone(),
+    };
+    let iterate_range = match (&loop_range, &self.iterate_count) {
+      (Some(range), Some(_)) => 0..range.start,
+      (Some(range), None) => range.clone(),
+      (None, _) => (0..1),
     };
     let mut iteration_result: Option<MatcherSuccess>;
     let mut loop_count = 0;
 
     for _ in iterate_range {
       iteration_result = None;
+      let iteration_savepoint = scope.borrow().savepoint();
 
       if sub_context.borrow().debug_mode_level() > 1 {
         println!("{{{}/Iterating}}", program_token.borrow().get_name());
       }
 
       for pattern in &self.patterns {
+        let savepoint = scope.borrow().savepoint();
+
         sub_context
           .borrow_mut()
           .push_token_to_stack(program_token.clone());
@@ -318,10 +403,17 @@ impl ProgramPattern {
             match failure {
               MatcherFailure::Fail => match self.on_first_match {
                 MatchAction::Stop => {
+                  // The next alternative shouldn't see anything this one stored
+                  scope.borrow_mut().rollback_to_savepoint(savepoint);
                   continue;
                 }
                 _ => {
                   if is_loop {
+                    // Only keep what was stored by iterations that completed
+                    scope
+                      .borrow_mut()
+                      .rollback_to_savepoint(iteration_savepoint);
+
                     if sub_context.debug_mode_level() > 1 {
                       println!(
                         "{{{}/Finalizing}}: Failure",
@@ -334,9 +426,9 @@ impl ProgramPattern {
                       children,
                       captured_range,
                       matched_range,
-                      &self.iterate_range,
+                      &loop_range,
                       loop_count,
-                      false,
+                      self.iterate_count.is_some(),
                     );
                   }
 
@@ -428,7 +520,7 @@ impl ProgramPattern {
                 children,
                 captured_range,
                 matched_range,
-                &self.iterate_range,
+                &loop_range,
                 loop_count,
                 false,
               );
@@ -446,7 +538,7 @@ impl ProgramPattern {
                 children,
                 captured_range,
                 matched_range,
-                &self.iterate_range,
+                &loop_range,
                 loop_count,
                 false,
               ) {
@@ -535,7 +627,7 @@ impl ProgramPattern {
                 children,
                 captured_range,
                 matched_range,
-                &self.iterate_range,
+                &loop_range,
                 loop_count,
                 false,
               );
@@ -564,7 +656,7 @@ impl ProgramPattern {
       children,
       captured_range,
       matched_range,
-      &self.iterate_range,
+      &loop_range,
       loop_count,
       true,
     )
@@ -876,7 +968,16 @@ impl Matcher for ProgramPattern {
     scope: ScopeContextRef,
   ) -> Result<MatcherSuccess, MatcherFailure> {
     self.before_exec(this_matcher.clone(), context.clone(), scope.clone());
+    let checkpoint = scope.borrow_mut().begin();
     let result = self._exec(context.clone(), scope.clone());
+
+    // Undo anything stored by a program that failed, so
+    // whatever is tried next doesn't see stale variables
+    match result {
+      Ok(_) => scope.borrow_mut().commit(),
+      Err(_) => scope.borrow_mut().rollback(checkpoint),
+    }
+
     self.after_exec(this_matcher.clone(), context.clone(), scope.clone());
 
     result
@@ -914,6 +1015,17 @@ impl Matcher for ProgramPattern {
   fn to_string(&self) -> String {
     format!("{:?}", self)
   }
+
+  fn get_kind(&self) -> MatcherKind {
+    if let Some(range) = &self.iterate_range {
+      return MatcherKind::Loop(range.clone());
+    }
+
+    match self.on_first_match {
+      MatchAction::Stop => MatcherKind::Switch,
+      MatchAction::Continue => MatcherKind::Program,
+    }
+  }
 }
 
 #[macro_export]
@@ -1079,7 +1191,7 @@ macro_rules! Loop {
 mod tests {
   use crate::{
     matcher::MatcherFailure, parser::Parser, parser_context::ParserContext,
-    source_range::SourceRange, Break, Equals, Matches, Optional,
+    source_range::SourceRange, Break, Discard, Equals, Fetch, Matches, Optional, Store,
   };
 
   #[test]
@@ -1092,7 +1204,7 @@ mod tests {
       let token = token.borrow();
       assert_eq!(token.get_name(), "Program");
       assert_eq!(*token.get_captured_range(), SourceRange::new(0, 12));
-      assert_eq!(token.get_value(), &parser.borrow().source);
+      assert_eq!(token.get_value(), parser.borrow().get_source());
     } else {
       unreachable!("Test failed!");
     };
@@ -1137,8 +1249,8 @@ mod tests {
       let token = token.borrow();
       assert_eq!(token.get_name(), "Loop");
       assert_eq!(*token.get_captured_range(), SourceRange::new(0, 12));
-      assert_eq!(token.get_value(), &parser.borrow().source);
-      assert_eq!(token.get_matched_value(), &parser.borrow().source);
+      assert_eq!(token.get_value(), parser.borrow().get_source());
+      assert_eq!(token.get_matched_value(), parser.borrow().get_source());
 
       assert_eq!(token.get_children().len(), 12);
 
@@ -1181,8 +1293,8 @@ mod tests {
       let token = token.borrow();
       assert_eq!(token.get_name(), "Loop");
       assert_eq!(*token.get_captured_range(), SourceRange::new(0, 12));
-      assert_eq!(token.get_value(), &parser.borrow().source);
-      assert_eq!(token.get_matched_value(), &parser.borrow().source);
+      assert_eq!(token.get_value(), parser.borrow().get_source());
+      assert_eq!(token.get_matched_value(), parser.borrow().get_source());
 
       assert_eq!(token.get_children().len(), 6);
 
@@ -1217,6 +1329,18 @@ mod tests {
     };
   }
 
+  #[test]
+  fn it_still_fails_a_program_that_consumed_nothing() {
+    let parser = Parser::new("abc");
+    let parser_context = ParserContext::new(&parser, "Test");
+    let matcher = Program!(Discard!(Optional!(Equals!("#"))));
+
+    assert_eq!(
+      Err(MatcherFailure::Fail),
+      ParserContext::tokenize(parser_context, matcher)
+    );
+  }
+
   #[test]
   fn it_can_break_from_a_loop() {
     let parser = Parser::new("A B C break D E F ");
@@ -1284,4 +1408,63 @@ mod tests {
       unreachable!("Test failed!");
     };
   }
+
+  #[test]
+  fn it_matches_a_fetched_number_of_times() {
+    let parser = Parser::new("2:a,b,c");
+    let parser_context = ParserContext::new(&parser, "Test");
+    let matcher = Program!(
+      Discard!(Store!("Count"; Matches!(r"\d+"))),
+      Discard!(Equals!(":")),
+      Loop!(Fetch!("Count.value"); Matches!(r"\w"), Discard!(Equals!(","))),
+    );
+
+    if let Ok(token) = ParserContext::tokenize(parser_context, matcher) {
+      let token = token.borrow();
+      assert_eq!(*token.get_matched_range(), SourceRange::new(0, 6));
+      assert_eq!(token.get_children()[0].borrow().get_children().len(), 2);
+    } else {
+      unreachable!("Test failed!");
+    };
+
+    let parser = Parser::new("3:a,b");
+    let parser_context = ParserContext::new(&parser, "Test");
+    let matcher = Program!(
+      Discard!(Store!("Count"; Matches!(r"\d+"))),
+      Discard!(Equals!(":")),
+      Loop!(Fetch!("Count.value"); Matches!(r"\w"), Optional!(Equals!(","))),
+    );
+
+    assert_eq!(
+      ParserContext::tokenize(parser_context, matcher),
+      Err(MatcherFailure::Fail)
+    );
+
+    let parser = Parser::new("x:a");
+    let parser_context = ParserContext::new(&parser, "Test");
+    let matcher = Program!(
+      Store!("Count"; Matches!(r"\w")),
+      Loop!(Fetch!("Count.value"); Matches!(r"\w")),
+    );
+
+    if let Err(MatcherFailure::Error(error)) = ParserContext::tokenize(parser_context, matcher) {
+      assert!(error.message.contains("could not use `x` as a count"));
+    } else {
+      unreachable!("Test failed!");
+    };
+  }
+
+  #[test]
+  fn it_converts_loop_ranges_into_iteration_counts() {
+    use super::{get_range, loop_iteration_counts};
+
+    assert_eq!(get_range(0..=3), 0..4);
+    assert_eq!(get_range(2..), 2..usize::MAX);
+    assert_eq!(get_range(..), 0..usize::MAX);
+
+    // `{3}` in a script is a loop over `0..3`, and `{1,3}` over `1..3`
+    assert_eq!(loop_iteration_counts(&(0..3)), 0..4);
+    assert_eq!(loop_iteration_counts(&(1..3)), 1..3);
+    assert_eq!(loop_iteration_counts(&(1..usize::MAX)), 1..usize::MAX);
+  }
 }
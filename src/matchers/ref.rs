@@ -1,7 +1,7 @@
 use std::cell::RefCell;
 use std::rc::Rc;
 
-use crate::matcher::{Matcher, MatcherFailure, MatcherRef, MatcherSuccess};
+use crate::matcher::{Matcher, MatcherFailure, MatcherKind, MatcherRef, MatcherSuccess};
 use crate::parse_error::ParseError;
 use crate::parser_context::ParserContextRef;
 use crate::scope::VariableType;
@@ -61,7 +61,9 @@ where
     scope: ScopeContextRef,
   ) -> Result<MatcherSuccess, MatcherFailure> {
     let sub_context = context.borrow().clone_with_name(self.get_name());
-    let target = self.target.fetch_value(sub_context.clone(), scope.clone());
+    let target = self
+      .target
+      .fetch_value(sub_context.clone(), scope.clone())?;
 
     match target {
       FetchableType::String(ref target_name) => {
@@ -83,6 +85,10 @@ where
           .borrow()
           .exec(matcher.clone(), sub_context, scope.clone())
       }
+      target => Err(MatcherFailure::Error(ParseError::new(&format!(
+        "`Ref` matcher expected a matcher or the name of one, but received a {}",
+        target.type_name(),
+      )))),
     }
   }
 }
@@ -130,6 +136,10 @@ where
   fn to_string(&self) -> String {
     format!("{:?}", self)
   }
+
+  fn get_kind(&self) -> MatcherKind {
+    MatcherKind::Ref(self.target.as_literal())
+  }
 }
 
 #[macro_export]
@@ -0,0 +1,510 @@
+use super::program::get_range;
+use crate::matcher::{Matcher, MatcherFailure, MatcherKind, MatcherRef, MatcherSuccess};
+use crate::parser_context::ParserContextRef;
+use crate::scope_context::ScopeContextRef;
+use crate::source_range::SourceRange;
+use crate::token::{StandardToken, TokenRef};
+use std::cell::RefCell;
+use std::ops::{Range, RangeBounds};
+use std::rc::Rc;
+
+/// What to do with a separator that follows the last item
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum TrailingSeparator {
+  /// Never consume a trailing separator (it is left for whatever comes next)
+  Forbid,
+  /// Consume a trailing separator if there is one
+  Allow,
+  /// Fail unless the last item is followed by a separator
+  Require,
+}
+
+// Items separated by a separator. Its range is a count of items, with an
+// exclusive end, unlike the range of a `Loop` (see `loop_iteration_counts`)
+#[derive(Debug)]
+pub struct SepByPattern {
+  item: MatcherRef,
+  separator: MatcherRef,
+  name: String,
+  range: Range<usize>,
+  trailing: TrailingSeparator,
+  custom_name: bool,
+}
+
+// The result of running a single item or separator
+struct Step {
+  children: Vec<TokenRef>,
+  end: usize,
+}
+
+impl SepByPattern {
+  pub fn new<T>(
+    range: T,
+    item: MatcherRef,
+    separator: MatcherRef,
+    trailing: TrailingSeparator,
+  ) -> MatcherRef
+  where
+    T: RangeBounds<usize>,
+  {
+    Rc::new(RefCell::new(Box::new(Self {
+      item,
+      separator,
+      name: "SepBy".to_string(),
+      range: get_range(range),
+      trailing,
+      custom_name: false,
+    })))
+  }
+
+  pub fn new_with_name<T>(
+    name: &str,
+    range: T,
+    item: MatcherRef,
+    separator: MatcherRef,
+    trailing: TrailingSeparator,
+  ) -> MatcherRef
+  where
+    T: RangeBounds<usize>,
+  {
+    Rc::new(RefCell::new(Box::new(Self {
+      item,
+      separator,
+      name: name.to_string(),
+      range: get_range(range),
+      trailing,
+      custom_name: true,
+    })))
+  }
+
+  fn step(
+    &self,
+    matcher: &MatcherRef,
+    context: &ParserContextRef,
+    scope: &ScopeContextRef,
+    offset: usize,
+  ) -> Result<Option<Step>, MatcherFailure> {
+    let sub_context = context.borrow().clone_with_name(self.get_name());
+    sub_context.borrow_mut().set_start(offset);
+
+    let savepoint = scope.borrow().savepoint();
+    let result = matcher
+      .borrow()
+      .exec(matcher.clone(), sub_context, scope.clone());
+
+    // Whatever a failed attempt stored goes with it
+    if result.is_err() {
+      scope.borrow_mut().rollback_to_savepoint(savepoint);
+    }
+
+    match result {
+      Ok(MatcherSuccess::Token(token)) => {
+        let end = token.borrow().get_matched_range().end;
+        let children = if token.borrow().should_discard() {
+          Vec::new()
+        } else {
+          vec![token]
+        };
+
+        Ok(Some(Step { children, end }))
+      }
+      Ok(MatcherSuccess::ProxyChildren(token)) => {
+        let _token = token.borrow();
+
+        Ok(Some(Step {
+          children: _token
+            .get_children()
+            .iter()
+            .filter(|child| !child.borrow().should_discard())
+            .cloned()
+            .collect(),
+          end: _token.get_matched_range().end,
+        }))
+      }
+      Ok(MatcherSuccess::Skip(amount)) => Ok(Some(Step {
+        children: Vec::new(),
+        end: (offset as isize + amount) as usize,
+      })),
+      Ok(_) => Ok(Some(Step {
+        children: Vec::new(),
+        end: offset,
+      })),
+      Err(MatcherFailure::Fail) => Ok(None),
+      Err(error) => Err(error),
+    }
+  }
+
+  fn _exec(
+    &self,
+    context: ParserContextRef,
+    scope: ScopeContextRef,
+  ) -> Result<MatcherSuccess, MatcherFailure> {
+    let start_offset = context.borrow().offset.start;
+    let max_items = self.range.end.saturating_sub(1);
+    let mut offset = start_offset;
+    let mut children = Vec::<TokenRef>::new();
+    let mut count = 0;
+    // A separator that followed the last item. It is only
+    // consumed once another item follows, or if trailing
+    // separators are permitted
+    let mut pending: Option<Step> = None;
+    // Where to roll back to, if the pending separator is left alone
+    let mut separator_savepoint = scope.borrow().savepoint();
+
+    while count < max_items {
+      let item_offset = pending.as_ref().map_or(offset, |separator| separator.end);
+      let item = match self.step(&self.item, &context, &scope, item_offset)? {
+        Some(item) => item,
+        None => break,
+      };
+
+      if let Some(separator) = pending.take() {
+        children.extend(separator.children);
+      }
+
+      children.extend(item.children);
+      offset = item.end;
+      count += 1;
+
+      separator_savepoint = scope.borrow().savepoint();
+      pending = self.step(&self.separator, &context, &scope, offset)?;
+
+      match &pending {
+        // Neither the item nor the separator consumed anything,
+        // so there is no way to make any further progress
+        Some(separator) if separator.end == item_offset => break,
+        Some(_) => continue,
+        None => break,
+      }
+    }
+
+    if count < self.range.start {
+      return Err(MatcherFailure::Fail);
+    }
+
+    match (pending, self.trailing) {
+      (Some(separator), TrailingSeparator::Allow | TrailingSeparator::Require) => {
+        children.extend(separator.children);
+        offset = separator.end;
+      }
+      (Some(_), TrailingSeparator::Forbid) => {
+        scope
+          .borrow_mut()
+          .rollback_to_savepoint(separator_savepoint);
+      }
+      (None, TrailingSeparator::Require) if count > 0 => return Err(MatcherFailure::Fail),
+      _ => {}
+    }
+
+    if children.is_empty() {
+      return Ok(MatcherSuccess::Skip((offset - start_offset) as isize));
+    }
+
+    let mut captured_range = SourceRange::new(usize::MAX, 0);
+    for child in &children {
+      let child_range = *child.borrow().get_captured_range();
+      captured_range.start = captured_range.start.min(child_range.start);
+      captured_range.end = captured_range.end.max(child_range.end);
+    }
+
+    let token = StandardToken::new_with_matched_range(
+      &context.borrow().parser,
+      self.name.to_string(),
+      captured_range,
+      SourceRange::new(start_offset, offset),
+    );
+
+    for child in &children {
+      child.borrow_mut().set_parent(Some(token.clone()));
+    }
+
+    token.borrow_mut().set_children(children);
+
+    Ok(MatcherSuccess::Token(token))
+  }
+}
+
+impl Matcher for SepByPattern {
+  fn exec(
+    &self,
+    this_matcher: MatcherRef,
+    context: ParserContextRef,
+    scope: ScopeContextRef,
+  ) -> Result<MatcherSuccess, MatcherFailure> {
+    self.before_exec(this_matcher.clone(), context.clone(), scope.clone());
+    let checkpoint = scope.borrow_mut().begin();
+    let result = self._exec(context.clone(), scope.clone());
+
+    // Items and separators are tried speculatively, so only a
+    // transaction lets the ones that are given up be rolled back
+    match result {
+      Ok(_) => scope.borrow_mut().commit(),
+      Err(_) => scope.borrow_mut().rollback(checkpoint),
+    }
+
+    self.after_exec(this_matcher.clone(), context.clone(), scope.clone());
+
+    result
+  }
+
+  fn has_custom_name(&self) -> bool {
+    self.custom_name
+  }
+
+  fn get_name(&self) -> &str {
+    self.name.as_str()
+  }
+
+  fn set_name(&mut self, name: &str) {
+    self.name = name.to_string();
+    self.custom_name = name != "SepBy";
+  }
+
+  fn set_child(&mut self, index: usize, matcher: MatcherRef) {
+    match index {
+      0 => self.item = matcher,
+      1 => self.separator = matcher,
+      _ => panic!("Attempt to set child at an index that is out of bounds"),
+    }
+  }
+
+  fn get_children(&self) -> Option<Vec<MatcherRef>> {
+    Some(vec![self.item.clone(), self.separator.clone()])
+  }
+
+  fn add_pattern(&mut self, _: MatcherRef) {
+    panic!("Can not add a pattern to a `SepBy` matcher");
+  }
+
+  fn to_string(&self) -> String {
+    format!("{:?}", self)
+  }
+
+  fn get_kind(&self) -> MatcherKind {
+    MatcherKind::SepBy(self.range.clone())
+  }
+}
+
+#[macro_export]
+macro_rules! SepBy {
+  (@trailing) => {
+    $crate::matchers::sep_by::TrailingSeparator::Forbid
+  };
+
+  (@trailing $trailing:expr) => {
+    $trailing
+  };
+
+  ($range:expr; $name:literal; $item:expr, $separator:expr $(, $trailing:expr)? $(,)?) => {
+    $crate::matchers::sep_by::SepByPattern::new_with_name(
+      $name,
+      $range,
+      $item,
+      $separator,
+      $crate::SepBy!(@trailing $($trailing)?),
+    )
+  };
+
+  ($name:literal; $item:expr, $separator:expr $(, $trailing:expr)? $(,)?) => {
+    $crate::matchers::sep_by::SepByPattern::new_with_name(
+      $name,
+      1..,
+      $item,
+      $separator,
+      $crate::SepBy!(@trailing $($trailing)?),
+    )
+  };
+
+  ($range:expr; $item:expr, $separator:expr $(, $trailing:expr)? $(,)?) => {
+    $crate::matchers::sep_by::SepByPattern::new(
+      $range,
+      $item,
+      $separator,
+      $crate::SepBy!(@trailing $($trailing)?),
+    )
+  };
+
+  ($item:expr, $separator:expr $(, $trailing:expr)? $(,)?) => {
+    $crate::matchers::sep_by::SepByPattern::new(
+      1..,
+      $item,
+      $separator,
+      $crate::SepBy!(@trailing $($trailing)?),
+    )
+  };
+}
+
+#[cfg(test)]
+mod tests {
+  use super::TrailingSeparator;
+  use crate::{
+    matcher::{MatcherFailure, MatcherSuccess},
+    parser::Parser,
+    parser_context::ParserContext,
+    scope::VariableType,
+    source_range::SourceRange,
+    Discard, Equals, Matches, Store,
+  };
+
+  fn values(token: &crate::token::TokenRef) -> Vec<String> {
+    token
+      .borrow()
+      .get_children()
+      .iter()
+      .map(|child| child.borrow().get_value().clone())
+      .collect()
+  }
+
+  #[test]
+  fn it_matches_a_separated_list() {
+    let parser = Parser::new("one,two,three;");
+    let parser_context = ParserContext::new(&parser, "Test");
+    let matcher = SepBy!("List"; Matches!(r"\w+"), Discard!(Equals!(",")));
+
+    if let Ok(token) = ParserContext::tokenize(parser_context, matcher) {
+      assert_eq!(token.borrow().get_name(), "List");
+      assert_eq!(
+        *token.borrow().get_captured_range(),
+        SourceRange::new(0, 13)
+      );
+      assert_eq!(*token.borrow().get_matched_range(), SourceRange::new(0, 13));
+      assert_eq!(values(&token), vec!["one", "two", "three"]);
+    } else {
+      unreachable!("Test failed!");
+    };
+  }
+
+  #[test]
+  fn it_can_keep_separators() {
+    let parser = Parser::new("one, two");
+    let parser_context = ParserContext::new(&parser, "Test");
+    let matcher = SepBy!(Matches!(r"\w+"), Matches!(r",\s*"));
+
+    if let Ok(token) = ParserContext::tokenize(parser_context, matcher) {
+      assert_eq!(token.borrow().get_name(), "SepBy");
+      assert_eq!(values(&token), vec!["one", ", ", "two"]);
+    } else {
+      unreachable!("Test failed!");
+    };
+  }
+
+  #[test]
+  fn it_leaves_a_trailing_separator_alone_by_default() {
+    let parser = Parser::new("one,two,");
+    let parser_context = ParserContext::new(&parser, "Test");
+    let matcher = SepBy!(Matches!(r"\w+"), Discard!(Equals!(",")));
+
+    if let Ok(token) = ParserContext::tokenize(parser_context, matcher) {
+      assert_eq!(*token.borrow().get_matched_range(), SourceRange::new(0, 7));
+      assert_eq!(values(&token), vec!["one", "two"]);
+    } else {
+      unreachable!("Test failed!");
+    };
+  }
+
+  #[test]
+  fn it_can_allow_or_require_a_trailing_separator() {
+    let parser = Parser::new("one,two,");
+    let parser_context = ParserContext::new(&parser, "Test");
+    let matcher = SepBy!(
+      Matches!(r"\w+"),
+      Discard!(Equals!(",")),
+      TrailingSeparator::Allow
+    );
+
+    if let Ok(token) = ParserContext::tokenize(parser_context, matcher) {
+      assert_eq!(*token.borrow().get_matched_range(), SourceRange::new(0, 8));
+      assert_eq!(values(&token), vec!["one", "two"]);
+    } else {
+      unreachable!("Test failed!");
+    };
+
+    let parser = Parser::new("one,two");
+    let parser_context = ParserContext::new(&parser, "Test");
+    let matcher = SepBy!(
+      Matches!(r"\w+"),
+      Discard!(Equals!(",")),
+      TrailingSeparator::Require
+    );
+
+    assert_eq!(
+      Err(MatcherFailure::Fail),
+      ParserContext::tokenize(parser_context, matcher)
+    );
+  }
+
+  #[test]
+  fn it_respects_the_item_count() {
+    let parser = Parser::new("1,2,3,4");
+    let parser_context = ParserContext::new(&parser, "Test");
+    let matcher = SepBy!(2..=3; Matches!(r"\d"), Discard!(Equals!(",")));
+
+    if let Ok(token) = ParserContext::tokenize(parser_context, matcher) {
+      assert_eq!(*token.borrow().get_matched_range(), SourceRange::new(0, 5));
+      assert_eq!(values(&token), vec!["1", "2", "3"]);
+    } else {
+      unreachable!("Test failed!");
+    };
+
+    let parser = Parser::new("1;2");
+    let parser_context = ParserContext::new(&parser, "Test");
+    let matcher = SepBy!(2..=3; Matches!(r"\d"), Discard!(Equals!(",")));
+
+    assert_eq!(
+      Err(MatcherFailure::Fail),
+      ParserContext::tokenize(parser_context, matcher)
+    );
+
+    let parser = Parser::new(";");
+    let parser_context = ParserContext::new(&parser, "Test");
+    let matcher = SepBy!(0..; Matches!(r"\d"), Discard!(Equals!(",")));
+
+    assert_eq!(
+      Ok(MatcherSuccess::Skip(0)),
+      matcher.borrow().exec(
+        matcher.clone(),
+        parser_context.clone(),
+        parser_context.borrow().scope.clone(),
+      )
+    );
+  }
+
+  #[test]
+  fn it_rolls_back_what_rejected_items_and_separators_stored() {
+    let parser = Parser::new("1;2,x");
+    let parser_context = ParserContext::new(&parser, "Test");
+    let matcher = SepBy!(
+      Store!("item"; Matches!(r"\d")),
+      Store!("separator"; Matches!(r"[;,]")),
+    );
+
+    if let Ok(token) = ParserContext::tokenize(parser_context.clone(), matcher) {
+      assert_eq!(*token.borrow().get_matched_range(), SourceRange::new(0, 3));
+
+      // The item after `,` failed, so `,` was left alone as well
+      let context = parser_context.borrow();
+      match (
+        context.get_scope_variable("item"),
+        context.get_scope_variable("separator"),
+      ) {
+        (Some(VariableType::Token(item)), Some(VariableType::Token(separator))) => {
+          assert_eq!(item.borrow().get_value(), "2");
+          assert_eq!(separator.borrow().get_value(), ";");
+        }
+        variables => unreachable!("{:?}", variables),
+      }
+    } else {
+      unreachable!("Test failed!");
+    };
+
+    let parser = Parser::new("1,2");
+    let parser_context = ParserContext::new(&parser, "Test");
+    let matcher = SepBy!(3..; Store!("item"; Matches!(r"\d")), Discard!(Equals!(",")));
+
+    assert_eq!(
+      Err(MatcherFailure::Fail),
+      ParserContext::tokenize(parser_context.clone(), matcher)
+    );
+    assert!(parser_context.borrow().get_scope_variable("item").is_none());
+  }
+}
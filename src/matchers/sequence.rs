@@ -1,14 +1,14 @@
 use std::cell::RefCell;
 use std::rc::Rc;
 
-use crate::matcher::{Matcher, MatcherFailure, MatcherRef, MatcherSuccess};
+use crate::matcher::{Matcher, MatcherFailure, MatcherKind, MatcherRef, MatcherSuccess};
 use crate::parse_error::ParseError;
 use crate::parser_context::ParserContextRef;
 use crate::scope_context::ScopeContextRef;
 use crate::source_range::SourceRange;
 use crate::token::StandardToken;
 
-use super::fetch::{Fetchable, FetchableType};
+use super::fetch::Fetchable;
 
 pub struct SequencePattern<T>
 where
@@ -74,18 +74,16 @@ where
     let _sc = sub_context.borrow();
 
     let start = _sc.offset.start;
-    let end = _sc.offset.end;
     let scan_start;
-    let source = &_sc.parser.borrow().source[..];
-    // let source = source_copy.as_str();
 
     let debug_mode = _sc.debug_mode_level();
 
-    let start_fetchable = self.start.fetch_value(sub_context.clone(), scope.clone());
-    let start_pattern = match start_fetchable {
-      FetchableType::String(ref value) => value,
-      FetchableType::Matcher(_) => return Err(MatcherFailure::Error(ParseError::new(
-        &format!("`Sequence` ({}) matcher received another matcher as a `start_pattern`... this makes no sense... aborting...", self.get_name())
+    let start_fetchable = self.start.fetch_value(sub_context.clone(), scope.clone())?;
+    let start_type = start_fetchable.type_name();
+    let start_pattern = &match start_fetchable.into_string() {
+      Some(value) => value,
+      None => return Err(MatcherFailure::Error(ParseError::new(
+        &format!("`Sequence` ({}) matcher received a {} as a `start_pattern`... this makes no sense... aborting...", self.get_name(), start_type)
       ))),
     };
 
@@ -93,22 +91,24 @@ where
       panic!("Sequence `start` pattern of \"\" makes no sense");
     }
 
-    let end_fetchable = self.end.fetch_value(sub_context.clone(), scope.clone());
-    let end_pattern = match end_fetchable {
-      FetchableType::String(ref value) => value,
-      FetchableType::Matcher(_) => return Err(MatcherFailure::Error(ParseError::new(
-        &format!("`Sequence` ({}) matcher received another matcher as a `end_pattern`... this makes no sense... aborting...", self.get_name())
+    let end_fetchable = self.end.fetch_value(sub_context.clone(), scope.clone())?;
+    let end_type = end_fetchable.type_name();
+    let end_pattern = &match end_fetchable.into_string() {
+      Some(value) => value,
+      None => return Err(MatcherFailure::Error(ParseError::new(
+        &format!("`Sequence` ({}) matcher received a {} as a `end_pattern`... this makes no sense... aborting...", self.get_name(), end_type)
       ))),
     };
     if end_pattern.len() == 0 {
       panic!("Sequence `end` pattern of \"\" makes no sense");
     }
 
-    let escape_fetchable = self.escape.fetch_value(sub_context.clone(), scope.clone());
-    let escape_pattern = match escape_fetchable {
-      FetchableType::String(ref value) => value,
-      FetchableType::Matcher(_) => return Err(MatcherFailure::Error(ParseError::new(
-        &format!("`Sequence` ({}) matcher received another matcher as a `end_pattern`... this makes no sense... aborting...", self.get_name())
+    let escape_fetchable = self.escape.fetch_value(sub_context.clone(), scope.clone())?;
+    let escape_type = escape_fetchable.type_name();
+    let escape_pattern = &match escape_fetchable.into_string() {
+      Some(value) => value,
+      None => return Err(MatcherFailure::Error(ParseError::new(
+        &format!("`Sequence` ({}) matcher received a {} as a `escape_pattern`... this makes no sense... aborting...", self.get_name(), escape_type)
       ))),
     };
 
@@ -142,10 +142,10 @@ where
 
     let mut index = scan_start;
     let mut previous_index = scan_start;
-    let mut parts: Vec<&str> = Vec::new();
+    let mut parts: Vec<String> = Vec::new();
 
     loop {
-      if index >= end {
+      if _sc.is_at_end(index) {
         if debug_mode > 0 {
           println!(
             "`{}` Failed to match against `{}...{} (escape {})` -->|{}|--> @[{}-{}]",
@@ -170,7 +170,7 @@ where
       let result = _sc.matches_str_at_offset(end_pattern, index);
       if let Some(source_range) = result {
         if previous_index < index {
-          parts.push(&source[previous_index..index]);
+          parts.push(_sc.get_input(previous_index, index).to_string());
         }
 
         index = source_range.end;
@@ -212,7 +212,7 @@ where
 
         if let Some(source_range) = result {
           if previous_index < index {
-            parts.push(&source[previous_index..index]);
+            parts.push(_sc.get_input(previous_index, index).to_string());
           }
 
           index = source_range.end + 1;
@@ -271,6 +271,14 @@ where
   fn to_string(&self) -> String {
     format!("{:?}", self)
   }
+
+  fn get_kind(&self) -> MatcherKind {
+    MatcherKind::Sequence(
+      self.start.as_literal(),
+      self.end.as_literal(),
+      self.escape.as_literal(),
+    )
+  }
 }
 
 #[macro_export]
@@ -1,6 +1,6 @@
-use crate::matcher::{Matcher, MatcherFailure, MatcherRef, MatcherSuccess};
+use crate::matcher::{Matcher, MatcherFailure, MatcherKind, MatcherRef, MatcherSuccess};
 use crate::parser_context::ParserContextRef;
-use crate::scope::Scope;
+use crate::scope::{Scope, VariableType};
 use crate::scope_context::ScopeContextRef;
 use std::cell::RefCell;
 use std::rc::Rc;
@@ -8,6 +8,8 @@ use std::rc::Rc;
 #[derive(Debug)]
 pub struct SetScopePattern {
   scope: Option<ScopeContextRef>,
+  bindings: Vec<(String, MatcherRef)>,
+  fresh_frame: bool,
   matcher: MatcherRef,
 }
 
@@ -15,6 +17,8 @@ impl SetScopePattern {
   pub fn new(matcher: MatcherRef) -> MatcherRef {
     Rc::new(RefCell::new(Box::new(Self {
       scope: None,
+      bindings: Vec::new(),
+      fresh_frame: false,
       matcher,
     })))
   }
@@ -22,15 +26,55 @@ impl SetScopePattern {
   pub fn new_with_scope(scope: ScopeContextRef, matcher: MatcherRef) -> MatcherRef {
     Rc::new(RefCell::new(Box::new(Self {
       scope: Some(scope.clone()),
+      bindings: Vec::new(),
+      fresh_frame: false,
       matcher,
     })))
   }
 
+  /// Run `matcher` in a fresh scope, stacked on top of the current scope, with
+  /// each of `bindings` set as a matcher. Bound matchers still resolve their
+  /// own references from the scope they were bound in.
+  pub fn new_with_bindings(bindings: Vec<(String, MatcherRef)>, matcher: MatcherRef) -> MatcherRef {
+    Rc::new(RefCell::new(Box::new(Self {
+      scope: None,
+      bindings,
+      fresh_frame: true,
+      matcher,
+    })))
+  }
+
+  fn bind(&self, scope: ScopeContextRef) -> ScopeContextRef {
+    let frame = Scope::new();
+
+    for (name, matcher) in &self.bindings {
+      frame.borrow_mut().set(
+        name,
+        VariableType::Matcher(Self::new_with_scope(scope.clone(), matcher.clone())),
+      );
+    }
+
+    let mut bound_scope = scope.borrow().clone();
+    bound_scope.push(frame);
+
+    Rc::new(RefCell::new(bound_scope))
+  }
+
   fn _exec(
     &self,
     context: ParserContextRef,
-    _: ScopeContextRef,
+    scope: ScopeContextRef,
   ) -> Result<MatcherSuccess, MatcherFailure> {
+    if self.fresh_frame {
+      let scope = self.scope.clone().unwrap_or(scope);
+
+      return self.matcher.borrow().exec(
+        self.matcher.clone(),
+        context.borrow().clone_with_name(self.get_name()),
+        self.bind(scope),
+      );
+    }
+
     let scope = match &self.scope {
       Some(scope) => scope.clone(),
       None => {
@@ -99,6 +143,14 @@ impl Matcher for SetScopePattern {
   fn to_string(&self) -> String {
     format!("{:?}", self)
   }
+
+  fn get_kind(&self) -> MatcherKind {
+    MatcherKind::Wrapper
+  }
+
+  fn get_scope(&self) -> Option<ScopeContextRef> {
+    self.scope.clone()
+  }
 }
 
 #[macro_export]
@@ -115,8 +167,9 @@ macro_rules! SetScope {
 #[cfg(test)]
 mod tests {
   use crate::{
-    parser::Parser, parser_context::ParserContext, scope::VariableType,
-    scope_context::ScopeContext, source_range::SourceRange, Equals, Store,
+    matchers::set_scope::SetScopePattern, parser::Parser, parser_context::ParserContext,
+    scope::VariableType, scope_context::ScopeContext, source_range::SourceRange, Equals, Program,
+    Ref, Store,
   };
 
   #[test]
@@ -159,4 +212,45 @@ mod tests {
       unreachable!("Test failed!");
     };
   }
+
+  #[test]
+  fn it_binds_matchers_in_a_fresh_scope() {
+    let parser = Parser::new("Testing 1234");
+    let parser_context = ParserContext::new(&parser, "Test");
+    let scope = ScopeContext::new();
+
+    scope.borrow_mut().set(
+      "Word",
+      VariableType::Matcher(Program!(Ref!("item"), Ref!("separator"))),
+    );
+    scope
+      .borrow_mut()
+      .set("Space", VariableType::Matcher(Equals!(" ")));
+
+    let matcher = SetScope!(
+      scope.clone(),
+      SetScopePattern::new_with_bindings(
+        vec![
+          ("item".to_string(), Equals!("Testing")),
+          // Resolved from the outer scope, not the bound one
+          ("separator".to_string(), Ref!("Space")),
+        ],
+        Ref!("Word"),
+      )
+    );
+
+    if let Ok(token) = ParserContext::tokenize(parser_context, matcher) {
+      let token = token.borrow();
+      assert_eq!(token.get_name(), "Program");
+      assert_eq!(*token.get_matched_range(), SourceRange::new(0, 8));
+      assert_eq!(token.get_children().len(), 2);
+      assert_eq!(token.get_children()[0].borrow().get_value(), "Testing");
+      assert_eq!(token.get_children()[1].borrow().get_value(), " ");
+
+      // Bindings never leak into the scope they were stacked on
+      assert!(scope.borrow().get("item").is_none());
+    } else {
+      unreachable!("Test failed!");
+    };
+  }
 }
@@ -1,7 +1,7 @@
 use std::cell::RefCell;
 use std::rc::Rc;
 
-use crate::matcher::{Matcher, MatcherFailure, MatcherRef, MatcherSuccess};
+use crate::matcher::{Matcher, MatcherFailure, MatcherKind, MatcherRef, MatcherSuccess};
 use crate::parser_context::ParserContextRef;
 use crate::scope::VariableType;
 use crate::scope_context::ScopeContextRef;
@@ -126,6 +126,13 @@ impl Matcher for StorePattern {
   fn to_string(&self) -> String {
     format!("{:?}", self)
   }
+
+  fn get_kind(&self) -> MatcherKind {
+    match &self.pattern {
+      StorePatternType::Matcher(_) => MatcherKind::Wrapper,
+      StorePatternType::String(_) => MatcherKind::Other,
+    }
+  }
 }
 
 #[macro_export]
@@ -143,7 +150,7 @@ macro_rules! Store {
 mod tests {
   use crate::{
     parser::Parser, parser_context::ParserContext, scope::VariableType, source_range::SourceRange,
-    Equals, Switch,
+    Equals, Matches, Optional, Program, Switch,
   };
 
   #[test]
@@ -200,4 +207,47 @@ mod tests {
       unreachable!("Test failed!");
     };
   }
+
+  #[test]
+  fn it_rolls_back_stores_from_failed_switch_alternatives() {
+    let parser = Parser::new("Testing 1234");
+    let parser_context = ParserContext::new(&parser, "Test");
+    let matcher = Switch!(
+      Program!(Store!("kind"; "number"), Matches!(r"\d+")),
+      Program!(Store!("word"; Matches!(r"\w+")), Equals!("!")),
+      Program!(Store!("kind"; "word"), Matches!(r"\w+")),
+    );
+
+    if let Ok(token) = ParserContext::tokenize(parser_context.clone(), matcher) {
+      assert_eq!(token.borrow().get_value(), "Testing");
+
+      let variable = parser_context.borrow().get_scope_variable("kind");
+      if let Some(VariableType::String(value)) = variable {
+        assert_eq!(value, "word");
+      } else {
+        unreachable!("Test failed!");
+      }
+
+      assert!(parser_context.borrow().get_scope_variable("word").is_none());
+    } else {
+      unreachable!("Test failed!");
+    };
+  }
+
+  #[test]
+  fn it_rolls_back_stores_from_failed_optionals() {
+    let parser = Parser::new("Testing 1234");
+    let parser_context = ParserContext::new(&parser, "Test");
+    let matcher = Program!(
+      Optional!(Program!(Store!("sign"; "-"), Equals!("-"))),
+      Matches!(r"\w+"),
+    );
+
+    if let Ok(token) = ParserContext::tokenize(parser_context.clone(), matcher) {
+      assert_eq!(token.borrow().get_value(), "Testing");
+      assert!(parser_context.borrow().get_scope_variable("sign").is_none());
+    } else {
+      unreachable!("Test failed!");
+    };
+  }
 }
@@ -0,0 +1,186 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use super::fetch::{fetch_count, Fetchable};
+use crate::matcher::{Matcher, MatcherFailure, MatcherRef, MatcherSuccess};
+use crate::parser_context::ParserContextRef;
+use crate::scope_context::ScopeContextRef;
+use crate::source_range::SourceRange;
+use crate::token::StandardToken;
+
+// Consumes a fixed number of bytes, i.e. the body of a
+// length-prefixed field. Fails if there aren't enough bytes
+// left, or if the count would end in the middle of a character
+#[derive(Debug)]
+pub struct TakePattern<T>
+where
+  T: Fetchable,
+  T: std::fmt::Debug,
+{
+  count: T,
+  name: String,
+  custom_name: bool,
+}
+
+impl<T> TakePattern<T>
+where
+  T: Fetchable,
+  T: 'static,
+  T: std::fmt::Debug,
+{
+  pub fn new(count: T) -> MatcherRef {
+    Rc::new(RefCell::new(Box::new(Self {
+      count,
+      name: "Take".to_string(),
+      custom_name: false,
+    })))
+  }
+
+  pub fn new_with_name(name: &str, count: T) -> MatcherRef {
+    Rc::new(RefCell::new(Box::new(Self {
+      count,
+      name: name.to_string(),
+      custom_name: true,
+    })))
+  }
+
+  fn _exec(
+    &self,
+    context: ParserContextRef,
+    scope: ScopeContextRef,
+  ) -> Result<MatcherSuccess, MatcherFailure> {
+    let sub_context = context.borrow().clone_with_name(self.get_name());
+    let count = fetch_count(&self.count, self.get_name(), sub_context.clone(), scope)?;
+
+    if count == 0 {
+      return Ok(MatcherSuccess::Skip(0));
+    }
+
+    let sub_context = sub_context.borrow();
+    let start = sub_context.offset.start;
+    let end = start + count;
+
+    // Cut short at the end of the input, or of the character `end` is in
+    if sub_context.get_input(start, end).len() != count {
+      return Err(MatcherFailure::Fail);
+    }
+
+    Ok(MatcherSuccess::Token(StandardToken::new(
+      &sub_context.parser,
+      self.name.to_string(),
+      SourceRange::new(start, end),
+    )))
+  }
+}
+
+impl<T> Matcher for TakePattern<T>
+where
+  T: Fetchable,
+  T: 'static,
+  T: std::fmt::Debug,
+{
+  fn exec(
+    &self,
+    this_matcher: MatcherRef,
+    context: ParserContextRef,
+    scope: ScopeContextRef,
+  ) -> Result<MatcherSuccess, MatcherFailure> {
+    self.before_exec(this_matcher.clone(), context.clone(), scope.clone());
+    let result = self._exec(context.clone(), scope.clone());
+    self.after_exec(this_matcher.clone(), context.clone(), scope.clone());
+
+    result
+  }
+
+  fn has_custom_name(&self) -> bool {
+    self.custom_name
+  }
+
+  fn get_name(&self) -> &str {
+    self.name.as_str()
+  }
+
+  fn set_name(&mut self, name: &str) {
+    self.name = name.to_string();
+    self.custom_name = name != "Take";
+  }
+
+  fn get_children(&self) -> Option<Vec<MatcherRef>> {
+    None
+  }
+
+  fn add_pattern(&mut self, _: MatcherRef) {
+    panic!("Can not add a pattern to a `Take` matcher");
+  }
+
+  fn to_string(&self) -> String {
+    format!("{:?}", self)
+  }
+}
+
+#[macro_export]
+macro_rules! Take {
+  ($name:literal; $count:expr) => {
+    $crate::matchers::take::TakePattern::new_with_name($name, $count)
+  };
+
+  ($count:expr) => {
+    $crate::matchers::take::TakePattern::new($count)
+  };
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::{
+    matcher::MatcherFailure, parser::Parser, parser_context::ParserContext,
+    source_range::SourceRange, Discard, Equals, Fetch, Matches, Program, Store,
+  };
+
+  #[test]
+  fn it_takes_a_fetched_number_of_bytes() {
+    let parser = Parser::new("3:abc,rest");
+    let parser_context = ParserContext::new(&parser, "Test");
+    let matcher = Program!(
+      Discard!(Store!("Len"; Matches!(r"\d+"))),
+      Discard!(Equals!(":")),
+      Take!("Body"; Fetch!("Len.value")),
+      Discard!(Equals!(",")),
+    );
+
+    if let Ok(token) = ParserContext::tokenize(parser_context, matcher) {
+      let token = token.borrow();
+      let body = token.get_children()[0].borrow();
+      assert_eq!(body.get_name(), "Body");
+      assert_eq!(body.get_value(), "abc");
+      assert_eq!(*token.get_matched_range(), SourceRange::new(0, 6));
+    } else {
+      unreachable!("Test failed!");
+    };
+  }
+
+  #[test]
+  fn it_fails_if_there_is_not_enough_input() {
+    let parser = Parser::new("abc");
+    let parser_context = ParserContext::new(&parser, "Test");
+
+    assert_eq!(
+      ParserContext::tokenize(parser_context, Take!("4")),
+      Err(MatcherFailure::Fail)
+    );
+  }
+
+  #[test]
+  fn it_reports_a_bad_count() {
+    let parser = Parser::new("abc");
+    let parser_context = ParserContext::new(&parser, "Test");
+
+    if let Err(MatcherFailure::Error(error)) = ParserContext::tokenize(parser_context, Take!("-1"))
+    {
+      assert!(error
+        .message
+        .contains("`Take` matcher could not use `-1` as a count"));
+    } else {
+      unreachable!("Test failed!");
+    };
+  }
+}
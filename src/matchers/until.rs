@@ -0,0 +1,381 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::matcher::{Matcher, MatcherFailure, MatcherRef, MatcherSuccess};
+use crate::parser_context::ParserContextRef;
+use crate::scope_context::ScopeContextRef;
+use crate::source_range::SourceRange;
+use crate::token::StandardToken;
+
+// How much input the search for a literal starts with
+const CHUNK_SIZE: usize = 4096;
+
+#[derive(Debug)]
+pub enum Terminator {
+  /// Found with `memchr`/`memmem`
+  Literal(String),
+  /// Tried at each position, for anything a literal can't express
+  Matcher(MatcherRef),
+}
+
+impl From<&str> for Terminator {
+  fn from(literal: &str) -> Self {
+    Terminator::Literal(literal.to_string())
+  }
+}
+
+impl From<String> for Terminator {
+  fn from(literal: String) -> Self {
+    Terminator::Literal(literal)
+  }
+}
+
+impl From<MatcherRef> for Terminator {
+  fn from(matcher: MatcherRef) -> Self {
+    Terminator::Matcher(matcher)
+  }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub struct UntilOptions {
+  /// Consume the terminator as well. It is never part of the captured value
+  pub include_terminator: bool,
+  /// Consume the rest of the input if there is no terminator, instead of failing
+  pub succeed_at_eof: bool,
+}
+
+#[derive(Debug)]
+pub struct UntilPattern {
+  terminator: Terminator,
+  options: UntilOptions,
+  name: String,
+  custom_name: bool,
+}
+
+impl UntilPattern {
+  pub fn new<T>(terminator: T, options: UntilOptions) -> MatcherRef
+  where
+    T: Into<Terminator>,
+  {
+    Rc::new(RefCell::new(Box::new(Self {
+      terminator: terminator.into(),
+      options,
+      name: "Until".to_string(),
+      custom_name: false,
+    })))
+  }
+
+  pub fn new_with_name<T>(name: &str, terminator: T, options: UntilOptions) -> MatcherRef
+  where
+    T: Into<Terminator>,
+  {
+    Rc::new(RefCell::new(Box::new(Self {
+      terminator: terminator.into(),
+      options,
+      name: name.to_string(),
+      custom_name: true,
+    })))
+  }
+
+  // Where the terminator starts, and where it ends
+  fn find_terminator(
+    &self,
+    context: &ParserContextRef,
+    scope: &ScopeContextRef,
+  ) -> Result<Option<(usize, usize)>, MatcherFailure> {
+    let start = context.borrow().offset.start;
+
+    match &self.terminator {
+      Terminator::Literal(literal) => {
+        // Search a chunk of input at a time (twice as much each time),
+        // so that no more of it is loaded than is needed
+        let mut searched = start;
+        let mut window = CHUNK_SIZE;
+
+        loop {
+          let _context = context.borrow();
+          let input_end = {
+            let haystack = _context.get_input(start, start.saturating_add(window));
+            let found = match literal.as_bytes() {
+              [] => Some(0),
+              [byte] => memchr::memchr(*byte, &haystack.as_bytes()[searched - start..]),
+              needle => memchr::memmem::find(&haystack.as_bytes()[searched - start..], needle),
+            };
+
+            if let Some(offset) = found {
+              let offset = searched + offset;
+              return Ok(Some((offset, offset + literal.len())));
+            }
+
+            start + haystack.len()
+          };
+
+          if _context.is_at_end(input_end) {
+            return Ok(None);
+          }
+
+          // The terminator could start just before the end of the chunk
+          searched = input_end
+            .saturating_sub(literal.len().saturating_sub(1))
+            .max(searched);
+          window = window.saturating_mul(2);
+        }
+      }
+      Terminator::Matcher(matcher) => {
+        let mut offset = start;
+
+        loop {
+          let sub_context = context.borrow().clone_with_name(self.get_name());
+          sub_context.borrow_mut().set_start(offset);
+
+          let savepoint = scope.borrow().savepoint();
+          let result = matcher
+            .borrow()
+            .exec(matcher.clone(), sub_context, scope.clone());
+
+          match result {
+            Ok(MatcherSuccess::Token(token)) | Ok(MatcherSuccess::ProxyChildren(token)) => {
+              return Ok(Some((offset, token.borrow().get_matched_range().end)));
+            }
+            Ok(MatcherSuccess::Skip(amount)) => {
+              return Ok(Some((offset, (offset as isize + amount) as usize)));
+            }
+            Ok(_) => return Ok(Some((offset, offset))),
+            // Try the next offset with a clean slate
+            Err(MatcherFailure::Fail) => scope.borrow_mut().rollback_to_savepoint(savepoint),
+            Err(error) => return Err(error),
+          }
+
+          if context.borrow().is_at_end(offset) {
+            return Ok(None);
+          }
+
+          // Only try at character boundaries
+          offset += context
+            .borrow()
+            .get_input(offset, offset + 4)
+            .chars()
+            .next()
+            .map_or(1, char::len_utf8);
+        }
+      }
+    }
+  }
+
+  fn _exec(
+    &self,
+    context: ParserContextRef,
+    scope: ScopeContextRef,
+  ) -> Result<MatcherSuccess, MatcherFailure> {
+    let sub_context = context.borrow().clone_with_name(self.get_name());
+    let (start, end) = {
+      let _sc = sub_context.borrow();
+      (_sc.offset.start, _sc.offset.end)
+    };
+
+    let (body_end, matched_end) = match self.find_terminator(&sub_context, &scope)? {
+      Some((terminator_start, terminator_end)) if self.options.include_terminator => {
+        (terminator_start, terminator_end)
+      }
+      Some((terminator_start, _)) => (terminator_start, terminator_start),
+      None if self.options.succeed_at_eof => {
+        // The search loaded everything up to the end of the input
+        let end = sub_context
+          .borrow()
+          .parser
+          .borrow_mut()
+          .load_to(end)
+          .min(end);
+        (end, end)
+      }
+      None => return Err(MatcherFailure::Fail),
+    };
+
+    // Nothing to capture, so just skip over whatever was matched
+    if body_end == start {
+      return Ok(MatcherSuccess::Skip((matched_end - start) as isize));
+    }
+
+    let parser = sub_context.borrow().parser.clone();
+
+    Ok(MatcherSuccess::Token(
+      StandardToken::new_with_matched_range(
+        &parser,
+        self.name.to_string(),
+        SourceRange::new(start, body_end),
+        SourceRange::new(start, matched_end),
+      ),
+    ))
+  }
+}
+
+impl Matcher for UntilPattern {
+  fn exec(
+    &self,
+    this_matcher: MatcherRef,
+    context: ParserContextRef,
+    scope: ScopeContextRef,
+  ) -> Result<MatcherSuccess, MatcherFailure> {
+    self.before_exec(this_matcher.clone(), context.clone(), scope.clone());
+    let checkpoint = scope.borrow_mut().begin();
+    let result = self._exec(context.clone(), scope.clone());
+
+    // Without a transaction, the writes of a terminator
+    // that failed to match aren't journaled, and stick
+    match result {
+      Ok(_) => scope.borrow_mut().commit(),
+      Err(_) => scope.borrow_mut().rollback(checkpoint),
+    }
+
+    self.after_exec(this_matcher.clone(), context.clone(), scope.clone());
+
+    result
+  }
+
+  fn has_custom_name(&self) -> bool {
+    self.custom_name
+  }
+
+  fn get_name(&self) -> &str {
+    self.name.as_str()
+  }
+
+  fn set_name(&mut self, name: &str) {
+    self.name = name.to_string();
+    self.custom_name = name != "Until";
+  }
+
+  fn set_child(&mut self, index: usize, matcher: MatcherRef) {
+    match (&mut self.terminator, index) {
+      (Terminator::Matcher(terminator), 0) => *terminator = matcher,
+      _ => panic!("Attempt to set child at an index that is out of bounds"),
+    }
+  }
+
+  fn get_children(&self) -> Option<Vec<MatcherRef>> {
+    match &self.terminator {
+      Terminator::Literal(_) => None,
+      Terminator::Matcher(matcher) => Some(vec![matcher.clone()]),
+    }
+  }
+
+  fn add_pattern(&mut self, _: MatcherRef) {
+    panic!("Can not add a pattern to a `Until` matcher");
+  }
+
+  fn to_string(&self) -> String {
+    format!("{:?}", self)
+  }
+}
+
+#[macro_export]
+macro_rules! Until {
+  ($name:literal; $terminator:expr, $options:expr) => {
+    $crate::matchers::until::UntilPattern::new_with_name($name, $terminator, $options)
+  };
+
+  ($name:literal; $terminator:expr) => {
+    $crate::matchers::until::UntilPattern::new_with_name(
+      $name,
+      $terminator,
+      $crate::matchers::until::UntilOptions::default(),
+    )
+  };
+
+  ($terminator:expr, $options:expr) => {
+    $crate::matchers::until::UntilPattern::new($terminator, $options)
+  };
+
+  ($terminator:expr) => {
+    $crate::matchers::until::UntilPattern::new(
+      $terminator,
+      $crate::matchers::until::UntilOptions::default(),
+    )
+  };
+}
+
+#[cfg(test)]
+mod tests {
+  use super::UntilOptions;
+  use crate::{
+    matcher::MatcherFailure, parser::Parser, parser_context::ParserContext,
+    source_range::SourceRange, Equals, Matches, Program,
+  };
+
+  #[test]
+  fn it_scans_until_a_literal() {
+    let parser = Parser::new("<!-- a comment -->rest");
+    let parser_context = ParserContext::new(&parser, "Test");
+    let matcher = Program!(Equals!("<!--"), Until!("Body"; "-->"), Equals!("-->"),);
+
+    if let Ok(token) = ParserContext::tokenize(parser_context, matcher) {
+      let token = token.borrow();
+      let body = token.get_children()[1].borrow();
+      assert_eq!(body.get_name(), "Body");
+      assert_eq!(body.get_value(), " a comment ");
+      assert_eq!(*token.get_matched_range(), SourceRange::new(0, 18));
+    } else {
+      unreachable!("Test failed!");
+    };
+  }
+
+  #[test]
+  fn it_can_include_the_terminator() {
+    let parser = Parser::new("a comment\nnext");
+    let parser_context = ParserContext::new(&parser, "Test");
+    let matcher = Until!(
+      "\n",
+      UntilOptions {
+        include_terminator: true,
+        ..UntilOptions::default()
+      }
+    );
+
+    if let Ok(token) = ParserContext::tokenize(parser_context, matcher) {
+      let token = token.borrow();
+      assert_eq!(*token.get_captured_range(), SourceRange::new(0, 9));
+      assert_eq!(*token.get_matched_range(), SourceRange::new(0, 10));
+    } else {
+      unreachable!("Test failed!");
+    };
+  }
+
+  #[test]
+  fn it_scans_until_a_matcher() {
+    let parser = Parser::new("raw text 123 more");
+    let parser_context = ParserContext::new(&parser, "Test");
+    let matcher = Until!(Matches!(r"\d+"));
+
+    if let Ok(token) = ParserContext::tokenize(parser_context, matcher) {
+      assert_eq!(token.borrow().get_value(), "raw text ");
+    } else {
+      unreachable!("Test failed!");
+    };
+  }
+
+  #[test]
+  fn it_can_succeed_at_eof() {
+    let parser = Parser::new("no terminator");
+    let parser_context = ParserContext::new(&parser, "Test");
+
+    assert_eq!(
+      ParserContext::tokenize(parser_context, Until!("-->")),
+      Err(MatcherFailure::Fail)
+    );
+
+    let parser_context = ParserContext::new(&parser, "Test");
+    let matcher = Until!(
+      "-->",
+      UntilOptions {
+        succeed_at_eof: true,
+        ..UntilOptions::default()
+      }
+    );
+
+    if let Ok(token) = ParserContext::tokenize(parser_context, matcher) {
+      assert_eq!(token.borrow().get_value(), "no terminator");
+    } else {
+      unreachable!("Test failed!");
+    };
+  }
+}
@@ -1,41 +1,105 @@
 use std::{fs, io::Error};
 
+use crate::{parse_error::ParseError, source::Source};
+
 pub type ParserRef = std::rc::Rc<std::cell::RefCell<Parser>>;
 
 #[allow(unused)]
-#[derive(Debug)]
 pub struct Parser {
-  pub(crate) source: String,
+  pub(crate) source: Box<dyn Source>,
   pub(crate) filename: String,
+  // The first error loading more input, after which
+  // the input ends where it was loaded up to
+  pub(crate) error: Option<ParseError>,
 }
 
 impl Parser {
   pub fn new(source: &str) -> ParserRef {
-    std::rc::Rc::new(std::cell::RefCell::new(Self {
-      source: source.to_string(),
-      filename: String::from(""),
-    }))
+    Self::new_from_source(source.to_string(), "")
   }
 
   pub fn new_with_file_name(source: &str, filename: &str) -> ParserRef {
-    std::rc::Rc::new(std::cell::RefCell::new(Self {
-      source: source.to_string(),
-      filename: filename.to_string(),
-    }))
+    Self::new_from_source(source.to_string(), filename)
   }
 
   pub fn new_from_file(filename: &str) -> Result<ParserRef, Error> {
     let contents = fs::read_to_string(filename)?;
 
-    Ok(std::rc::Rc::new(std::cell::RefCell::new(Self {
-      source: contents,
+    Ok(Self::new_from_source(contents, filename))
+  }
+
+  /// A parser that loads its input from `source` as matchers need it
+  pub fn new_from_source(source: impl Source + 'static, filename: &str) -> ParserRef {
+    std::rc::Rc::new(std::cell::RefCell::new(Self {
+      source: Box::new(source),
       filename: String::from(filename),
-    })))
+      error: None,
+    }))
   }
 
+  /// The input loaded so far (all of it, unless it comes from a stream)
   pub fn get_source(&self) -> &String {
-    &self.source
+    self.source.buffer()
+  }
+
+  /// Where the input ends, or `usize::MAX` while that isn't known yet
+  pub fn get_end(&self) -> usize {
+    if self.source.is_complete() {
+      self.source.end()
+    } else {
+      usize::MAX
+    }
+  }
+
+  /// Load input up to `offset`, if there is that much of it, and return
+  /// where the loaded input ends. If the source fails to load (i.e. its
+  /// buffer is full), the input ends early, and `take_error` returns why
+  pub fn load_to(&mut self, offset: usize) -> usize {
+    if self.error.is_none() && offset > self.source.end() {
+      if let Err(error) = self.source.load_to(offset) {
+        self.error = Some(error);
+      }
+    }
+
+    self.source.end()
+  }
+
+  /// The input between `start` and `end`, cut down to what is in memory,
+  /// with `end` moved back to the start of the character it is in
+  pub fn slice(&self, start: usize, end: usize) -> &str {
+    let start = start.clamp(self.source.start(), self.source.end());
+    let end = end.min(self.source.end()).max(start);
+    let window = self.source.slice(start, self.source.end());
+    let mut len = end - start;
+
+    while !window.is_char_boundary(len) {
+      len -= 1;
+    }
+
+    &window[..len]
+  }
+
+  /// Drop the input before `offset` from memory
+  pub fn release(&mut self, offset: usize) {
+    self.source.release(offset);
+  }
+
+  pub fn get_lookahead(&self) -> usize {
+    self.source.get_lookahead()
+  }
+
+  pub fn take_error(&mut self) -> Option<ParseError> {
+    self.error.take()
   }
 
   pub fn tokenize() {}
 }
+
+impl std::fmt::Debug for Parser {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.debug_struct("Parser")
+      .field("source", &self.get_source())
+      .field("filename", &self.filename)
+      .finish()
+  }
+}
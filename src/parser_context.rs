@@ -1,16 +1,28 @@
 use super::source_range::SourceRange;
 use crate::{
   matcher::{MatcherFailure, MatcherRef, MatcherSuccess},
+  parse_error::ParseError,
   parser::ParserRef,
   scope::VariableType,
   scope_context::{ScopeContext, ScopeContextRef},
   token::{TokenRef, IS_ERROR},
+  token_iterator::TokenIterator,
 };
 use regex::Regex;
-use std::{cell::RefCell, collections::HashMap, rc::Rc};
+use std::{
+  any::Any,
+  cell::{Ref, RefCell},
+  collections::HashMap,
+  rc::Rc,
+};
 
 pub type ParserContextRef = Rc<RefCell<ParserContext>>;
 
+/// Application state for a single parse (i.e. a symbol table), shared
+/// by every context cloned from the one it was set on, so that `Map`
+/// and `Catch` closures can get to it through their parser context
+pub type UserDataRef = Rc<RefCell<Box<dyn Any>>>;
+
 lazy_static::lazy_static! {
   static ref NEWLINES: regex::Regex = regex::Regex::new(r"(\r\n|\n|\r)").expect("Could not compile needed Regex for `parser_context`");
 }
@@ -23,6 +35,7 @@ pub struct ParserContext {
   pub offset: SourceRange,
   pub parser: ParserRef,
   pub name: String,
+  pub(crate) user_data: Option<UserDataRef>,
 }
 
 impl ParserContext {
@@ -30,10 +43,11 @@ impl ParserContext {
     std::rc::Rc::new(std::cell::RefCell::new(ParserContext {
       scope: ScopeContext::new(),
       token_stack: vec![],
-      offset: SourceRange::new(0, parser.borrow().source.len()),
+      offset: SourceRange::new(0, parser.borrow().get_end()),
       parser: parser.clone(),
       debug_mode: 0,
       name: name.to_string(),
+      user_data: None,
     }))
   }
 
@@ -45,6 +59,7 @@ impl ParserContext {
       parser: parser.clone(),
       debug_mode: 0,
       name: name.to_string(),
+      user_data: None,
     }))
   }
 
@@ -90,16 +105,66 @@ impl ParserContext {
     self.scope.borrow_mut().set(name, value)
   }
 
+  pub fn get_user_data(&self) -> Option<UserDataRef> {
+    self.user_data.clone()
+  }
+
+  pub fn set_user_data<T: Any>(&mut self, data: T) {
+    self.user_data = Some(Rc::new(RefCell::new(Box::new(data))));
+  }
+
+  /// Calls `func` with the user data, if there is some, and it is a `T`.
+  /// The user data is borrowed for as long as `func` runs, so calling this
+  /// again from inside `func` (i.e. by running a matcher that uses it)
+  /// returns an error instead
+  pub fn with_user_data<T: Any, R>(
+    &self,
+    func: impl FnOnce(&mut T) -> R,
+  ) -> Result<Option<R>, ParseError> {
+    let user_data = match self.user_data.as_ref() {
+      Some(user_data) => user_data,
+      None => return Ok(None),
+    };
+
+    let mut user_data = user_data.try_borrow_mut().map_err(|_| {
+      let range = SourceRange::new(self.offset.start, self.offset.start);
+
+      ParseError::new_with_range(
+        &self.get_error_as_string(
+          "User data is already in use, `with_user_data` can not be nested",
+          &range,
+        ),
+        range,
+      )
+    })?;
+
+    Ok(user_data.downcast_mut::<T>().map(func))
+  }
+
   pub fn get_scope(&self) -> ScopeContextRef {
     self.scope.clone()
   }
 
+  /// The input from `start` up to `end`, cut short at the end of this
+  /// context (or of the input). More of the source is loaded if needed
+  pub fn get_input(&self, start: usize, end: usize) -> Ref<'_, str> {
+    let end = end.min(self.offset.end);
+    self.parser.borrow_mut().load_to(end);
+
+    Ref::map(self.parser.borrow(), |parser| parser.slice(start, end))
+  }
+
+  /// Whether there is no input left at `offset`, within this context
+  pub fn is_at_end(&self, offset: usize) -> bool {
+    offset >= self.offset.end || self.parser.borrow_mut().load_to(offset + 1) <= offset
+  }
+
   pub fn matches_str(&self, pattern: &str) -> Option<SourceRange> {
     if pattern.len() == 0 {
       return None;
     }
 
-    let chunk = &self.parser.borrow().source[self.offset.start..self.offset.end];
+    let chunk = self.get_input(self.offset.start, self.offset.start + pattern.len());
 
     if chunk.starts_with(pattern) {
       Some(self.offset.clone_with_len(pattern.len()))
@@ -113,7 +178,7 @@ impl ParserContext {
       return None;
     }
 
-    let chunk = &self.parser.borrow().source[offset..self.offset.end];
+    let chunk = self.get_input(offset, offset + pattern.len());
 
     if chunk.starts_with(pattern) {
       Some(SourceRange::new(offset, offset + pattern.len()))
@@ -122,33 +187,54 @@ impl ParserContext {
     }
   }
 
+  // The search starts at the offset, but the regex can still see what
+  // comes before it, so `^`, `\b` and friends work against the full source
+  // (or as much of it as is still in memory). From a source that is still
+  // loading, the input the regex sees is doubled until its result is at
+  // least `lookahead` bytes away from the end of it
   pub fn matches_regexp(&self, pattern: &Regex) -> Option<SourceRange> {
-    let chunk = &self.parser.borrow().source[self.offset.start..self.offset.end];
+    let start = self.offset.start;
+    let (lookahead, mut window) = {
+      let parser = self.parser.borrow();
+      let lookahead = parser.get_lookahead();
 
-    match pattern.find(chunk) {
-      Some(m) => {
-        let start = m.start() + self.offset.start;
-        let end = m.end() + self.offset.start;
+      if parser.source.is_complete() {
+        (lookahead, usize::MAX)
+      } else {
+        (lookahead, lookahead + 1)
+      }
+    };
 
-        if start != self.offset.start {
-          None
-        } else {
-          Some(SourceRange::new(start, end))
-        }
+    loop {
+      let wanted = start.saturating_add(window).min(self.offset.end);
+      let end = self.parser.borrow_mut().load_to(wanted).min(wanted);
+
+      let parser = self.parser.borrow();
+      let haystack_start = parser.source.start();
+      let haystack = parser.slice(haystack_start, end);
+      let found = pattern
+        .find_at(haystack, start - haystack_start)
+        .map(|m| SourceRange::new(m.start() + haystack_start, m.end() + haystack_start));
+
+      let is_settled = end < wanted
+        || wanted == self.offset.end
+        || match found {
+          Some(range) => range.end + lookahead < end,
+          None => end >= start + lookahead,
+        };
+
+      if is_settled {
+        return found.filter(|range| range.start == start);
       }
-      None => None,
+
+      window = window.saturating_mul(2);
     }
   }
 
   pub fn debug_range(&self, max_len: usize) -> String {
-    let parser = self.parser.borrow();
-    let mut end_offset = self.offset.start + max_len;
-
-    if end_offset > self.offset.end {
-      end_offset = self.offset.end;
-    }
-
-    parser.source[self.offset.start..end_offset].to_string()
+    self
+      .get_input(self.offset.start, self.offset.start + max_len)
+      .to_string()
   }
 
   pub fn push_token_to_stack(&mut self, token: TokenRef) {
@@ -167,9 +253,15 @@ impl ParserContext {
     Some(self.token_stack[self.token_stack.len() - 1].clone())
   }
 
+  // Lines and columns are counted from the start of the input that is still in memory
   pub fn get_lines(&self, range: &SourceRange) -> (usize, usize) {
     let parser = self.parser.borrow();
-    let source = parser.source.as_str();
+    let source = parser.get_source();
+    let base = parser.source.start();
+    let range = &SourceRange::new(
+      range.start.saturating_sub(base),
+      range.end.saturating_sub(base),
+    );
 
     let first_line = NEWLINES.find_iter(&source[0..range.start]).count() + 1;
     let last_line = NEWLINES.find_iter(&source[0..range.end]).count() + 1;
@@ -179,7 +271,12 @@ impl ParserContext {
 
   pub fn get_columns(&self, range: &SourceRange) -> (usize, usize) {
     let parser = self.parser.borrow();
-    let source = parser.source.as_str();
+    let source = parser.get_source();
+    let base = parser.source.start();
+    let range = &SourceRange::new(
+      range.start.saturating_sub(base),
+      range.end.saturating_sub(base),
+    );
     let mut last_newline = 0;
     let mut first_column = 0;
     let mut last_column = 0;
@@ -296,7 +393,7 @@ impl ParserContext {
     }
   }
 
-  fn collect_errors(root_token: TokenRef, token: TokenRef, is_root: bool) {
+  pub(crate) fn collect_errors(root_token: TokenRef, token: TokenRef, is_root: bool) {
     if is_root {
       // We need to make a copy of this vector so that we can drop
       // the _token reference... we only want to do this for the root
@@ -379,6 +476,12 @@ impl ParserContext {
       Err(error) => Err(error),
     }
   }
+
+  /// Lexer mode: rather than building a whole tree, apply `matcher` at
+  /// the current offset over and over, yielding each token as it is matched
+  pub fn tokens(context: ParserContextRef, matcher: MatcherRef) -> TokenIterator {
+    TokenIterator::new(context, matcher)
+  }
 }
 
 #[cfg(test)]
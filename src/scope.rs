@@ -7,6 +7,24 @@ pub enum VariableType {
   Token(TokenRef),
   String(String),
   Matcher(MatcherRef),
+  Int(i64),
+  Bool(bool),
+  List(Vec<VariableType>),
+  Map(HashMap<String, VariableType>),
+}
+
+impl VariableType {
+  pub fn type_name(&self) -> &'static str {
+    match self {
+      VariableType::Token(_) => "Token",
+      VariableType::String(_) => "String",
+      VariableType::Matcher(_) => "Matcher",
+      VariableType::Int(_) => "Int",
+      VariableType::Bool(_) => "Bool",
+      VariableType::List(_) => "List",
+      VariableType::Map(_) => "Map",
+    }
+  }
 }
 
 pub type ScopeRef = Rc<RefCell<Scope>>;
@@ -34,6 +52,14 @@ impl Scope {
   pub fn set(&mut self, name: &str, value: VariableType) -> Option<VariableType> {
     self.references.insert(name.to_string(), value)
   }
+
+  pub fn remove(&mut self, name: &str) -> Option<VariableType> {
+    self.references.remove(name)
+  }
+
+  pub fn keys(&self) -> Vec<String> {
+    self.references.keys().cloned().collect()
+  }
 }
 
 #[cfg(test)]
@@ -4,14 +4,75 @@ use crate::scope::{Scope, ScopeRef, VariableType};
 
 pub type ScopeContextRef = Rc<RefCell<ScopeContext>>;
 
+// A write made while a transaction was open, and what it overwrote
+#[derive(Debug, Clone)]
+struct JournalEntry {
+  scope: ScopeRef,
+  name: String,
+  previous: Option<VariableType>,
+}
+
 #[derive(Debug, Clone)]
 pub struct ScopeContext {
   stack: Vec<ScopeRef>,
+  journal: Vec<JournalEntry>,
+  transactions: usize,
 }
 
 impl ScopeContext {
   pub fn new() -> ScopeContextRef {
-    Rc::new(RefCell::new(Self { stack: Vec::new() }))
+    Rc::new(RefCell::new(Self {
+      stack: Vec::new(),
+      journal: Vec::new(),
+      transactions: 0,
+    }))
+  }
+
+  /// Start recording writes, so they can be rolled back if the
+  /// matcher making them fails. Transactions nest, and writes are
+  /// only forgotten once the outermost transaction is committed.
+  /// Returns the checkpoint to hand to `rollback`
+  pub fn begin(&mut self) -> usize {
+    self.transactions += 1;
+    self.journal.len()
+  }
+
+  pub fn commit(&mut self) {
+    self.end();
+  }
+
+  pub fn rollback(&mut self, checkpoint: usize) {
+    self.rollback_to_savepoint(checkpoint);
+    self.end();
+  }
+
+  /// A point inside the current transaction that can be rolled
+  /// back to without ending the transaction
+  pub fn savepoint(&self) -> usize {
+    self.journal.len()
+  }
+
+  pub fn rollback_to_savepoint(&mut self, savepoint: usize) {
+    while self.journal.len() > savepoint {
+      let entry = match self.journal.pop() {
+        Some(entry) => entry,
+        None => break,
+      };
+
+      let mut scope = entry.scope.borrow_mut();
+      match entry.previous {
+        Some(previous) => scope.set(&entry.name, previous),
+        None => scope.remove(&entry.name),
+      };
+    }
+  }
+
+  fn end(&mut self) {
+    self.transactions = self.transactions.saturating_sub(1);
+
+    if self.transactions == 0 {
+      self.journal.clear();
+    }
   }
 
   pub fn push(&mut self, scope: ScopeRef) {
@@ -29,11 +90,7 @@ impl ScopeContext {
 
     for scope in (&self.stack).into_iter().rev() {
       match scope.borrow().get(name) {
-        Some(value) => match value {
-          VariableType::Token(token) => return Some(VariableType::Token(token.clone())),
-          VariableType::String(value) => return Some(VariableType::String(value.clone())),
-          VariableType::Matcher(matcher) => return Some(VariableType::Matcher(matcher.clone())),
-        },
+        Some(value) => return Some(value.clone()),
         None => continue,
       }
     }
@@ -47,8 +104,56 @@ impl ScopeContext {
     }
 
     let top_index = self.stack.len() - 1;
-    let top = &mut self.stack[top_index];
-    top.borrow_mut().set(name, value)
+    self.set_in_frame(top_index, name, value)
+  }
+
+  /// Like `set`, but writes to the innermost frame that already holds
+  /// `name`, so a variable owned by an outer frame outlives the frame
+  /// it was updated from. Falls back to `set` if `name` is missing
+  pub fn update(&mut self, name: &str, value: VariableType) -> Option<VariableType> {
+    let owner = self
+      .stack
+      .iter()
+      .rposition(|scope| scope.borrow().contains_key(name));
+
+    match owner {
+      Some(index) => self.set_in_frame(index, name, value),
+      None => self.set(name, value),
+    }
+  }
+
+  fn set_in_frame(
+    &mut self,
+    index: usize,
+    name: &str,
+    value: VariableType,
+  ) -> Option<VariableType> {
+    let frame = &self.stack[index];
+    let previous = frame.borrow_mut().set(name, value);
+
+    if self.transactions > 0 {
+      self.journal.push(JournalEntry {
+        scope: frame.clone(),
+        name: name.to_string(),
+        previous: previous.clone(),
+      });
+    }
+
+    previous
+  }
+
+  // All names visible from this context, sorted
+  pub fn keys(&self) -> Vec<String> {
+    let mut keys: Vec<String> = Vec::new();
+
+    for scope in &self.stack {
+      keys.extend(scope.borrow().keys());
+    }
+
+    keys.sort();
+    keys.dedup();
+
+    keys
   }
 }
 
@@ -92,4 +197,38 @@ mod tests {
       }
     }
   }
+
+  #[test]
+  fn it_can_roll_back_writes() {
+    let scope_context = ScopeContext::new();
+    let mut sc = scope_context.borrow_mut();
+    let value = |value: &str| VariableType::String(value.to_string());
+    let get = |sc: &ScopeContext, name: &str| match sc.get(name) {
+      Some(VariableType::String(value)) => Some(value),
+      _ => None,
+    };
+
+    sc.set("Kept", value("1"));
+
+    let outer = sc.begin();
+    sc.set("Kept", value("2"));
+
+    sc.begin();
+    sc.set("Kept", value("3"));
+    sc.set("New", value("4"));
+    sc.commit();
+
+    let savepoint = sc.savepoint();
+    sc.set("New", value("5"));
+    sc.rollback_to_savepoint(savepoint);
+
+    assert_eq!(get(&sc, "Kept"), Some("3".to_string()));
+    assert_eq!(get(&sc, "New"), Some("4".to_string()));
+
+    // Committing the inner transaction still lets the outer one undo it
+    sc.rollback(outer);
+
+    assert_eq!(get(&sc, "Kept"), Some("1".to_string()));
+    assert_eq!(get(&sc, "New"), None);
+  }
 }
@@ -0,0 +1,56 @@
+// Host-registered functions applied to the produced token, in order, i.e. `<Number @parse_int>`
+#[macro_export]
+macro_rules! ScriptActions {
+  () => {
+    $crate::Optional!(
+      $crate::Loop!(1..; "Actions";
+        $crate::Discard!($crate::Equals!("@")),
+        $crate::ScriptIdentifier!("Action"),
+        $crate::ScriptWSN0!(?),
+      )
+    )
+  };
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::{
+    matcher::MatcherFailure, parser::Parser, parser_context::ParserContext,
+    source_range::SourceRange,
+  };
+
+  #[test]
+  fn it_works1() {
+    let parser = Parser::new("@trim @parse_int");
+    let parser_context = ParserContext::new(&parser, "Test");
+    let matcher = ScriptActions!();
+
+    if let Ok(token) = ParserContext::tokenize(parser_context, matcher) {
+      let token = token.borrow();
+      assert_eq!(token.get_name(), "Actions");
+      assert_eq!(*token.get_matched_range(), SourceRange::new(0, 16));
+
+      let actions: Vec<String> = token
+        .get_children()
+        .iter()
+        .map(|child| child.borrow().get_value().clone())
+        .collect();
+
+      assert_eq!(actions, vec!["trim", "parse_int"]);
+    } else {
+      unreachable!("Test failed!");
+    };
+  }
+
+  #[test]
+  fn it_fails1() {
+    let parser = Parser::new("@ parse_int");
+    let parser_context = ParserContext::new(&parser, "Test");
+    let matcher = ScriptActions!();
+
+    if let Err(MatcherFailure::Fail) = ParserContext::tokenize(parser_context, matcher) {
+    } else {
+      unreachable!("Test failed!");
+    };
+  }
+}
@@ -0,0 +1,72 @@
+#[macro_export]
+macro_rules! ScriptArguments {
+  () => {
+    $crate::Loop!(1..; "Arguments";
+      $crate::ScriptWSN0!(?),
+      // Arguments are pattern definitions, so stop
+      // as soon as the next thing isn't a pattern
+      $crate::Peek!($crate::Matches!(r"[?!]?<")),
+      $crate::Ref!("PatternDefinition"),
+    )
+  };
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::{
+    matcher::MatcherFailure,
+    parser::Parser,
+    parser_context::{ParserContext, ParserContextRef},
+    source_range::SourceRange,
+    ScriptPatternDefinition, ScriptProgramMatcher, ScriptSwitchMatcher,
+  };
+
+  fn register_matchers(parser_context: &ParserContextRef) {
+    let parser_context = parser_context.borrow();
+
+    parser_context.register_matchers(vec![ScriptSwitchMatcher!(), ScriptProgramMatcher!()]);
+    parser_context.register_matcher_with_name("PatternDefinition", ScriptPatternDefinition!());
+  }
+
+  #[test]
+  fn it_works1() {
+    let parser = Parser::new(" <Word> ?<=','>");
+    let parser_context = ParserContext::new(&parser, "Test");
+    let matcher = ScriptArguments!();
+
+    register_matchers(&parser_context);
+
+    let result = ParserContext::tokenize(parser_context, matcher);
+
+    if let Ok(token) = result {
+      let token = token.borrow();
+      assert_eq!(token.get_name(), "Arguments");
+      assert_eq!(*token.get_matched_range(), SourceRange::new(0, 15));
+      assert_eq!(token.get_children().len(), 2);
+
+      let first = token.get_children()[0].borrow();
+      assert_eq!(first.get_name(), "PatternDefinition");
+      assert_eq!(first.get_matched_value(), "<Word>");
+
+      let second = token.get_children()[1].borrow();
+      assert_eq!(second.get_name(), "PatternDefinition");
+      assert_eq!(second.get_matched_value(), "?<=','>");
+    } else {
+      unreachable!("Test failed!");
+    };
+  }
+
+  #[test]
+  fn it_fails1() {
+    let parser = Parser::new(" test='derp'");
+    let parser_context = ParserContext::new(&parser, "Test");
+    let matcher = ScriptArguments!();
+
+    register_matchers(&parser_context);
+
+    if let Err(MatcherFailure::Fail) = ParserContext::tokenize(parser_context, matcher) {
+    } else {
+      unreachable!("Test failed!");
+    };
+  }
+}
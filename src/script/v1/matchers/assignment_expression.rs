@@ -4,6 +4,8 @@ macro_rules! ScriptAssignmentExpression {
     $crate::Program!("AssignmentExpression";
       $crate::ScriptIdentifier!(),
       $crate::ScriptWSN0!(?),
+      $crate::Optional!($crate::ScriptParameters!()),
+      $crate::ScriptWSN0!(?),
       $crate::Discard!($crate::Equals!("=")),
       $crate::ScriptWSN0!(?),
       $crate::Switch!(
@@ -104,6 +106,38 @@ mod tests {
     };
   }
 
+  #[test]
+  fn it_works_with_parameters() {
+    let parser = Parser::new("List(item, sep) = <item>");
+    let parser_context = ParserContext::new(&parser, "Test");
+    let matcher = ScriptAssignmentExpression!();
+
+    register_matchers(&parser_context);
+
+    let result = ParserContext::tokenize(parser_context, matcher);
+
+    if let Ok(token) = result {
+      let token = token.borrow();
+      assert_eq!(token.get_name(), "AssignmentExpression");
+      assert_eq!(token.get_children().len(), 3);
+
+      let first = token.get_children()[0].borrow();
+      assert_eq!(first.get_name(), "Identifier");
+      assert_eq!(first.get_value(), "List");
+
+      let second = token.get_children()[1].borrow();
+      assert_eq!(second.get_name(), "Parameters");
+      assert_eq!(second.get_value(), "item, sep");
+      assert_eq!(second.get_children().len(), 2);
+
+      let third = token.get_children()[2].borrow();
+      assert_eq!(third.get_name(), "PatternDefinition");
+      assert_eq!(third.get_matched_value(), "<item>");
+    } else {
+      unreachable!("Test failed!");
+    };
+  }
+
   #[test]
   fn it_fails1() {
     let parser = Parser::new("Testing = ");
@@ -0,0 +1,130 @@
+// A lone operand checks that a variable exists, i.e. `<if (Async) <...>>`,
+// otherwise both sides are compared, i.e. `<if (AdextopaScope.version >= '2') <...> else <...>>`
+#[macro_export]
+macro_rules! ScriptConditionOperand {
+  () => {
+    $crate::Switch!("Operand";
+      $crate::ScriptString!(),
+      $crate::Matches!("Number"; r"-?\d+"),
+      $crate::Matches!("Path"; r"[a-zA-Z$_][a-zA-Z0-9$_]*(\.[a-zA-Z0-9$_]+)*"),
+    )
+  };
+}
+
+#[macro_export]
+macro_rules! ScriptConditionalMatcher {
+  () => {
+    $crate::Program!("ConditionalMatcher";
+      $crate::Discard!($crate::Matches!(r"if\b")),
+      $crate::ScriptWSN0!(?),
+      $crate::Discard!($crate::Equals!("(")),
+      $crate::ScriptWSN0!(?),
+      $crate::Program!("Condition";
+        $crate::Optional!($crate::Equals!("Negate"; "!")),
+        $crate::ScriptWSN0!(?),
+        $crate::ScriptConditionOperand!(),
+        $crate::Optional!(
+          $crate::ProxyChildren!(
+            $crate::Program!(
+              $crate::ScriptWSN0!(?),
+              $crate::Matches!("Operator"; r"==|!=|<=|>=|<|>"),
+              $crate::ScriptWSN0!(?),
+              $crate::ScriptConditionOperand!(),
+            )
+          )
+        ),
+      ),
+      $crate::ScriptWSN0!(?),
+      $crate::Discard!($crate::Equals!(")")),
+      $crate::ScriptWSN0!(?),
+      $crate::Ref!("Pattern"),
+      $crate::Optional!(
+        $crate::Program!("Else";
+          $crate::ScriptWSN0!(?),
+          $crate::Discard!($crate::Matches!(r"else\b")),
+          $crate::ScriptWSN0!(?),
+          $crate::Ref!("Pattern"),
+        )
+      ),
+    )
+  };
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::{
+    matcher::MatcherFailure,
+    parser::Parser,
+    parser_context::{ParserContext, ParserContextRef},
+    source_range::SourceRange,
+    ScriptPattern, ScriptPatternDefinition, ScriptProgramMatcher, ScriptSwitchMatcher,
+  };
+
+  fn register_matchers(parser_context: &ParserContextRef) {
+    let parser_context = parser_context.borrow();
+
+    parser_context.register_matchers(vec![ScriptSwitchMatcher!(), ScriptProgramMatcher!()]);
+    parser_context.register_matcher_with_name("PatternDefinition", ScriptPatternDefinition!());
+    parser_context.register_matcher_with_name("Pattern", ScriptPattern!());
+  }
+
+  #[test]
+  fn it_works1() {
+    let parser = Parser::new("if (!Scope.version >= '2') <='a'> else <='b'>");
+    let parser_context = ParserContext::new(&parser, "Test");
+
+    register_matchers(&parser_context);
+
+    let matcher = ScriptConditionalMatcher!();
+
+    if let Ok(token) = ParserContext::tokenize(parser_context, matcher) {
+      let token = token.borrow();
+      assert_eq!(token.get_name(), "ConditionalMatcher");
+      assert_eq!(*token.get_matched_range(), SourceRange::new(0, 45));
+      assert_eq!(token.get_children().len(), 3);
+
+      let condition = token.get_children()[0].borrow();
+      let parts: Vec<(String, String)> = condition
+        .get_children()
+        .iter()
+        .map(|child| {
+          let child = child.borrow();
+          (child.get_name().clone(), child.get_value().clone())
+        })
+        .collect();
+
+      assert_eq!(
+        parts,
+        vec![
+          ("Negate".to_string(), "!".to_string()),
+          ("Path".to_string(), "Scope.version".to_string()),
+          ("Operator".to_string(), ">=".to_string()),
+          ("String".to_string(), "2".to_string()),
+        ]
+      );
+
+      assert_eq!(
+        token.get_children()[1].borrow().get_name(),
+        "PatternDefinition"
+      );
+      assert_eq!(token.get_children()[2].borrow().get_name(), "Else");
+    } else {
+      unreachable!("Test failed!");
+    };
+  }
+
+  #[test]
+  fn it_fails1() {
+    let parser = Parser::new("iffy");
+    let parser_context = ParserContext::new(&parser, "Test");
+
+    register_matchers(&parser_context);
+
+    let matcher = ScriptConditionalMatcher!();
+
+    if let Err(MatcherFailure::Fail) = ParserContext::tokenize(parser_context, matcher) {
+    } else {
+      unreachable!("Test failed!");
+    };
+  }
+}
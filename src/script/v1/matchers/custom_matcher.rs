@@ -3,6 +3,7 @@ macro_rules! ScriptCustomMatcher {
   () => {
     $crate::Program!("CustomMatcher";
       $crate::ScriptIdentifier!(),
+      $crate::Optional!($crate::ScriptArguments!()),
     )
   };
 }
@@ -14,6 +15,7 @@ mod tests {
     parser::Parser,
     parser_context::ParserContext,
     source_range::SourceRange,
+    ScriptPatternDefinition, ScriptProgramMatcher, ScriptSwitchMatcher,
   };
 
   #[test]
@@ -36,6 +38,38 @@ mod tests {
     };
   }
 
+  #[test]
+  fn it_works_with_arguments() {
+    let parser = Parser::new("List <Word> <=','>");
+    let parser_context = ParserContext::new(&parser, "Test");
+    let matcher = ScriptCustomMatcher!();
+
+    {
+      let parser_context = parser_context.borrow();
+      parser_context.register_matchers(vec![ScriptSwitchMatcher!(), ScriptProgramMatcher!()]);
+      parser_context.register_matcher_with_name("PatternDefinition", ScriptPatternDefinition!());
+    }
+
+    let result = ParserContext::tokenize(parser_context, matcher);
+
+    if let Ok(token) = result {
+      let token = token.borrow();
+      assert_eq!(token.get_name(), "CustomMatcher");
+      assert_eq!(*token.get_matched_range(), SourceRange::new(0, 18));
+      assert_eq!(token.get_children().len(), 2);
+
+      let first = token.get_children()[0].borrow();
+      assert_eq!(first.get_name(), "Identifier");
+      assert_eq!(first.get_value(), "List");
+
+      let second = token.get_children()[1].borrow();
+      assert_eq!(second.get_name(), "Arguments");
+      assert_eq!(second.get_children().len(), 2);
+    } else {
+      unreachable!("Test failed!");
+    };
+  }
+
   #[test]
   fn it_fails1() {
     let parser = Parser::new("<test>");
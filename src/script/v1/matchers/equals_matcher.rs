@@ -4,6 +4,9 @@ macro_rules! ScriptEqualsMatcher {
     $crate::Program!("EqualsMatcher";
       $crate::Discard!($crate::Equals!("=")),
       $crate::ScriptString!(),
+      // i = ignore case, a = ignore ASCII case,
+      // n = NFC normalization, k = NFKC normalization
+      $crate::Optional!($crate::Matches!("Flags"; r"[iakn]+")),
     )
   };
 }
@@ -38,6 +41,23 @@ mod tests {
     };
   }
 
+  #[test]
+  fn it_works_with_flags() {
+    let parser = Parser::new("='select'ia>");
+    let parser_context = ParserContext::new(&parser, "Test");
+    let matcher = ScriptEqualsMatcher!();
+
+    if let Ok(token) = ParserContext::tokenize(parser_context, matcher) {
+      let token = token.borrow();
+      assert_eq!(*token.get_matched_range(), SourceRange::new(0, 11));
+      assert_eq!(token.get_children().len(), 2);
+      assert_eq!(token.get_children()[1].borrow().get_name(), "Flags");
+      assert_eq!(token.get_children()[1].borrow().get_value(), "ia");
+    } else {
+      unreachable!("Test failed!");
+    };
+  }
+
   #[test]
   fn it_fails1() {
     let parser = Parser::new("Testing");
@@ -6,33 +6,32 @@ macro_rules! ScriptImportStatement {
       $crate::ScriptWS0!(?),
       $crate::Program!("ImportIdentifiers";
         $crate::Discard!($crate::Equals!("{")),
+        $crate::ScriptWSN0!(?),
         $crate::ProxyChildren!(
-          $crate::Loop!("IdentifiersLoop";
-            $crate::ScriptWSN0!(?),
-            $crate::Switch!(
-              $crate::Discard!(
-                $crate::Program!(
-                  $crate::Equals!("}"),
-                  $crate::Break!("IdentifiersLoop"),
-                )
-              ),
-              $crate::Program!("ImportIdentifier";
-                $crate::ScriptIdentifier!("ImportName"),
-                $crate::Optional!(
-                  $crate::ProxyChildren!(
-                    $crate::Program!(
-                      $crate::Discard!($crate::Matches!(r"\s+as\s+")),
-                      $crate::ScriptIdentifier!("ImportAsName"),
-                    )
+          $crate::SepBy!(0..;
+            $crate::Program!("ImportIdentifier";
+              $crate::ScriptIdentifier!("ImportName"),
+              $crate::Optional!(
+                $crate::ProxyChildren!(
+                  $crate::Program!(
+                    $crate::Discard!($crate::Matches!(r"\s+as\s+")),
+                    $crate::ScriptIdentifier!("ImportAsName"),
                   )
-                ),
-                $crate::Discard!(
-                  $crate::Optional!($crate::Equals!(","))
                 )
               ),
-            )
+            ),
+            $crate::Discard!(
+              $crate::Program!(
+                $crate::ScriptWSN0!(?),
+                $crate::Equals!(","),
+                $crate::ScriptWSN0!(?),
+              )
+            ),
+            $crate::matchers::sep_by::TrailingSeparator::Allow,
           )
         ),
+        $crate::ScriptWSN0!(?),
+        $crate::Discard!($crate::Equals!("}")),
       ),
       $crate::ScriptWS0!(?),
       $crate::Discard!($crate::Equals!("from")),
@@ -86,17 +85,17 @@ mod tests {
       let ident_first = first.get_children()[0].borrow();
       assert_eq!(ident_first.get_name(), "ImportIdentifier");
       assert_eq!(*ident_first.get_captured_range(), SourceRange::new(9, 18));
-      assert_eq!(*ident_first.get_matched_range(), SourceRange::new(9, 19));
+      assert_eq!(*ident_first.get_matched_range(), SourceRange::new(9, 18));
       assert_eq!(ident_first.get_value(), "_ as derp");
-      assert_eq!(ident_first.get_matched_value(), "_ as derp,");
+      assert_eq!(ident_first.get_matched_value(), "_ as derp");
       assert_eq!(ident_first.get_children().len(), 2);
 
       let ident_second = first.get_children()[1].borrow();
       assert_eq!(ident_second.get_name(), "ImportIdentifier");
       assert_eq!(*ident_second.get_captured_range(), SourceRange::new(20, 35));
-      assert_eq!(*ident_second.get_matched_range(), SourceRange::new(20, 36));
+      assert_eq!(*ident_second.get_matched_range(), SourceRange::new(20, 35));
       assert_eq!(ident_second.get_value(), "Stuff as Things");
-      assert_eq!(ident_second.get_matched_value(), "Stuff as Things,");
+      assert_eq!(ident_second.get_matched_value(), "Stuff as Things");
       assert_eq!(ident_second.get_children().len(), 2);
 
       let ident_third = first.get_children()[2].borrow();
@@ -5,6 +5,8 @@ macro_rules! ScriptMatcher {
       $crate::ScriptRegexMatcher!(),
       $crate::ScriptEqualsMatcher!(),
       $crate::ScriptSequenceMatcher!(),
+      $crate::ScriptConditionalMatcher!(),
+      $crate::ScriptMutationMatcher!(),
       $crate::ScriptCustomMatcher!(),
       $crate::Ref!("SwitchMatcher"),
       $crate::Ref!("ProgramMatcher"),
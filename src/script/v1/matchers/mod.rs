@@ -1,14 +1,19 @@
+pub mod actions;
 pub mod adextopa_scope;
+pub mod arguments;
 pub mod assignment_expression;
 pub mod attribute;
 pub mod attributes;
 pub mod comment;
+pub mod conditional_matcher;
 pub mod custom_matcher;
 pub mod equals_matcher;
 pub mod identifier;
 pub mod import_statement;
 pub mod matcher;
 pub mod matcher_name;
+pub mod mutation_matcher;
+pub mod parameters;
 pub mod pattern;
 pub mod pattern_definition;
 pub mod pattern_scope;
@@ -0,0 +1,90 @@
+// Updates a scope variable, i.e. `<set Depth = 0>`, `<incr Depth>`,
+// `<decr Depth 2>`, `<push Delimiters 'EOF'>`, or `<pop Delimiters>`
+#[macro_export]
+macro_rules! ScriptMutationMatcher {
+  () => {
+    $crate::Program!("MutationMatcher";
+      $crate::Matches!("Operation"; r"(set|incr|decr|push|pop)\b"),
+      $crate::ScriptWSN0!(?),
+      $crate::ScriptIdentifier!("Variable"),
+      $crate::Optional!(
+        $crate::ProxyChildren!(
+          $crate::Program!(
+            $crate::ScriptWSN0!(?),
+            $crate::Optional!($crate::Discard!($crate::Equals!("="))),
+            $crate::ScriptWSN0!(?),
+            $crate::ScriptConditionOperand!(),
+          )
+        )
+      ),
+    )
+  };
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::{
+    matcher::MatcherFailure, parser::Parser, parser_context::ParserContext,
+    source_range::SourceRange,
+  };
+
+  #[test]
+  fn it_works1() {
+    let parser = Parser::new("set Depth = 0");
+    let parser_context = ParserContext::new(&parser, "Test");
+    let matcher = ScriptMutationMatcher!();
+
+    if let Ok(token) = ParserContext::tokenize(parser_context, matcher) {
+      let token = token.borrow();
+      assert_eq!(token.get_name(), "MutationMatcher");
+      assert_eq!(*token.get_matched_range(), SourceRange::new(0, 13));
+
+      let parts: Vec<(String, String)> = token
+        .get_children()
+        .iter()
+        .map(|child| {
+          let child = child.borrow();
+          (child.get_name().clone(), child.get_value().clone())
+        })
+        .collect();
+
+      assert_eq!(
+        parts,
+        vec![
+          ("Operation".to_string(), "set".to_string()),
+          ("Variable".to_string(), "Depth".to_string()),
+          ("Number".to_string(), "0".to_string()),
+        ]
+      );
+    } else {
+      unreachable!("Test failed!");
+    };
+  }
+
+  #[test]
+  fn it_works2() {
+    let parser = Parser::new("pop Stack");
+    let parser_context = ParserContext::new(&parser, "Test");
+    let matcher = ScriptMutationMatcher!();
+
+    if let Ok(token) = ParserContext::tokenize(parser_context, matcher) {
+      let token = token.borrow();
+      assert_eq!(token.get_children().len(), 2);
+      assert_eq!(token.get_children()[1].borrow().get_value(), "Stack");
+    } else {
+      unreachable!("Test failed!");
+    };
+  }
+
+  #[test]
+  fn it_fails1() {
+    let parser = Parser::new("settings");
+    let parser_context = ParserContext::new(&parser, "Test");
+    let matcher = ScriptMutationMatcher!();
+
+    if let Err(MatcherFailure::Fail) = ParserContext::tokenize(parser_context, matcher) {
+    } else {
+      unreachable!("Test failed!");
+    };
+  }
+}
@@ -0,0 +1,69 @@
+#[macro_export]
+macro_rules! ScriptParameters {
+  () => {
+    $crate::Program!("Parameters";
+      $crate::Discard!($crate::Equals!("(")),
+      $crate::ScriptWSN0!(?),
+      $crate::ScriptIdentifier!("Parameter"),
+      $crate::Optional!(
+        $crate::ProxyChildren!(
+          $crate::Loop!(
+            $crate::ScriptWSN0!(?),
+            $crate::Discard!($crate::Equals!(",")),
+            $crate::ScriptWSN0!(?),
+            $crate::ScriptIdentifier!("Parameter"),
+          )
+        )
+      ),
+      $crate::ScriptWSN0!(?),
+      $crate::Discard!($crate::Equals!(")")),
+    )
+  };
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::{
+    matcher::MatcherFailure, parser::Parser, parser_context::ParserContext,
+    source_range::SourceRange,
+  };
+
+  #[test]
+  fn it_works1() {
+    let parser = Parser::new("( item, sep )");
+    let parser_context = ParserContext::new(&parser, "Test");
+    let matcher = ScriptParameters!();
+
+    let result = ParserContext::tokenize(parser_context, matcher);
+
+    if let Ok(token) = result {
+      let token = token.borrow();
+      assert_eq!(token.get_name(), "Parameters");
+      assert_eq!(*token.get_captured_range(), SourceRange::new(2, 11));
+      assert_eq!(*token.get_matched_range(), SourceRange::new(0, 13));
+      assert_eq!(token.get_children().len(), 2);
+
+      let first = token.get_children()[0].borrow();
+      assert_eq!(first.get_name(), "Parameter");
+      assert_eq!(first.get_value(), "item");
+
+      let second = token.get_children()[1].borrow();
+      assert_eq!(second.get_name(), "Parameter");
+      assert_eq!(second.get_value(), "sep");
+    } else {
+      unreachable!("Test failed!");
+    };
+  }
+
+  #[test]
+  fn it_fails1() {
+    let parser = Parser::new("()");
+    let parser_context = ParserContext::new(&parser, "Test");
+    let matcher = ScriptParameters!();
+
+    if let Err(MatcherFailure::Fail) = ParserContext::tokenize(parser_context, matcher) {
+    } else {
+      unreachable!("Test failed!");
+    };
+  }
+}
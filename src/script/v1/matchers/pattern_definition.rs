@@ -34,8 +34,17 @@ macro_rules! ScriptPatternDefinition {
         $crate::ScriptMatcher!(),
         $crate::ScriptWSN0!(?),
         $crate::ScriptAttributes!(),
+        $crate::ScriptActions!(),
         $crate::Discard!($crate::Equals!(">")),
         $crate::Optional!($crate::ScriptRepeatSpecifier!()),
+        // A separator turns a repeated pattern into a separated list.
+        // `%?` allows a trailing separator, and `%!` requires one
+        $crate::Optional!($crate::Program!("Separator";
+          $crate::ScriptWSN0!(?),
+          $crate::Matches!("SeparatorMode"; r"%[?!]?"),
+          $crate::ScriptWSN0!(?),
+          $crate::Ref!("Pattern"),
+        )),
       ),
       |token, _, __| {
         let mut _token = token.borrow_mut();
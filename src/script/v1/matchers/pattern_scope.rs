@@ -13,7 +13,8 @@ macro_rules! ScriptPatternScope {
         let _token = token.borrow();
         let token_range = _token.get_matched_range();
         let parser = _token.get_parser();
-        let source = &parser.borrow().source;
+        let parser = parser.borrow();
+        let source = parser.get_source();
 
         if token_range.end < source.len() {
           return $crate::ErrorTokenResult!(context.clone(), &format!("Syntax error. Expected a pattern defintion, but instead found: {}", &source[token_range.end..]));
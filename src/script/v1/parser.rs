@@ -2,22 +2,30 @@ use std::{cell::RefCell, collections::HashMap, path::Path};
 
 use crate::{
   matcher::MatcherRef,
-  matchers::program::{MatchAction, ProgramPattern},
+  matchers::chars::CharsPattern,
+  matchers::equals::{CaseFolding, EqualsOptions, Normalization},
+  matchers::fetch::FetchPattern,
+  matchers::mutate::{MutatePattern, Mutation},
+  matchers::program::{loop_iteration_counts, MatchAction, ProgramPattern},
+  matchers::r#if::{Comparison, Condition, IfPattern, Operand},
+  matchers::sep_by::TrailingSeparator,
+  matchers::set_scope::SetScopePattern,
   parse_error::ParseError,
   parser::{Parser, ParserRef},
   parser_context::{ParserContext, ParserContextRef},
   scope::VariableType,
   scope_context::{ScopeContext, ScopeContextRef},
   source_range::SourceRange,
-  token::TokenRef,
-  Loop, Map, Not, Optional, ProxyChildren, Ref, ScriptProgramMatcher, ScriptSwitchMatcher,
-  SetScope, TokenResult, Visit,
+  token::{Token, TokenRef},
+  transforms::{get_transform, Transform, TRANSFORM_NAMES},
+  Loop, Map, Not, Optional, ProxyChildren, Ref, ScriptPattern, ScriptPatternDefinition,
+  ScriptProgramMatcher, ScriptSwitchMatcher, SepBy, SetScope, TokenResult, Visit,
 };
 
 use super::matchers::repeat_specifier::get_repeat_specifier_range;
 
 lazy_static::lazy_static! {
-  static ref PATTERN_MATCHER: regex::Regex = regex::Regex::new(r"^(CustomMatcher|EqualsMatcher|RegexMatcher|SequenceMatcher|ProgramMatcher|SwitchMatcher)$").expect("Could not compile needed Regex for `script::Parser`");
+  static ref PATTERN_MATCHER: regex::Regex = regex::Regex::new(r"^(ConditionalMatcher|CustomMatcher|EqualsMatcher|MutationMatcher|RegexMatcher|SequenceMatcher|ProgramMatcher|SwitchMatcher)$").expect("Could not compile needed Regex for `script::Parser`");
 }
 
 lazy_static::lazy_static! {
@@ -26,9 +34,178 @@ lazy_static::lazy_static! {
 
 pub const FLAG_LOG_STDERR: u32 = 0x01;
 
+/// Host functions a script can apply to the token a pattern
+/// produces, by name, i.e. `<Number @parse_int>`
+pub type ActionRegistry = HashMap<String, Transform>;
+
+// The built-in transforms are always available as actions,
+// unless the host registers an action of the same name
+fn with_builtin_actions(actions: &ActionRegistry) -> ActionRegistry {
+  let mut registry: ActionRegistry = TRANSFORM_NAMES
+    .iter()
+    .filter_map(|name| Some((name.to_string(), get_transform(name)?)))
+    .collect();
+
+  for (name, action) in actions {
+    registry.insert(name.clone(), action.clone());
+  }
+
+  registry
+}
+
+// Parameter names of each pattern function (e.g. `List(item, sep) = <...>`)
+type PatternParameters = HashMap<String, Vec<String>>;
+
+// Collected before any matchers are built, so that a pattern
+// function can be invoked before the place it is defined
+fn collect_pattern_parameters(token: &TokenRef, parameters: &mut PatternParameters) {
+  let _token = token.borrow();
+
+  if _token.get_name() == "AssignmentExpression" {
+    if let Some(parameters_token) = _token.find_child("Parameters") {
+      let names = parameters_token
+        .borrow()
+        .get_children()
+        .iter()
+        .map(|parameter| parameter.borrow().get_value().clone())
+        .collect();
+
+      parameters.insert(_token.get_children()[0].borrow().get_value().clone(), names);
+    }
+
+    return;
+  }
+
+  for child in _token.get_children() {
+    collect_pattern_parameters(child, parameters);
+  }
+}
+
+fn get_equals_options(flags: &str) -> Result<EqualsOptions, String> {
+  let mut options = EqualsOptions::default();
+
+  for flag in flags.chars() {
+    let (case_folding, normalization) = match flag {
+      'i' => (CaseFolding::Unicode, Normalization::None),
+      'a' => (CaseFolding::Ascii, Normalization::None),
+      'n' => (CaseFolding::None, Normalization::Nfc),
+      _ => (CaseFolding::None, Normalization::Nfkc),
+    };
+
+    if case_folding != CaseFolding::None {
+      if options.case_folding != CaseFolding::None && options.case_folding != case_folding {
+        return Err("Can not use both the `i` and `a` flags on an `Equals` matcher".to_string());
+      }
+
+      options.case_folding = case_folding;
+    }
+
+    if normalization != Normalization::None {
+      if options.normalization != Normalization::None && options.normalization != normalization {
+        return Err("Can not use both the `n` and `k` flags on an `Equals` matcher".to_string());
+      }
+
+      options.normalization = normalization;
+    }
+  }
+
+  Ok(options)
+}
+
+// A `Path` operand is a variable reference, everything else is a literal
+fn construct_operand(token: &TokenRef) -> Box<dyn Operand> {
+  let token = token.borrow();
+
+  match token.get_name().as_str() {
+    "Path" => Box::new(FetchPattern::new(token.get_value())),
+    "Number" => match token.get_value().parse::<i64>() {
+      Ok(value) => Box::new(value),
+      Err(_) => Box::new(token.get_value().clone()),
+    },
+    _ => Box::new(token.get_value().clone()),
+  }
+}
+
+fn construct_mutation(matcher_token: &dyn Token) -> Result<MatcherRef, String> {
+  let children = matcher_token.get_children();
+  let operation = children[0].borrow().get_value().clone();
+  let variable = children[1].borrow().get_value().clone();
+  let operand = children.get(2);
+
+  let mutation = match (operation.as_str(), operand) {
+    ("set", Some(operand)) => Mutation::Set(construct_operand(operand)),
+    ("push", Some(operand)) => Mutation::Push(construct_operand(operand)),
+    ("incr" | "decr", operand) => {
+      let amount = match operand {
+        Some(operand) if operand.borrow().get_name() == "Number" => operand
+          .borrow()
+          .get_value()
+          .parse::<i64>()
+          .map_err(|error| format!("Invalid amount for `{}`: {}", operation, error))?,
+        Some(operand) => {
+          return Err(format!(
+            "Expected a number for `{}`, but found `{}`",
+            operation,
+            operand.borrow().get_value()
+          ))
+        }
+        None => 1,
+      };
+
+      Mutation::Increment(if operation == "decr" { -amount } else { amount })
+    }
+    ("pop", None) => Mutation::Pop,
+    ("pop", Some(_)) => return Err("`pop` does not take a value".to_string()),
+    _ => return Err(format!("`{}` expects a value", operation)),
+  };
+
+  Ok(MutatePattern::new(&variable, mutation))
+}
+
+fn construct_condition(condition_token: TokenRef) -> Result<Condition, String> {
+  let condition_token = condition_token.borrow();
+  let mut parts = condition_token.get_children().as_slice();
+  let negate = parts[0].borrow().get_name() == "Negate";
+
+  if negate {
+    parts = &parts[1..];
+  }
+
+  let condition = if parts.len() == 3 {
+    let operator = parts[1].borrow().get_value().clone();
+    let comparison = Comparison::from_operator(&operator)
+      .ok_or_else(|| format!("Unknown operator `{}` in `if` condition", operator))?;
+
+    Condition::Compare(
+      construct_operand(&parts[0]),
+      comparison,
+      construct_operand(&parts[2]),
+    )
+  } else {
+    let operand = parts[0].borrow();
+
+    if operand.get_name() != "Path" {
+      return Err(format!(
+        "Expected a variable reference in `if` condition, but found `{}`",
+        operand.get_value()
+      ));
+    }
+
+    Condition::exists(operand.get_value())
+  };
+
+  if negate {
+    Ok(!condition)
+  } else {
+    Ok(condition)
+  }
+}
+
 fn construct_matcher_from_inner_definition(
   parser_context: ParserContextRef,
   matcher_token: TokenRef,
+  parameters: &PatternParameters,
+  actions: &ActionRegistry,
 ) -> Result<MatcherRef, String> {
   let matcher_token = matcher_token.borrow();
   let matcher_token_name = matcher_token.get_name();
@@ -40,7 +217,13 @@ fn construct_matcher_from_inner_definition(
       return Err("Value can not be empty for an `Equals` pattern definition".to_string());
     }
 
-    Ok(crate::Equals!(value))
+    match matcher_token.find_child("Flags") {
+      Some(flags) => Ok(crate::Equals!(
+        value,
+        get_equals_options(flags.borrow().get_value())?
+      )),
+      None => Ok(crate::Equals!(value)),
+    }
   } else if matcher_token_name == "RegexMatcher" {
     let mut value: String = matcher_token.get_value().clone();
 
@@ -52,7 +235,11 @@ fn construct_matcher_from_inner_definition(
       value = format!("(?{}){}", flags.borrow().get_captured_value(), value);
     }
 
-    Ok(crate::Matches!(&value))
+    // Simple character runs (i.e. `[a-z_]+`) don't need a regex
+    match CharsPattern::new_from_regex(&value) {
+      Some(matcher) => Ok(matcher),
+      None => Ok(crate::Matches!(&value)),
+    }
   } else if matcher_token_name == "SequenceMatcher" {
     let start_pattern = matcher_token.get_children()[0].borrow();
     let end_pattern = matcher_token.get_children()[1].borrow();
@@ -80,7 +267,46 @@ fn construct_matcher_from_inner_definition(
       );
     }
 
-    Ok(crate::Ref!(identifier))
+    let arguments = match matcher_token.find_child("Arguments") {
+      Some(arguments_token) => arguments_token.borrow().get_children().clone(),
+      None => Vec::new(),
+    };
+
+    let parameter_names = match parameters.get(&identifier) {
+      Some(parameter_names) => parameter_names,
+      None if arguments.is_empty() => return Ok(crate::Ref!(identifier)),
+      None => return Err(format!("`{}` does not take any arguments", identifier)),
+    };
+
+    if parameter_names.len() != arguments.len() {
+      return Err(format!(
+        "`{}` expects {} arguments, but received {}",
+        identifier,
+        parameter_names.len(),
+        arguments.len()
+      ));
+    }
+
+    // Arguments are always captured, it is up to
+    // the pattern function to discard them or not
+    let mut bindings = Vec::with_capacity(arguments.len());
+    for (name, argument) in parameter_names.iter().zip(arguments) {
+      let matcher = construct_matcher_from_pattern_definition(
+        parser_context.clone(),
+        argument,
+        "",
+        true,
+        parameters,
+        actions,
+      )?;
+
+      bindings.push((name.clone(), matcher.0));
+    }
+
+    Ok(SetScopePattern::new_with_bindings(
+      bindings,
+      crate::Ref!(identifier),
+    ))
   } else if matcher_token_name == "SwitchMatcher" {
     let children = matcher_token.get_children();
 
@@ -93,7 +319,8 @@ fn construct_matcher_from_inner_definition(
     let switch_matcher = crate::Switch!();
     let mut _switch_matcher = switch_matcher.borrow_mut();
     for child in children {
-      let matcher = construct_matcher_from_pattern(parser_context.clone(), child.clone())?;
+      let matcher =
+        construct_matcher_from_pattern(parser_context.clone(), child.clone(), parameters, actions)?;
       _switch_matcher.add_pattern(matcher.0);
     }
 
@@ -112,13 +339,39 @@ fn construct_matcher_from_inner_definition(
     let program_matcher = crate::Program!();
     let mut _program_matcher = program_matcher.borrow_mut();
     for child in children {
-      let matcher = construct_matcher_from_pattern(parser_context.clone(), child.clone())?;
+      let matcher =
+        construct_matcher_from_pattern(parser_context.clone(), child.clone(), parameters, actions)?;
       _program_matcher.add_pattern(matcher.0);
     }
 
     drop(_program_matcher);
 
     Ok(program_matcher)
+  } else if matcher_token_name == "ConditionalMatcher" {
+    let children = matcher_token.get_children();
+    let condition = construct_condition(children[0].clone())?;
+    let (then_matcher, _) = construct_matcher_from_pattern(
+      parser_context.clone(),
+      children[1].clone(),
+      parameters,
+      actions,
+    )?;
+    let else_matcher = match matcher_token.find_child("Else") {
+      Some(else_token) => Some(
+        construct_matcher_from_pattern(
+          parser_context.clone(),
+          else_token.borrow().get_children()[0].clone(),
+          parameters,
+          actions,
+        )?
+        .0,
+      ),
+      None => None,
+    };
+
+    Ok(IfPattern::new(condition, then_matcher, else_matcher))
+  } else if matcher_token_name == "MutationMatcher" {
+    construct_mutation(&**matcher_token)
   } else {
     Err("Unkown pattern type".to_string())
   }
@@ -129,6 +382,8 @@ fn construct_matcher_from_pattern_definition(
   token: TokenRef,
   name: &str,
   captured: bool,
+  parameters: &PatternParameters,
+  actions: &ActionRegistry,
 ) -> Result<(MatcherRef, MatcherRef), String> {
   let token = token.borrow();
   let token_name = token.get_name();
@@ -151,8 +406,12 @@ fn construct_matcher_from_pattern_definition(
   }
 
   let matcher_token = matcher_token_result.unwrap();
-  let mut matcher =
-    construct_matcher_from_inner_definition(parser_context.clone(), matcher_token.clone())?;
+  let mut matcher = construct_matcher_from_inner_definition(
+    parser_context.clone(),
+    matcher_token.clone(),
+    parameters,
+    actions,
+  )?;
   let inner_matcher = matcher.clone();
 
   if name != "" {
@@ -163,10 +422,10 @@ fn construct_matcher_from_pattern_definition(
   if let Some(attributes_token) = token.find_child("Attributes") {
     let attributes_token = attributes_token.borrow();
     let attributes_token_children = attributes_token.get_children();
+    let mut attributes = HashMap::<String, String>::new();
 
     if attributes_token_children.len() > 0 {
       // First, collect attributes from token into a map
-      let mut attributes = HashMap::<String, String>::new();
       for child in attributes_token_children {
         let child = child.borrow();
         let child_children = child.get_children();
@@ -175,7 +434,9 @@ fn construct_matcher_from_pattern_definition(
 
         attributes.insert(key, value);
       }
+    }
 
+    if !attributes.is_empty() {
       // Next, move the attributes hashmap into the
       // "Map" matcher, to apply the attributes
       // to a generated token
@@ -195,18 +456,54 @@ fn construct_matcher_from_pattern_definition(
     }
   }
 
+  // Actions run after the attributes are set, in the order they are given
+  if let Some(actions_token) = token.find_child("Actions") {
+    for action_token in actions_token.borrow().get_children() {
+      let action_name = action_token.borrow().get_value().clone();
+      let action = match actions.get(&action_name) {
+        Some(action) => action.clone(),
+        None => return Err(format!("Unknown action `@{}`", action_name)),
+      };
+
+      matcher = crate::Map!(matcher.clone(), move |token, context, scope| {
+        action(token, context, scope)
+      });
+    }
+  }
+
   if has_inner_optional {
     matcher = Optional!(matcher);
   } else if has_inner_not {
     matcher = Not!(matcher);
   }
 
-  if let Some(repeat_range) = token.find_child_fuzzy(&REPEAT_SPECIFIER) {
-    let range = match get_repeat_specifier_range(repeat_range.clone()) {
-      Ok(result) => result,
-      Err(error) => return Err(error),
-    };
+  let repeat_range = match token.find_child_fuzzy(&REPEAT_SPECIFIER) {
+    Some(repeat_range) => Some(get_repeat_specifier_range(repeat_range)?),
+    None => None,
+  };
 
+  if let Some(separator_token) = token.find_child("Separator") {
+    let separator_token = separator_token.borrow();
+    let separator_children = separator_token.get_children();
+    let trailing = match separator_children[0].borrow().get_value().as_str() {
+      "%?" => TrailingSeparator::Allow,
+      "%!" => TrailingSeparator::Require,
+      _ => TrailingSeparator::Forbid,
+    };
+    let (separator, _) = construct_matcher_from_pattern(
+      parser_context.clone(),
+      separator_children[1].clone(),
+      parameters,
+      actions,
+    )?;
+
+    // A repeat specifier allows as many items in a
+    // separated list as it allows iterations of a loop
+    matcher = match repeat_range {
+      Some(range) => SepBy!(loop_iteration_counts(&range); matcher, separator, trailing),
+      None => SepBy!(matcher, separator, trailing),
+    };
+  } else if let Some(range) = repeat_range {
     matcher = Loop!(range; matcher);
   }
 
@@ -226,6 +523,8 @@ fn construct_matcher_from_pattern_definition(
 fn construct_matcher_from_pattern(
   parser_context: ParserContextRef,
   token: TokenRef,
+  parameters: &PatternParameters,
+  actions: &ActionRegistry,
 ) -> Result<(MatcherRef, MatcherRef), String> {
   let _token = token.borrow();
   let token_name = _token.get_name();
@@ -241,6 +540,8 @@ fn construct_matcher_from_pattern(
         _token.get_children()[1].clone(),
         name,
         true,
+        parameters,
+        actions,
       )
     } else {
       construct_matcher_from_pattern_definition(
@@ -248,10 +549,19 @@ fn construct_matcher_from_pattern(
         _token.get_children()[0].clone(),
         "",
         true,
+        parameters,
+        actions,
       )
     }
   } else if token_name == "PatternDefinition" {
-    construct_matcher_from_pattern_definition(parser_context, token.clone(), "", false)
+    construct_matcher_from_pattern_definition(
+      parser_context,
+      token.clone(),
+      "",
+      false,
+      parameters,
+      actions,
+    )
   } else {
     Err(format!(
       "Expected a `PatternDefinitionCaptured`, or `PatternDefinition` token, but received a `{}` token instead",
@@ -266,6 +576,7 @@ fn build_matcher_from_tokens(
   name: String,
   from_file: Option<&str>,
   flags: u32,
+  actions: &ActionRegistry,
 ) -> Result<(MatcherRef, ScopeContextRef), Vec<ParseError>> {
   if root_token.borrow().get_name() != "Script" {
     return Err(vec![ParseError::new(
@@ -277,6 +588,9 @@ fn build_matcher_from_tokens(
   let scoped_matcher = ProgramPattern::new_program(Vec::new(), MatchAction::Continue);
   let scope_context = ScopeContext::new();
   let parse_errors = RefCell::new(Vec::<ParseError>::new());
+  let mut parameters = PatternParameters::new();
+
+  collect_pattern_parameters(&root_token, &mut parameters);
 
   root_matcher.borrow_mut().add_pattern(SetScope!(
     scope_context.clone(),
@@ -301,6 +615,16 @@ fn build_matcher_from_tokens(
         }
       }
 
+      // Expose the header attributes to the script,
+      // i.e. `<if (AdextopaScope.version >= '2') ...>`
+      let attributes = _token
+        .get_attributes()
+        .iter()
+        .map(|(name, value)| (name.clone(), VariableType::String(value.clone())))
+        .collect();
+
+      scope_context.borrow_mut().set("AdextopaScope", VariableType::Map(attributes));
+
       Ok(())
     },
     "AssignmentExpression" => |token| {
@@ -309,7 +633,8 @@ fn build_matcher_from_tokens(
       let token_children = _token.get_children();
       let identifier = token_children[0].borrow();
       let matcher_name = identifier.get_value().clone();
-      let value = &token_children[1];
+      // Parameters (if any) sit between the name and the value
+      let value = token_children.last().unwrap();
       let _value = value.borrow();
       let value_name = _value.get_name();
 
@@ -320,7 +645,7 @@ fn build_matcher_from_tokens(
       } else if value_name == "PatternDefinition" {
         // This is a pattern definition, so turn it into
         // a matcher, and store it as a reference
-        match construct_matcher_from_pattern_definition(parser_context.clone(), value.clone(), &matcher_name, true) {
+        match construct_matcher_from_pattern_definition(parser_context.clone(), value.clone(), &matcher_name, true, &parameters, actions) {
           Ok(defined_matchers) => {
             scope_context.borrow_mut().set(&matcher_name, VariableType::Matcher(defined_matchers.0.clone()));
           },
@@ -347,7 +672,7 @@ fn build_matcher_from_tokens(
       let full_path = Path::new(from_file).parent().unwrap().join(path).canonicalize().unwrap();
       let file_name = full_path.to_str().unwrap();
 
-      let import_result = match compile_script_from_file_internal(file_name, flags) {
+      let import_result = match compile_script_from_file_internal(file_name, flags, actions) {
         Ok(result) => result,
         Err(errors) => {
           let mut _parse_errors = parse_errors.borrow_mut();
@@ -417,7 +742,7 @@ fn build_matcher_from_tokens(
         let child_name = _child.get_name();
 
         if child_name == "PatternDefinitionCaptured" || child_name == "PatternDefinition" {
-          match construct_matcher_from_pattern(parser_context.clone(), child.clone()) {
+          match construct_matcher_from_pattern(parser_context.clone(), child.clone(), &parameters, actions) {
             Ok(defined_matchers) => {
               scoped_matcher.borrow_mut().add_pattern(defined_matchers.0);
             },
@@ -491,6 +816,16 @@ pub fn compile_script(
   name: String,
   from_file: Option<&str>,
   flags: u32,
+) -> Result<(ParserContextRef, MatcherRef, ScopeContextRef), Vec<ParseError>> {
+  compile_script_with_actions(parser, name, from_file, flags, &ActionRegistry::new())
+}
+
+pub fn compile_script_with_actions(
+  parser: ParserRef,
+  name: String,
+  from_file: Option<&str>,
+  flags: u32,
+  actions: &ActionRegistry,
 ) -> Result<(ParserContextRef, MatcherRef, ScopeContextRef), Vec<ParseError>> {
   let parser_context = ParserContext::new(&parser, &name);
 
@@ -498,6 +833,15 @@ pub fn compile_script(
     .borrow()
     .register_matchers(vec![ScriptSwitchMatcher!(), ScriptProgramMatcher!()]);
 
+  // Arguments to pattern functions are themselves pattern definitions,
+  // and separators of separated lists are (possibly captured) patterns
+  (*parser_context)
+    .borrow()
+    .register_matcher_with_name("PatternDefinition", ScriptPatternDefinition!());
+  (*parser_context)
+    .borrow()
+    .register_matcher_with_name("Pattern", ScriptPattern!());
+
   let pattern = crate::Script!();
 
   let result = ParserContext::tokenize(parser_context.clone(), pattern);
@@ -510,6 +854,7 @@ pub fn compile_script(
         name,
         from_file,
         flags,
+        &with_builtin_actions(actions),
       ) {
         Ok(result) => {
           if flags & FLAG_LOG_STDERR > 0 {
@@ -554,9 +899,18 @@ pub fn compile_script_from_str(
   source: &str,
   name: String,
   flags: u32,
+) -> Result<MatcherRef, Vec<ParseError>> {
+  compile_script_from_str_with_actions(source, name, flags, &ActionRegistry::new())
+}
+
+pub fn compile_script_from_str_with_actions(
+  source: &str,
+  name: String,
+  flags: u32,
+  actions: &ActionRegistry,
 ) -> Result<MatcherRef, Vec<ParseError>> {
   let parser = Parser::new(source);
-  match compile_script(parser, name, None, flags) {
+  match compile_script_with_actions(parser, name, None, flags, actions) {
     Ok(result) => Ok(result.1),
     Err(errors) => Err(errors),
   }
@@ -565,23 +919,34 @@ pub fn compile_script_from_str(
 fn compile_script_from_file_internal(
   file_name: &str,
   flags: u32,
+  actions: &ActionRegistry,
 ) -> Result<(ParserContextRef, MatcherRef, ScopeContextRef), Vec<ParseError>> {
   let full_path = Path::new(file_name).canonicalize().unwrap();
   let full_file_name = full_path.to_str().unwrap();
 
   let parser = Parser::new_from_file(full_file_name).unwrap();
-  compile_script(parser, file_name.to_string(), Some(full_file_name), flags)
+  compile_script_with_actions(
+    parser,
+    file_name.to_string(),
+    Some(full_file_name),
+    flags,
+    actions,
+  )
 }
 
 pub fn compile_script_from_file(
   file_name: &str,
   flags: u32,
 ) -> Result<MatcherRef, Vec<ParseError>> {
-  let full_path = Path::new(file_name).canonicalize().unwrap();
-  let full_file_name = full_path.to_str().unwrap();
+  compile_script_from_file_with_actions(file_name, flags, &ActionRegistry::new())
+}
 
-  let parser = Parser::new_from_file(full_file_name).unwrap();
-  match compile_script(parser, file_name.to_string(), Some(full_file_name), flags) {
+pub fn compile_script_from_file_with_actions(
+  file_name: &str,
+  flags: u32,
+  actions: &ActionRegistry,
+) -> Result<MatcherRef, Vec<ParseError>> {
+  match compile_script_from_file_internal(file_name, flags, actions) {
     Ok(result) => Ok(result.1),
     Err(errors) => Err(errors),
   }
@@ -589,15 +954,22 @@ pub fn compile_script_from_file(
 
 #[cfg(test)]
 mod tests {
+  use std::rc::Rc;
+
   use crate::{
     parser::Parser,
     parser_context::{ParserContext, ParserContextRef},
+    scope::VariableType,
     script::current::parser::construct_matcher_from_pattern,
     source_range::SourceRange,
-    ScriptPattern, ScriptPatternDefinition, ScriptProgramMatcher, ScriptSwitchMatcher,
+    token::TokenRef,
+    ScriptPattern, ScriptPatternDefinition, ScriptProgramMatcher, ScriptSwitchMatcher, TokenResult,
   };
 
-  use super::{compile_script_from_file, construct_matcher_from_pattern_definition};
+  use super::{
+    compile_script, compile_script_from_file, compile_script_with_actions,
+    construct_matcher_from_pattern_definition, ActionRegistry, PatternParameters,
+  };
 
   #[test]
   fn it_compiles_a_script_and_returns_a_matcher() {
@@ -717,7 +1089,12 @@ mod tests {
     let result = ParserContext::tokenize(parser_context.clone(), matcher);
 
     if let Ok(token) = result {
-      let recreated_matcher = construct_matcher_from_pattern(parser_context.clone(), token.clone());
+      let recreated_matcher = construct_matcher_from_pattern(
+        parser_context.clone(),
+        token.clone(),
+        &PatternParameters::new(),
+        &ActionRegistry::new(),
+      );
 
       assert_eq!(recreated_matcher.is_ok(), true);
       let recreated_matcher = recreated_matcher.unwrap();
@@ -748,7 +1125,12 @@ mod tests {
     let result = ParserContext::tokenize(parser_context.clone(), matcher);
 
     if let Ok(token) = result {
-      let recreated_matcher = construct_matcher_from_pattern(parser_context.clone(), token.clone());
+      let recreated_matcher = construct_matcher_from_pattern(
+        parser_context.clone(),
+        token.clone(),
+        &PatternParameters::new(),
+        &ActionRegistry::new(),
+      );
 
       assert_eq!(recreated_matcher.is_ok(), true);
       let recreated_matcher = recreated_matcher.unwrap();
@@ -774,7 +1156,12 @@ mod tests {
     let result = ParserContext::tokenize(parser_context.clone(), matcher);
 
     if let Ok(token) = result {
-      let recreated_matcher = construct_matcher_from_pattern(parser_context.clone(), token.clone());
+      let recreated_matcher = construct_matcher_from_pattern(
+        parser_context.clone(),
+        token.clone(),
+        &PatternParameters::new(),
+        &ActionRegistry::new(),
+      );
 
       assert_eq!(recreated_matcher.is_ok(), true);
       let recreated_matcher = recreated_matcher.unwrap();
@@ -800,8 +1187,14 @@ mod tests {
     let result = ParserContext::tokenize(parser_context.clone(), matcher);
 
     if let Ok(token) = result {
-      let recreated_matcher =
-        construct_matcher_from_pattern_definition(parser_context.clone(), token.clone(), "", false);
+      let recreated_matcher = construct_matcher_from_pattern_definition(
+        parser_context.clone(),
+        token.clone(),
+        "",
+        false,
+        &PatternParameters::new(),
+        &ActionRegistry::new(),
+      );
 
       assert_eq!(recreated_matcher.is_ok(), true);
       let recreated_matcher = recreated_matcher.unwrap();
@@ -832,8 +1225,14 @@ mod tests {
     let result = ParserContext::tokenize(parser_context.clone(), matcher);
 
     if let Ok(token) = result {
-      let recreated_matcher =
-        construct_matcher_from_pattern_definition(parser_context.clone(), token.clone(), "", false);
+      let recreated_matcher = construct_matcher_from_pattern_definition(
+        parser_context.clone(),
+        token.clone(),
+        "",
+        false,
+        &PatternParameters::new(),
+        &ActionRegistry::new(),
+      );
 
       assert_eq!(recreated_matcher.is_ok(), true);
       let recreated_matcher = recreated_matcher.unwrap();
@@ -877,8 +1276,14 @@ mod tests {
     let result = ParserContext::tokenize(parser_context.clone(), matcher);
 
     if let Ok(token) = result {
-      let recreated_matcher =
-        construct_matcher_from_pattern_definition(parser_context.clone(), token.clone(), "", false);
+      let recreated_matcher = construct_matcher_from_pattern_definition(
+        parser_context.clone(),
+        token.clone(),
+        "",
+        false,
+        &PatternParameters::new(),
+        &ActionRegistry::new(),
+      );
 
       assert_eq!(recreated_matcher.is_ok(), true);
       let recreated_matcher = recreated_matcher.unwrap();
@@ -898,6 +1303,370 @@ mod tests {
     };
   }
 
+  #[test]
+  fn it_compiles_a_script_with_a_pattern_function() {
+    let parser = Parser::new(
+      r#"<!--[adextopa version='1' name='Lists']
+        List(item, separator) = <{ (<item>) (?<{ <separator> (<item>) }>*) }>
+        # Passing parameters through to another pattern function
+        Csv(item) = <List <item> <=','>>
+        Word = </\w+/>
+      -->
+
+      (<Csv <Word>>)"#,
+    );
+
+    let (_, compiled_matcher, _) = compile_script(parser, "Lists".to_string(), None, 0).unwrap();
+
+    let parser = Parser::new("one,two,three");
+    let parser_context = ParserContext::new(&parser, "Test");
+    let token = ParserContext::tokenize(parser_context, compiled_matcher).unwrap();
+
+    fn collect_words(token: &TokenRef, words: &mut Vec<String>) {
+      let token = token.borrow();
+
+      if token.get_name() == "Word" {
+        words.push(token.get_value().clone());
+      }
+
+      for child in token.get_children() {
+        collect_words(child, words);
+      }
+    }
+
+    let mut words = Vec::new();
+    collect_words(&token, &mut words);
+
+    assert_eq!(token.borrow().get_name(), "Lists");
+    assert_eq!(*token.borrow().get_matched_range(), SourceRange::new(0, 13));
+    assert_eq!(words, vec!["one", "two", "three"]);
+  }
+
+  #[test]
+  fn it_compiles_a_script_with_case_insensitive_equals() {
+    let parser = Parser::new(r#"(<='select'i>)"#);
+    let (_, compiled_matcher, _) = compile_script(parser, "Sql".to_string(), None, 0).unwrap();
+
+    let parser = Parser::new("SeLeCt *");
+    let parser_context = ParserContext::new(&parser, "Test");
+    let token = ParserContext::tokenize(parser_context, compiled_matcher).unwrap();
+    let keyword = token.borrow().find_child("Equals").unwrap();
+
+    assert_eq!(keyword.borrow().get_value(), "SeLeCt");
+
+    let parser = Parser::new("<='select'ia>");
+    if let Err(errors) = compile_script(parser, "Sql".to_string(), None, 0) {
+      assert!(errors[0]
+        .message
+        .contains("Can not use both the `i` and `a` flags"));
+    } else {
+      unreachable!("Test failed!");
+    }
+  }
+
+  #[test]
+  fn it_compiles_a_script_with_a_separated_list() {
+    let parser = Parser::new(
+      r#"<!--[adextopa version='1' name='Lists']
+        Word = </\w+/>
+      -->
+
+      (<Word>{2,} %? (<=','>))"#,
+    );
+
+    let (_, compiled_matcher, _) = compile_script(parser, "Lists".to_string(), None, 0).unwrap();
+
+    let parser = Parser::new("one,two,");
+    let parser_context = ParserContext::new(&parser, "Test");
+    let token = ParserContext::tokenize(parser_context, compiled_matcher.clone()).unwrap();
+    let words = token.borrow().find_child("SepBy").unwrap();
+    let values: Vec<String> = words
+      .borrow()
+      .get_children()
+      .iter()
+      .map(|child| child.borrow().get_value().clone())
+      .collect();
+
+    assert_eq!(*token.borrow().get_matched_range(), SourceRange::new(0, 8));
+    assert_eq!(values, vec!["one", ",", "two", ","]);
+
+    let parser = Parser::new("one");
+    let parser_context = ParserContext::new(&parser, "Test");
+
+    assert!(ParserContext::tokenize(parser_context, compiled_matcher).is_err());
+  }
+
+  #[test]
+  fn it_allows_as_many_separated_items_as_loop_iterations() {
+    let compile = |pattern: &str| {
+      let parser = Parser::new(&format!(
+        "<!--[adextopa version='1' name='Counts']\n  Word = </\\w/>\n-->\n\n{}",
+        pattern
+      ));
+      compile_script(parser, "Counts".to_string(), None, 0)
+        .unwrap()
+        .1
+    };
+
+    let count_words = |matcher, source: &str| {
+      let parser = Parser::new(source);
+      let parser_context = ParserContext::new(&parser, "Test");
+
+      ParserContext::tokenize(parser_context, matcher).map(|token| {
+        let end = token.borrow().get_matched_range().end;
+        source[..end].matches(|c: char| c.is_alphanumeric()).count()
+      })
+    };
+
+    // A loop over `1..3` iterates at most twice, and so does the separated list
+    let looped = compile("(<{ (<Word>) (?<=','>) }>{1,3})");
+    let separated = compile("(<Word>{1,3} % (<=','>))");
+
+    assert_eq!(count_words(looped.clone(), "a,b,c"), Ok(2));
+    assert_eq!(count_words(separated.clone(), "a,b,c"), Ok(2));
+    assert!(count_words(looped, "").is_err());
+    assert!(count_words(separated, "").is_err());
+
+    let looped = compile("(<{ (<Word>) (?<=','>) }>{3})");
+    let separated = compile("(<Word>{3} % (<=','>))");
+
+    assert_eq!(count_words(looped, "a,b,c,d"), Ok(3));
+    assert_eq!(count_words(separated, "a,b,c,d"), Ok(3));
+  }
+
+  #[test]
+  fn it_compiles_a_script_with_a_conditional_pattern() {
+    let parser = Parser::new(
+      r#"<!--[adextopa version='2' name='Versioned']
+        Keyword = <if (AdextopaScope.version >= 2) (<='async'>) else (<='sync'>)>
+      -->
+
+      (<Keyword>)"#,
+    );
+
+    let (_, compiled_matcher, _) =
+      compile_script(parser, "Versioned".to_string(), None, 0).unwrap();
+
+    let parser = Parser::new("async");
+    let parser_context = ParserContext::new(&parser, "Test");
+    let token = ParserContext::tokenize(parser_context, compiled_matcher.clone()).unwrap();
+
+    assert_eq!(*token.borrow().get_matched_range(), SourceRange::new(0, 5));
+
+    let parser = Parser::new("sync");
+    let parser_context = ParserContext::new(&parser, "Test");
+
+    assert!(ParserContext::tokenize(parser_context, compiled_matcher).is_err());
+
+    let parser = Parser::new(
+      r#"<!--[adextopa version='1' name='Versioned']
+        Keyword = <if (AdextopaScope.version >= 2) (<='async'>) else (<='sync'>)>
+      -->
+
+      (<Keyword>)"#,
+    );
+
+    let (_, compiled_matcher, _) =
+      compile_script(parser, "Versioned".to_string(), None, 0).unwrap();
+
+    let parser = Parser::new("sync");
+    let parser_context = ParserContext::new(&parser, "Test");
+    let token = ParserContext::tokenize(parser_context, compiled_matcher).unwrap();
+
+    assert_eq!(*token.borrow().get_matched_range(), SourceRange::new(0, 4));
+
+    let parser = Parser::new("(<if ('1') <='a'>>)");
+    if let Err(errors) = compile_script(parser, "Test".to_string(), None, 0) {
+      assert!(errors[0]
+        .message
+        .contains("Expected a variable reference in `if` condition"));
+    } else {
+      unreachable!("Test failed!");
+    }
+  }
+
+  #[test]
+  fn it_compiles_a_script_that_mutates_the_scope() {
+    let parser = Parser::new(
+      r#"<!--[adextopa version='1' name='Counted']
+        Item = <{ (<='a'>) <incr Count> }>
+      -->
+
+      <set Count = 0>
+      (<Item>+)
+      <if (Count >= 3) (<='!'>)>"#,
+    );
+
+    let (_, compiled_matcher, scope_context) =
+      compile_script(parser, "Counted".to_string(), None, 0).unwrap();
+
+    // Run twice, to make sure the script's own `set` starts the count over
+    for _ in 0..2 {
+      let parser = Parser::new("aaa!");
+      let parser_context = ParserContext::new(&parser, "Test");
+      let token = ParserContext::tokenize(parser_context, compiled_matcher.clone()).unwrap();
+
+      assert_eq!(*token.borrow().get_matched_range(), SourceRange::new(0, 4));
+    }
+
+    // Writes from the script root end up in the scope of the script
+    assert!(matches!(
+      scope_context.borrow().get("Count"),
+      Some(VariableType::Int(3))
+    ));
+
+    let parser = Parser::new("aa!");
+    let parser_context = ParserContext::new(&parser, "Test");
+
+    assert!(ParserContext::tokenize(parser_context, compiled_matcher).is_err());
+
+    let parser = Parser::new("<incr Count 'x'>");
+    if let Err(errors) = compile_script(parser, "Test".to_string(), None, 0) {
+      assert!(errors[0]
+        .message
+        .contains("Expected a number for `incr`, but found `x`"));
+    } else {
+      unreachable!("Test failed!");
+    }
+  }
+
+  #[test]
+  fn it_compiles_a_script_with_builtin_actions() {
+    let parser = Parser::new(
+      r#"<!--[adextopa version='1' name='Values']
+        String = </[^,]+/ @trim @unescape_json>
+        Number = </\s*[\d_]+\s*/ kind='int' @parse_int>
+      -->
+
+      (<Number>) (<=','>) (<String>)"#,
+    );
+
+    let (_, compiled_matcher, _) = compile_script(parser, "Values".to_string(), None, 0).unwrap();
+
+    let parser = Parser::new(r#" 1_024 , \"hello\tworld\" "#);
+    let parser_context = ParserContext::new(&parser, "Test");
+    let token = ParserContext::tokenize(parser_context, compiled_matcher).unwrap();
+    let number = token.borrow().find_child("Number").unwrap();
+    let string = token.borrow().find_child("String").unwrap();
+
+    assert_eq!(number.borrow().get_attribute("kind").unwrap(), "int");
+    assert_eq!(number.borrow().get_attribute("int").unwrap(), "1024");
+    assert_eq!(string.borrow().get_value(), "\"hello\tworld\"");
+
+    let parser = Parser::new("(<=',' @shout>)");
+    if let Err(errors) = compile_script(parser, "Test".to_string(), None, 0) {
+      assert!(errors[0].message.contains("Unknown action `@shout`"));
+    } else {
+      unreachable!("Test failed!");
+    }
+  }
+
+  #[test]
+  fn it_compiles_a_script_with_actions() {
+    let mut actions = ActionRegistry::new();
+    actions.insert(
+      "parse_int".to_string(),
+      Rc::new(|token: TokenRef, _, __| {
+        let value = token
+          .borrow()
+          .get_value()
+          .parse::<i64>()
+          .unwrap_or_default();
+        token.borrow_mut().set_attribute("int", &value.to_string());
+
+        TokenResult!(token.clone())
+      }),
+    );
+    actions.insert(
+      "double".to_string(),
+      Rc::new(|token: TokenRef, _, __| {
+        let value = token.borrow().get_attribute("int").unwrap().parse::<i64>();
+        token
+          .borrow_mut()
+          .set_attribute("int", &(value.unwrap() * 2).to_string());
+
+        TokenResult!(token.clone())
+      }),
+    );
+
+    let parser = Parser::new(
+      r#"<!--[adextopa version='1' name='Numbers']
+        Number = </\d+/ kind='int' @parse_int @double>
+      -->
+
+      (<Number>)"#,
+    );
+
+    let (_, compiled_matcher, _) =
+      compile_script_with_actions(parser, "Numbers".to_string(), None, 0, &actions).unwrap();
+
+    let parser = Parser::new("21");
+    let parser_context = ParserContext::new(&parser, "Test");
+    let token = ParserContext::tokenize(parser_context, compiled_matcher).unwrap();
+    let number = token.borrow().find_child("Number").unwrap();
+
+    assert_eq!(number.borrow().get_attribute("kind").unwrap(), "int");
+    assert_eq!(number.borrow().get_attribute("int").unwrap(), "42");
+
+    let parser = Parser::new("(<=',' @triple>)");
+    if let Err(errors) = compile_script_with_actions(parser, "Test".to_string(), None, 0, &actions)
+    {
+      assert!(errors[0].message.contains("Unknown action `@triple`"));
+    } else {
+      unreachable!("Test failed!");
+    }
+
+    // Host actions take the place of built-in actions of the same name
+    actions.insert(
+      "trim".to_string(),
+      Rc::new(|token: TokenRef, _, __| {
+        token.borrow_mut().set_attribute("trimmed", "false");
+
+        TokenResult!(token.clone())
+      }),
+    );
+
+    let parser = Parser::new(
+      r#"<!--[adextopa version='1' name='Commas']
+        Comma = <=' , ' @trim>
+      -->
+
+      (<Comma>)"#,
+    );
+
+    let (_, compiled_matcher, _) =
+      compile_script_with_actions(parser, "Commas".to_string(), None, 0, &actions).unwrap();
+
+    let parser = Parser::new(" , ");
+    let parser_context = ParserContext::new(&parser, "Test");
+    let token = ParserContext::tokenize(parser_context, compiled_matcher).unwrap();
+    let comma = token.borrow().find_child("Comma").unwrap();
+
+    assert_eq!(comma.borrow().get_value(), " , ");
+    assert_eq!(comma.borrow().get_attribute("trimmed").unwrap(), "false");
+  }
+
+  #[test]
+  fn it_reports_a_pattern_function_called_with_the_wrong_arguments() {
+    let parser = Parser::new(
+      r#"<!--[adextopa version='1' name='Lists']
+        List(item, separator) = <{ (<item>) (?<{ <separator> (<item>) }>*) }>
+        Word = </\w+/>
+      -->
+
+      (<List <Word>>)"#,
+    );
+
+    if let Err(errors) = compile_script(parser, "Lists".to_string(), None, 0) {
+      assert_eq!(errors.len(), 1);
+      assert!(errors[0]
+        .message
+        .contains("`List` expects 2 arguments, but received 1"));
+    } else {
+      unreachable!("Test failed!");
+    };
+  }
+
   #[test]
   fn it_can_construct_a_switch_matcher_from_a_pattern_token1() {
     let parser = Parser::new(r"(<[<='test'>|(?'Derp'</wow/i>)]>)");
@@ -909,7 +1678,12 @@ mod tests {
     let result = ParserContext::tokenize(parser_context.clone(), matcher);
 
     if let Ok(token) = result {
-      let recreated_matcher = construct_matcher_from_pattern(parser_context.clone(), token.clone());
+      let recreated_matcher = construct_matcher_from_pattern(
+        parser_context.clone(),
+        token.clone(),
+        &PatternParameters::new(),
+        &ActionRegistry::new(),
+      );
 
       assert_eq!(recreated_matcher.is_ok(), true);
       let recreated_matcher = recreated_matcher.unwrap();
@@ -935,7 +1709,12 @@ mod tests {
     let result = ParserContext::tokenize(parser_context.clone(), matcher);
 
     if let Ok(token) = result {
-      let recreated_matcher = construct_matcher_from_pattern(parser_context.clone(), token.clone());
+      let recreated_matcher = construct_matcher_from_pattern(
+        parser_context.clone(),
+        token.clone(),
+        &PatternParameters::new(),
+        &ActionRegistry::new(),
+      );
 
       assert_eq!(recreated_matcher.is_ok(), true);
       let recreated_matcher = recreated_matcher.unwrap();
@@ -961,7 +1740,12 @@ mod tests {
     let result = ParserContext::tokenize(parser_context.clone(), matcher);
 
     if let Ok(token) = result {
-      let recreated_matcher = construct_matcher_from_pattern(parser_context.clone(), token.clone());
+      let recreated_matcher = construct_matcher_from_pattern(
+        parser_context.clone(),
+        token.clone(),
+        &PatternParameters::new(),
+        &ActionRegistry::new(),
+      );
 
       assert_eq!(recreated_matcher.is_ok(), true);
       let recreated_matcher = recreated_matcher.unwrap().0;
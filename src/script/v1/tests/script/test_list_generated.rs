@@ -0,0 +1,166 @@
+// This file is generated by `adextopa_core::codegen`. Do not edit it by hand.
+
+#[allow(unused_imports)]
+use std::convert::TryFrom;
+
+#[allow(unused_imports)]
+use adextopa_core::codegen::runtime::{self, Text};
+#[allow(unused_imports)]
+use adextopa_core::parse_error::ParseError;
+#[allow(unused_imports)]
+use adextopa_core::source_range::SourceRange;
+#[allow(unused_imports)]
+use adextopa_core::token::TokenRef;
+
+#[allow(dead_code)]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Number {
+  pub range: SourceRange,
+  pub value: String,
+}
+
+impl TryFrom<TokenRef> for Number {
+  type Error = ParseError;
+
+  fn try_from(token: TokenRef) -> Result<Self, Self::Error> {
+    runtime::expect_name(&token, &["Number"])?;
+    let text = Text::try_from(token)?;
+
+    Ok(Self {
+      range: text.range,
+      value: text.value,
+    })
+  }
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Name {
+  pub range: SourceRange,
+  pub value: String,
+}
+
+impl TryFrom<TokenRef> for Name {
+  type Error = ParseError;
+
+  fn try_from(token: TokenRef) -> Result<Self, Self::Error> {
+    runtime::expect_name(&token, &["Name"])?;
+    let text = Text::try_from(token)?;
+
+    Ok(Self {
+      range: text.range,
+      value: text.value,
+    })
+  }
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Clone)]
+pub enum Value {
+  Number(Number),
+  Name(Name),
+}
+
+impl TryFrom<TokenRef> for Value {
+  type Error = ParseError;
+
+  fn try_from(token: TokenRef) -> Result<Self, Self::Error> {
+    runtime::expect_name(&token, &["Number", "Name"])?;
+
+    let name = token.borrow().get_name().clone();
+
+    match name.as_str() {
+      "Number" => Ok(Self::Number(Number::try_from(token)?)),
+      "Name" => Ok(Self::Name(Name::try_from(token)?)),
+      _ => Err(runtime::unexpected_token(&token, &["Number", "Name"])),
+    }
+  }
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Clone)]
+pub struct Values {
+  pub range: SourceRange,
+  pub value: Value,
+}
+
+impl TryFrom<TokenRef> for Values {
+  type Error = ParseError;
+
+  fn try_from(token: TokenRef) -> Result<Self, Self::Error> {
+    #[allow(unused_mut)]
+    let mut children = runtime::Children::collect(
+      &token,
+      &["Values"],
+      &[
+        (&["Number", "Name"], runtime::Multiplicity::One),
+      ],
+      false,
+    )?;
+
+    Ok(Self {
+      range: children.range(),
+      value: Value::try_from(children.one(0))?,
+    })
+  }
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Clone)]
+pub struct ListLoop {
+  pub range: SourceRange,
+  pub values: Vec<Values>,
+}
+
+impl TryFrom<TokenRef> for ListLoop {
+  type Error = ParseError;
+
+  fn try_from(token: TokenRef) -> Result<Self, Self::Error> {
+    #[allow(unused_mut)]
+    let mut children = runtime::Children::collect(
+      &token,
+      &["Loop"],
+      &[
+        (&["Values"], runtime::Multiplicity::Many),
+      ],
+      true,
+    )?;
+
+    Ok(Self {
+      range: children.range(),
+      values: children
+        .many(0)
+        .into_iter()
+        .map(Values::try_from)
+        .collect::<Result<Vec<_>, _>>()?,
+    })
+  }
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Clone)]
+pub struct List {
+  pub range: SourceRange,
+  pub loop_: ListLoop,
+}
+
+impl TryFrom<TokenRef> for List {
+  type Error = ParseError;
+
+  fn try_from(token: TokenRef) -> Result<Self, Self::Error> {
+    #[allow(unused_mut)]
+    let mut children = runtime::Children::collect(
+      &token,
+      &["List"],
+      &[
+        (&["Loop"], runtime::Multiplicity::One),
+      ],
+      false,
+    )?;
+
+    Ok(Self {
+      range: children.range(),
+      loop_: ListLoop::try_from(children.one(0))?,
+    })
+  }
+}
@@ -0,0 +1,404 @@
+use std::{
+  fs::File,
+  io::{Error, ErrorKind, Read},
+};
+
+use crate::{
+  matcher::MatcherRef, parse_error::ParseError, parser::Parser, parser_context::ParserContext,
+  source_range::SourceRange, token_iterator::TokenIterator,
+};
+
+pub const DEFAULT_CHUNK_SIZE: usize = 64 * 1024;
+pub const DEFAULT_CAPACITY: usize = 16 * 1024 * 1024;
+pub const DEFAULT_LOOKAHEAD: usize = 1024;
+
+/// Where a `Parser` gets its input from. Offsets are always into the
+/// whole input, but only the part between `start()` and `end()` needs
+/// to be in memory, and more of it is loaded as matchers ask for it
+pub trait Source {
+  /// Offset of the first byte still in memory
+  fn start(&self) -> usize;
+
+  /// Offset just past the last byte loaded so far
+  fn end(&self) -> usize;
+
+  /// Whether everything has been loaded
+  fn is_complete(&self) -> bool;
+
+  /// Load until `end()` reaches `offset`, or everything is loaded
+  fn load_to(&mut self, offset: usize) -> Result<(), ParseError>;
+
+  /// The input in memory, from `start()` up to `end()`
+  fn buffer(&self) -> &String;
+
+  /// The input between `start` and `end`, which must be in memory
+  fn slice(&self, start: usize, end: usize) -> &str {
+    &self.buffer()[start - self.start()..end - self.start()]
+  }
+
+  /// Drop everything before `offset` from memory
+  fn release(&mut self, offset: usize);
+
+  /// How far past the end of what a matcher matched it may need to see
+  /// (i.e. the rest of a regex match). A match that ends closer than this
+  /// to the end of what was loaded is only trusted once more is loaded
+  fn get_lookahead(&self) -> usize {
+    0
+  }
+
+  /// Apply `matcher` over and over, like the body of a top-level `Loop!`,
+  /// yielding each token as soon as it is matched. See `TokenIterator`
+  fn tokens(self, name: &str, matcher: MatcherRef) -> TokenIterator
+  where
+    Self: Sized + 'static,
+  {
+    let parser = Parser::new_from_source(self, "");
+
+    ParserContext::tokens(ParserContext::new(&parser, name), matcher)
+  }
+}
+
+// A source that is already complete
+impl Source for String {
+  fn start(&self) -> usize {
+    0
+  }
+
+  fn end(&self) -> usize {
+    self.len()
+  }
+
+  fn is_complete(&self) -> bool {
+    true
+  }
+
+  fn load_to(&mut self, _: usize) -> Result<(), ParseError> {
+    Ok(())
+  }
+
+  fn buffer(&self) -> &String {
+    self
+  }
+
+  fn release(&mut self, _: usize) {}
+}
+
+/// Input that is read in chunks, as it is needed, instead of all at
+/// once like `Parser::new_from_file`. Input before a `release`d offset
+/// is dropped, and loading fails rather than holding more than
+/// `capacity` bytes in memory
+pub struct ChunkedSource {
+  reader: Option<Box<dyn Read>>,
+  buffer: String,
+  // Bytes of a character that was split between chunks
+  pending: Vec<u8>,
+  start: usize,
+  chunk_size: usize,
+  capacity: usize,
+  lookahead: usize,
+}
+
+impl ChunkedSource {
+  pub fn from_reader(reader: impl Read + 'static, chunk_size: usize) -> Self {
+    Self {
+      reader: Some(Box::new(reader)),
+      buffer: String::new(),
+      pending: Vec::new(),
+      start: 0,
+      chunk_size: chunk_size.max(1),
+      capacity: DEFAULT_CAPACITY,
+      lookahead: DEFAULT_LOOKAHEAD,
+    }
+  }
+
+  pub fn from_file(filename: &str) -> Result<Self, Error> {
+    Ok(Self::from_reader(File::open(filename)?, DEFAULT_CHUNK_SIZE))
+  }
+
+  pub fn set_capacity(&mut self, capacity: usize) {
+    self.capacity = capacity;
+  }
+
+  pub fn set_lookahead(&mut self, lookahead: usize) {
+    self.lookahead = lookahead;
+  }
+
+  fn read_chunk(&mut self) -> Result<(), Error> {
+    let reader = match &mut self.reader {
+      Some(reader) => reader,
+      None => return Ok(()),
+    };
+
+    let mut chunk = vec![0; self.chunk_size];
+    let size = loop {
+      match reader.read(&mut chunk) {
+        Err(error) if error.kind() == ErrorKind::Interrupted => continue,
+        result => break result?,
+      }
+    };
+
+    if size == 0 {
+      self.reader = None;
+
+      if !self.pending.is_empty() {
+        return Err(Error::new(
+          ErrorKind::InvalidData,
+          "stream did not end with valid UTF-8",
+        ));
+      }
+
+      return Ok(());
+    }
+
+    self.pending.extend_from_slice(&chunk[..size]);
+
+    // Keep the start of a character that was cut off for the next chunk
+    let valid = match std::str::from_utf8(&self.pending) {
+      Ok(text) => text.len(),
+      Err(error) if error.error_len().is_none() => error.valid_up_to(),
+      Err(error) => return Err(Error::new(ErrorKind::InvalidData, error)),
+    };
+
+    let rest = self.pending.split_off(valid);
+    self
+      .buffer
+      .push_str(&String::from_utf8_lossy(&self.pending));
+    self.pending = rest;
+
+    Ok(())
+  }
+}
+
+impl Source for ChunkedSource {
+  fn start(&self) -> usize {
+    self.start
+  }
+
+  fn end(&self) -> usize {
+    self.start + self.buffer.len()
+  }
+
+  fn is_complete(&self) -> bool {
+    self.reader.is_none()
+  }
+
+  fn load_to(&mut self, offset: usize) -> Result<(), ParseError> {
+    while self.end() < offset && !self.is_complete() {
+      if self.buffer.len() >= self.capacity {
+        return Err(ParseError::new_with_range(
+          &format!(
+            "More than {} bytes of input are needed in memory at once",
+            self.capacity
+          ),
+          SourceRange::new(self.start, self.end()),
+        ));
+      }
+
+      if let Err(error) = self.read_chunk() {
+        self.reader = None;
+
+        return Err(ParseError::new_with_range(
+          &error.to_string(),
+          SourceRange::new(self.end(), self.end()),
+        ));
+      }
+    }
+
+    Ok(())
+  }
+
+  fn buffer(&self) -> &String {
+    &self.buffer
+  }
+
+  fn release(&mut self, offset: usize) {
+    let offset = offset.clamp(self.start, self.end());
+    let mut index = offset - self.start;
+
+    while !self.buffer.is_char_boundary(index) {
+      index -= 1;
+    }
+
+    self.buffer.drain(..index);
+    self.start += index;
+  }
+
+  fn get_lookahead(&self) -> usize {
+    self.lookahead
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::io::Read;
+
+  use super::{ChunkedSource, Source};
+  use crate::{
+    parser::Parser, parser_context::ParserContext, source_range::SourceRange, Discard, Loop,
+    Matches, Program, Switch,
+  };
+
+  // Hands out a few bytes at a time, like a slow socket
+  struct Trickle {
+    data: Vec<u8>,
+    offset: usize,
+  }
+
+  impl Read for Trickle {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+      let size = buf.len().min(3).min(self.data.len() - self.offset);
+      buf[..size].copy_from_slice(&self.data[self.offset..self.offset + size]);
+      self.offset += size;
+
+      Ok(size)
+    }
+  }
+
+  fn trickle(source: &str) -> Trickle {
+    Trickle {
+      data: source.as_bytes().to_vec(),
+      offset: 0,
+    }
+  }
+
+  fn line() -> crate::matcher::MatcherRef {
+    Program!("Line";
+      Matches!("Word"; r"[^\s]+"),
+      Discard!(Matches!(r"[ ]+")),
+      Matches!("Number"; r"\d+"),
+      Discard!(Matches!(r"\n")),
+    )
+  }
+
+  #[test]
+  fn it_yields_tokens_as_the_input_arrives() {
+    let source = "hello 12\nworld 345\nagain 6\n";
+    let mut chunked_source = ChunkedSource::from_reader(trickle(source), 4);
+    chunked_source.set_lookahead(2);
+
+    let mut tokens = chunked_source.tokens("Test", line());
+    let mut lines = Vec::new();
+    while let Some(token) = tokens.next() {
+      let token = token.unwrap();
+      let token = token.borrow();
+
+      lines.push((
+        token.get_children()[0].borrow().get_value().clone(),
+        *token.get_children()[1].borrow().get_matched_range(),
+      ));
+
+      // Only a window around the current line is kept in memory
+      let parser = tokens.get_context().borrow().get_parser();
+      assert!(parser.borrow().get_source().len() < 20);
+    }
+
+    assert_eq!(
+      lines,
+      vec![
+        ("hello".to_string(), SourceRange::new(6, 8)),
+        ("world".to_string(), SourceRange::new(15, 18)),
+        ("again".to_string(), SourceRange::new(25, 26)),
+      ]
+    );
+  }
+
+  #[test]
+  fn it_loads_input_as_matchers_need_it() {
+    let source = "hello 12\nworld 345\nagain 6\n";
+    let parser = Parser::new_from_source(ChunkedSource::from_reader(trickle(source), 2), "");
+    let parser_context = ParserContext::new(&parser, "Test");
+    let token = ParserContext::tokenize(parser_context, Loop!(1..; "Lines"; line())).unwrap();
+
+    // The same as parsing it all at once
+    let parser = Parser::new(source);
+    let parser_context = ParserContext::new(&parser, "Test");
+    let expected = ParserContext::tokenize(parser_context, Loop!(1..; "Lines"; line())).unwrap();
+
+    assert_eq!(
+      token.borrow().get_children().len(),
+      expected.borrow().get_children().len()
+    );
+    assert_eq!(
+      *token.borrow().get_children()[2]
+        .borrow()
+        .get_matched_range(),
+      SourceRange::new(19, 27)
+    );
+    assert_eq!(
+      token.borrow().get_children()[2].borrow().get_children()[0]
+        .borrow()
+        .get_value(),
+      "again"
+    );
+  }
+
+  #[test]
+  fn it_reads_more_when_a_match_could_continue() {
+    let matcher = Switch!(Matches!("Number"; r"\d+"), Discard!(Matches!(r",")));
+    let tokens = ChunkedSource::from_reader(trickle("12345,6"), 2).tokens("Test", matcher);
+    let values: Vec<String> = tokens
+      .map(|token| token.unwrap().borrow().get_value().clone())
+      .collect();
+
+    assert_eq!(values, vec!["12345", "6"]);
+  }
+
+  #[test]
+  fn it_keeps_characters_split_between_chunks() {
+    let mut source = ChunkedSource::from_reader(trickle("aé€b"), 2);
+
+    source.load_to(3).unwrap();
+    assert_eq!(source.slice(0, source.end()), "aé");
+
+    source.release(2);
+    assert_eq!((source.start(), source.slice(1, source.end())), (1, "é"));
+
+    source.load_to(usize::MAX).unwrap();
+    assert!(source.is_complete());
+    assert_eq!(
+      (source.start(), source.end(), source.slice(1, 7)),
+      (1, 7, "é€b")
+    );
+  }
+
+  #[test]
+  fn it_fails_instead_of_holding_too_much_input() {
+    let mut source = ChunkedSource::from_reader(trickle("12 345678901234 5"), 2);
+    source.set_capacity(8);
+    source.set_lookahead(0);
+
+    let mut tokens = source.tokens(
+      "Test",
+      Switch!(Matches!("Number"; r"\d+"), Discard!(Matches!(r" "))),
+    );
+
+    assert_eq!(tokens.next().unwrap().unwrap().borrow().get_value(), "12");
+
+    match tokens.next() {
+      Some(Err(error)) => {
+        assert!(error.message.contains("More than 8 bytes"))
+      }
+      result => unreachable!("{:?}", result),
+    }
+
+    assert_eq!(tokens.next(), None);
+  }
+
+  #[test]
+  fn it_fails_on_unmatched_input() {
+    let matcher = Matches!("Number"; r"\d+");
+    let mut tokens = ChunkedSource::from_reader(trickle("12ab"), 2).tokens("Test", matcher);
+
+    assert_eq!(tokens.next().unwrap().unwrap().borrow().get_value(), "12");
+
+    let error = tokens.next().unwrap().unwrap_err();
+    assert_eq!(error.range, Some(SourceRange::new(2, 3)));
+    assert!(error.message.contains("Unexpected input `a`"));
+    assert_eq!(tokens.next(), None);
+
+    let source = ChunkedSource::from_reader(&[0x61, 0xff, 0x61][..], 2);
+    let mut tokens = source.tokens("Test", Matches!(r"\w"));
+
+    assert!(matches!(tokens.next(), Some(Err(_))));
+  }
+}
@@ -1,5 +1,3 @@
-use substring::Substring;
-
 use super::parser::ParserRef;
 
 #[derive(Copy, Clone, Debug, PartialEq)]
@@ -18,11 +16,7 @@ impl SourceRange {
   }
 
   pub fn to_string<'a>(&self, parser: &ParserRef) -> String {
-    parser
-      .borrow()
-      .source
-      .substring(self.start, self.end)
-      .to_string()
+    parser.borrow().slice(self.start, self.end).to_string()
   }
 
   pub fn clone_with_len(&self, len: usize) -> Self {
@@ -68,4 +62,12 @@ mod tests {
 
     assert_eq!(sr1.to_string(&parser), "Hello");
   }
+
+  #[test]
+  fn it_gets_a_range_slice_by_byte_offsets() {
+    let parser = Parser::new("héllo wörld");
+    let sr1 = SourceRange::new(7, 13);
+
+    assert_eq!(sr1.to_string(&parser), "wörld");
+  }
 }
@@ -116,6 +116,8 @@ impl PartialEq for TokenRefInner {
 // Token<'a> is required because name: &'a str is required (name lives as long as the underlying struct)
 pub trait Token {
   fn get_parser(&self) -> crate::parser::ParserRef;
+  /// Only swaps the parser: the values of the token are kept as they are
+  fn set_parser(&mut self, parser: &crate::parser::ParserRef);
   fn get_captured_range(&self) -> &crate::source_range::SourceRange;
   fn get_captured_range_mut(&mut self) -> &mut crate::source_range::SourceRange;
   fn set_captured_range(&mut self, range: crate::source_range::SourceRange);
@@ -0,0 +1,236 @@
+use std::collections::{HashSet, VecDeque};
+
+use crate::{
+  matcher::{MatcherFailure, MatcherRef, MatcherSuccess},
+  parse_error::ParseError,
+  parser_context::{ParserContext, ParserContextRef},
+  source_range::SourceRange,
+  token::TokenRef,
+};
+
+/// Applies a matcher at the current offset over and over, yielding each
+/// token it matches (see `ParserContext::tokens` and `Source::tokens`).
+/// Input that the matcher discards is always skipped, and tokens can be
+/// skipped by name (i.e. whitespace and comments). Iteration ends at the
+/// end of the input, or after the first error.
+///
+/// Input before the current offset is released from the source as it
+/// goes (apart from the last `lookahead` bytes, so that anchors and `\b`
+/// can still see what came before), so a streamed source only holds
+/// about as much input as one match needs
+pub struct TokenIterator {
+  context: ParserContextRef,
+  matcher: MatcherRef,
+  skipped: HashSet<String>,
+  queue: VecDeque<TokenRef>,
+  is_done: bool,
+}
+
+impl TokenIterator {
+  pub fn new(context: ParserContextRef, matcher: MatcherRef) -> Self {
+    Self {
+      context,
+      matcher,
+      skipped: HashSet::new(),
+      queue: VecDeque::new(),
+      is_done: false,
+    }
+  }
+
+  /// Consume tokens named `name` without yielding them
+  pub fn skipping(mut self, name: &str) -> Self {
+    self.skipped.insert(name.to_string());
+    self
+  }
+
+  pub fn get_context(&self) -> ParserContextRef {
+    self.context.clone()
+  }
+
+  fn error(&mut self, message: &str, range: SourceRange) -> Option<Result<TokenRef, ParseError>> {
+    self.is_done = true;
+
+    Some(Err(ParseError::new_with_range(
+      &self.context.borrow().get_error_as_string(message, &range),
+      range,
+    )))
+  }
+
+  fn push_token(&mut self, token: TokenRef) {
+    if !self.skipped.contains(token.borrow().get_name()) {
+      self.queue.push_back(token);
+    }
+  }
+}
+
+impl Iterator for TokenIterator {
+  type Item = Result<TokenRef, ParseError>;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    loop {
+      if let Some(token) = self.queue.pop_front() {
+        return Some(Ok(token));
+      }
+
+      if self.is_done {
+        return None;
+      }
+
+      let offset = self.context.borrow().offset;
+      let parser = self.context.borrow().get_parser();
+
+      {
+        let mut parser = parser.borrow_mut();
+        let lookahead = parser.get_lookahead();
+        parser.release(offset.start.saturating_sub(lookahead));
+      }
+
+      let result = if self.context.borrow().is_at_end(offset.start) {
+        Ok(MatcherSuccess::None)
+      } else {
+        let scope = self.context.borrow().scope.clone();
+        self
+          .matcher
+          .borrow()
+          .exec(self.matcher.clone(), self.context.clone(), scope)
+      };
+
+      // What was matched can't be trusted if the input ended early
+      if let Some(error) = parser.borrow_mut().take_error() {
+        self.is_done = true;
+        return Some(Err(error));
+      }
+
+      let end = match result {
+        Ok(MatcherSuccess::Token(token)) => {
+          ParserContext::collect_errors(token.clone(), token.clone(), true);
+
+          let end = token.borrow().get_matched_range().end;
+          self.push_token(token);
+          end
+        }
+        Ok(MatcherSuccess::ProxyChildren(token)) => {
+          ParserContext::collect_errors(token.clone(), token.clone(), true);
+
+          for child in token.borrow().get_children() {
+            self.push_token(child.clone());
+          }
+
+          let end = token.borrow().get_matched_range().end;
+          end
+        }
+        Ok(MatcherSuccess::Skip(amount)) => (offset.start as isize + amount) as usize,
+        Ok(MatcherSuccess::Break((_, result))) => {
+          if let MatcherSuccess::Token(token) = *result {
+            self.push_token(token);
+          }
+
+          self.is_done = true;
+          continue;
+        }
+        Ok(_) => {
+          self.is_done = true;
+          continue;
+        }
+        Err(MatcherFailure::Error(error)) => {
+          self.is_done = true;
+          return Some(Err(error));
+        }
+        Err(MatcherFailure::Fail) => {
+          let input = self.context.borrow().debug_range(4);
+          let unexpected = match input.chars().next() {
+            Some(c) => c.to_string(),
+            None => String::new(),
+          };
+          let next = offset.start + unexpected.len();
+
+          return self.error(
+            &format!("Unexpected input `{}`", unexpected),
+            SourceRange::new(offset.start, next),
+          );
+        }
+      };
+
+      // Nothing was consumed, so the same thing would match forever
+      if end <= offset.start {
+        self.queue.clear();
+        return self.error(
+          "Matcher did not consume any input",
+          SourceRange::new(offset.start, offset.start),
+        );
+      }
+
+      self.context.borrow_mut().set_start(end);
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::{
+    parser::Parser, parser_context::ParserContext, source_range::SourceRange, Discard, Equals,
+    Matches, Switch,
+  };
+
+  #[test]
+  fn it_lexes_one_token_at_a_time() {
+    let parser = Parser::new("let x = 42; // answer\nlet y = x;");
+    let parser_context = ParserContext::new(&parser, "Test");
+    let lexer = Switch!(
+      Matches!("Whitespace"; r"\s+"),
+      Matches!("Comment"; r"//[^\n]*"),
+      Matches!("Keyword"; r"let\b"),
+      Matches!("Identifier"; r"[a-z]+"),
+      Matches!("Number"; r"\d+"),
+      Equals!("Operator"; "="),
+      Discard!(Equals!(";")),
+    );
+
+    let tokens: Vec<(String, String)> = ParserContext::tokens(parser_context, lexer)
+      .skipping("Whitespace")
+      .skipping("Comment")
+      .map(|token| {
+        let token = token.unwrap();
+        let token = token.borrow();
+        (token.get_name().clone(), token.get_value().clone())
+      })
+      .collect();
+
+    let expected = [
+      ("Keyword", "let"),
+      ("Identifier", "x"),
+      ("Operator", "="),
+      ("Number", "42"),
+      ("Keyword", "let"),
+      ("Identifier", "y"),
+      ("Operator", "="),
+      ("Identifier", "x"),
+    ];
+
+    assert_eq!(
+      tokens,
+      expected
+        .iter()
+        .map(|(name, value)| (name.to_string(), value.to_string()))
+        .collect::<Vec<_>>()
+    );
+  }
+
+  #[test]
+  fn it_stops_at_unexpected_input() {
+    let parser = Parser::new("12 ab");
+    let parser_context = ParserContext::new(&parser, "Test");
+    let mut tokens = ParserContext::tokens(
+      parser_context,
+      Switch!(Matches!("Number"; r"\d+"), Discard!(Matches!(r"\s+"))),
+    );
+
+    assert_eq!(tokens.next().unwrap().unwrap().borrow().get_value(), "12");
+
+    let error = tokens.next().unwrap().unwrap_err();
+    assert_eq!(error.range, Some(SourceRange::new(3, 4)));
+    assert!(error.message.contains("Unexpected input `a`"));
+
+    assert!(tokens.next().is_none());
+  }
+}
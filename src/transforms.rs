@@ -0,0 +1,506 @@
+//! Reusable token transforms, for use as the success function of a `Map`
+//! matcher, i.e. `Map!(Matches!(r#"(?:[^"\\]|\\.)*"#), unescape(EscapeDialect::Json))`.
+//! Each one rewrites the value of the token (or sets an attribute on it), and
+//! turns the token into an error token if its value is malformed
+
+use std::rc::Rc;
+
+use crate::matcher::{MatcherFailure, MatcherSuccess};
+use crate::matchers::error::new_error_token_with_range;
+use crate::parser_context::ParserContextRef;
+use crate::scope_context::ScopeContextRef;
+use crate::source_range::SourceRange;
+use crate::token::TokenRef;
+use crate::TokenResult;
+
+/// Attribute set by `parse_int`, holding the integer in base 10
+pub const INT_ATTRIBUTE: &str = "int";
+/// Attribute set by `parse_float`
+pub const FLOAT_ATTRIBUTE: &str = "float";
+
+pub type Transform =
+  Rc<dyn Fn(TokenRef, ParserContextRef, ScopeContextRef) -> Result<MatcherSuccess, MatcherFailure>>;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum EscapeDialect {
+  /// `\a`, `\?`, octal (`\101`), `\xHH`, `\uHHHH` and `\UHHHHHHHH`
+  C,
+  /// Only `\"`, `\\`, `\/`, `\b`, `\f`, `\n`, `\r`, `\t` and `\uHHHH`
+  Json,
+  /// `\v`, `\0`, `\xHH`, `\uHHHH`, `\u{H...}`, line continuations,
+  /// and any other character escaping itself
+  Js,
+}
+
+// A bad escape, as a byte range into the value
+#[derive(Debug, PartialEq)]
+struct EscapeError {
+  start: usize,
+  end: usize,
+  message: String,
+}
+
+fn take_hex(chars: &[(usize, char)], index: &mut usize, min: usize, max: usize) -> Option<u32> {
+  let mut value: u32 = 0;
+  let mut count = 0;
+
+  while count < max {
+    let digit = match chars.get(*index).and_then(|(_, c)| c.to_digit(16)) {
+      Some(digit) => digit,
+      None => break,
+    };
+
+    value = value.checked_mul(16)?.checked_add(digit)?;
+    *index += 1;
+    count += 1;
+  }
+
+  if count < min {
+    return None;
+  }
+
+  Some(value)
+}
+
+fn unescape_str(value: &str, dialect: EscapeDialect) -> Result<String, EscapeError> {
+  let chars: Vec<(usize, char)> = value.char_indices().collect();
+  let mut result = String::with_capacity(value.len());
+  let mut index = 0;
+
+  while index < chars.len() {
+    let (start, c) = chars[index];
+    index += 1;
+
+    if c != '\\' {
+      result.push(c);
+      continue;
+    }
+
+    let error = |index: usize, message: &str| {
+      let end = chars.get(index).map_or(value.len(), |(offset, _)| *offset);
+
+      EscapeError {
+        start,
+        end,
+        message: message.to_string(),
+      }
+    };
+
+    let escaped = match chars.get(index) {
+      Some((_, escaped)) => *escaped,
+      None => return Err(error(index, "Unterminated escape sequence")),
+    };
+    index += 1;
+
+    let unescaped = match (escaped, dialect) {
+      ('"', _) | ('\\', _) => Some(escaped),
+      ('n', _) => Some('\n'),
+      ('t', _) => Some('\t'),
+      ('r', _) => Some('\r'),
+      ('b', _) => Some('\u{8}'),
+      ('f', _) => Some('\u{c}'),
+      ('/', EscapeDialect::Json) => Some('/'),
+      ('\'', EscapeDialect::C | EscapeDialect::Js) => Some('\''),
+      ('v', EscapeDialect::C | EscapeDialect::Js) => Some('\u{b}'),
+      ('a', EscapeDialect::C) => Some('\u{7}'),
+      ('?', EscapeDialect::C) => Some('?'),
+      ('0'..='7', EscapeDialect::C) => {
+        let mut code = escaped.to_digit(8).unwrap_or_default();
+        for _ in 0..2 {
+          match chars.get(index).and_then(|(_, c)| c.to_digit(8)) {
+            Some(digit) => {
+              code = code * 8 + digit;
+              index += 1;
+            }
+            None => break,
+          }
+        }
+
+        char::from_u32(code)
+      }
+      ('0', EscapeDialect::Js) if !matches!(chars.get(index), Some((_, '0'..='9'))) => Some('\0'),
+      ('0'..='9', EscapeDialect::Js) => {
+        return Err(error(index, "Octal escape sequences are not allowed"))
+      }
+      ('x', EscapeDialect::C) => match take_hex(&chars, &mut index, 1, 8) {
+        Some(code) => match char::from_u32(code) {
+          Some(c) => Some(c),
+          None => return Err(error(index, "Escape sequence is not a valid character")),
+        },
+        None => return Err(error(index, "Invalid hex escape sequence")),
+      },
+      ('x', EscapeDialect::Js) => match take_hex(&chars, &mut index, 2, 2) {
+        Some(code) => char::from_u32(code),
+        None => return Err(error(index, "Invalid hex escape sequence")),
+      },
+      ('U', EscapeDialect::C) => {
+        match take_hex(&chars, &mut index, 8, 8).and_then(char::from_u32) {
+          Some(c) => Some(c),
+          None => return Err(error(index, "Invalid unicode escape sequence")),
+        }
+      }
+      ('u', EscapeDialect::Js) if matches!(chars.get(index), Some((_, '{'))) => {
+        index += 1;
+        let code = take_hex(&chars, &mut index, 1, 6);
+
+        match (code.and_then(char::from_u32), chars.get(index)) {
+          (Some(c), Some((_, '}'))) => {
+            index += 1;
+            Some(c)
+          }
+          _ => return Err(error(index, "Invalid unicode escape sequence")),
+        }
+      }
+      ('u', _) => {
+        let high = match take_hex(&chars, &mut index, 4, 4) {
+          Some(code) => code,
+          None => return Err(error(index, "Invalid unicode escape sequence")),
+        };
+
+        if (0xD800..0xDC00).contains(&high) && dialect != EscapeDialect::C {
+          // A surrogate pair, i.e. `😀`
+          let mut low_index = index + 2;
+          let low = match (chars.get(index), chars.get(index + 1)) {
+            (Some((_, '\\')), Some((_, 'u'))) => take_hex(&chars, &mut low_index, 4, 4),
+            _ => None,
+          };
+
+          match low {
+            Some(low) if (0xDC00..0xE000).contains(&low) => {
+              index = low_index;
+              char::from_u32(0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00))
+            }
+            _ => {
+              return Err(error(
+                index,
+                "Unpaired surrogate in unicode escape sequence",
+              ))
+            }
+          }
+        } else {
+          match char::from_u32(high) {
+            Some(c) => Some(c),
+            None => return Err(error(index, "Invalid unicode escape sequence")),
+          }
+        }
+      }
+      // Line continuations
+      ('\r', EscapeDialect::C | EscapeDialect::Js) => {
+        if let Some((_, '\n')) = chars.get(index) {
+          index += 1;
+        }
+
+        None
+      }
+      ('\n', EscapeDialect::C | EscapeDialect::Js) => None,
+      ('\u{2028}' | '\u{2029}', EscapeDialect::Js) => None,
+      (_, EscapeDialect::Js) => Some(escaped),
+      (_, _) => {
+        return Err(error(
+          index,
+          &format!("Invalid escape sequence `\\{}`", escaped),
+        ))
+      }
+    };
+
+    if let Some(c) = unescaped {
+      result.push(c);
+    }
+  }
+
+  Ok(result)
+}
+
+// Replace `token` with an error token, covering `range`, but still
+// consuming everything the original token did
+fn error_result(
+  token: &TokenRef,
+  context: ParserContextRef,
+  message: &str,
+  range: &SourceRange,
+) -> Result<MatcherSuccess, MatcherFailure> {
+  let error_token = new_error_token_with_range(context, message, range);
+  error_token
+    .borrow_mut()
+    .set_matched_range(*token.borrow().get_matched_range());
+
+  TokenResult!(error_token)
+}
+
+/// Resolves the escape sequences in the value of the token
+pub fn unescape(
+  dialect: EscapeDialect,
+) -> impl Fn(TokenRef, ParserContextRef, ScopeContextRef) -> Result<MatcherSuccess, MatcherFailure>
+{
+  move |token, context, _| {
+    let value = token.borrow().get_value().clone();
+
+    match unescape_str(&value, dialect) {
+      Ok(value) => {
+        token.borrow_mut().set_value(&value);
+        TokenResult!(token)
+      }
+      Err(error) => {
+        let offset = token.borrow().get_captured_range().start;
+        let range = SourceRange::new(offset + error.start, offset + error.end);
+
+        error_result(&token, context, &error.message, &range)
+      }
+    }
+  }
+}
+
+/// Removes leading and trailing whitespace from the value of the token
+pub fn trim(
+  token: TokenRef,
+  _: ParserContextRef,
+  _: ScopeContextRef,
+) -> Result<MatcherSuccess, MatcherFailure> {
+  let value = token.borrow().get_value().trim().to_string();
+  token.borrow_mut().set_value(&value);
+
+  TokenResult!(token)
+}
+
+/// Removes the indentation common to every line of the value of the
+/// token. Lines with nothing but whitespace are left empty
+pub fn dedent(
+  token: TokenRef,
+  _: ParserContextRef,
+  _: ScopeContextRef,
+) -> Result<MatcherSuccess, MatcherFailure> {
+  let value = token.borrow().get_value().clone();
+  let is_indent = |c: char| c == ' ' || c == '\t';
+  let indent = value
+    .lines()
+    .filter(|line| !line.trim().is_empty())
+    .map(|line| line.len() - line.trim_start_matches(is_indent).len())
+    .min()
+    .unwrap_or(0);
+
+  let lines: Vec<&str> = value
+    .split('\n')
+    .map(|line| {
+      if line.trim().is_empty() {
+        ""
+      } else {
+        &line[indent..]
+      }
+    })
+    .collect();
+
+  token.borrow_mut().set_value(&lines.join("\n"));
+
+  TokenResult!(token)
+}
+
+fn parse_int_str(value: &str) -> Option<i64> {
+  let value = value.trim().replace('_', "");
+  let (negative, digits) = match value.strip_prefix('-') {
+    Some(digits) => (true, digits),
+    None => (false, value.strip_prefix('+').unwrap_or(&value)),
+  };
+
+  let lower = digits.to_ascii_lowercase();
+  let (radix, digits) = match lower.get(0..2) {
+    Some("0x") => (16, &digits[2..]),
+    Some("0o") => (8, &digits[2..]),
+    Some("0b") => (2, &digits[2..]),
+    _ => (10, digits),
+  };
+
+  // Signs were already taken care of
+  if digits.starts_with(['+', '-']) {
+    return None;
+  }
+
+  let value = i128::from_str_radix(digits, radix).ok()?;
+  i64::try_from(if negative { -value } else { value }).ok()
+}
+
+/// Parses the value of the token as an integer (in base 10, or with a
+/// `0x`, `0o` or `0b` prefix, and `_` separators), into the `int` attribute
+pub fn parse_int(
+  token: TokenRef,
+  context: ParserContextRef,
+  _: ScopeContextRef,
+) -> Result<MatcherSuccess, MatcherFailure> {
+  let value = token.borrow().get_value().clone();
+
+  match parse_int_str(&value) {
+    Some(int) => {
+      token
+        .borrow_mut()
+        .set_attribute(INT_ATTRIBUTE, &int.to_string());
+      TokenResult!(token)
+    }
+    None => {
+      let range = *token.borrow().get_captured_range();
+      error_result(
+        &token,
+        context,
+        &format!("Invalid integer `{}`", value),
+        &range,
+      )
+    }
+  }
+}
+
+/// Parses the value of the token as a (finite) float, into the `float` attribute
+pub fn parse_float(
+  token: TokenRef,
+  context: ParserContextRef,
+  _: ScopeContextRef,
+) -> Result<MatcherSuccess, MatcherFailure> {
+  let value = token.borrow().get_value().clone();
+
+  match value.trim().replace('_', "").parse::<f64>() {
+    Ok(float) if float.is_finite() => {
+      token
+        .borrow_mut()
+        .set_attribute(FLOAT_ATTRIBUTE, &format!("{:?}", float));
+      TokenResult!(token)
+    }
+    _ => {
+      let range = *token.borrow().get_captured_range();
+      error_result(
+        &token,
+        context,
+        &format!("Invalid float `{}`", value),
+        &range,
+      )
+    }
+  }
+}
+
+/// Names of the transforms scripts can use as actions, i.e. `<String @trim @unescape_json>`
+pub const TRANSFORM_NAMES: &[&str] = &[
+  "unescape_c",
+  "unescape_json",
+  "unescape_js",
+  "trim",
+  "dedent",
+  "parse_int",
+  "parse_float",
+];
+
+/// Looks up a transform by the name scripts use for it
+pub fn get_transform(name: &str) -> Option<Transform> {
+  let transform: Transform = match name {
+    "unescape_c" => Rc::new(unescape(EscapeDialect::C)),
+    "unescape_json" => Rc::new(unescape(EscapeDialect::Json)),
+    "unescape_js" => Rc::new(unescape(EscapeDialect::Js)),
+    "trim" => Rc::new(trim),
+    "dedent" => Rc::new(dedent),
+    "parse_int" => Rc::new(parse_int),
+    "parse_float" => Rc::new(parse_float),
+    _ => return None,
+  };
+
+  Some(transform)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{
+    dedent, parse_float, parse_int, unescape, unescape_str, EscapeDialect, EscapeError,
+    FLOAT_ATTRIBUTE, INT_ATTRIBUTE,
+  };
+  use crate::{
+    parser::Parser, parser_context::ParserContext, source_range::SourceRange, Map, Matches,
+  };
+
+  #[test]
+  fn it_unescapes_each_dialect() {
+    assert_eq!(
+      unescape_str(r#"a\"b\\c\/\né😀"#, EscapeDialect::Json),
+      Ok("a\"b\\c/\né😀".to_string())
+    );
+    assert_eq!(
+      unescape_str(r"\x41\101\a\?\U0001F600\'", EscapeDialect::C),
+      Ok("AA\u{7}?😀'".to_string())
+    );
+    assert_eq!(
+      unescape_str("\\x41\\u{1F600}\\0\\q\\\nb", EscapeDialect::Js),
+      Ok("A😀\0qb".to_string())
+    );
+
+    assert_eq!(
+      unescape_str(r"ab\q", EscapeDialect::Json),
+      Err(EscapeError {
+        start: 2,
+        end: 4,
+        message: "Invalid escape sequence `\\q`".to_string(),
+      })
+    );
+    assert_eq!(
+      unescape_str(r"\ud83d!", EscapeDialect::Js)
+        .unwrap_err()
+        .message,
+      "Unpaired surrogate in unicode escape sequence"
+    );
+    assert_eq!(
+      unescape_str(r"\u12", EscapeDialect::C).unwrap_err().message,
+      "Invalid unicode escape sequence"
+    );
+  }
+
+  #[test]
+  fn it_unescapes_a_token_or_reports_the_bad_escape() {
+    let parser = Parser::new(r#"tab\there" + 1"#);
+    let parser_context = ParserContext::new(&parser, "Test");
+    let matcher = Map!(
+      Matches!(r#"(?:[^"\\]|\\.)*"#),
+      unescape(EscapeDialect::Json)
+    );
+
+    let token = ParserContext::tokenize(parser_context, matcher.clone()).unwrap();
+    assert_eq!(token.borrow().get_value(), "tab\there");
+
+    let parser = Parser::new(r#"bad\qescape""#);
+    let parser_context = ParserContext::new(&parser, "Test");
+
+    let token = ParserContext::tokenize(parser_context, matcher).unwrap();
+    let token = token.borrow();
+    assert_eq!(token.get_name(), "Error");
+    assert_eq!(*token.get_captured_range(), SourceRange::new(3, 5));
+    assert_eq!(*token.get_matched_range(), SourceRange::new(0, 11));
+    assert!(token
+      .get_attribute("__message")
+      .unwrap()
+      .contains("Invalid escape sequence `\\q`"));
+  }
+
+  #[test]
+  fn it_dedents_and_parses_numbers() {
+    let parser = Parser::new("    a\n      b\n\n    c");
+    let parser_context = ParserContext::new(&parser, "Test");
+    let token = ParserContext::tokenize(parser_context, Map!(Matches!(r"(?s).+"), dedent)).unwrap();
+    assert_eq!(token.borrow().get_value(), "a\n  b\n\nc");
+
+    let matcher = Map!(Matches!(r".+"), parse_int);
+    for (source, expected) in [("-0x_ff", "-255"), ("1_000", "1000"), ("0b101", "5")] {
+      let parser = Parser::new(source);
+      let parser_context = ParserContext::new(&parser, "Test");
+      let token = ParserContext::tokenize(parser_context, matcher.clone()).unwrap();
+      assert_eq!(
+        token.borrow().get_attribute(INT_ATTRIBUTE).unwrap(),
+        expected
+      );
+    }
+
+    let parser = Parser::new("2.5e3");
+    let parser_context = ParserContext::new(&parser, "Test");
+    let token =
+      ParserContext::tokenize(parser_context, Map!(Matches!(r".+"), parse_float)).unwrap();
+    assert_eq!(
+      token.borrow().get_attribute(FLOAT_ATTRIBUTE).unwrap(),
+      "2500.0"
+    );
+
+    let parser = Parser::new("12abc");
+    let parser_context = ParserContext::new(&parser, "Test");
+    let token = ParserContext::tokenize(parser_context, Map!(Matches!(r".+"), parse_int)).unwrap();
+    assert_eq!(token.borrow().get_name(), "Error");
+  }
+}